@@ -1,18 +1,45 @@
 //! Application state and message handling (Elm architecture) with async support.
 
-use std::collections::VecDeque;
-use std::net::{IpAddr, Ipv4Addr};
+use std::collections::{BTreeMap, VecDeque};
+use std::future::Future;
+use std::io::IsTerminal;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
-use crate::config::Config;
-use crate::error::Result;
-use crate::health::{self, HealthStatus};
+use crate::cli::Args;
+use crate::config::{self, Config, Profile};
+use crate::diagnostics::{self, Diagnostic, Expectations, SystemFacts};
+use crate::error::{Result, TunshareError};
+use crate::event_log::{Event, EventSink, Record};
+use crate::health::{self, FailoverAction, HealthStatus, SamplerPause};
+use crate::http_control::{self, ControlCommand, ControlRequest, ControlResponse};
+use crate::schedule::ActiveHours;
 use crate::session::SharingSession;
+use crate::status_socket::{self, MappingInfo, StatusQuery, StatusRequest, StatusResponse};
+use crate::system::arp;
+use crate::system::clipboard;
+use crate::system::exec::RetryPolicy;
+use crate::system::ifaddr;
+use crate::system::natpmp;
+use crate::system::power;
+use crate::system::probe::{self, DnsProbeResult, ForwardProbeResult};
+use crate::system::process;
+use crate::system::sysctl;
 use crate::system::{
-    detect_lan_interfaces, detect_vpn_interfaces, discover_vpn_dns, dns::get_default_dns,
-    DhcpServer, Firewall, InterfaceInfo, IpForwarding, NatPmpServer,
+    detect_lan_interfaces, detect_vpn_interfaces,
+    dhcp::{self, DhcpBackend, DhcpBoot, DnsmasqVersion, KeptDhcp},
+    discover_vpn_dns,
+    dns::{self, get_default_dns, DnsPreset},
+    network::sort_by_mru,
+    verify::{self, Baseline, Residue},
+    DhcpServer, Firewall, InterfaceInfo, IpForwarding, LanInterfaces, NatPmpServer, PfState,
+    PfTemplate, Uplink,
 };
-use crate::ui::status::LogEntryLevel;
+use crate::ui::debug::format_bytes;
+use crate::ui::status::{LogCategory, LogEntryLevel};
+use crate::ui::theme::colors;
+use ipnet::Ipv4Net;
 use tokio::sync::mpsc;
 
 /// Maximum number of log entries kept in memory.
@@ -27,17 +54,56 @@ const TIMEOUT_START_NATPMP: Duration = Duration::from_secs(5);
 const TIMEOUT_STOP_SHARING: Duration = Duration::from_secs(10);
 const TIMEOUT_DEBUG_INFO: Duration = Duration::from_secs(5);
 const TIMEOUT_HEALTH_CHECK: Duration = Duration::from_secs(3);
+const TIMEOUT_CLIENT_SYNC: Duration = Duration::from_secs(5);
+/// Kept short: a black-holed server never answers, and the user is waiting.
+const TIMEOUT_DNS_TEST: Duration = Duration::from_secs(2);
 
 /// Interval between periodic health checks while sharing is active.
 const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
 
+/// How long the stop summary stays up unless dismissed.
+const STOP_SUMMARY_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// How long a DNS change can be undone with `u`.
+const DNS_UNDO_WINDOW: Duration = Duration::from_secs(5);
+
+/// Number of pf states PageUp/PageDown scroll by in the debug panel.
+const PF_STATE_PAGE: usize = 10;
+
+/// Network service the copied join commands configure when the LAN's hardware
+/// port is unknown; users edit it if theirs differs.
+const CLIENT_SETUP_SERVICE: &str = "Wi-Fi";
+
+/// Minimum time between two terminal bells, so a flapping connection stays bearable.
+const BELL_DEBOUNCE: Duration = Duration::from_secs(60);
+/// Paused health monitoring resumes by itself after this long.
+const HEALTH_PAUSE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Interval between checks that the DHCP daemon is still running.
+const DHCP_WATCH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Automatic DHCP restarts per session before giving up.
+const DHCP_MAX_RESTARTS: u32 = 3;
+
+/// Delay before the first automatic DHCP restart; doubles with each attempt.
+const DHCP_RESTART_BACKOFF: Duration = Duration::from_secs(2);
+
+/// Interval between refreshes of the per-client accounting rules.
+const CLIENT_SYNC_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Interval between checks of the power source.
+const POWER_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Screen repaint interval on AC power; doubled on battery, like the samplers.
+const REPAINT_INTERVAL: Duration = Duration::from_millis(50);
+
 /// Debug information about current system state.
 #[derive(Debug, Clone, Default)]
 pub struct DebugInfo {
     /// Current pf rules.
     pub pf_rules: String,
-    /// Current pf states (count and sample).
-    pub pf_states: String,
+    /// Current pf states.
+    pub pf_states: Vec<PfState>,
     /// Number of active pf states.
     pub pf_state_count: usize,
     /// Whether pf is enabled.
@@ -50,8 +116,22 @@ pub struct DebugInfo {
     pub dhcp_running: bool,
     /// DHCP range if enabled.
     pub dhcp_range: Option<(String, String)>,
+    /// Command line the DHCP daemon was started with.
+    pub dhcp_command: Option<String>,
+    /// Netboot options handed to clients.
+    pub dhcp_boot: Option<DhcpBoot>,
     /// Whether NAT-PMP server is running.
     pub natpmp_running: bool,
+    /// Address NAT-PMP advertises as external.
+    pub natpmp_external_ip: Option<Ipv4Addr>,
+    /// Broadcast address of the shared LAN.
+    pub lan_broadcast: Option<Ipv4Addr>,
+    /// TCP MSS clamp in the loaded rules, and whether it's the user's override.
+    pub mss_clamp: (u16, bool),
+    /// Installed dnsmasq release (None = not installed or unrecognized).
+    pub dnsmasq_version: Option<DnsmasqVersion>,
+    /// Per-client traffic `(client, bytes_in, bytes_out)`, highest total first.
+    pub top_talkers: Vec<(String, u64, u64)>,
 }
 
 /// Result of an async operation.
@@ -59,7 +139,7 @@ pub enum AsyncOpResult {
     /// Interface detection completed.
     InterfacesDetected {
         vpn: Result<Vec<InterfaceInfo>>,
-        lan: Result<Vec<InterfaceInfo>>,
+        lan: Result<LanInterfaces>,
     },
     /// DNS discovery completed.
     DnsDiscovered {
@@ -71,24 +151,199 @@ pub enum AsyncOpResult {
         result: Result<()>,
         firewall: Firewall,
         ip_forwarding: IpForwarding,
+        /// Steps that failed while undoing a failed start.
+        rollback_errors: Vec<TunshareError>,
     },
     /// DHCP server started.
-    DhcpStarted { result: Result<()> },
+    DhcpStarted {
+        result: Result<()>,
+        /// PID of the started daemon, if it could be read.
+        pid: Option<u32>,
+        /// Features left out because the daemon is too old, as log messages.
+        skipped: Vec<String>,
+    },
     /// NAT-PMP server started.
     NatPmpStarted {
         result: Result<()>,
         server: Option<NatPmpServer>,
     },
+    /// DHCP server stopped mid-session.
+    DhcpStopped { result: Result<()> },
+    /// NAT-PMP server stopped mid-session.
+    NatPmpStopped { result: Result<()> },
     /// VPN sharing stopped.
     SharingStopped {
         result: Result<()>,
         firewall: Firewall,
         ip_forwarding: IpForwarding,
+        /// Total `(bytes up, bytes down)` from the accounting rules, read before teardown.
+        traffic: Option<(u64, u64)>,
+        /// Last `(packets, bytes)` reading of the NAT rules, read before teardown.
+        nat_counters: Option<(u64, u64)>,
+        /// What was still there when checked after teardown (empty = clean).
+        residue: Vec<Residue>,
+        /// The NAT rules came out even though other parts of cleanup failed.
+        nat_removed: bool,
     },
     /// Debug info fetched.
     DebugInfoFetched { info: Result<DebugInfo> },
+    /// Firewall rules reloaded with new config while sharing stayed up.
+    RulesReloaded {
+        result: Result<()>,
+        firewall: Firewall,
+        ip_forwarding: IpForwarding,
+        /// Settings the rules were built from, kept only if they loaded
+        no_nat_destinations: Vec<String>,
+        override_mss: Option<u16>,
+        pf_template: Option<PathBuf>,
+    },
+    /// NAT moved between the primary and failover VPN.
+    UplinkSwitched {
+        result: Result<()>,
+        firewall: Firewall,
+        ip_forwarding: IpForwarding,
+        /// Whether NAT now goes through the failover VPN.
+        on_standby: bool,
+    },
     /// Periodic health check result.
-    HealthCheck { status: HealthStatus },
+    ///
+    /// `other_up` is whether the other VPN of a failover pair is up (None =
+    /// no failover VPN set).
+    HealthCheck {
+        status: HealthStatus,
+        other_up: Option<bool>,
+    },
+    /// Power source read (runs outside the pending op system).
+    PowerChecked { on_battery: bool },
+    /// Per-client accounting rules refreshed.
+    ///
+    /// `connected` also counts devices only seen in the ARP table, so it
+    /// includes clients configured by hand that haven't sent traffic yet.
+    ClientsSynced {
+        clients: Result<Vec<Ipv4Addr>>,
+        connected: usize,
+        /// `(packets, bytes)` the NAT rules have forwarded (None = unreadable).
+        nat_counters: Option<(u64, u64)>,
+    },
+    /// Port forward reachability probes finished.
+    ForwardsProbed { report: ForwardProbeReport },
+    /// Advertised DNS servers queried from the LAN side.
+    ClientDnsProbed { report: DnsProbeReport },
+    /// A custom DNS server was queried before switching to it.
+    DnsTested {
+        server: String,
+        result: DnsProbeResult,
+    },
+    /// System state read for the diagnostics checklist.
+    Diagnosed { facts: SystemFacts },
+    /// A post-start tunnel probe failed; another attempt follows.
+    TunnelSettling { attempt: u32, attempts: u32 },
+    /// Leftovers from earlier runs were cleaned up.
+    LeftoversCleaned { report: LeftoverReport },
+    /// Gateway address assigned to a LAN interface that had no IPv4.
+    LanAddressAssigned {
+        result: Result<()>,
+        vpn_name: String,
+        lan_name: String,
+        address: Ipv4Net,
+    },
+}
+
+/// What the "clean up leftover rules" action found and undid.
+#[derive(Debug)]
+pub struct LeftoverReport {
+    /// pf anchors flushed (and `/etc/pf.conf` if the main ruleset was reloaded).
+    pub anchors: Result<Vec<String>>,
+    /// Whether orphaned IP forwarding was turned off.
+    pub forwarding_disabled: Result<bool>,
+    /// DHCP daemons from an earlier run that were stopped.
+    pub dhcp_pids: Vec<u32>,
+}
+
+/// End-of-session summary shown after sharing stops.
+#[derive(Debug, Clone)]
+pub struct StopSummary {
+    pub vpn_name: String,
+    pub lan_name: String,
+    /// Instance label, if one is set.
+    pub label: Option<String>,
+    /// How long sharing was up.
+    pub duration: Duration,
+    /// Total `(bytes up, bytes down)` through the accounting rules, if any were loaded.
+    pub traffic: Option<(u64, u64)>,
+    /// `(packets, bytes)` forwarded by the NAT rules (None = never read).
+    pub forwarded: Option<(u64, u64)>,
+    /// Most LAN clients seen at once.
+    pub peak_clients: usize,
+    /// Problems hit while tearing down (empty = clean).
+    pub warnings: Vec<String>,
+}
+
+impl std::fmt::Display for StopSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Shared {} -> {}{} for {}",
+            self.vpn_name,
+            self.lan_name,
+            self.label
+                .as_ref()
+                .map(|label| format!(" ({})", label))
+                .unwrap_or_default(),
+            format_duration(self.duration)
+        )?;
+        if let Some((up, down)) = self.traffic {
+            writeln!(
+                f,
+                "Traffic: {} up, {} down",
+                format_bytes(up),
+                format_bytes(down)
+            )?;
+        }
+        if let Some((packets, bytes)) = self.forwarded {
+            writeln!(
+                f,
+                "Forwarded: {} in {} packets",
+                format_bytes(bytes),
+                packets
+            )?;
+        }
+        writeln!(f, "Peak clients: {}", self.peak_clients)?;
+        if self.warnings.is_empty() {
+            write!(f, "Cleanup: complete")
+        } else {
+            write!(f, "Cleanup warnings: {}", self.warnings.join("; "))
+        }
+    }
+}
+
+/// Format a duration as e.g. "1h 02m 03s" (or "4m 05s" under an hour).
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if h > 0 {
+        format!("{}h {:02}m {:02}s", h, m, s)
+    } else {
+        format!("{}m {:02}s", m, s)
+    }
+}
+
+/// Results of probing the active port forwards, shown in an overlay.
+#[derive(Debug, Clone)]
+pub struct ForwardProbeReport {
+    /// Address the forwards were probed on (the VPN interface address).
+    pub external_ip: Ipv4Addr,
+    /// `(forward description, result)` per mapping.
+    pub results: Vec<(String, ForwardProbeResult)>,
+}
+
+/// Results of querying the DNS servers clients are told to use, shown in an overlay.
+#[derive(Debug, Clone)]
+pub struct DnsProbeReport {
+    /// LAN address the queries were sent from.
+    pub source: Ipv4Addr,
+    /// `(server, result)` per advertised server.
+    pub results: Vec<(String, DnsProbeResult)>,
 }
 
 /// Pending async operation type (for UI display).
@@ -98,16 +353,36 @@ pub enum PendingOp {
     DetectingInterfaces,
     /// Discovering DNS servers.
     DiscoveringDns,
+    /// Assigning a gateway address to the LAN interface.
+    AssigningLanAddress,
     /// Starting VPN sharing.
     StartingSharing,
     /// Starting DHCP server.
     StartingDhcp,
     /// Starting NAT-PMP server.
     StartingNatPmp,
+    /// Stopping the DHCP server while sharing continues.
+    StoppingDhcp,
+    /// Stopping the NAT-PMP server while sharing continues.
+    StoppingNatPmp,
     /// Stopping VPN sharing.
     StoppingSharing,
     /// Fetching debug info.
     FetchingDebugInfo,
+    /// Probing port forwards.
+    ProbingForwards,
+    /// Querying the DNS servers advertised to clients.
+    ProbingClientDns,
+    /// Querying a custom DNS server before using it.
+    TestingDns,
+    /// Reading system state for the diagnostics checklist.
+    Diagnosing,
+    /// Removing rules and daemons left behind by earlier runs.
+    CleaningUpLeftovers,
+    /// Reloading the pf ruleset after a firewall-only config change.
+    ReloadingRules,
+    /// Moving NAT between the primary and failover VPN.
+    SwitchingUplink,
 }
 
 impl PendingOp {
@@ -116,41 +391,69 @@ impl PendingOp {
         match self {
             PendingOp::DetectingInterfaces => "Detecting interfaces...",
             PendingOp::DiscoveringDns => "Discovering DNS...",
+            PendingOp::AssigningLanAddress => "Assigning LAN address...",
             PendingOp::StartingSharing => "Starting VPN sharing...",
             PendingOp::StartingDhcp => "Starting DHCP server...",
             PendingOp::StartingNatPmp => "Starting NAT-PMP server...",
+            PendingOp::StoppingDhcp => "Stopping DHCP server...",
+            PendingOp::StoppingNatPmp => "Stopping NAT-PMP server...",
             PendingOp::StoppingSharing => "Stopping VPN sharing...",
             PendingOp::FetchingDebugInfo => "Fetching debug info...",
+            PendingOp::ProbingForwards => "Testing port forwards...",
+            PendingOp::ProbingClientDns => "Testing client DNS...",
+            PendingOp::TestingDns => "Testing DNS server...",
+            PendingOp::Diagnosing => "Checking system state...",
+            PendingOp::CleaningUpLeftovers => "Cleaning up leftover rules...",
+            PendingOp::ReloadingRules => "Reloading firewall rules...",
+            PendingOp::SwitchingUplink => "Switching VPN uplink...",
         }
     }
 }
 
-/// A DNS preset entry.
-#[derive(Debug, Clone)]
-pub struct DnsPreset {
-    pub name: &'static str,
-    pub ip: &'static str,
+/// Whether `result` is one to handle while `pending` is the pending operation.
+fn result_matches(result: &AsyncOpResult, pending: Option<PendingOp>) -> bool {
+    match (result, pending) {
+        // These carry firewall/ip_forwarding -- always accept
+        (AsyncOpResult::SharingStarted { .. }, _) => true,
+        (AsyncOpResult::SharingStopped { .. }, _) => true,
+        (AsyncOpResult::RulesReloaded { .. }, _) => true,
+        (AsyncOpResult::UplinkSwitched { .. }, _) => true,
+        // Health checks and client syncs run outside the pending op system -- always accept
+        (AsyncOpResult::HealthCheck { .. }, _) => true,
+        (AsyncOpResult::ClientsSynced { .. }, _) => true,
+        (AsyncOpResult::PowerChecked { .. }, _) => true,
+        // Progress only -- the start result that follows is checked on its own
+        (AsyncOpResult::TunnelSettling { .. }, _) => true,
+        // The service is gone either way -- always record it
+        (AsyncOpResult::DhcpStopped { .. }, _) => true,
+        (AsyncOpResult::NatPmpStopped { .. }, _) => true,
+        // Normal matching
+        (AsyncOpResult::InterfacesDetected { .. }, Some(PendingOp::DetectingInterfaces)) => true,
+        (AsyncOpResult::DnsDiscovered { .. }, Some(PendingOp::DiscoveringDns)) => true,
+        (AsyncOpResult::DhcpStarted { .. }, Some(PendingOp::StartingDhcp)) => true,
+        (AsyncOpResult::NatPmpStarted { .. }, Some(PendingOp::StartingNatPmp)) => true,
+        (AsyncOpResult::DebugInfoFetched { .. }, Some(PendingOp::FetchingDebugInfo)) => true,
+        (AsyncOpResult::ForwardsProbed { .. }, Some(PendingOp::ProbingForwards)) => true,
+        (AsyncOpResult::ClientDnsProbed { .. }, Some(PendingOp::ProbingClientDns)) => true,
+        (AsyncOpResult::DnsTested { .. }, Some(PendingOp::TestingDns)) => true,
+        (AsyncOpResult::Diagnosed { .. }, Some(PendingOp::Diagnosing)) => true,
+        // Report what was undone even if the wait was cancelled
+        (AsyncOpResult::LeftoversCleaned { .. }, _) => true,
+        // A cancelled assignment still has to be undone
+        (AsyncOpResult::LanAddressAssigned { .. }, _) => true,
+        _ => false,
+    }
 }
 
-/// Well-known DNS presets.
-pub const DNS_PRESETS: &[DnsPreset] = &[
-    DnsPreset {
-        name: "Cloudflare",
-        ip: "1.1.1.1",
-    },
-    DnsPreset {
-        name: "Google",
-        ip: "8.8.8.8",
-    },
-    DnsPreset {
-        name: "Quad9",
-        ip: "9.9.9.9",
-    },
-    DnsPreset {
-        name: "OpenDNS",
-        ip: "208.67.222.222",
-    },
-];
+/// Settings a launched profile replaced, put back when its session ends.
+#[derive(Debug, Clone)]
+struct ProfileRestore {
+    dns_custom: Option<String>,
+    dns_extra: Vec<String>,
+    failover_vpn: Option<String>,
+    dhcp_enabled: bool,
+    natpmp_enabled: bool,
+}
 
 /// DNS edit sub-mode.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -159,6 +462,8 @@ pub enum DnsEditMode {
     SelectingPreset,
     /// Typing a custom IP.
     CustomInput,
+    /// Reordering and toggling the servers in use.
+    Ordering,
 }
 
 /// DNS configuration and edit state.
@@ -169,30 +474,70 @@ pub struct DnsConfig {
     pub system_servers: Vec<String>,
     /// User-specified custom DNS server (overrides auto-detected).
     pub custom: Option<String>,
+    /// Further custom servers after `custom` (persisted; set from the command line).
+    pub extra: Vec<String>,
+    /// Servers from `--dns`, overriding everything else for this run only.
+    pub cli: Vec<String>,
     /// Text input buffer for DNS editing.
     pub input_buffer: String,
     /// DNS edit sub-mode (preset list vs custom input).
     pub edit_mode: DnsEditMode,
+    /// Presets listed in the DNS editor: built-ins merged with the config's.
+    pub presets: Vec<DnsPreset>,
     /// Selected index in the DNS preset list (0=Auto-detect, 1..N=presets, N+1=Custom...).
     pub preset_selected: usize,
+    /// Index into `presets` of the preset last picked (persisted by name).
+    pub last_preset: Option<usize>,
+    /// Preferred server order (persisted; servers not listed keep discovery order).
+    pub order: Vec<String>,
+    /// Servers the user switched off (persisted).
+    pub disabled: Vec<String>,
+    /// Highlighted row in the ordering list.
+    pub order_selected: usize,
 }
 
 impl DnsConfig {
-    fn new(custom: Option<String>) -> Self {
+    fn new(
+        custom: Option<String>,
+        extra: Vec<String>,
+        order: Vec<String>,
+        disabled: Vec<String>,
+        presets: Vec<DnsPreset>,
+        last_preset: Option<String>,
+    ) -> Self {
         Self {
             vpn_servers: Vec::new(),
             system_servers: Vec::new(),
             custom,
+            extra,
+            cli: Vec::new(),
             input_buffer: String::new(),
             edit_mode: DnsEditMode::SelectingPreset,
             preset_selected: 0,
+            last_preset: last_preset.and_then(|name| presets.iter().position(|p| p.name == name)),
+            presets,
+            order,
+            disabled,
+            order_selected: 0,
         }
     }
 
-    /// Get the effective DNS servers (custom > vpn > system).
-    pub fn effective(&self) -> Vec<String> {
-        if let Some(ref dns) = self.custom {
-            vec![dns.clone()]
+    /// Servers set by hand: `--dns` for this run, else the custom ones
+    /// (empty = auto-detect).
+    pub fn custom_servers(&self) -> Vec<String> {
+        if !self.cli.is_empty() {
+            self.cli.clone()
+        } else {
+            self.custom.iter().chain(&self.extra).cloned().collect()
+        }
+    }
+
+    /// Servers from the current source (command line > custom > vpn > system),
+    /// before ordering.
+    fn candidates(&self) -> Vec<String> {
+        let custom = self.custom_servers();
+        if !custom.is_empty() {
+            custom
         } else if !self.vpn_servers.is_empty() {
             self.vpn_servers.clone()
         } else {
@@ -200,9 +545,28 @@ impl DnsConfig {
         }
     }
 
+    /// Every candidate server in the user's order, including disabled ones.
+    pub fn listed(&self) -> Vec<String> {
+        dns::apply_order(&self.candidates(), &self.order)
+    }
+
+    /// Get the effective DNS servers (custom > vpn > system), in the user's
+    /// order and without the ones switched off.
+    pub fn effective(&self) -> Vec<String> {
+        dns::select(&self.candidates(), &self.order, &self.disabled)
+    }
+
+    /// Whether servers set by hand replace DNS servers the VPN pushed, which
+    /// can break its split DNS for internal domains.
+    pub fn overrides_vpn(&self) -> bool {
+        !self.custom_servers().is_empty() && !self.vpn_servers.is_empty()
+    }
+
     /// Get the source label for the current DNS.
     pub fn source(&self) -> &'static str {
-        if self.custom.is_some() {
+        if !self.cli.is_empty() {
+            "command line"
+        } else if self.custom.is_some() {
             "custom"
         } else if !self.vpn_servers.is_empty() {
             "vpn"
@@ -224,6 +588,12 @@ pub struct App {
     pub dns: DnsConfig,
     /// Currently selected VPN interface index.
     pub selected_vpn: Option<usize>,
+    /// Additional VPN interfaces (by name) to balance LAN traffic across,
+    /// marked with Space during VPN selection.
+    pub extra_uplinks: Vec<String>,
+    /// VPN interface NAT moves to if the primary goes down, marked with `f`
+    /// during VPN selection.
+    pub failover_vpn: Option<String>,
     /// Currently selected LAN interface index.
     pub selected_lan: Option<usize>,
     /// Active sharing session (None when not sharing).
@@ -244,28 +614,189 @@ pub struct App {
     pub pending_op: Option<PendingOp>,
     /// When the current pending operation started (for elapsed time display).
     pub pending_op_started: Option<Instant>,
+    /// Holds background samplers back while `pending_op` is in flight.
+    sampler_pause: SamplerPause,
     /// Whether to show debug panel.
     pub show_debug: bool,
     /// Cached debug information.
     pub debug_info: Option<DebugInfo>,
+    /// Debug information from the previous fetch (for diffing pf rules).
+    pub debug_info_prev: Option<DebugInfo>,
+    /// Whether the debug panel highlights pf rule changes since the previous fetch.
+    pub show_rule_diff: bool,
+    /// Whether the debug panel lists the pf state table.
+    pub show_pf_states: bool,
+    /// Whether the debug panel shows the NAT-PMP anchor rules the current
+    /// mappings would produce, in place of the loaded ruleset.
+    pub show_anchor_preview: bool,
+    /// NAT-PMP mapping selected in the anchor preview.
+    pub selected_mapping: usize,
+    /// First pf state shown in the state list.
+    pub pf_state_scroll: usize,
     /// Whether the log panel is expanded.
     pub logs_expanded: bool,
-    /// User preference: whether to start DHCP when sharing (default: true if dnsmasq installed).
+    /// Entries below this level aren't recorded (warnings and errors always are).
+    pub min_log_level: LogEntryLevel,
+    /// Log level from the config file, saved back instead of a `--log-level` override.
+    log_level_preference: LogEntryLevel,
+    /// VPN interfaces shared from recently, most recent first; sorts the VPN list.
+    mru_vpn: Vec<String>,
+    /// LAN interfaces shared onto recently, most recent first; sorts the LAN list.
+    mru_lan: Vec<String>,
+    /// Index into `logs` of the highlighted entry while browsing the expanded log.
+    pub selected_log: Option<usize>,
+    /// Whether the full text of the selected log entry is shown in an overlay.
+    pub show_log_detail: bool,
+    /// User preference: whether to start DHCP when sharing (default: true if a DHCP daemon is installed).
     pub dhcp_enabled: bool,
     /// User preference: whether to start NAT-PMP when sharing (default: true).
     pub natpmp_enabled: bool,
-    /// Cached: is dnsmasq installed on this system.
-    pub dnsmasq_installed: bool,
+    /// Cached: DHCP backend to use on this system (None if no DHCP daemon is installed).
+    pub dhcp_backend: Option<DhcpBackend>,
+    /// User preference for the DHCP backend (None = auto-detect, from config).
+    dhcp_backend_preference: Option<DhcpBackend>,
+    /// Destinations excluded from NAT (from config, not editable in the UI).
+    no_nat_destinations: Vec<String>,
+    /// Tunnel probes after loading rules, and the delay between them (from config).
+    tunnel_probe_attempts: u32,
+    tunnel_probe_interval: Duration,
+    /// Retry policy passed to each read-only system command (from config).
+    command_retry: RetryPolicy,
+    /// pf anchor for NAT-PMP mapping rules (from config).
+    natpmp_anchor: String,
+    /// Don't flush the NAT-PMP anchor on start (from config).
+    natpmp_preserve_existing: bool,
+    /// External ports NAT-PMP clients may map (from config).
+    natpmp_port_range: (u16, u16),
+    /// Color-blind friendly palette (from config, applied to the theme at startup).
+    colorblind_mode: bool,
+    /// Still loading indicator: the config preference, and whether it's in
+    /// effect (preference or `--no-animation`).
+    reduced_motion_preference: bool,
+    pub reduced_motion: bool,
+    /// Keep IP forwarding on when sharing stops (from config).
+    leave_forwarding_enabled: bool,
+    /// Daily window sharing may run in, as written in the config.
+    active_hours: Option<(String, String)>,
+    /// `active_hours` parsed at startup (None if unset or invalid).
+    active_window: Option<ActiveHours>,
+    /// Extra sysctls set while sharing (from config).
+    sysctls: BTreeMap<String, i64>,
+    /// Restart the DHCP daemon if it dies (from config).
+    dhcp_auto_restart: bool,
+    /// Leave the DHCP daemon running between shares (from config).
+    persistent_dhcp: bool,
+    /// DHCP daemon left running by the last share, for the next one to take over.
+    kept_dhcp: Option<KeptDhcp>,
+    /// MACs allowed to get DHCP leases; empty = everyone (from config).
+    dhcp_mac_allowlist: Vec<String>,
+    /// Netboot options for PXE clients (from config).
+    dhcp_boot: Option<DhcpBoot>,
+    /// User DNS presets, and whether they replace the built-ins (from config).
+    dns_presets: Vec<DnsPreset>,
+    dns_presets_replace: bool,
+    /// Address for LAN interfaces without IPv4 (from config, None = `ifaddr::DEFAULT_GATEWAY`).
+    lan_gateway_ip: Option<Ipv4Net>,
+    /// TCP MSS clamp replacing the default (from config).
+    override_mss: Option<u16>,
+    /// pf ruleset template loaded instead of the generated rules (from config).
+    pf_template: Option<PathBuf>,
+    /// Where preferences are saved (None = not saved).
+    config_path: Option<PathBuf>,
+    /// Custom DNS server whose test timed out; entering it again uses it untested.
+    dns_unanswered: Option<String>,
+    /// Ring the terminal bell on health transitions (from config).
+    bell_on_down: bool,
+    bell_on_recovery: bool,
+    /// Move back to the primary VPN once it recovers (from config).
+    failover_prefer_primary: bool,
+    /// When the bell last rang, for debouncing.
+    last_bell: Option<Instant>,
+    /// Set when the terminal bell should ring; the main loop writes it and clears it.
+    pub bell_pending: bool,
+    /// Domains resolved through `connectivity_check_resolver` via dnsmasq (from config).
+    connectivity_check_domains: Vec<String>,
+    connectivity_check_resolver: String,
+    /// Next check that the DHCP daemon is alive (None when not sharing).
+    next_dhcp_watch: Option<Instant>,
     /// Next scheduled health check time (None when not sharing).
     next_health_check: Option<Instant>,
+    /// When paused health monitoring resumes on its own (None = not paused).
+    health_paused_until: Option<Instant>,
+    /// Next scheduled client accounting refresh (None when not sharing).
+    next_client_sync: Option<Instant>,
+    /// Whether the Mac runs on battery; background sampling backs off if so.
+    pub on_battery: bool,
+    /// Next check of the power source.
+    next_power_check: Instant,
+    /// Process `(pid, name)` holding the NAT-PMP port when startup hit a conflict.
+    pub natpmp_conflict: Option<(u32, String)>,
+    /// VPN address NAT-PMP advertises as external (picked per VPN interface).
+    pub natpmp_external_ip: Option<Ipv4Addr>,
+    /// Preferred external address when the VPN has several (from config, set by `e`).
+    natpmp_external_preference: Option<Ipv4Addr>,
+    /// Start sharing again with the same interfaces once the current stop completes.
+    restart_pending: bool,
+    /// Latest port forward probe results (shown as an overlay until dismissed).
+    pub probe_report: Option<ForwardProbeReport>,
+    /// Latest client DNS test results (shown as an overlay until dismissed).
+    pub dns_probe_report: Option<DnsProbeReport>,
+    /// System state behind the open diagnostics overlay (None = closed).
+    pub diagnostic_facts: Option<SystemFacts>,
+    /// Saved sharing profiles (from config).
+    pub profiles: Vec<Profile>,
+    /// Highlighted entry in the profile picker.
+    pub selected_profile: usize,
+    /// Profile being started, carried across interface detection and DNS discovery.
+    launching_profile: Option<Profile>,
+    /// The user's own settings while a profile's are in effect (None = no profile).
+    profile_restore: Option<ProfileRestore>,
+    /// Type-to-filter text for the interface lists (empty = show all).
+    pub filter_buffer: String,
+    /// Whether keystrokes currently go to `filter_buffer`.
+    pub filtering: bool,
+    /// LAN index whose public address the user was warned about; Enter on it again proceeds.
+    public_lan_confirmation: Option<usize>,
+    /// LAN index without IPv4 the user was offered a gateway address for; Enter on it again assigns it.
+    lan_address_confirmation: Option<usize>,
+    /// Text input buffer for the profile name.
+    pub profile_name_input: String,
+    /// Label for this instance (`--label`, else the config file's, until changed with N).
+    pub session_label: Option<String>,
+    /// The config file's label, written back unchanged.
+    session_label_preference: Option<String>,
+    /// Text input for the instance label.
+    pub label_input: String,
+    /// `(vpn, lan, lan_ip)` of a start that failed because the VPN interface
+    /// went away, offered again with `r` from the menu.
+    pub start_retry: Option<(String, String, Option<Ipv4Addr>)>,
+    /// Summary of the last stopped session and when it was shown (overlay until dismissed).
+    pub stop_summary: Option<(StopSummary, Instant)>,
+    /// Custom DNS before the last change (empty = auto-detect) and when it changed.
+    /// Cleared once `DNS_UNDO_WINDOW` has passed.
+    pub dns_undo: Option<(Vec<String>, Instant)>,
+    /// Queries from status socket clients (None if the socket couldn't be bound).
+    status_rx: Option<mpsc::UnboundedReceiver<StatusRequest>>,
+    /// Commands from the HTTP control API (None if `--http` wasn't given or it couldn't bind).
+    control_rx: Option<mpsc::UnboundedReceiver<ControlRequest>>,
+    /// Started without root (`--demo`): browsing works, sharing is disabled.
+    pub read_only: bool,
+    /// Where JSON event records go (`--log-format json`; None = nowhere).
+    event_sink: Option<EventSink>,
+    /// Running without the TUI (`--headless`): the human log goes to stderr.
+    headless: bool,
 }
 
 /// Log entry for the status panel.
 #[derive(Debug, Clone)]
 pub struct LogEntry {
     pub timestamp: String,
+    /// When the entry was made, in full (`timestamp` is just the minute).
+    pub time: chrono::DateTime<chrono::Local>,
     pub message: String,
     pub level: LogEntryLevel,
+    /// Subsystem the entry relates to (for programmatic filtering).
+    pub category: LogCategory,
 }
 
 /// Current UI state.
@@ -281,32 +812,65 @@ pub enum AppState {
     Active,
     /// Editing custom DNS server.
     EditingDns,
+    /// Picking a saved profile to start.
+    SelectingProfile,
+    /// Naming the current selection to save it as a profile.
+    NamingProfile,
+    /// Typing a label for this instance.
+    NamingLabel,
+    /// Detection found no VPN and/or no LAN interface to share between.
+    NoInterfaces,
+}
+
+/// Which side of the share detection came back empty for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissingInterfaces {
+    Vpn,
+    Lan,
+    Both,
 }
 
 /// Menu items.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MenuItem {
     StartSharing,
+    Profiles,
     StopSharing,
+    RestartSharing,
     ToggleDhcp,
     ToggleNatPmp,
     SetDns,
+    CleanUpLeftovers,
     Quit,
 }
 
 impl App {
     /// Create a new application instance.
-    pub fn new() -> Self {
+    pub fn new(args: &Args) -> Self {
+        Self::with_config(args, Config::load(), Config::path())
+    }
+
+    /// Create an application instance from an already loaded config, saving
+    /// preferences to `config_path`.
+    fn with_config(args: &Args, config: Config, config_path: Option<PathBuf>) -> Self {
         let (op_tx, op_rx) = mpsc::unbounded_channel();
 
-        let config = Config::load();
-        let dnsmasq_available = DhcpServer::is_dnsmasq_installed();
+        let dhcp_backend = DhcpBackend::resolve(config.dhcp_backend);
 
         let mut app = Self {
             vpn_interfaces: Vec::new(),
             lan_interfaces: Vec::new(),
-            dns: DnsConfig::new(config.custom_dns),
+            dns: DnsConfig::new(
+                config.custom_dns,
+                config.custom_dns_extra,
+                config.dns_order,
+                config.dns_disabled,
+                dns::combine_presets(&config.dns_presets, config.dns_presets_replace),
+                config.last_dns_preset,
+            ),
             selected_vpn: None,
+            extra_uplinks: Vec::new(),
+            failover_vpn: None,
             selected_lan: None,
             session: None,
             logs: VecDeque::with_capacity(MAX_LOG_ENTRIES),
@@ -317,19 +881,219 @@ impl App {
             op_rx,
             pending_op: None,
             pending_op_started: None,
+            sampler_pause: SamplerPause::new(),
             show_debug: false,
             debug_info: None,
-            logs_expanded: false,
-            dhcp_enabled: config.dhcp_enabled && dnsmasq_available,
+            debug_info_prev: None,
+            show_rule_diff: false,
+            show_pf_states: false,
+            show_anchor_preview: false,
+            selected_mapping: 0,
+            pf_state_scroll: 0,
+            logs_expanded: config.logs_expanded_default,
+            min_log_level: args.log_level.unwrap_or(config.log_level).as_threshold(),
+            log_level_preference: config.log_level,
+            mru_vpn: config.mru_vpn,
+            mru_lan: config.mru_lan,
+            selected_log: None,
+            show_log_detail: false,
+            dhcp_enabled: config.dhcp_enabled && dhcp_backend.is_some(),
             natpmp_enabled: config.natpmp_enabled,
-            dnsmasq_installed: dnsmasq_available,
+            dhcp_backend,
+            dhcp_backend_preference: config.dhcp_backend,
+            no_nat_destinations: config.no_nat_destinations,
+            tunnel_probe_attempts: config.tunnel_probe_attempts,
+            tunnel_probe_interval: Duration::from_millis(config.tunnel_probe_interval_ms),
+            command_retry: RetryPolicy {
+                attempts: config.command_retry_attempts,
+                backoff: Duration::from_millis(config.command_retry_backoff_ms),
+            },
+            natpmp_anchor: config.natpmp_anchor.clone(),
+            natpmp_preserve_existing: config.natpmp_preserve_existing,
+            natpmp_port_range: config.natpmp_allowed_port_range,
+            colorblind_mode: config.colorblind_mode,
+            reduced_motion_preference: config.reduced_motion,
+            reduced_motion: config.reduced_motion || args.no_animation,
+            leave_forwarding_enabled: config.leave_forwarding_enabled,
+            active_hours: config.active_hours.clone(),
+            active_window: config
+                .active_hours
+                .as_ref()
+                .and_then(|(start, end)| ActiveHours::parse(start, end)),
+            sysctls: config.sysctls.clone(),
+            dhcp_auto_restart: config.dhcp_auto_restart,
+            persistent_dhcp: config.persistent_dhcp,
+            kept_dhcp: None,
+            dhcp_mac_allowlist: config.dhcp_mac_allowlist.clone(),
+            dhcp_boot: config.dhcp_boot.clone(),
+            dns_presets: config.dns_presets.clone(),
+            dns_presets_replace: config.dns_presets_replace,
+            lan_gateway_ip: config.lan_gateway_ip,
+            override_mss: config.override_mss,
+            pf_template: config.pf_template.clone(),
+            config_path,
+            dns_unanswered: None,
+            bell_on_down: config.bell_on_down,
+            bell_on_recovery: config.bell_on_recovery,
+            failover_prefer_primary: config.failover_prefer_primary,
+            last_bell: None,
+            bell_pending: false,
+            connectivity_check_domains: config.connectivity_check_domains.clone(),
+            connectivity_check_resolver: config.connectivity_check_resolver.clone(),
+            next_dhcp_watch: None,
             next_health_check: None,
+            health_paused_until: None,
+            on_battery: false,
+            next_power_check: Instant::now(),
+            next_client_sync: None,
+            natpmp_conflict: None,
+            natpmp_external_ip: None,
+            natpmp_external_preference: config.natpmp_external_address,
+            restart_pending: false,
+            probe_report: None,
+            dns_probe_report: None,
+            diagnostic_facts: None,
+            profiles: config.profiles,
+            selected_profile: 0,
+            launching_profile: None,
+            profile_restore: None,
+            filter_buffer: String::new(),
+            filtering: false,
+            public_lan_confirmation: None,
+            lan_address_confirmation: None,
+            profile_name_input: String::new(),
+            session_label: args.label.clone().or(config.session_label.clone()),
+            session_label_preference: config.session_label.clone(),
+            label_input: String::new(),
+            start_retry: None,
+            stop_summary: None,
+            dns_undo: None,
+            status_rx: None,
+            control_rx: None,
+            read_only: args.read_only,
+            event_sink: match (args.json_log, args.headless) {
+                (true, true) => Some(EventSink::Stdout),
+                // JSON lines on the terminal would scribble over the TUI
+                (true, false) if !std::io::stderr().is_terminal() => Some(EventSink::Stderr),
+                _ => None,
+            },
+            headless: args.headless,
         };
 
-        app.log_info("Ready. Press Enter to start VPN sharing.");
-        if !dnsmasq_available {
-            app.log_warning("dnsmasq not found. Install with: brew install dnsmasq");
-            app.log_info("DHCP will be disabled; router needs manual IP config.");
+        colors::set_colorblind(app.colorblind_mode);
+
+        if args.json_log && app.event_sink.is_none() {
+            app.log_warning("--log-format json needs stderr redirected, e.g. 2>tunshare.jsonl");
+        }
+
+        if !args.dns.is_empty() {
+            if args.save {
+                let mut servers = args.dns.iter().cloned();
+                app.dns.custom = servers.next();
+                app.dns.extra = servers.collect();
+                app.save_preferences();
+                app.log_info_cat(
+                    LogCategory::Dns,
+                    format!("Custom DNS saved: {}", args.dns.join(", ")),
+                );
+            } else {
+                app.dns.cli = args.dns.clone();
+                app.log_info_cat(
+                    LogCategory::Dns,
+                    format!("DNS for this run: {}", args.dns.join(", ")),
+                );
+            }
+        }
+
+        // The socket lives under /var/run, which needs root
+        if !app.read_only {
+            match status_socket::start() {
+                Ok(rx) => app.status_rx = Some(rx),
+                Err(e) => app.log_warning(format!("Status socket unavailable: {}", e)),
+            }
+        }
+
+        if let Some(addr) = args.http {
+            match http_control::start(addr) {
+                Ok(rx) => {
+                    app.control_rx = Some(rx);
+                    app.log_info(format!("HTTP control API listening on http://{}", addr));
+                }
+                Err(e) => app.log_warning(format!("HTTP control API unavailable: {}", e)),
+            }
+        }
+
+        if !NatPmpServer::is_valid_anchor_name(&app.natpmp_anchor) {
+            app.log_warning(format!(
+                "Invalid NAT-PMP anchor name {:?}, using {}",
+                config.natpmp_anchor,
+                natpmp::DEFAULT_ANCHOR_NAME
+            ));
+            app.natpmp_anchor = natpmp::DEFAULT_ANCHOR_NAME.to_string();
+        }
+
+        let invalid: Vec<&str> = app
+            .no_nat_destinations
+            .iter()
+            .filter(|cidr| cidr.trim().parse::<Ipv4Net>().is_err())
+            .map(String::as_str)
+            .collect();
+        if !invalid.is_empty() {
+            app.log_warning_cat(
+                LogCategory::Firewall,
+                format!(
+                    "Ignoring no_nat_destinations that aren't IPv4 CIDR ranges: {}",
+                    invalid.join(", ")
+                ),
+            );
+        }
+
+        if let (Some((start, end)), None) = (&app.active_hours, app.active_window) {
+            app.log_warning(format!(
+                "active_hours [{:?}, {:?}] isn't a valid HH:MM window; sharing won't start until it's fixed",
+                start, end
+            ));
+        }
+
+        // `Config::load` doesn't validate, so say which ones won't be set
+        let ignored: Vec<&str> = app
+            .sysctls
+            .keys()
+            .filter(|key| !sysctl::is_allowed_tunable(key))
+            .map(String::as_str)
+            .collect();
+        if !ignored.is_empty() {
+            app.log_warning_cat(
+                LogCategory::Network,
+                format!(
+                    "Ignoring sysctls (only net.* besides forwarding): {}",
+                    ignored.join(", ")
+                ),
+            );
+        }
+
+        if app.read_only {
+            app.log_warning("Read-only mode: sharing is disabled. Run with sudo to share.");
+        } else {
+            app.log_info("Ready. Press Enter to start VPN sharing.");
+        }
+        match (config.dhcp_backend, dhcp_backend) {
+            (_, None) => {
+                app.log_warning(
+                    "No DHCP server found. Install with: brew install dnsmasq (or isc-dhcp)",
+                );
+                app.log_info("DHCP will be disabled; router needs manual IP config.");
+            }
+            (Some(preferred), Some(backend)) if preferred != backend => {
+                app.log_warning_cat(
+                    LogCategory::Dhcp,
+                    format!(
+                        "DHCP backend {} not available, using {}",
+                        preferred, backend
+                    ),
+                );
+            }
+            _ => {}
         }
         app
     }
@@ -349,11 +1113,72 @@ impl App {
         self.session.as_ref().is_some_and(|s| s.natpmp_active)
     }
 
+    /// IPv4 addresses of the selected VPN interface.
+    pub fn selected_vpn_addresses(&self) -> &[Ipv4Addr] {
+        self.selected_vpn
+            .and_then(|i| self.vpn_interfaces.get(i))
+            .map_or(&[], |vpn| vpn.ipv4_addresses.as_slice())
+    }
+
     /// DHCP range (None if not sharing or DHCP inactive).
     pub fn dhcp_range(&self) -> Option<&(String, String)> {
         self.session.as_ref().and_then(|s| s.dhcp_range.as_ref())
     }
 
+    /// Network service the join snippet configures: the other Mac is on the
+    /// same kind of link, so the LAN interface's hardware port name, falling
+    /// back to [`CLIENT_SETUP_SERVICE`].
+    pub fn client_setup_service(&self) -> String {
+        self.session
+            .as_ref()
+            .and_then(|session| {
+                self.lan_interfaces
+                    .iter()
+                    .find(|iface| iface.name == session.lan_name)
+            })
+            .and_then(|iface| iface.description.clone())
+            .unwrap_or_else(|| CLIENT_SETUP_SERVICE.to_string())
+    }
+
+    /// Shell snippet that configures another Mac's network service to join
+    /// this share: a static IP in the LAN's subnet but outside the DHCP range
+    /// (skipping addresses of known clients), our LAN address as the router,
+    /// and the effective DNS. Empty when not sharing.
+    pub fn client_setup_command(&self) -> String {
+        let Some(session) = &self.session else {
+            return String::new();
+        };
+
+        let gateway = session.lan_ip;
+        let subnet = session.lan_net().trunc();
+        let (start, end) = DhcpServer::calculate_dhcp_range(session.lan_net());
+        let pool = start.parse::<Ipv4Addr>().unwrap_or(gateway)..=end.parse().unwrap_or(gateway);
+        // From the top of the subnet, where DHCP never hands out addresses
+        let client_ip = subnet
+            .hosts()
+            .rev()
+            .find(|ip| {
+                *ip != gateway && !pool.contains(ip) && !session.accounted_clients.contains(ip)
+            })
+            .unwrap_or(*pool.end());
+
+        let mut dns = self.dns.effective();
+        if dns.is_empty() {
+            dns.push(gateway.to_string());
+        }
+
+        format!(
+            "SERVICE=\"{}\"  # see: networksetup -listallnetworkservices\n\
+             sudo networksetup -setmanual \"$SERVICE\" {} {} {}\n\
+             sudo networksetup -setdnsservers \"$SERVICE\" {}\n",
+            self.client_setup_service(),
+            client_ip,
+            subnet.netmask(),
+            gateway,
+            dns.join(" ")
+        )
+    }
+
     /// Connection health status (Healthy if not sharing).
     pub fn health_status(&self) -> &HealthStatus {
         static HEALTHY: HealthStatus = HealthStatus::Healthy;
@@ -363,6 +1188,39 @@ impl App {
             .unwrap_or(&HEALTHY)
     }
 
+    /// The daily window sharing may run in, if one is set (and valid).
+    pub fn active_hours(&self) -> Option<ActiveHours> {
+        self.active_window
+    }
+
+    /// Log and return true if sharing can't start now because of `active_hours`.
+    /// An `active_hours` that doesn't parse allows no time at all.
+    fn outside_active_hours(&mut self) -> bool {
+        let Some(hours) = self.active_window else {
+            if self.active_hours.is_some() {
+                self.log_error(
+                    "active_hours isn't a valid HH:MM window; fix it in the config file to share",
+                );
+                return true;
+            }
+            return false;
+        };
+        if hours.contains_now() {
+            return false;
+        }
+        self.log_warning(format!(
+            "Sharing is only allowed {} (active_hours); it's {} now",
+            hours,
+            chrono::Local::now().format("%H:%M")
+        ));
+        true
+    }
+
+    /// Whether health checks, with their alerts and failover, are paused.
+    pub fn health_paused(&self) -> bool {
+        self.health_paused_until.is_some()
+    }
+
     /// Check if there's a pending operation (UI should show loading indicator).
     #[allow(dead_code)]
     pub fn is_loading(&self) -> bool {
@@ -373,12 +1231,14 @@ impl App {
     fn set_pending_op(&mut self, op: PendingOp) {
         self.pending_op = Some(op);
         self.pending_op_started = Some(Instant::now());
+        self.sampler_pause.set(true);
     }
 
     /// Clear the pending operation and its start time.
     fn clear_pending_op(&mut self) {
         self.pending_op = None;
         self.pending_op_started = None;
+        self.sampler_pause.set(false);
     }
 
     /// Get elapsed time since the pending operation started.
@@ -398,12 +1258,16 @@ impl App {
             // Return to an appropriate state
             match op {
                 PendingOp::DetectingInterfaces => {
+                    self.launching_profile = None;
                     self.state = AppState::Menu;
                 }
                 PendingOp::DiscoveringDns => {
+                    self.launching_profile = None;
+                    self.end_profile_session();
                     self.state = AppState::SelectingVpn;
                 }
-                PendingOp::StartingSharing
+                PendingOp::AssigningLanAddress
+                | PendingOp::StartingSharing
                 | PendingOp::StartingDhcp
                 | PendingOp::StartingNatPmp => {
                     // If sharing was already marked active (e.g. DHCP/NAT-PMP phase), stay in Menu
@@ -411,10 +1275,23 @@ impl App {
                 }
                 PendingOp::StoppingSharing => {
                     // Can't really undo a stop -- stay in current state, result will arrive
-                    // and handle cleanup via the always-restore path for SharingStopped
+                    // and handle cleanup via the always-restore path for SharingStopped.
+                    // A queued restart is dropped though.
+                    self.restart_pending = false;
+                }
+                PendingOp::StoppingDhcp
+                | PendingOp::StoppingNatPmp
+                | PendingOp::CleaningUpLeftovers
+                | PendingOp::ReloadingRules
+                | PendingOp::SwitchingUplink => {
+                    // The stop result is still applied when it arrives
                 }
-                PendingOp::FetchingDebugInfo => {
-                    // Just dismiss, stay where we are
+                PendingOp::FetchingDebugInfo
+                | PendingOp::ProbingForwards
+                | PendingOp::ProbingClientDns
+                | PendingOp::TestingDns
+                | PendingOp::Diagnosing => {
+                    // Just dismiss, stay where we are (a cancelled DNS test changes nothing)
                 }
             }
         }
@@ -426,13 +1303,175 @@ impl App {
             self.handle_async_result(result);
         }
 
+        // Answer status socket queries from current state
+        while let Some(request) = self.status_rx.as_mut().and_then(|rx| rx.try_recv().ok()) {
+            let response = self.answer_status_query(&request.query);
+            let _ = request.reply.send(response);
+        }
+        while let Some(request) = self.control_rx.as_mut().and_then(|rx| rx.try_recv().ok()) {
+            let response = self.answer_control_command(request.command);
+            let _ = request.reply.send(response);
+        }
+
+        // Auto-dismiss the stop summary (kept when quitting, it's printed on exit)
+        if !self.should_quit
+            && self
+                .stop_summary
+                .as_ref()
+                .is_some_and(|(_, shown)| shown.elapsed() >= STOP_SUMMARY_TIMEOUT)
+        {
+            self.stop_summary = None;
+        }
+
+        // Expire the DNS undo toast
+        if self
+            .dns_undo
+            .as_ref()
+            .is_some_and(|(_, changed)| changed.elapsed() >= DNS_UNDO_WINDOW)
+        {
+            self.dns_undo = None;
+        }
+
+        // Port mappings are answered even when pf rejects their rules; say so
+        if let Some(error) = self.session.as_mut().and_then(|s| s.natpmp_anchor_error()) {
+            self.log_error_cat(
+                LogCategory::NatPmp,
+                format!("Port forward rules not loaded: {}", error),
+            );
+        }
+
+        if Instant::now() >= self.next_power_check {
+            self.spawn_power_check();
+        }
+
+        if self
+            .health_paused_until
+            .is_some_and(|until| Instant::now() >= until)
+        {
+            self.resume_health(format!(
+                "Health monitoring resumed after {} minutes",
+                HEALTH_PAUSE_TIMEOUT.as_secs() / 60
+            ));
+        }
+
+        // Stop once the active_hours window closes
+        if self.is_live() && self.pending_op.is_none() {
+            if let Some(hours) = self.active_hours().filter(|hours| !hours.contains_now()) {
+                self.log_info(format!(
+                    "Outside active hours ({}): stopping sharing",
+                    hours
+                ));
+                self.stop_sharing_async();
+            }
+        }
+
         // Periodic health check while sharing is active
         if self.is_sharing() && self.pending_op.is_none() {
             if let Some(next) = self.next_health_check {
-                if Instant::now() >= next {
+                if Instant::now() >= next && !self.health_paused() {
                     self.spawn_health_check();
                 }
             }
+            if let Some(next) = self.next_client_sync {
+                if Instant::now() >= next {
+                    self.spawn_client_sync();
+                }
+            }
+            if let Some(next) = self.next_dhcp_watch {
+                if Instant::now() >= next {
+                    self.watch_dhcp();
+                }
+            }
+        }
+    }
+
+    /// Build the response to a status socket query.
+    fn answer_status_query(&self, query: &StatusQuery) -> StatusResponse {
+        match query {
+            StatusQuery::Status => StatusResponse::Status {
+                label: self.session_label.clone(),
+                sharing: self.is_live(),
+            },
+            StatusQuery::Mappings => {
+                let mappings = self
+                    .session
+                    .as_ref()
+                    .map(|s| s.natpmp_mappings())
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|m| MappingInfo {
+                        lifetime_remaining: m.remaining_secs(),
+                        protocol: m.protocol.to_string(),
+                        external_port: m.external_port,
+                        internal_ip: m.internal_ip.to_string(),
+                        internal_port: m.internal_port,
+                    })
+                    .collect();
+                StatusResponse::Mappings(mappings)
+            }
+        }
+    }
+
+    /// Carry out an HTTP control command. Start and stop go through the same
+    /// paths as the keyboard and answer as soon as they're underway.
+    fn answer_control_command(&mut self, command: ControlCommand) -> ControlResponse {
+        match command {
+            ControlCommand::Status => {
+                let session = self.session.as_ref();
+                ControlResponse::ok(serde_json::json!({
+                    "ok": true,
+                    "sharing": self.is_live(),
+                    "label": self.session_label,
+                    "state": format!("{:?}", self.state),
+                    "pending": self.pending_op.map(|op| op.display()),
+                    "vpn": session.map(|s| s.vpn_name.clone()),
+                    "lan": session.map(|s| s.lan_name.clone()),
+                    "gateway": session.map(|s| s.lan_ip.to_string()),
+                    "uptime_secs": session.map(|s| s.started_at.elapsed().as_secs()),
+                    "dhcp": self.dhcp_active(),
+                    "natpmp": self.natpmp_active(),
+                }))
+            }
+            ControlCommand::Start { vpn, lan } => {
+                if self.read_only {
+                    return ControlResponse::error(
+                        409,
+                        "sharing requires sudo (running read-only)",
+                    );
+                }
+                if self.is_sharing() {
+                    return ControlResponse::error(409, "already sharing; stop first");
+                }
+                if let Some(op) = self.pending_op {
+                    return ControlResponse::error(409, format!("busy: {}", op.display()));
+                }
+                self.log_info(format!("HTTP: starting {} -> {}", vpn, lan));
+                // Same path as a saved profile: detect, then start from the named interfaces
+                self.launching_profile = Some(Profile {
+                    name: "HTTP request".to_string(),
+                    vpn: vpn.clone(),
+                    lan: lan.clone(),
+                    dns: self.dns.custom.clone(),
+                    dns_extra: self.dns.extra.clone(),
+                    dhcp_enabled: self.dhcp_enabled,
+                    natpmp_enabled: self.natpmp_enabled,
+                    failover_vpn: None,
+                });
+                self.state = AppState::Menu;
+                self.refresh_interfaces_async();
+                ControlResponse::accepted(format!("starting {} -> {}", vpn, lan))
+            }
+            ControlCommand::Stop => {
+                if !self.is_sharing() {
+                    return ControlResponse::error(409, "not sharing");
+                }
+                if let Some(op) = self.pending_op {
+                    return ControlResponse::error(409, format!("busy: {}", op.display()));
+                }
+                self.log_info("HTTP: stopping sharing");
+                self.stop_sharing_async();
+                ControlResponse::accepted("stopping")
+            }
         }
     }
 
@@ -440,22 +1479,7 @@ impl App {
     /// SharingStarted/SharingStopped always match because we must restore ownership
     /// of firewall/ip_forwarding regardless.
     fn result_matches_pending(&self, result: &AsyncOpResult) -> bool {
-        match (result, self.pending_op) {
-            // These carry firewall/ip_forwarding -- always accept
-            (AsyncOpResult::SharingStarted { .. }, _) => true,
-            (AsyncOpResult::SharingStopped { .. }, _) => true,
-            // Health checks run outside the pending op system -- always accept
-            (AsyncOpResult::HealthCheck { .. }, _) => true,
-            // Normal matching
-            (AsyncOpResult::InterfacesDetected { .. }, Some(PendingOp::DetectingInterfaces)) => {
-                true
-            }
-            (AsyncOpResult::DnsDiscovered { .. }, Some(PendingOp::DiscoveringDns)) => true,
-            (AsyncOpResult::DhcpStarted { .. }, Some(PendingOp::StartingDhcp)) => true,
-            (AsyncOpResult::NatPmpStarted { .. }, Some(PendingOp::StartingNatPmp)) => true,
-            (AsyncOpResult::DebugInfoFetched { .. }, Some(PendingOp::FetchingDebugInfo)) => true,
-            _ => false,
-        }
+        result_matches(result, self.pending_op)
     }
 
     /// Handle a completed async operation.
@@ -474,47 +1498,92 @@ impl App {
             AsyncOpResult::InterfacesDetected { vpn, lan } => {
                 self.clear_pending_op();
 
+                // A fresh selection starts with a single uplink
+                self.extra_uplinks.clear();
+                self.failover_vpn = None;
                 match vpn {
-                    Ok(interfaces) => {
+                    Ok(mut interfaces) => {
+                        sort_by_mru(&mut interfaces, &self.mru_vpn);
                         let count = interfaces.len();
                         self.vpn_interfaces = interfaces;
                         if count > 0 {
-                            self.log_success(format!("Found {} VPN interface(s)", count));
+                            self.log_success_cat(
+                                LogCategory::Network,
+                                format!("Found {} VPN interface(s)", count),
+                            );
                         } else {
-                            self.log_warning("No VPN interfaces found. Is your VPN connected?");
+                            self.log_warning_cat(
+                                LogCategory::Network,
+                                "No VPN interfaces found. Is your VPN connected?",
+                            );
                         }
                     }
                     Err(e) => {
-                        self.log_error(format!("Failed to detect VPN interfaces: {}", e));
+                        self.log_error_cat(
+                            LogCategory::Network,
+                            format!("Failed to detect VPN interfaces: {}", e),
+                        );
                         self.vpn_interfaces.clear();
                     }
                 }
 
                 match lan {
-                    Ok(interfaces) => {
+                    Ok(LanInterfaces {
+                        mut interfaces,
+                        descriptions_unavailable,
+                    }) => {
+                        sort_by_mru(&mut interfaces, &self.mru_lan);
+                        if let Some(reason) = descriptions_unavailable {
+                            self.log_warning_cat(
+                                LogCategory::Network,
+                                format!(
+                                    "Interface descriptions unavailable ({}); using ifconfig only",
+                                    reason
+                                ),
+                            );
+                        }
                         let count = interfaces.len();
                         self.lan_interfaces = interfaces;
                         if count > 0 {
-                            self.log_success(format!("Found {} LAN interface(s)", count));
+                            self.log_success_cat(
+                                LogCategory::Network,
+                                format!("Found {} LAN interface(s)", count),
+                            );
                         } else {
-                            self.log_warning("No LAN interfaces found");
+                            self.log_warning_cat(LogCategory::Network, "No LAN interfaces found");
                         }
                     }
                     Err(e) => {
-                        self.log_error(format!("Failed to detect LAN interfaces: {}", e));
+                        self.log_error_cat(
+                            LogCategory::Network,
+                            format!("Failed to detect LAN interfaces: {}", e),
+                        );
                         self.lan_interfaces.clear();
                     }
                 }
 
+                // A profile skips the wizard when its interfaces are present
+                if let Some(profile) = self.launching_profile.take() {
+                    if self.launch_profile(profile) {
+                        return;
+                    }
+                }
+
                 // Continue to interface selection if we have interfaces
                 if !self.vpn_interfaces.is_empty() && !self.lan_interfaces.is_empty() {
                     self.state = AppState::SelectingVpn;
                     self.selected_vpn = Some(0);
                     self.log_info("Select VPN interface to share from");
-                } else if self.vpn_interfaces.is_empty() {
-                    self.log_error("No VPN interfaces found. Connect to VPN first.");
                 } else {
-                    self.log_error("No LAN interfaces found.");
+                    if self.vpn_interfaces.is_empty() {
+                        self.log_error_cat(
+                            LogCategory::Network,
+                            "No VPN interfaces found. Connect to VPN first.",
+                        );
+                    } else {
+                        self.log_error_cat(LogCategory::Network, "No LAN interfaces found.");
+                    }
+                    self.state = AppState::NoInterfaces;
                 }
             }
             AsyncOpResult::DnsDiscovered {
@@ -526,14 +1595,20 @@ impl App {
                 match vpn_servers {
                     Ok(servers) => {
                         if servers.is_empty() {
-                            self.log_warning("No VPN DNS servers found");
+                            self.log_warning_cat(LogCategory::Dns, "No VPN DNS servers found");
                         } else {
-                            self.log_success(format!("VPN DNS: {}", servers.join(", ")));
+                            self.log_success_cat(
+                                LogCategory::Dns,
+                                format!("VPN DNS: {}", servers.join(", ")),
+                            );
                         }
                         self.dns.vpn_servers = servers;
                     }
                     Err(e) => {
-                        self.log_warning(format!("VPN DNS discovery failed: {}", e));
+                        self.log_warning_cat(
+                            LogCategory::Dns,
+                            format!("VPN DNS discovery failed: {}", e),
+                        );
                         self.dns.vpn_servers.clear();
                     }
                 }
@@ -541,7 +1616,10 @@ impl App {
                 match system_servers {
                     Ok(servers) => {
                         if !servers.is_empty() {
-                            self.log_info(format!("System DNS: {}", servers.join(", ")));
+                            self.log_info_cat(
+                                LogCategory::Dns,
+                                format!("System DNS: {}", servers.join(", ")),
+                            );
                         }
                         self.dns.system_servers = servers;
                     }
@@ -550,8 +1628,21 @@ impl App {
                     }
                 }
 
+                self.natpmp_external_ip = natpmp::select_external_ip(
+                    self.selected_vpn_addresses(),
+                    self.natpmp_external_preference,
+                );
+
+                // Launching a profile: interfaces are already chosen
+                if self.launching_profile.take().is_some() {
+                    self.start_selected_sharing();
+                    return;
+                }
+
                 // Continue to LAN selection
                 self.state = AppState::SelectingLan;
+                self.public_lan_confirmation = None;
+                self.lan_address_confirmation = None;
                 self.selected_lan = if self.lan_interfaces.is_empty() {
                     None
                 } else {
@@ -563,6 +1654,7 @@ impl App {
                 result,
                 firewall,
                 ip_forwarding,
+                rollback_errors,
             } => {
                 // ALWAYS restore managers to prevent Drop cleanup, even if cancelled
                 if let Some(ref mut session) = self.session {
@@ -582,21 +1674,28 @@ impl App {
                             .as_ref()
                             .map(|s| s.lan_ip.to_string())
                             .unwrap_or_else(|| "unknown".into());
-                        self.log_success(format!(
-                            "VPN sharing active! Gateway: {}",
-                            lan_ip_display
-                        ));
-
-                        // Try to start DHCP server if enabled and dnsmasq is available
-                        if self.dhcp_enabled && self.dnsmasq_installed {
+                        self.log_success_cat(
+                            LogCategory::Firewall,
+                            format!("VPN sharing active! Gateway: {}", lan_ip_display),
+                        );
+                        self.remember_interfaces();
+
+                        // Try to start DHCP server if enabled and a backend is available
+                        if !(self.dhcp_enabled && self.dhcp_backend.is_some()) {
+                            self.stop_kept_dhcp();
+                        }
+                        if self.dhcp_enabled && self.dhcp_backend.is_some() {
                             if let Some(session) = self.session.as_ref() {
                                 let lan_name = session.lan_name.clone();
-                                let lan_ip = session.lan_ip;
-                                self.start_dhcp_async(lan_name, lan_ip);
+                                let lan = session.lan_net();
+                                self.start_dhcp_async(lan_name, lan);
                                 return;
                             }
                         } else if !self.dhcp_enabled {
-                            self.log_info("DHCP disabled by user preference");
+                            self.log_info_cat(
+                                LogCategory::Dhcp,
+                                "DHCP disabled by user preference",
+                            );
                             let eff = self.dns.effective();
                             if !eff.is_empty() {
                                 self.log_info(format!(
@@ -605,7 +1704,10 @@ impl App {
                                 ));
                             }
                         } else {
-                            self.log_info("DHCP disabled (dnsmasq not installed)");
+                            self.log_info_cat(
+                                LogCategory::Dhcp,
+                                "DHCP disabled (no DHCP server installed)",
+                            );
                             let eff = self.dns.effective();
                             if !eff.is_empty() {
                                 self.log_info(format!(
@@ -623,18 +1725,82 @@ impl App {
                         self.finish_startup();
                     }
                     Err(e) => {
-                        self.log_error(format!("Failed to start sharing: {}", e));
+                        // An uplink without an address mid-start means the tunnel dropped
+                        let vanished = match (&e, self.session.as_ref()) {
+                            (TunshareError::PfInterfaceMissing { iface, .. }, Some(session))
+                                if *iface == session.vpn_name
+                                    || session.extra_uplinks.contains(iface) =>
+                            {
+                                self.start_retry = Some((
+                                    session.vpn_name.clone(),
+                                    session.lan_name.clone(),
+                                    Some(session.lan_ip),
+                                ));
+                                Some(iface.clone())
+                            }
+                            _ => None,
+                        };
+                        if let Some(iface) = vanished {
+                            self.log_error_cat(
+                                LogCategory::Network,
+                                format!(
+                                    "VPN interface {} went away during startup \u{2014} is the tunnel stable?",
+                                    iface
+                                ),
+                            );
+                            if rollback_errors.is_empty() {
+                                self.log_info_cat(
+                                    LogCategory::Network,
+                                    "IP forwarding was put back; press r to retry once the VPN is up",
+                                );
+                            }
+                        } else {
+                            self.log_error_cat(
+                                LogCategory::Firewall,
+                                format!("Failed to start sharing: {}", e),
+                            );
+                            if let Some(output) = e.pfctl_output() {
+                                self.log_info_cat(
+                                    LogCategory::Firewall,
+                                    format!("pfctl said: {}", output),
+                                );
+                            }
+                            let template = self
+                                .pf_template
+                                .as_deref()
+                                .and_then(Config::resolve_pf_template);
+                            if let Some(hint) = e.hint(template.as_deref()) {
+                                self.log_info_cat(LogCategory::Firewall, hint);
+                            }
+                        }
+                        for e in &rollback_errors {
+                            self.log_error_cat(
+                                LogCategory::Network,
+                                format!("Couldn't undo the failed start: {}", e),
+                            );
+                        }
                         self.clear_pending_op();
                         self.state = AppState::Menu;
                         self.session = None;
+                        if self.start_retry.is_none() {
+                            self.end_profile_session();
+                        }
                     }
                 }
             }
-            AsyncOpResult::DhcpStarted { result } => {
+            AsyncOpResult::DhcpStarted {
+                result,
+                pid,
+                skipped,
+            } => {
+                for message in skipped {
+                    self.log_warning_cat(LogCategory::Dhcp, message);
+                }
                 match result {
                     Ok(()) => {
                         let log_msg = if let Some(ref mut session) = self.session {
                             session.dhcp_active = true;
+                            session.dhcp_pid = pid;
                             match &session.dhcp_range {
                                 Some((start, end)) => {
                                     format!("DHCP server active ({}-{})", start, end)
@@ -644,12 +1810,21 @@ impl App {
                         } else {
                             "DHCP server active".to_string()
                         };
-                        self.log_success(log_msg);
-                        self.log_info("Router can now use DHCP on WAN interface");
+                        self.log_success_cat(LogCategory::Dhcp, log_msg);
+                        self.log_info_cat(
+                            LogCategory::Dhcp,
+                            "Router can now use DHCP on WAN interface",
+                        );
                     }
                     Err(e) => {
-                        self.log_warning(format!("DHCP server failed: {}", e));
-                        self.log_info("Router needs manual IP configuration");
+                        self.log_warning_cat(
+                            LogCategory::Dhcp,
+                            format!("DHCP server failed: {}", e),
+                        );
+                        self.log_info_cat(
+                            LogCategory::Dhcp,
+                            "Router needs manual IP configuration",
+                        );
                         let eff = self.dns.effective();
                         if !eff.is_empty() {
                             self.log_info(format!(
@@ -657,9 +1832,19 @@ impl App {
                                 eff.join(", ")
                             ));
                         }
+                        // A failed automatic restart gets another go, within the limit
+                        if self.session.as_ref().is_some_and(|s| s.dhcp_restarts > 0) {
+                            self.schedule_dhcp_restart();
+                        }
                     }
                 }
 
+                // Started on its own while sharing: nothing else to chain
+                if self.is_live() {
+                    self.clear_pending_op();
+                    return;
+                }
+
                 // Try to start NAT-PMP server if enabled
                 if self.maybe_start_natpmp() {
                     return;
@@ -674,48 +1859,282 @@ impl App {
                             session.natpmp_active = true;
                             session.set_natpmp_server(server);
                         }
-                        self.log_success("NAT-PMP server active");
+                        self.natpmp_conflict = None;
+                        self.log_success_cat(LogCategory::NatPmp, "NAT-PMP server active");
+                    }
+                    Err(TunshareError::PortInUse { port, holder }) => {
+                        match &holder {
+                            Some((pid, name)) => {
+                                self.log_warning_cat(
+                                    LogCategory::NatPmp,
+                                    format!(
+                                        "NAT-PMP port {} is held by {} (pid {})",
+                                        port, name, pid
+                                    ),
+                                );
+                                self.log_info_cat(
+                                    LogCategory::NatPmp,
+                                    format!("Press k to stop {} and retry NAT-PMP", name),
+                                );
+                            }
+                            None => {
+                                self.log_warning_cat(
+                                    LogCategory::NatPmp,
+                                    format!("NAT-PMP port {} is already in use", port),
+                                );
+                                self.log_info_cat(
+                                    LogCategory::NatPmp,
+                                    "Stop the other NAT-PMP daemon (e.g. miniupnpd) and restart sharing",
+                                );
+                            }
+                        }
+                        self.natpmp_conflict = holder;
                     }
                     Err(e) => {
-                        self.log_warning(format!("NAT-PMP server failed: {}", e));
+                        self.log_warning_cat(
+                            LogCategory::NatPmp,
+                            format!("NAT-PMP server failed: {}", e),
+                        );
                     }
                 }
 
-                self.finish_startup();
+                if self.is_live() {
+                    self.clear_pending_op();
+                } else {
+                    self.finish_startup();
+                }
+            }
+            AsyncOpResult::RulesReloaded {
+                result,
+                firewall,
+                ip_forwarding,
+                no_nat_destinations,
+                override_mss,
+                pf_template,
+            } => {
+                if let Some(session) = self.session.as_mut() {
+                    session.restore_managers(firewall, ip_forwarding);
+                }
+                if self.pending_op == Some(PendingOp::ReloadingRules) {
+                    self.clear_pending_op();
+                }
+                match result {
+                    Ok(()) => {
+                        self.no_nat_destinations = no_nat_destinations;
+                        self.override_mss = override_mss;
+                        self.pf_template = pf_template;
+                        let message = match &self.pf_template {
+                            Some(path) => {
+                                format!("Firewall rules reloaded from template {}", path.display())
+                            }
+                            None => format!(
+                                "Firewall rules reloaded (MSS {}, {} NAT exclusion(s))",
+                                Firewall::effective_mss(self.override_mss),
+                                self.no_nat_networks().len()
+                            ),
+                        };
+                        self.log_success_cat(LogCategory::Firewall, message)
+                    }
+                    Err(e) => self.log_error_cat(
+                        LogCategory::Firewall,
+                        format!(
+                            "Couldn't reload rules, the previous ones stay loaded: {}",
+                            e
+                        ),
+                    ),
+                }
+            }
+            AsyncOpResult::DhcpStopped { result } => {
+                if self.pending_op == Some(PendingOp::StoppingDhcp) {
+                    self.clear_pending_op();
+                }
+                if let Some(session) = self.session.as_mut() {
+                    session.dhcp_active = false;
+                    session.dhcp_range = None;
+                    session.dhcp_pid = None;
+                }
+                match result {
+                    Ok(()) => self.log_success_cat(
+                        LogCategory::Dhcp,
+                        "DHCP server stopped; sharing continues",
+                    ),
+                    Err(e) => self.log_warning_cat(
+                        LogCategory::Dhcp,
+                        format!("DHCP server stop reported: {}", e),
+                    ),
+                }
+            }
+            AsyncOpResult::NatPmpStopped { result } => {
+                if self.pending_op == Some(PendingOp::StoppingNatPmp) {
+                    self.clear_pending_op();
+                }
+                if let Some(session) = self.session.as_mut() {
+                    session.natpmp_active = false;
+                }
+                match result {
+                    Ok(()) => self.log_success_cat(
+                        LogCategory::NatPmp,
+                        "NAT-PMP server stopped; sharing continues",
+                    ),
+                    Err(e) => self.log_warning_cat(
+                        LogCategory::NatPmp,
+                        format!("NAT-PMP rules flush failed: {}", e),
+                    ),
+                }
             }
             AsyncOpResult::SharingStopped {
                 result,
                 firewall,
                 ip_forwarding,
+                traffic,
+                nat_counters,
+                residue,
+                nat_removed,
             } => {
                 // Restore managers before dropping session (prevents double cleanup)
                 if let Some(ref mut session) = self.session {
                     session.restore_managers(firewall, ip_forwarding);
+                    if let Some(reading) = nat_counters {
+                        session.forwarded.record(reading);
+                    }
                 }
                 self.clear_pending_op();
 
+                let restart = std::mem::take(&mut self.restart_pending) && !self.should_quit;
+                let cleanup_ok = result.is_ok();
+
+                let mut warnings = Vec::new();
                 match result {
+                    Ok(()) if residue.is_empty() => {
+                        self.log_success_cat(
+                            LogCategory::Firewall,
+                            "VPN sharing stopped; system verified clean",
+                        );
+                    }
                     Ok(()) => {
-                        self.log_success("VPN sharing stopped");
+                        self.log_warning_cat(
+                            LogCategory::Firewall,
+                            format!(
+                                "VPN sharing stopped, but {} leftover(s) found",
+                                residue.len()
+                            ),
+                        );
                     }
                     Err(e) => {
-                        self.log_error(format!("Cleanup warning: {}", e));
+                        self.log_error_cat(
+                            LogCategory::Firewall,
+                            format!("Cleanup warning: {}", e),
+                        );
+                        // Say what did get undone so the user knows what's left over
+                        if nat_removed {
+                            self.log_info_cat(
+                                LogCategory::Firewall,
+                                "Firewall cleanup: NAT rules removed",
+                            );
+                        }
+                        warnings.push(e.to_string());
                     }
                 }
+                for item in &residue {
+                    self.log_warning_cat(
+                        LogCategory::Firewall,
+                        format!("Left behind: {} (fix: {})", item, item.fix_command()),
+                    );
+                    warnings.push(format!("{} (fix: {})", item, item.fix_command()));
+                }
+
+                if let Some(s) = &self.session {
+                    self.emit(
+                        LogEntryLevel::Info,
+                        Event::SharingStopped {
+                            vpn: s.vpn_name.clone(),
+                            lan: s.lan_name.clone(),
+                            duration_secs: s.started_at.elapsed().as_secs(),
+                            peak_clients: s.peak_clients,
+                            clean: cleanup_ok && residue.is_empty(),
+                        },
+                    );
+                }
+
+                // A restart isn't the end of the session, so no summary for it
+                if !restart {
+                    self.stop_summary = self.session.as_ref().map(|s| {
+                        let summary = StopSummary {
+                            vpn_name: s.vpn_name.clone(),
+                            lan_name: s.lan_name.clone(),
+                            label: self.session_label.clone(),
+                            duration: s.started_at.elapsed(),
+                            traffic,
+                            forwarded: Some(s.forwarded.total())
+                                .filter(|&(packets, _)| packets > 0),
+                            peak_clients: s.peak_clients,
+                            warnings,
+                        };
+                        (summary, Instant::now())
+                    });
+                }
 
-                // Drop session (its Drop is a no-op because async cleanup already ran)
+                // Remember the interfaces before the session goes away
+                let restart_target = self.session.as_ref().map(|s| {
+                    let lan_ip = Some(s.lan_ip).filter(|ip| !ip.is_unspecified());
+                    (
+                        s.vpn_name.clone(),
+                        s.lan_name.clone(),
+                        lan_ip,
+                        s.assigned_lan_address,
+                    )
+                });
+
+                // Drop session (async cleanup already ran; Drop only removes
+                // a LAN address we assigned)
                 self.session = None;
                 self.next_health_check = None;
+                self.health_paused_until = None;
+                self.next_client_sync = None;
+                self.next_dhcp_watch = None;
+                self.natpmp_conflict = None;
+                self.probe_report = None;
+                self.dns_probe_report = None;
+                self.diagnostic_facts = None;
                 self.state = AppState::Menu;
                 self.selected_menu_item = 0;
                 self.show_debug = false;
                 self.debug_info = None;
+                self.debug_info_prev = None;
+
+                let restarting = restart && cleanup_ok && restart_target.is_some();
+                if !restarting {
+                    self.end_profile_session();
+                }
+                if let (true, Some((vpn_name, lan_name, lan_ip, assigned))) =
+                    (restart, restart_target)
+                {
+                    if cleanup_ok {
+                        // The old session's managers were handed back and dropped above;
+                        // start_sharing_async creates fresh ones for the new session.
+                        match assigned {
+                            Some(address) => {
+                                self.assign_lan_address_async(vpn_name, lan_name, address)
+                            }
+                            None => self.start_sharing_async(vpn_name, lan_name, lan_ip, None),
+                        }
+                    } else {
+                        self.log_warning_cat(
+                            LogCategory::Firewall,
+                            "Restart skipped because cleanup did not complete cleanly",
+                        );
+                    }
+                }
             }
             AsyncOpResult::DebugInfoFetched { info } => {
                 self.clear_pending_op();
 
                 match info {
                     Ok(debug_info) => {
+                        self.stash_debug_info();
+                        self.pf_state_scroll = self
+                            .pf_state_scroll
+                            .min(debug_info.pf_states.len().saturating_sub(1));
                         self.debug_info = Some(debug_info);
                     }
                     Err(e) => {
@@ -724,7 +2143,22 @@ impl App {
                     }
                 }
             }
-            AsyncOpResult::HealthCheck { status } => {
+            AsyncOpResult::PowerChecked { on_battery } => {
+                if on_battery != self.on_battery {
+                    self.log_info(if on_battery {
+                        "On battery: checking the connection half as often"
+                    } else {
+                        "On AC power: checking the connection at the usual rate"
+                    });
+                }
+                self.on_battery = on_battery;
+            }
+            AsyncOpResult::HealthCheck { status, other_up } => {
+                // A check already running when monitoring was paused
+                if self.health_paused() {
+                    return;
+                }
+
                 // Only log when status changes to avoid spamming
                 let prev = self
                     .session
@@ -733,10 +2167,23 @@ impl App {
                     .cloned()
                     .unwrap_or_default();
 
+                if health::rings_bell(&prev, &status, self.bell_on_down, self.bell_on_recovery)
+                    && self
+                        .last_bell
+                        .is_none_or(|last| last.elapsed() >= BELL_DEBOUNCE)
+                {
+                    self.bell_pending = true;
+                    self.last_bell = Some(Instant::now());
+                }
+
                 if status != prev {
                     match &status {
                         HealthStatus::Healthy => {
                             self.log_success("Connection recovered");
+                            // A reconnected VPN often comes back with a new address
+                            if let Some(ref session) = self.session {
+                                session.refresh_natpmp_external_ip();
+                            }
                         }
                         HealthStatus::Degraded(reason) => {
                             self.log_warning(format!("Connection degraded: {}", reason));
@@ -747,19 +2194,270 @@ impl App {
                     }
                 }
 
+                let action = match (&self.session, other_up) {
+                    (Some(session), Some(other_up)) => health::failover_action(
+                        &status,
+                        session.on_standby,
+                        other_up,
+                        self.failover_prefer_primary,
+                    ),
+                    _ => FailoverAction::Stay,
+                };
                 if let Some(ref mut session) = self.session {
                     session.health_status = status;
                 }
+                match action {
+                    FailoverAction::Stay => {}
+                    FailoverAction::ToStandby => self.switch_uplink_async(true),
+                    FailoverAction::ToPrimary => self.switch_uplink_async(false),
+                }
+            }
+            AsyncOpResult::UplinkSwitched {
+                result,
+                firewall,
+                ip_forwarding,
+                on_standby,
+            } => {
+                if self.pending_op == Some(PendingOp::SwitchingUplink) {
+                    self.clear_pending_op();
+                }
+                let Some(session) = self.session.as_mut() else {
+                    return;
+                };
+                session.restore_managers(firewall, ip_forwarding);
+                match result {
+                    Ok(()) => {
+                        session.on_standby = on_standby;
+                        // The next check reports on the new uplink
+                        session.health_status = HealthStatus::Healthy;
+                        let message = if on_standby {
+                            format!(
+                                "Failed over: NAT now goes through {} ({} is down)",
+                                session.active_vpn(),
+                                session.vpn_name
+                            )
+                        } else {
+                            format!("Switched back: NAT goes through {} again", session.vpn_name)
+                        };
+                        self.log_warning_cat(LogCategory::Firewall, message);
+                    }
+                    Err(e) => self.log_error_cat(
+                        LogCategory::Firewall,
+                        format!("Couldn't switch VPN uplink: {}", e),
+                    ),
+                }
+            }
+            AsyncOpResult::ForwardsProbed { report } => {
+                self.clear_pending_op();
+
+                for (forward, result) in &report.results {
+                    let msg = format!("Forward {}: {}", forward, result);
+                    match result {
+                        ForwardProbeResult::Open => self.log_success_cat(LogCategory::NatPmp, msg),
+                        ForwardProbeResult::OpenOrFiltered => {
+                            self.log_info_cat(LogCategory::NatPmp, msg)
+                        }
+                        _ => self.log_warning_cat(LogCategory::NatPmp, msg),
+                    }
+                }
+                self.log_info_cat(
+                    LogCategory::NatPmp,
+                    format!(
+                        "Probed from this Mac; test {} from outside the VPN to be sure",
+                        report.external_ip
+                    ),
+                );
+                self.probe_report = Some(report);
+            }
+            AsyncOpResult::ClientDnsProbed { report } => {
+                self.clear_pending_op();
+
+                for (server, result) in &report.results {
+                    let msg = format!("Client DNS {}: {}", server, result);
+                    match result {
+                        DnsProbeResult::Answered { .. } => {
+                            self.log_success_cat(LogCategory::Dns, msg)
+                        }
+                        _ => self.log_warning_cat(LogCategory::Dns, msg),
+                    }
+                }
+                self.dns_probe_report = Some(report);
+            }
+            AsyncOpResult::DnsTested { server, result } => {
+                self.clear_pending_op();
+
+                if let DnsProbeResult::Answered { .. } = result {
+                    if let Some(i) = dns::preset_index(&self.dns.presets, &server) {
+                        self.dns.last_preset = Some(i);
+                    }
+                    self.commit_dns_change(Some(server.clone()));
+                    self.log_success_cat(
+                        LogCategory::Dns,
+                        format!("Custom DNS set to {} ({})", server, result),
+                    );
+                } else if result == DnsProbeResult::TimedOut {
+                    // Some resolvers only answer clients on their own network
+                    self.log_warning_cat(
+                        LogCategory::Dns,
+                        format!(
+                            "{} didn't answer in time; DNS left unchanged. Enter it again to use it anyway",
+                            server
+                        ),
+                    );
+                    self.dns_unanswered = Some(server);
+                } else {
+                    self.log_warning_cat(
+                        LogCategory::Dns,
+                        format!("{} didn't answer ({}); DNS left unchanged", server, result),
+                    );
+                }
+            }
+            AsyncOpResult::LanAddressAssigned {
+                result,
+                vpn_name,
+                lan_name,
+                address,
+            } => {
+                if self.pending_op != Some(PendingOp::AssigningLanAddress) {
+                    if result.is_ok() {
+                        self.log_info("LAN address assigned after cancel; removing it");
+                        Self::release_lan_address(lan_name, address);
+                    }
+                    return;
+                }
+                self.clear_pending_op();
+
+                if let Err(e) = result {
+                    self.log_error_cat(
+                        LogCategory::Network,
+                        format!("Failed to assign {} to {}: {}", address, lan_name, e),
+                    );
+                    self.state = AppState::SelectingLan;
+                    return;
+                }
+                self.log_success_cat(
+                    LogCategory::Network,
+                    format!("Assigned {} to {}", address, lan_name),
+                );
+
+                // Reflect the new address so the session picks up the subnet
+                if let Some(iface) = self.lan_interfaces.iter_mut().find(|i| i.name == lan_name) {
+                    iface.ipv4_address = Some(address.addr());
+                    iface.ipv4_addresses = vec![address.addr()];
+                    iface.netmask = Some(address.netmask());
+                }
+                self.start_sharing_async(vpn_name, lan_name, Some(address.addr()), Some(address));
+            }
+            AsyncOpResult::LeftoversCleaned { report } => {
+                if self.pending_op == Some(PendingOp::CleaningUpLeftovers) {
+                    self.clear_pending_op();
+                }
+                self.log_leftover_report(report);
+            }
+            AsyncOpResult::Diagnosed { facts } => {
+                self.clear_pending_op();
+                self.diagnostic_facts = Some(facts);
+                let failed = self.diagnose().iter().filter(|d| !d.passed).count();
+                if failed == 0 {
+                    self.log_success("Diagnostics: everything matches");
+                } else {
+                    self.log_warning(format!("Diagnostics: {} check(s) failed", failed));
+                }
+            }
+            AsyncOpResult::ClientsSynced {
+                clients,
+                connected,
+                nat_counters,
+            } => {
+                if let Some(session) = self.session.as_mut() {
+                    session.connected_clients = connected;
+                    if let Some(reading) = nat_counters {
+                        session.forwarded.record(reading);
+                    }
+                    // Failures are transient (pfctl busy, etc.) -- keep the previous set
+                    if let Ok(clients) = clients {
+                        session.peak_clients = session.peak_clients.max(clients.len());
+                        let joined: Vec<Ipv4Addr> = clients
+                            .iter()
+                            .filter(|ip| !session.accounted_clients.contains(ip))
+                            .copied()
+                            .collect();
+                        session.accounted_clients = clients;
+                        let lan = session.lan_name.clone();
+                        for client in joined {
+                            self.emit(
+                                LogEntryLevel::Info,
+                                Event::ClientConnected {
+                                    client,
+                                    lan: lan.clone(),
+                                },
+                            );
+                        }
+                    }
+                }
             }
+            AsyncOpResult::TunnelSettling { attempt, attempts } => {
+                self.log_info_cat(
+                    LogCategory::Network,
+                    format!(
+                        "Waiting for tunnel to settle (probe {}/{} failed)...",
+                        attempt, attempts
+                    ),
+                );
+            }
+        }
+    }
+
+    /// Whether sharing has fully started (as opposed to still running its startup chain).
+    fn is_live(&self) -> bool {
+        self.session.is_some() && self.next_health_check.is_some()
+    }
+
+    /// Pause health monitoring while the VPN is being cycled on purpose, so
+    /// it doesn't log, ring or fail over; or resume it.
+    fn toggle_health_pause(&mut self) {
+        if self.health_paused() {
+            self.resume_health("Health monitoring resumed");
+            return;
+        }
+        self.health_paused_until = Some(Instant::now() + HEALTH_PAUSE_TIMEOUT);
+        self.log_info(format!(
+            "Health monitoring paused for up to {} minutes (H resumes it)",
+            HEALTH_PAUSE_TIMEOUT.as_secs() / 60
+        ));
+    }
+
+    /// End a health pause and check right away.
+    fn resume_health(&mut self, message: impl Into<String>) {
+        self.health_paused_until = None;
+        if let Some(next) = self.next_health_check.as_mut() {
+            *next = Instant::now();
         }
+        self.log_info(message);
     }
 
     /// Clear pending startup state and transition to Active.
     fn finish_startup(&mut self) {
         self.clear_pending_op();
         self.state = AppState::Active;
-        // Start periodic health checks
-        self.next_health_check = Some(Instant::now() + HEALTH_CHECK_INTERVAL);
+        if let Some(session) = &self.session {
+            self.emit(
+                LogEntryLevel::Success,
+                Event::SharingStarted {
+                    vpn: session.vpn_name.clone(),
+                    lan: session.lan_name.clone(),
+                    gateway: session.lan_ip,
+                    label: self.session_label.clone(),
+                    dhcp: session.dhcp_active,
+                    natpmp: session.natpmp_active,
+                },
+            );
+        }
+        // Start periodic health checks and client accounting
+        self.next_health_check =
+            Some(Instant::now() + self.sampler_interval(HEALTH_CHECK_INTERVAL));
+        self.next_client_sync = Some(Instant::now());
+        self.next_dhcp_watch = Some(Instant::now() + self.sampler_interval(DHCP_WATCH_INTERVAL));
     }
 
     /// Try to start NAT-PMP if enabled.
@@ -769,8 +2467,8 @@ impl App {
             if let Some(session) = self.session.as_ref() {
                 let vpn_name = session.vpn_name.clone();
                 let lan_name = session.lan_name.clone();
-                let lan_ip = session.lan_ip;
-                self.start_natpmp_async(vpn_name, lan_name, lan_ip);
+                let lan = session.lan_net();
+                self.start_natpmp_async(vpn_name, lan_name, lan, None);
                 return true;
             }
         }
@@ -780,10 +2478,16 @@ impl App {
     /// Get the menu items based on current state.
     pub fn menu_items(&self) -> Vec<MenuItem> {
         if self.is_sharing() {
-            vec![MenuItem::StopSharing, MenuItem::Quit]
+            vec![
+                MenuItem::StopSharing,
+                MenuItem::RestartSharing,
+                MenuItem::Quit,
+            ]
         } else {
             vec![
                 MenuItem::StartSharing,
+                MenuItem::Profiles,
+                MenuItem::CleanUpLeftovers,
                 MenuItem::ToggleDhcp,
                 MenuItem::ToggleNatPmp,
                 MenuItem::SetDns,
@@ -798,14 +2502,15 @@ impl App {
             return; // Already busy
         }
 
-        self.log_info("Detecting network interfaces...");
+        self.log_info_cat(LogCategory::Network, "Detecting network interfaces...");
         self.set_pending_op(PendingOp::DetectingInterfaces);
 
         let tx = self.op_tx.clone();
+        let retry = self.command_retry;
         tokio::spawn(async move {
             let result = tokio::time::timeout(TIMEOUT_INTERFACES, async {
-                let vpn = detect_vpn_interfaces().await;
-                let lan = detect_lan_interfaces().await;
+                let vpn = detect_vpn_interfaces(retry).await;
+                let lan = detect_lan_interfaces(retry).await;
                 (vpn, lan)
             })
             .await;
@@ -813,13 +2518,11 @@ impl App {
             let (vpn, lan) = match result {
                 Ok(pair) => pair,
                 Err(_) => {
-                    let err = || {
-                        Err(crate::error::TunshareError::CommandFailed {
-                            command: "detect_interfaces".into(),
-                            message: "operation timed out".into(),
-                        })
+                    let err = || crate::error::TunshareError::CommandFailed {
+                        command: "detect_interfaces".into(),
+                        message: "operation timed out".into(),
                     };
-                    (err(), err())
+                    (Err(err()), Err(err()))
                 }
             };
             let _ = tx.send(AsyncOpResult::InterfacesDetected { vpn, lan });
@@ -832,13 +2535,17 @@ impl App {
             return; // Already busy
         }
 
-        self.log_info(format!("Discovering DNS for {}...", vpn_name));
+        self.log_info_cat(
+            LogCategory::Dns,
+            format!("Discovering DNS for {}...", vpn_name),
+        );
         self.set_pending_op(PendingOp::DiscoveringDns);
 
         let tx = self.op_tx.clone();
+        let retry = self.command_retry;
         tokio::spawn(async move {
             let result = tokio::time::timeout(TIMEOUT_DNS, async {
-                tokio::join!(discover_vpn_dns(&vpn_name), get_default_dns())
+                tokio::join!(discover_vpn_dns(&vpn_name, retry), get_default_dns(retry))
             })
             .await;
 
@@ -861,545 +2568,2605 @@ impl App {
         });
     }
 
+    /// Remove a LAN address we assigned, off the event loop.
+    fn release_lan_address(lan_name: String, address: Ipv4Net) {
+        tokio::task::spawn_blocking(move || ifaddr::remove_sync(&lan_name, address));
+    }
+
     /// Start VPN sharing (async).
+    ///
+    /// `assigned` is a LAN address tunshare put on the interface for this
+    /// start; the session owns it from here, and it is removed again if
+    /// sharing doesn't get going.
     fn start_sharing_async(
         &mut self,
         vpn_name: String,
         lan_name: String,
         lan_ip: Option<Ipv4Addr>,
+        assigned: Option<Ipv4Net>,
     ) {
         if self.pending_op.is_some() {
+            if let Some(address) = assigned {
+                Self::release_lan_address(lan_name, address);
+            }
             return; // Already busy
         }
+        self.start_retry = None;
+        if self.outside_active_hours() {
+            if let Some(address) = assigned {
+                self.log_info_cat(
+                    LogCategory::Network,
+                    format!("Removing {} from {}", address, lan_name),
+                );
+                Self::release_lan_address(lan_name, address);
+            }
+            return;
+        }
+
+        self.log_info_cat(
+            LogCategory::Firewall,
+            format!("Starting VPN sharing: {} -> {}", vpn_name, lan_name),
+        );
+
+        let lan_subnet = self
+            .lan_interfaces
+            .iter()
+            .find(|iface| iface.name == lan_name)
+            .and_then(|iface| iface.subnet())
+            .or(assigned.map(|address| address.trunc()));
+        let pf_template = self.pf_template.clone();
+        let template = match self.load_pf_template(pf_template.as_deref(), lan_subnet) {
+            Ok(template) => template,
+            Err(e) => {
+                self.log_error_cat(LogCategory::Firewall, e);
+                if let Some(address) = assigned {
+                    self.log_info_cat(
+                        LogCategory::Network,
+                        format!("Removing {} from {}", address, lan_name),
+                    );
+                    Self::release_lan_address(lan_name, address);
+                }
+                return;
+            }
+        };
+        if template.is_some() {
+            let no_nat_destinations = self.no_nat_destinations.clone();
+            self.warn_template_ignores(&no_nat_destinations, self.override_mss);
+        }
 
-        self.log_info(format!(
-            "Starting VPN sharing: {} -> {}",
-            vpn_name, lan_name
-        ));
         self.set_pending_op(PendingOp::StartingSharing);
 
         // Create session with fresh managers
         let lan_ip = lan_ip.unwrap_or(Ipv4Addr::UNSPECIFIED);
         let mut session = SharingSession::new(
-            Firewall::new(),
-            IpForwarding::new(),
+            Firewall::new()
+                .with_natpmp_anchor(&self.natpmp_anchor)
+                .with_natpmp_sub_anchors(self.natpmp_preserve_existing)
+                .with_mss_override(self.override_mss)
+                .with_template(template),
+            IpForwarding::new()
+                .with_leave_enabled(self.leave_forwarding_enabled)
+                .with_tunables(
+                    self.sysctls
+                        .iter()
+                        .map(|(key, value)| (key.clone(), value.to_string()))
+                        .collect(),
+                ),
             vpn_name.clone(),
             lan_name.clone(),
             lan_ip,
         );
+        // From here the session's Drop removes the address on any failure
+        session.assigned_lan_address = assigned;
+        let uplinks = self.uplinks(&vpn_name, &self.extra_uplinks);
+        session.extra_uplinks = uplinks[1..]
+            .iter()
+            .map(|uplink| uplink.interface.clone())
+            .collect();
+        if !session.extra_uplinks.is_empty() {
+            self.log_info_cat(
+                LogCategory::Firewall,
+                "Per-client traffic accounting is off while balancing across uplinks",
+            );
+        }
+        session.failover_vpn = self.failover_vpn.clone().filter(|f| *f != vpn_name);
+        if let Some(ref standby) = session.failover_vpn {
+            self.log_info_cat(
+                LogCategory::Network,
+                format!(
+                    "Failover: NAT moves to {} if {} goes down",
+                    standby, vpn_name
+                ),
+            );
+        }
+
+        // Snapshot the addresses: the interface lists can be rescanned while sharing
+        session.vpn_ip = self
+            .vpn_interfaces
+            .iter()
+            .find(|iface| iface.name == vpn_name)
+            .and_then(|iface| iface.ipv4_address);
+        session.lan_subnet = lan_subnet;
+        session.lan_broadcast = self
+            .lan_interfaces
+            .iter()
+            .find(|iface| iface.name == lan_name)
+            .and_then(|iface| iface.broadcast_ipv4);
+        let vpn_ip = session.vpn_ip;
 
         // Take managers out for async operation
-        let (mut firewall, mut ip_forwarding) = session.take_managers();
+        let (firewall, ip_forwarding) = session.take_managers();
         self.session = Some(session);
 
-        let tx = self.op_tx.clone();
+        let no_nat = self.no_nat_networks();
+        if !no_nat.is_empty() {
+            let list: Vec<String> = no_nat.iter().map(|n| n.to_string()).collect();
+            self.log_info_cat(
+                LogCategory::Firewall,
+                format!("Excluding from NAT: {}", list.join(", ")),
+            );
+        }
 
-        tokio::spawn(async move {
-            let result = tokio::time::timeout(TIMEOUT_START_SHARING, async {
-                ip_forwarding.enable().await?;
+        let list: Vec<String> = self
+            .sysctls
+            .iter()
+            .filter(|(key, _)| sysctl::is_allowed_tunable(key))
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+        if !list.is_empty() {
+            self.log_info_cat(
+                LogCategory::Network,
+                format!("Setting sysctls: {}", list.join(", ")),
+            );
+        }
 
-                if let Err(e) = firewall.load_rules(&vpn_name, &lan_name).await {
-                    let _ = ip_forwarding.restore().await;
-                    return Err(e);
-                }
+        let tx = self.op_tx.clone();
+        let attempts = self.tunnel_probe_attempts;
+        let interval = self.tunnel_probe_interval;
+        // Leave room for the tunnel probes on top of the normal start budget
+        let timeout = TIMEOUT_START_SHARING + (interval + health::TUNNEL_PROBE_TIMEOUT) * attempts;
 
-                Ok(())
-            })
+        tokio::spawn(async move {
+            let mut managers = (firewall, ip_forwarding);
+            let result = tokio::time::timeout(
+                timeout,
+                bring_up(&mut managers, |step, managers| {
+                    let (uplinks, lan_name, no_nat) =
+                        (uplinks.clone(), lan_name.clone(), no_nat.clone());
+                    let (vpn_name, tx) = (vpn_name.clone(), tx.clone());
+                    async move {
+                        let (firewall, ip_forwarding) = &mut *managers;
+                        let result = match step {
+                            StartStep::EnableForwarding => ip_forwarding.enable().await,
+                            StartStep::LoadRules => {
+                                firewall.load_rules(&uplinks, &lan_name, &no_nat).await
+                            }
+                            StartStep::CheckTunnel => {
+                                let probe = || health::probe_tunnel(vpn_ip);
+                                if wait_for_tunnel(attempts, interval, &tx, probe).await {
+                                    Ok(())
+                                } else {
+                                    Err(TunshareError::TunnelNotReady(format!(
+                                        "no traffic through {} after {} probe(s)",
+                                        vpn_name, attempts
+                                    )))
+                                }
+                            }
+                            StartStep::RemoveRules => match firewall.cleanup().await {
+                                Ok(report) if !report.is_clean() => {
+                                    Err(TunshareError::FirewallError(report.errors.join("; ")))
+                                }
+                                other => other.map(|_| ()),
+                            },
+                            StartStep::RestoreForwarding => ip_forwarding.restore().await,
+                        };
+                        (managers, result)
+                    }
+                }),
+            )
             .await;
 
-            let result = match result {
-                Ok(inner) => inner,
-                Err(_) => Err(crate::error::TunshareError::FirewallError(
-                    "starting sharing timed out".into(),
-                )),
+            let (result, rollback_errors) = match result {
+                Ok((_, result, rollback_errors)) => (result, rollback_errors),
+                Err(_) => (
+                    Err(crate::error::TunshareError::FirewallError(
+                        "starting sharing timed out".into(),
+                    )),
+                    Vec::new(),
+                ),
             };
 
+            let (firewall, ip_forwarding) = managers;
             let _ = tx.send(AsyncOpResult::SharingStarted {
                 result,
                 firewall,
                 ip_forwarding,
+                rollback_errors,
             });
         });
     }
 
-    /// Start DHCP server (async).
-    fn start_dhcp_async(&mut self, lan_name: String, lan_ip: Ipv4Addr) {
-        self.log_info("Starting DHCP server...");
-        self.set_pending_op(PendingOp::StartingDhcp);
+    /// Read the `pf_template` file at `path`, if one is configured.
+    fn load_pf_template(
+        &mut self,
+        path: Option<&Path>,
+        lan_subnet: Option<Ipv4Net>,
+    ) -> std::result::Result<Option<PfTemplate>, String> {
+        let Some(path) = path else {
+            return Ok(None);
+        };
+        let path = Config::resolve_pf_template(path)
+            .ok_or_else(|| "Can't find the config directory for pf_template".to_string())?;
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Can't read pf template {}: {}", path.display(), e))?;
+        self.log_info_cat(
+            LogCategory::Firewall,
+            format!("Loading pf rules from template {}", path.display()),
+        );
+        Ok(Some(PfTemplate {
+            text,
+            lan_subnet,
+            dns: self.effective_dns_v4(),
+        }))
+    }
 
-        // Calculate and store the DHCP range on the session
-        if let Some(ref mut session) = self.session {
-            session.dhcp_range = Some(DhcpServer::calculate_dhcp_range(lan_ip));
+    /// Warn that `no_nat_destinations` and `override_mss` are set but a pf
+    /// template replaces the rules they would go into.
+    fn warn_template_ignores(&mut self, no_nat_destinations: &[String], override_mss: Option<u16>) {
+        let mut ignored = Vec::new();
+        if !no_nat_destinations.is_empty() {
+            ignored.push("no_nat_destinations");
         }
+        if override_mss.is_some() {
+            ignored.push("override_mss");
+        }
+        if !ignored.is_empty() {
+            self.log_warning_cat(
+                LogCategory::Firewall,
+                format!(
+                    "{} not applied: the pf template replaces the generated rules",
+                    ignored.join(" and ")
+                ),
+            );
+        }
+    }
 
-        let tx = self.op_tx.clone();
-        let dns_servers = self.dns.effective();
-
-        tokio::spawn(async move {
-            let result = tokio::time::timeout(TIMEOUT_START_DHCP, async {
-                let mut dhcp = DhcpServer::new(&lan_name, lan_ip, dns_servers);
-                dhcp.start().await
+    /// `vpn_name` followed by the distinct `extra` uplinks, with their
+    /// tunnel peers as `route-to` gateways.
+    fn uplinks(&self, vpn_name: &str, extra: &[String]) -> Vec<Uplink> {
+        std::iter::once(vpn_name)
+            .chain(
+                extra
+                    .iter()
+                    .map(String::as_str)
+                    .filter(|name| *name != vpn_name),
+            )
+            .map(|name| Uplink {
+                interface: name.to_string(),
+                gateway: self
+                    .vpn_interfaces
+                    .iter()
+                    .find(|iface| iface.name == name)
+                    .and_then(|iface| iface.peer_ipv4),
             })
-            .await;
-
-            let result = match result {
-                Ok(inner) => inner,
-                Err(_) => Err(crate::error::TunshareError::CommandFailed {
-                    command: "start_dhcp".into(),
-                    message: "operation timed out".into(),
-                }),
-            };
-
-            let _ = tx.send(AsyncOpResult::DhcpStarted { result });
-        });
+            .collect()
     }
 
-    /// Stop VPN sharing (async).
-    fn stop_sharing_async(&mut self) {
-        if self.pending_op.is_some() {
-            return; // Already busy
-        }
+    /// The usable `no_nat_destinations` (invalid entries are ignored).
+    fn no_nat_networks(&self) -> Vec<Ipv4Net> {
+        config::parse_cidrs(&self.no_nat_destinations)
+    }
 
-        if self.session.is_none() {
-            self.log_warning("VPN sharing is not active");
-            self.state = AppState::Menu;
+    /// Re-read the config file and reload the pf ruleset with its firewall-only
+    /// settings (`no_nat_destinations`, `override_mss`), without restarting
+    /// DHCP or NAT-PMP. Other settings need a restart (`r`).
+    fn reload_rules_async(&mut self) {
+        if self.pending_op.is_some() || !self.is_live() {
             return;
         }
 
-        self.log_info("Stopping VPN sharing...");
-        self.set_pending_op(PendingOp::StoppingSharing);
+        let config = match Config::reload() {
+            Ok(config) => config,
+            Err(errors) => {
+                for error in errors {
+                    self.log_error(format!("Config: {}", error));
+                }
+                self.log_warning_cat(
+                    LogCategory::Firewall,
+                    "Fix the config file and press R again; the current rules stay loaded",
+                );
+                return;
+            }
+        };
 
-        let session = self.session.as_mut().unwrap();
-        let dhcp_active = session.dhcp_active;
-        let natpmp_active = session.natpmp_active;
+        let Some(lan_subnet) = self.session.as_ref().map(|s| s.lan_subnet) else {
+            return;
+        };
+        // Re-read the template too, so edits to it take effect
+        let template = match self.load_pf_template(config.pf_template.as_deref(), lan_subnet) {
+            Ok(template) => template,
+            Err(e) => {
+                self.log_error_cat(LogCategory::Firewall, e);
+                self.log_warning_cat(
+                    LogCategory::Firewall,
+                    "Fix the pf template and press R again; the current rules stay loaded",
+                );
+                return;
+            }
+        };
+        if template.is_some() {
+            self.warn_template_ignores(&config.no_nat_destinations, config.override_mss);
+        }
 
-        // Signal NAT-PMP server to shut down before spawning the cleanup task
-        session.shutdown_natpmp();
+        let Some(session) = self.session.as_mut() else {
+            return;
+        };
+        let (mut firewall, ip_forwarding) = session.take_managers();
+        let (vpn_name, extra, lan_name) = (
+            session.active_vpn().to_string(),
+            session.extra_uplinks.clone(),
+            session.lan_name.clone(),
+        );
+        let uplinks = self.uplinks(&vpn_name, &extra);
 
-        // Take ownership of managers for the async operation
-        let (mut firewall, mut ip_forwarding) = session.take_managers();
+        self.log_info_cat(
+            LogCategory::Firewall,
+            "Reloading firewall rules from config...",
+        );
+        self.set_pending_op(PendingOp::ReloadingRules);
 
         let tx = self.op_tx.clone();
-
+        let no_nat = config::parse_cidrs(&config.no_nat_destinations);
+        let (no_nat_destinations, override_mss) = (config.no_nat_destinations, config.override_mss);
+        let pf_template = config.pf_template;
         tokio::spawn(async move {
-            let result = tokio::time::timeout(TIMEOUT_STOP_SHARING, async {
-                let mut errors = Vec::new();
-
-                if natpmp_active {
-                    if let Err(e) = NatPmpServer::stop().await {
-                        errors.push(format!("NAT-PMP cleanup: {}", e));
-                    }
-                }
-
-                if dhcp_active {
-                    if let Err(e) = DhcpServer::stop().await {
-                        errors.push(format!("DHCP cleanup: {}", e));
-                    }
-                }
-
-                if let Err(e) = firewall.cleanup().await {
-                    errors.push(format!("Firewall cleanup: {}", e));
-                }
-
-                if let Err(e) = ip_forwarding.restore().await {
-                    errors.push(format!("IP forwarding: {}", e));
-                }
-
-                if errors.is_empty() {
-                    Ok(())
-                } else {
-                    Err(crate::error::TunshareError::FirewallError(
-                        errors.join("; "),
-                    ))
-                }
-            })
-            .await;
-
-            let result = match result {
-                Ok(inner) => inner,
-                Err(_) => Err(crate::error::TunshareError::FirewallError(
-                    "stopping sharing timed out".into(),
-                )),
-            };
+            let result = tokio::time::timeout(
+                TIMEOUT_START_SHARING,
+                firewall.reload_rules(&uplinks, &lan_name, &no_nat, override_mss, template),
+            )
+            .await
+            .unwrap_or_else(|_| {
+                Err(TunshareError::FirewallError(
+                    "reloading rules timed out".into(),
+                ))
+            });
 
-            let _ = tx.send(AsyncOpResult::SharingStopped {
+            let _ = tx.send(AsyncOpResult::RulesReloaded {
                 result,
                 firewall,
                 ip_forwarding,
+                no_nat_destinations,
+                override_mss,
+                pf_template,
             });
         });
     }
 
-    /// Fetch debug information (async).
-    fn fetch_debug_info_async(&mut self) {
-        if self.pending_op.is_some() {
-            return; // Already busy
+    /// Reload the pf ruleset with NAT through the failover VPN (`to_standby`)
+    /// or back through the primary one.
+    fn switch_uplink_async(&mut self, to_standby: bool) {
+        if self.pending_op.is_some() || !self.is_live() {
+            return; // Retried on the next health check
         }
+        let Some(session) = self.session.as_mut() else {
+            return;
+        };
+        let target = match session.failover_vpn {
+            Some(ref standby) if to_standby => standby.clone(),
+            _ => session.vpn_name.clone(),
+        };
+        let (mut firewall, ip_forwarding) = session.take_managers();
+        let (extra, lan_name) = (session.extra_uplinks.clone(), session.lan_name.clone());
+        let uplinks = self.uplinks(&target, &extra);
 
-        self.set_pending_op(PendingOp::FetchingDebugInfo);
+        self.log_info_cat(
+            LogCategory::Firewall,
+            format!("Moving NAT to {}...", target),
+        );
+        self.set_pending_op(PendingOp::SwitchingUplink);
 
         let tx = self.op_tx.clone();
-        let ip_forwarding_modified = self
-            .session
-            .as_ref()
-            .is_some_and(|s| s.ip_forwarding_is_modified());
-        let dhcp_running = self.dhcp_active();
-        let dhcp_range = self.dhcp_range().cloned();
-        let natpmp_running = self.natpmp_active();
-
+        let no_nat = self.no_nat_networks();
+        let mss_override = self.override_mss;
+        let template = firewall.template().cloned();
         tokio::spawn(async move {
-            let info = tokio::time::timeout(TIMEOUT_DEBUG_INFO, async {
-                let ip_fwd = IpForwarding::new();
-                let (pf_rules, pf_states, pf_enabled, ip_fwd_state) = tokio::join!(
-                    Firewall::get_current_rules(),
-                    Firewall::get_current_states(),
-                    Firewall::is_enabled(),
-                    ip_fwd.get_state()
+            let result = tokio::time::timeout(
+                TIMEOUT_START_SHARING,
+                firewall.reload_rules(&uplinks, &lan_name, &no_nat, mss_override, template),
+            )
+            .await
+            .unwrap_or_else(|_| {
+                Err(TunshareError::FirewallError(
+                    "switching uplinks timed out".into(),
+                ))
+            });
+
+            let _ = tx.send(AsyncOpResult::UplinkSwitched {
+                result,
+                firewall,
+                ip_forwarding,
+                on_standby: to_standby,
+            });
+        });
+    }
+
+    /// Start DHCP server (async) on `lan`, the gateway address with the
+    /// LAN's prefix length.
+    fn start_dhcp_async(&mut self, lan_name: String, lan: Ipv4Net) {
+        let Some(backend) = self.dhcp_backend else {
+            return;
+        };
+        self.log_info_cat(
+            LogCategory::Dhcp,
+            format!("Starting DHCP server ({})...", backend),
+        );
+        self.set_pending_op(PendingOp::StartingDhcp);
+
+        let dhcp = DhcpServer::new(backend, &lan_name, lan, self.effective_dns_v4())
+            .with_connectivity_check(
+                &self.connectivity_check_domains,
+                &self.connectivity_check_resolver,
+            )
+            .with_mac_allowlist(&self.dhcp_mac_allowlist)
+            .with_boot(self.dhcp_boot.clone())
+            .with_dns_v6(&self.effective_dns_v6());
+        let settings = dhcp.settings();
+
+        // Calculate and store the DHCP range on the session
+        if let Some(ref mut session) = self.session {
+            session.dhcp_range = Some(DhcpServer::calculate_dhcp_range(lan));
+            session.dhcp_settings = Some(settings.clone());
+        }
+
+        // Take over the daemon the last share left running, leases and all;
+        // if anything changed, start() replaces it
+        if let Some(kept) = self.kept_dhcp.take() {
+            if kept.reusable(&settings, kept.is_running()) {
+                self.log_info_cat(
+                    LogCategory::Dhcp,
+                    format!(
+                        "Reusing the DHCP server kept from the last share (pid {})",
+                        kept.pid
+                    ),
                 );
+                let _ = self.op_tx.send(AsyncOpResult::DhcpStarted {
+                    result: Ok(()),
+                    pid: Some(kept.pid),
+                    skipped: Vec::new(),
+                });
+                return;
+            }
+        }
 
-                let pf_rules = pf_rules.unwrap_or_else(|e| format!("Error: {}", e));
-                let pf_states = pf_states.unwrap_or_else(|e| format!("Error: {}", e));
-                let pf_state_count = pf_states.lines().count().saturating_sub(1);
-                let pf_enabled = pf_enabled.unwrap_or(false);
-                let ip_forwarding_enabled = ip_fwd_state.unwrap_or(false);
-
-                Ok(DebugInfo {
-                    pf_rules,
-                    pf_states,
-                    pf_state_count,
-                    pf_enabled,
-                    ip_forwarding_modified,
-                    ip_forwarding_enabled,
-                    dhcp_running,
-                    dhcp_range,
-                    natpmp_running,
-                })
+        if !self.connectivity_check_domains.is_empty() && backend != DhcpBackend::Dnsmasq {
+            self.log_warning_cat(
+                LogCategory::Dhcp,
+                format!(
+                    "Connectivity-check domains need dnsmasq; {} can't resolve them separately",
+                    backend
+                ),
+            );
+        }
+
+        let tx = self.op_tx.clone();
+        if !self.dhcp_mac_allowlist.is_empty() {
+            self.log_info_cat(
+                LogCategory::Dhcp,
+                format!(
+                    "Only {} allowlisted device(s) will get leases",
+                    self.dhcp_mac_allowlist.len()
+                ),
+            );
+        }
+
+        let mut dhcp = dhcp;
+        tokio::spawn(async move {
+            let result = tokio::time::timeout(TIMEOUT_START_DHCP, async {
+                dhcp.start()
+                    .await
+                    .map(|()| (dhcp.pid(), dhcp.skipped_features()))
             })
             .await;
 
-            let info = match info {
+            let (result, pid, skipped) = match result {
+                Ok(Ok((pid, skipped))) => (Ok(()), pid, skipped),
+                Ok(Err(e)) => (Err(e), None, Vec::new()),
+                Err(_) => (
+                    Err(crate::error::TunshareError::CommandFailed {
+                        command: "start_dhcp".into(),
+                        message: "operation timed out".into(),
+                    }),
+                    None,
+                    Vec::new(),
+                ),
+            };
+
+            let _ = tx.send(AsyncOpResult::DhcpStarted {
+                result,
+                pid,
+                skipped,
+            });
+        });
+    }
+
+    /// Stop the DHCP daemon kept from the last share, if any.
+    fn stop_kept_dhcp(&mut self) {
+        if let Some(kept) = self.kept_dhcp.take() {
+            self.log_info_cat(
+                LogCategory::Dhcp,
+                "Stopping the DHCP server kept from the last share",
+            );
+            tokio::task::spawn_blocking(move || kept.stop_sync());
+        }
+    }
+
+    /// Notice a DHCP daemon that exited on its own, and restart it if allowed.
+    /// Also launches restarts once their backoff has passed.
+    fn watch_dhcp(&mut self) {
+        self.next_dhcp_watch = Some(Instant::now() + self.sampler_interval(DHCP_WATCH_INTERVAL));
+        let Some(session) = self.session.as_mut() else {
+            return;
+        };
+
+        if let Some(due) = session.dhcp_restart_at {
+            if Instant::now() >= due {
+                session.dhcp_restart_at = None;
+                let (lan_name, lan) = (session.lan_name.clone(), session.lan_net());
+                let attempt = session.dhcp_restarts;
+                self.log_info_cat(
+                    LogCategory::Dhcp,
+                    format!(
+                        "Restarting DHCP server (attempt {} of {})",
+                        attempt, DHCP_MAX_RESTARTS
+                    ),
+                );
+                self.start_dhcp_async(lan_name, lan);
+            }
+            return;
+        }
+
+        let Some(pid) = session.dhcp_pid.filter(|_| session.dhcp_active) else {
+            return;
+        };
+        if process::is_alive(pid) {
+            return;
+        }
+
+        session.dhcp_active = false;
+        session.dhcp_pid = None;
+        self.log_warning_cat(
+            LogCategory::Dhcp,
+            format!(
+                "DHCP server (pid {}) exited; clients can't get new leases",
+                pid
+            ),
+        );
+        if self.dhcp_auto_restart {
+            self.schedule_dhcp_restart();
+        } else {
+            self.log_info_cat(
+                LogCategory::Dhcp,
+                "Automatic restart is off (dhcp_auto_restart); press h to start it again",
+            );
+        }
+    }
+
+    /// Schedule the next automatic DHCP restart with exponential backoff,
+    /// or give up once `DHCP_MAX_RESTARTS` is reached.
+    fn schedule_dhcp_restart(&mut self) {
+        let Some(session) = self.session.as_mut() else {
+            return;
+        };
+        if session.dhcp_restarts >= DHCP_MAX_RESTARTS {
+            session.dhcp_range = None;
+            self.log_warning_cat(
+                LogCategory::Dhcp,
+                format!(
+                    "Giving up on DHCP after {} restarts; press h to start it again",
+                    DHCP_MAX_RESTARTS
+                ),
+            );
+            return;
+        }
+
+        let due = Instant::now() + DHCP_RESTART_BACKOFF * 2u32.pow(session.dhcp_restarts);
+        session.dhcp_restarts += 1;
+        session.dhcp_restart_at = Some(due);
+        self.next_dhcp_watch = Some(due);
+    }
+
+    /// Start or stop the DHCP server while sharing stays up.
+    fn toggle_live_dhcp(&mut self) {
+        if self.pending_op.is_some() || !self.is_live() {
+            return;
+        }
+        // Taking over by hand resets the watchdog
+        if let Some(session) = self.session.as_mut() {
+            session.dhcp_restarts = 0;
+            session.dhcp_restart_at = None;
+        }
+        if self.dhcp_active() {
+            self.stop_dhcp_async();
+        } else if self.dhcp_backend.is_none() {
+            self.log_warning_cat(LogCategory::Dhcp, "No DHCP server installed");
+        } else if let Some(session) = self.session.as_ref() {
+            let (lan_name, lan) = (session.lan_name.clone(), session.lan_net());
+            self.start_dhcp_async(lan_name, lan);
+        }
+    }
+
+    /// Start or stop the NAT-PMP server while sharing stays up.
+    fn toggle_live_natpmp(&mut self) {
+        if self.pending_op.is_some() || !self.is_live() {
+            return;
+        }
+        if self.natpmp_active() {
+            self.stop_natpmp_async();
+        } else if let Some(session) = self.session.as_ref() {
+            let vpn_name = session.vpn_name.clone();
+            let lan_name = session.lan_name.clone();
+            let lan = session.lan_net();
+            self.start_natpmp_async(vpn_name, lan_name, lan, None);
+        }
+    }
+
+    /// Stop the DHCP server without stopping sharing (async).
+    fn stop_dhcp_async(&mut self) {
+        self.log_info_cat(LogCategory::Dhcp, "Stopping DHCP server...");
+        self.set_pending_op(PendingOp::StoppingDhcp);
+
+        let tx = self.op_tx.clone();
+        tokio::spawn(async move {
+            let result = match tokio::time::timeout(TIMEOUT_START_DHCP, DhcpServer::stop()).await {
                 Ok(inner) => inner,
                 Err(_) => Err(crate::error::TunshareError::CommandFailed {
-                    command: "fetch_debug_info".into(),
+                    command: "stop_dhcp".into(),
                     message: "operation timed out".into(),
                 }),
             };
+            let _ = tx.send(AsyncOpResult::DhcpStopped { result });
+        });
+    }
 
-            let _ = tx.send(AsyncOpResult::DebugInfoFetched { info });
+    /// Stop the NAT-PMP server without stopping sharing (async).
+    fn stop_natpmp_async(&mut self) {
+        self.log_info_cat(LogCategory::NatPmp, "Stopping NAT-PMP server...");
+        self.set_pending_op(PendingOp::StoppingNatPmp);
+
+        // The server task flushes its anchor on shutdown; flush again in case it's wedged
+        if let Some(session) = self.session.as_mut() {
+            session.shutdown_natpmp();
+        }
+
+        let natpmp_anchor =
+            natpmp::instance_anchor(&self.natpmp_anchor, self.natpmp_preserve_existing);
+        let tx = self.op_tx.clone();
+        tokio::spawn(async move {
+            let result = match tokio::time::timeout(
+                TIMEOUT_START_NATPMP,
+                NatPmpServer::stop(&natpmp_anchor),
+            )
+            .await
+            {
+                Ok(inner) => inner,
+                Err(_) => Err(crate::error::TunshareError::CommandFailed {
+                    command: "stop_natpmp".into(),
+                    message: "operation timed out".into(),
+                }),
+            };
+            let _ = tx.send(AsyncOpResult::NatPmpStopped { result });
+        });
+    }
+
+    /// Stop VPN sharing (async).
+    fn stop_sharing_async(&mut self) {
+        if self.pending_op.is_some() {
+            return; // Already busy
+        }
+
+        if self.session.is_none() {
+            self.log_warning("VPN sharing is not active");
+            self.state = AppState::Menu;
+            return;
+        }
+
+        self.log_info_cat(LogCategory::Firewall, "Stopping VPN sharing...");
+        self.set_pending_op(PendingOp::StoppingSharing);
+
+        let session = self.session.as_mut().unwrap();
+        let natpmp_active = session.natpmp_active;
+
+        // With persistent_dhcp only the firewall side is torn down; the daemon
+        // is handed to the next share (or stopped when the app exits)
+        let kept = match (
+            session.dhcp_active,
+            session.dhcp_pid,
+            &session.dhcp_settings,
+        ) {
+            (true, Some(pid), Some(settings))
+                if dhcp::keep_between_shares(
+                    self.persistent_dhcp,
+                    self.should_quit,
+                    session.assigned_lan_address.is_some(),
+                ) =>
+            {
+                // Remember which program it is, in case the PID is reused
+                process::name(pid).map(|name| KeptDhcp {
+                    pid,
+                    name,
+                    settings: settings.clone(),
+                })
+            }
+            _ => None,
+        };
+        let keep_dhcp = kept.is_some();
+        let dhcp_active = session.dhcp_active && !keep_dhcp;
+        if keep_dhcp {
+            // Dropping the session mustn't stop it either
+            session.dhcp_active = false;
+        }
+
+        // Signal NAT-PMP server to shut down before spawning the cleanup task
+        session.shutdown_natpmp();
+
+        // Take ownership of managers for the async operation
+        let (mut firewall, mut ip_forwarding) = session.take_managers();
+
+        if ip_forwarding.will_leave_enabled() {
+            self.log_info_cat(
+                LogCategory::Firewall,
+                "Leaving IP forwarding enabled (leave_forwarding_enabled is set)",
+            );
+        }
+        if let Some(kept) = kept {
+            self.log_info_cat(
+                LogCategory::Dhcp,
+                format!(
+                    "Leaving the DHCP server running (pid {}) for the next share (persistent_dhcp is set)",
+                    kept.pid
+                ),
+            );
+            self.kept_dhcp = Some(kept);
+        }
+
+        let natpmp_anchor =
+            natpmp::instance_anchor(&self.natpmp_anchor, self.natpmp_preserve_existing);
+        let baseline = Baseline {
+            forwarding: ip_forwarding.original_state(),
+            leave_forwarding_enabled: self.leave_forwarding_enabled,
+            natpmp_anchor: natpmp_anchor.clone(),
+            dhcp_kept: keep_dhcp,
+        };
+        let tx = self.op_tx.clone();
+        let retry = self.command_retry;
+
+        tokio::spawn(async move {
+            // Read the accounting counters before the anchors are flushed
+            let traffic = tokio::time::timeout(TIMEOUT_DEBUG_INFO, Firewall::label_counters())
+                .await
+                .ok()
+                .and_then(|counters| counters.ok())
+                .filter(|counters| !counters.is_empty())
+                .map(|counters| {
+                    counters
+                        .iter()
+                        .fold((0, 0), |(up, down), (_, i, o)| (up + i, down + o))
+                });
+            let nat_counters = tokio::time::timeout(TIMEOUT_DEBUG_INFO, Firewall::nat_counters())
+                .await
+                .ok()
+                .and_then(|counters| counters.ok());
+
+            let mut nat_removed = false;
+            let result = tokio::time::timeout(TIMEOUT_STOP_SHARING, async {
+                let mut errors = Vec::new();
+
+                if natpmp_active {
+                    if let Err(e) = NatPmpServer::stop(&natpmp_anchor).await {
+                        errors.push(format!("NAT-PMP cleanup: {}", e));
+                    }
+                }
+
+                if dhcp_active {
+                    if let Err(e) = DhcpServer::stop().await {
+                        errors.push(format!("DHCP cleanup: {}", e));
+                    }
+                }
+
+                match firewall.cleanup().await {
+                    Ok(report) if !report.is_clean() => {
+                        nat_removed = report.nat_removed;
+                        errors.extend(
+                            report
+                                .errors
+                                .into_iter()
+                                .map(|e| format!("Firewall cleanup: {}", e)),
+                        );
+                    }
+                    Ok(_) => {}
+                    Err(e) => errors.push(format!("Firewall cleanup: {}", e)),
+                }
+
+                if let Err(e) = ip_forwarding.restore().await {
+                    errors.push(format!("IP forwarding: {}", e));
+                }
+
+                if errors.is_empty() {
+                    Ok(())
+                } else {
+                    Err(crate::error::TunshareError::FirewallError(
+                        errors.join("; "),
+                    ))
+                }
+            })
+            .await;
+
+            let result = match result {
+                Ok(inner) => inner,
+                Err(_) => Err(crate::error::TunshareError::FirewallError(
+                    "stopping sharing timed out".into(),
+                )),
+            };
+
+            // Check what's actually left, whatever cleanup reported
+            let residue = tokio::time::timeout(
+                TIMEOUT_DEBUG_INFO,
+                verify::verify_clean_state(&baseline, retry),
+            )
+            .await
+            .unwrap_or_default();
+
+            let _ = tx.send(AsyncOpResult::SharingStopped {
+                result,
+                firewall,
+                ip_forwarding,
+                traffic,
+                nat_counters,
+                residue,
+                nat_removed,
+            });
+        });
+    }
+
+    /// Probe each active NAT-PMP forward on the VPN address (async).
+    fn probe_forwards_async(&mut self) {
+        if self.pending_op.is_some() {
+            return;
+        }
+        let Some(session) = self.session.as_ref() else {
+            return;
+        };
+
+        let mappings = session.natpmp_mappings();
+        if mappings.is_empty() {
+            self.log_info_cat(LogCategory::NatPmp, "No port forwards to test");
+            return;
+        }
+
+        // The address NAT-PMP advertises, which is where clients expect the forwards
+        let external_ip = self
+            .natpmp_external_ip
+            .filter(|ip| !ip.is_unspecified())
+            .or(session.vpn_ip);
+        let Some(external_ip) = external_ip else {
+            self.log_warning_cat(
+                LogCategory::NatPmp,
+                "Can't test forwards: VPN interface address unknown",
+            );
+            return;
+        };
+
+        self.probe_report = None;
+        self.set_pending_op(PendingOp::ProbingForwards);
+
+        let tx = self.op_tx.clone();
+
+        tokio::spawn(async move {
+            let mut results = Vec::with_capacity(mappings.len());
+            for m in mappings {
+                let result = probe::probe_forward(external_ip, m.external_port, m.protocol).await;
+                let forward = format!(
+                    "{} {} -> {}:{}",
+                    m.protocol, m.external_port, m.internal_ip, m.internal_port
+                );
+                results.push((forward, result));
+            }
+
+            let _ = tx.send(AsyncOpResult::ForwardsProbed {
+                report: ForwardProbeReport {
+                    external_ip,
+                    results,
+                },
+            });
+        });
+    }
+
+    /// DNS servers clients were told to use: what the DHCP server advertises,
+    /// or the effective list to configure by hand when DHCP is off.
+    fn client_dns_servers(&self) -> Vec<String> {
+        let Some(session) = self.session.as_ref() else {
+            return Vec::new();
+        };
+        match self.dhcp_backend.filter(|_| session.dhcp_active) {
+            Some(backend) => DhcpServer::new(
+                backend,
+                &session.lan_name,
+                session.lan_net(),
+                self.effective_dns_v4(),
+            )
+            .with_connectivity_check(
+                &self.connectivity_check_domains,
+                &self.connectivity_check_resolver,
+            )
+            .with_dns_v6(&self.effective_dns_v6())
+            .client_dns(),
+            None => self.dns.effective(),
+        }
+    }
+
+    /// Query each DNS server advertised to clients from the LAN address (async).
+    ///
+    /// Unlike the host's own lookups, this exercises the path a client's
+    /// queries take, including overrides like a LAN Pi-hole.
+    fn probe_client_dns_async(&mut self) {
+        if self.pending_op.is_some() {
+            return;
+        }
+        let Some(source) = self.session.as_ref().map(|s| s.lan_ip) else {
+            return;
+        };
+
+        let servers = self.client_dns_servers();
+        if servers.is_empty() {
+            self.log_info_cat(LogCategory::Dns, "No DNS servers advertised to clients");
+            return;
+        }
+
+        self.dns_probe_report = None;
+        self.set_pending_op(PendingOp::ProbingClientDns);
+
+        let tx = self.op_tx.clone();
+
+        tokio::spawn(async move {
+            let mut results = Vec::with_capacity(servers.len());
+            for server in servers {
+                let result = probe::probe_dns(&server, Some(source)).await;
+                results.push((server, result));
+            }
+
+            let _ = tx.send(AsyncOpResult::ClientDnsProbed {
+                report: DnsProbeReport { source, results },
+            });
+        });
+    }
+
+    /// Query a custom DNS server from this Mac before switching to it.
+    fn test_dns_server_async(&mut self, server: String) {
+        if self.pending_op.is_some() {
+            return;
+        }
+        self.set_pending_op(PendingOp::TestingDns);
+
+        let tx = self.op_tx.clone();
+
+        tokio::spawn(async move {
+            let result = tokio::time::timeout(TIMEOUT_DNS_TEST, probe::probe_dns(&server, None))
+                .await
+                .unwrap_or(DnsProbeResult::TimedOut);
+            let _ = tx.send(AsyncOpResult::DnsTested { server, result });
         });
     }
 
-    /// Spawn a one-shot health check (no PendingOp — completely non-blocking).
-    fn spawn_health_check(&mut self) {
-        let Some(session) = self.session.as_ref() else {
+    /// Restart VPN sharing (async): stop, then start again on the same interfaces.
+    ///
+    /// Picks up DNS, DHCP and NAT-PMP settings changed while sharing was active.
+    fn restart_sharing_async(&mut self) {
+        if self.pending_op.is_some() || self.session.is_none() {
+            return;
+        }
+
+        self.log_info_cat(LogCategory::Firewall, "Restarting VPN sharing...");
+        self.restart_pending = true;
+        self.stop_sharing_async();
+    }
+
+    /// Remove pf anchors, IP forwarding and DHCP daemons left behind by
+    /// earlier runs that didn't stop cleanly (async).
+    fn clean_up_leftovers_async(&mut self) {
+        if self.refuse_read_only() || self.pending_op.is_some() || self.is_sharing() {
+            return;
+        }
+        // What looks left over may be another instance's live setup
+        if let Some(other) = Self::other_live_instance() {
+            self.log_warning_cat(
+                LogCategory::Firewall,
+                format!("Not cleaning up: {} is running", other),
+            );
+            return;
+        }
+
+        self.log_info_cat(LogCategory::Firewall, "Cleaning up leftover rules...");
+        self.set_pending_op(PendingOp::CleaningUpLeftovers);
+        // A kept DHCP server is stopped along with any others
+        self.kept_dhcp = None;
+
+        let tx = self.op_tx.clone();
+        let natpmp_anchor = self.natpmp_anchor.clone();
+        let retry = self.command_retry;
+        let leave_forwarding = self.leave_forwarding_enabled;
+
+        tokio::spawn(async move {
+            let dhcp_pids = DhcpServer::running_pids();
+            if !dhcp_pids.is_empty() {
+                let _ = DhcpServer::stop().await;
+            }
+            let anchors = Firewall::flush_all_tunshare_anchors(&[&natpmp_anchor], retry).await;
+            let forwarding_disabled = IpForwarding::restore_orphaned(leave_forwarding).await;
+
+            let _ = tx.send(AsyncOpResult::LeftoversCleaned {
+                report: LeftoverReport {
+                    anchors,
+                    forwarding_disabled,
+                    dhcp_pids,
+                },
+            });
+        });
+    }
+
+    /// Another running tunshare, found through the status socket or the
+    /// PID in the forwarding marker, as a description for the log.
+    fn other_live_instance() -> Option<String> {
+        if status_socket::other_instance_running() {
+            return Some("another tunshare instance".to_string());
+        }
+        IpForwarding::marker_pid()
+            .filter(|&pid| process::is_other_instance(pid))
+            .map(|pid| format!("tunshare (pid {})", pid))
+    }
+
+    /// Log each thing the leftover cleanup removed (or failed to).
+    fn log_leftover_report(&mut self, report: LeftoverReport) {
+        let mut cleaned = 0;
+
+        match report.anchors {
+            Ok(anchors) => {
+                for anchor in &anchors {
+                    self.log_success_cat(LogCategory::Firewall, format!("Flushed {}", anchor));
+                }
+                cleaned += anchors.len();
+            }
+            Err(e) => self.log_error_cat(
+                LogCategory::Firewall,
+                format!("Couldn't flush pf anchors: {}", e),
+            ),
+        }
+
+        match report.forwarding_disabled {
+            Ok(true) => {
+                self.log_success("Turned off IP forwarding left on by an earlier run");
+                cleaned += 1;
+            }
+            Ok(false) => {}
+            Err(e) => self.log_error(format!("Couldn't turn off IP forwarding: {}", e)),
+        }
+
+        for pid in &report.dhcp_pids {
+            self.log_success_cat(
+                LogCategory::Dhcp,
+                format!("Stopped leftover DHCP server (pid {})", pid),
+            );
+        }
+        cleaned += report.dhcp_pids.len();
+
+        if cleaned == 0 {
+            self.log_info("No leftovers found");
+        }
+    }
+
+    /// Read system state for the diagnostics checklist (async).
+    fn diagnose_async(&mut self) {
+        if self.pending_op.is_some() {
+            return;
+        }
+        let Some(session) = self.session.as_ref() else {
+            return;
+        };
+        let dhcp_pid = session.dhcp_pid.filter(|_| session.dhcp_active);
+
+        self.set_pending_op(PendingOp::Diagnosing);
+
+        let tx = self.op_tx.clone();
+        let retry = self.command_retry;
+        tokio::spawn(async move {
+            let facts =
+                tokio::time::timeout(TIMEOUT_DEBUG_INFO, diagnostics::gather(dhcp_pid, retry))
+                    .await
+                    .unwrap_or_default();
+            let _ = tx.send(AsyncOpResult::Diagnosed { facts });
+        });
+    }
+
+    /// The rules NAT-PMP would load into its anchor for the current mappings,
+    /// without applying them. None when the NAT-PMP server isn't running.
+    pub fn natpmp_anchor_preview(&self) -> Option<String> {
+        let session = self.session.as_ref().filter(|s| s.natpmp_active)?;
+        // After a failover the mappings are live on the standby
+        Some(natpmp::build_anchor_rules(
+            session.active_vpn(),
+            self.natpmp_external_ip.unwrap_or(Ipv4Addr::UNSPECIFIED),
+            &session.natpmp_mappings(),
+        ))
+    }
+
+    /// Evict the mapping selected in the anchor preview, e.g. one a client
+    /// that left never released.
+    fn remove_selected_mapping(&mut self) {
+        let Some(session) = self.session.as_ref() else {
+            return;
+        };
+        let mappings = session.natpmp_mappings();
+        let Some(mapping) = mappings.get(self.selected_mapping) else {
+            return;
+        };
+        session.remove_natpmp_mapping(mapping.key());
+        let message = format!(
+            "Removed NAT-PMP mapping {} {} -> {}:{}",
+            mapping.protocol, mapping.external_port, mapping.internal_ip, mapping.internal_port
+        );
+        self.log_info_cat(LogCategory::NatPmp, message);
+        // Stay on the same row, or the new last one
+        self.selected_mapping = self.selected_mapping.min(mappings.len().saturating_sub(2));
+    }
+
+    /// Expected-vs-actual checklist for the active share, from the last
+    /// `diagnose_async` read. Empty when not sharing or nothing was read yet.
+    pub fn diagnose(&self) -> Vec<Diagnostic> {
+        let (Some(session), Some(facts)) = (self.session.as_ref(), self.diagnostic_facts.as_ref())
+        else {
+            return Vec::new();
+        };
+        let expect = Expectations {
+            vpn_name: session.vpn_name.clone(),
+            dhcp: session.dhcp_active,
+            natpmp: session.natpmp_active,
+            natpmp_anchor: self.natpmp_anchor.clone(),
+            own_pid: std::process::id(),
+        };
+        diagnostics::evaluate(&expect, facts)
+    }
+
+    /// Fetch debug information (async).
+    fn fetch_debug_info_async(&mut self) {
+        if self.pending_op.is_some() {
+            return; // Already busy
+        }
+
+        self.set_pending_op(PendingOp::FetchingDebugInfo);
+
+        let tx = self.op_tx.clone();
+        let ip_forwarding_modified = self
+            .session
+            .as_ref()
+            .is_some_and(|s| s.ip_forwarding_is_modified());
+        let dhcp_running = self.dhcp_active();
+        let dhcp_range = self.dhcp_range().cloned();
+        let dhcp_command = DhcpServer::last_command().filter(|_| dhcp_running);
+        let dhcp_boot = self.dhcp_boot.clone().filter(|_| dhcp_running);
+        let natpmp_running = self.natpmp_active();
+        let natpmp_external_ip = self.natpmp_external_ip;
+        let lan_broadcast = self.session.as_ref().and_then(|s| s.lan_broadcast);
+        let mss_clamp = (
+            Firewall::effective_mss(self.override_mss),
+            self.override_mss.is_some(),
+        );
+        let retry = self.command_retry;
+
+        tokio::spawn(async move {
+            let info =
+                tokio::time::timeout(TIMEOUT_DEBUG_INFO, async {
+                    let ip_fwd = IpForwarding::new();
+                    let (
+                        pf_rules,
+                        pf_states,
+                        pf_enabled,
+                        ip_fwd_state,
+                        top_talkers,
+                        dnsmasq_version,
+                    ) = tokio::join!(
+                        Firewall::get_current_rules(retry),
+                        Firewall::get_current_states(retry),
+                        Firewall::is_enabled(),
+                        ip_fwd.get_state(),
+                        Firewall::label_counters(),
+                        DhcpServer::dnsmasq_version()
+                    );
+
+                    let pf_rules = pf_rules.unwrap_or_else(|e| format!("Error: {}", e));
+                    let pf_states = pf_states
+                        .map(|states| Firewall::parse_states(&states))
+                        .unwrap_or_default();
+                    let pf_state_count = pf_states.len();
+                    let pf_enabled = pf_enabled.unwrap_or(false);
+                    let ip_forwarding_enabled = ip_fwd_state.unwrap_or(false);
+                    let top_talkers = top_talkers.unwrap_or_default();
+
+                    Ok(DebugInfo {
+                        pf_rules,
+                        pf_states,
+                        pf_state_count,
+                        pf_enabled,
+                        ip_forwarding_modified,
+                        ip_forwarding_enabled,
+                        dhcp_running,
+                        dhcp_range,
+                        dhcp_command,
+                        dhcp_boot,
+                        natpmp_running,
+                        natpmp_external_ip,
+                        lan_broadcast,
+                        mss_clamp,
+                        dnsmasq_version,
+                        top_talkers,
+                    })
+                })
+                .await;
+
+            let info = match info {
+                Ok(inner) => inner,
+                Err(_) => Err(crate::error::TunshareError::CommandFailed {
+                    command: "fetch_debug_info".into(),
+                    message: "operation timed out".into(),
+                }),
+            };
+
+            let _ = tx.send(AsyncOpResult::DebugInfoFetched { info });
+        });
+    }
+
+    /// Spawn a one-shot health check (no PendingOp — completely non-blocking).
+    fn spawn_health_check(&mut self) {
+        let Some(session) = self.session.as_ref() else {
+            return;
+        };
+
+        let tx = self.op_tx.clone();
+        let vpn_name = session.active_vpn().to_string();
+        let extra_uplinks = session.extra_uplinks.clone();
+        // The failover pair's other half, which the switch decision also needs
+        let other = match session.failover_vpn {
+            Some(_) if session.on_standby => Some(session.vpn_name.clone()),
+            ref standby => standby.clone(),
+        };
+
+        // Bump the timer regardless of outcome
+        self.next_health_check =
+            Some(Instant::now() + self.sampler_interval(HEALTH_CHECK_INTERVAL));
+        let mut gate = self.sampler_pause.gate();
+
+        tokio::spawn(async move {
+            gate.wait().await;
+            let status = tokio::time::timeout(
+                TIMEOUT_HEALTH_CHECK,
+                health::check_health(&vpn_name, &extra_uplinks),
+            )
+            .await
+            .unwrap_or(HealthStatus::Healthy); // Timeout = assume OK
+            let other_up = match other {
+                Some(other) => Some(health::is_interface_up(&other).await),
+                None => None,
+            };
+
+            let _ = tx.send(AsyncOpResult::HealthCheck { status, other_up });
+        });
+    }
+
+    /// `base` for a background sampler, doubled on battery.
+    fn sampler_interval(&self, base: Duration) -> Duration {
+        if self.on_battery {
+            base * 2
+        } else {
+            base
+        }
+    }
+
+    /// How often the main loop redraws the screen.
+    pub fn repaint_interval(&self) -> Duration {
+        self.sampler_interval(REPAINT_INTERVAL)
+    }
+
+    /// Spawn a one-shot read of the power source (no PendingOp).
+    fn spawn_power_check(&mut self) {
+        self.next_power_check = Instant::now() + POWER_CHECK_INTERVAL;
+        let tx = self.op_tx.clone();
+        tokio::spawn(async move {
+            let on_battery = power::on_battery().await;
+            let _ = tx.send(AsyncOpResult::PowerChecked { on_battery });
+        });
+    }
+
+    /// Spawn a one-shot refresh of the per-client accounting rules (no PendingOp).
+    ///
+    /// Clients are gathered from DHCP leases and pf states; the labeled anchor
+    /// is only reloaded when the set of clients actually changed.
+    fn spawn_client_sync(&mut self) {
+        let Some(session) = self.session.as_ref() else {
+            return;
+        };
+
+        let tx = self.op_tx.clone();
+        let retry = self.command_retry;
+        let lan_name = session.lan_name.clone();
+        let lan_ip = session.lan_ip;
+        let lan_subnet = session
+            .lan_subnet
+            .unwrap_or_else(|| Ipv4Net::new_assert(lan_ip, 24).trunc());
+        let known = session.accounted_clients.clone();
+        // Labeled quick rules would bypass uplink balancing
+        let accounting = session.extra_uplinks.is_empty();
+
+        self.next_client_sync = Some(Instant::now() + self.sampler_interval(CLIENT_SYNC_INTERVAL));
+        let mut gate = self.sampler_pause.gate();
+
+        tokio::spawn(async move {
+            gate.wait().await;
+            let result = tokio::time::timeout(TIMEOUT_CLIENT_SYNC, async {
+                let mut clients = DhcpServer::leased_clients();
+                if let Ok(states) = Firewall::get_current_states(retry).await {
+                    clients.extend(Firewall::parse_state_clients(&states, lan_ip, lan_subnet));
+                }
+                clients.sort();
+                clients.dedup();
+
+                if accounting && clients != known {
+                    Firewall::load_client_rules(&lan_name, &clients).await?;
+                }
+                Ok(clients)
+            })
+            .await;
+
+            let clients = match result {
+                Ok(inner) => inner,
+                Err(_) => Err(crate::error::TunshareError::CommandFailed {
+                    command: "sync_clients".into(),
+                    message: "operation timed out".into(),
+                }),
+            };
+
+            let mut seen = arp::arp_clients(lan_subnet, lan_ip).await;
+            if let Ok(ref clients) = clients {
+                seen.extend(clients.iter().copied());
+            }
+            seen.sort();
+            seen.dedup();
+            let nat_counters = Firewall::nat_counters().await.ok();
+
+            let _ = tx.send(AsyncOpResult::ClientsSynced {
+                clients,
+                connected: seen.len(),
+                nat_counters,
+            });
+        });
+    }
+
+    /// Toggle debug panel visibility.
+    fn toggle_debug(&mut self) {
+        self.show_debug = !self.show_debug;
+        if self.show_debug {
+            self.fetch_debug_info_async();
+        } else {
+            self.stash_debug_info();
+        }
+    }
+
+    /// Keep the current debug info as the baseline for the next fetch's diff.
+    fn stash_debug_info(&mut self) {
+        if let Some(info) = self.debug_info.take() {
+            self.debug_info_prev = Some(info);
+        }
+    }
+
+    /// Toggle DHCP server preference (only when sharing is inactive).
+    fn toggle_dhcp_preference(&mut self) {
+        // Only allow toggling if a DHCP daemon is installed
+        if self.dhcp_backend.is_none() {
+            self.log_warning_cat(
+                LogCategory::Dhcp,
+                "Cannot toggle DHCP: no DHCP server installed",
+            );
+            return;
+        }
+
+        self.dhcp_enabled = !self.dhcp_enabled;
+        if self.dhcp_enabled {
+            self.log_info_cat(LogCategory::Dhcp, "DHCP server enabled");
+        } else {
+            self.log_info_cat(
+                LogCategory::Dhcp,
+                "DHCP server disabled (manual router config required)",
+            );
+        }
+        self.save_preferences();
+    }
+
+    /// Toggle NAT-PMP server preference (only when sharing is inactive).
+    fn toggle_natpmp_preference(&mut self) {
+        self.natpmp_enabled = !self.natpmp_enabled;
+        if self.natpmp_enabled {
+            self.log_info_cat(LogCategory::NatPmp, "NAT-PMP server enabled");
+        } else {
+            self.log_info_cat(LogCategory::NatPmp, "NAT-PMP server disabled");
+        }
+        self.save_preferences();
+    }
+
+    /// Start NAT-PMP server (async).
+    ///
+    /// If `stop_pid` is set, that process (a conflicting NAT-PMP daemon) is
+    /// asked to exit first so the port can be bound.
+    fn start_natpmp_async(
+        &mut self,
+        vpn_name: String,
+        lan_name: String,
+        lan: Ipv4Net,
+        stop_pid: Option<u32>,
+    ) {
+        self.log_info_cat(LogCategory::NatPmp, "Starting NAT-PMP server...");
+        self.set_pending_op(PendingOp::StartingNatPmp);
+
+        let natpmp_anchor = self.natpmp_anchor.clone();
+        let preserve_existing = self.natpmp_preserve_existing;
+        let (port_low, port_high) = self.natpmp_port_range;
+        let external_ip = self.natpmp_external_ip;
+        let tx = self.op_tx.clone();
+
+        tokio::spawn(async move {
+            if let Some(pid) = stop_pid {
+                if let Err(e) = NatPmpServer::stop_port_holder(pid).await {
+                    let _ = tx.send(AsyncOpResult::NatPmpStarted {
+                        result: Err(e),
+                        server: None,
+                    });
+                    return;
+                }
+            }
+
+            let lan_network = lan.trunc().to_string();
+            let mut server = match NatPmpServer::new(&vpn_name, &lan_name, &lan_network)
+                .with_anchor(&natpmp_anchor)
+                .with_port_range(port_low, port_high)
+            {
+                Ok(server) => server.with_preserve_existing(preserve_existing),
+                Err(e) => {
+                    let _ = tx.send(AsyncOpResult::NatPmpStarted {
+                        result: Err(e),
+                        server: None,
+                    });
+                    return;
+                }
+            };
+            if let Some(ip) = external_ip {
+                server = server.with_external_ip(ip);
+            }
+
+            let result = tokio::time::timeout(TIMEOUT_START_NATPMP, server.start()).await;
+
+            let (result, server) = match result {
+                Ok(inner) => {
+                    let server = if inner.is_ok() { Some(server) } else { None };
+                    (inner, server)
+                }
+                Err(_) => (
+                    Err(crate::error::TunshareError::CommandFailed {
+                        command: "start_natpmp".into(),
+                        message: "operation timed out".into(),
+                    }),
+                    None,
+                ),
+            };
+
+            let _ = tx.send(AsyncOpResult::NatPmpStarted { result, server });
+        });
+    }
+
+    /// Stop the process holding the NAT-PMP port and retry starting NAT-PMP.
+    fn retry_natpmp_after_conflict(&mut self) {
+        let Some((pid, name)) = self.natpmp_conflict.take() else {
+            return;
+        };
+        let Some(session) = self.session.as_ref() else {
+            return;
+        };
+
+        let vpn_name = session.vpn_name.clone();
+        let lan_name = session.lan_name.clone();
+        let lan = session.lan_net();
+
+        self.log_info_cat(
+            LogCategory::NatPmp,
+            format!("Stopping {} (pid {})...", name, pid),
+        );
+        self.start_natpmp_async(vpn_name, lan_name, lan, Some(pid));
+    }
+
+    /// Handle keyboard input.
+    pub fn handle_key(&mut self, key: crossterm::event::KeyCode) {
+        // While an operation is pending, only allow quit and cancel
+        if self.pending_op.is_some() {
+            match key {
+                crossterm::event::KeyCode::Char('q') => {
+                    self.should_quit = true;
+                }
+                crossterm::event::KeyCode::Esc => {
+                    self.cancel_pending_op();
+                }
+                _ => {}
+            }
+            return;
+        }
+
+        // Browsing the expanded log takes over navigation until dismissed
+        if self.selected_log.is_some() {
+            self.handle_log_select_key(key);
+            return;
+        }
+        if key == crossterm::event::KeyCode::Tab && self.logs_expanded && !self.logs.is_empty() {
+            self.selected_log = Some(self.logs.len() - 1);
+            return;
+        }
+
+        if self.stop_summary.is_some()
+            && matches!(
+                key,
+                crossterm::event::KeyCode::Enter | crossterm::event::KeyCode::Esc
+            )
+        {
+            self.stop_summary = None;
+            return;
+        }
+
+        if key == crossterm::event::KeyCode::Char('u')
+            && self.dns_undo.is_some()
+            && matches!(self.state, AppState::Menu | AppState::Active)
+        {
+            self.undo_dns_change();
+            return;
+        }
+
+        // Debug panel controls (the panel can be open from Menu and Active)
+        if self.show_debug && matches!(self.state, AppState::Menu | AppState::Active) {
+            match key {
+                crossterm::event::KeyCode::Char('f') => {
+                    self.fetch_debug_info_async();
+                    return;
+                }
+                crossterm::event::KeyCode::Char('c') => {
+                    self.show_rule_diff = !self.show_rule_diff;
+                    return;
+                }
+                crossterm::event::KeyCode::Char('m') => {
+                    self.show_anchor_preview = !self.show_anchor_preview;
+                    self.selected_mapping = 0;
+                    return;
+                }
+                crossterm::event::KeyCode::Up | crossterm::event::KeyCode::Char('k')
+                    if self.show_anchor_preview =>
+                {
+                    self.selected_mapping = self.selected_mapping.saturating_sub(1);
+                    return;
+                }
+                crossterm::event::KeyCode::Down | crossterm::event::KeyCode::Char('j')
+                    if self.show_anchor_preview =>
+                {
+                    let count = self
+                        .session
+                        .as_ref()
+                        .map_or(0, |s| s.natpmp_mappings().len());
+                    self.selected_mapping =
+                        (self.selected_mapping + 1).min(count.saturating_sub(1));
+                    return;
+                }
+                crossterm::event::KeyCode::Char('x') | crossterm::event::KeyCode::Delete
+                    if self.show_anchor_preview =>
+                {
+                    self.remove_selected_mapping();
+                    return;
+                }
+                crossterm::event::KeyCode::Char('Y') => {
+                    self.copy_debug_report();
+                    return;
+                }
+                crossterm::event::KeyCode::Char('p') => {
+                    self.show_pf_states = !self.show_pf_states;
+                    self.pf_state_scroll = 0;
+                    return;
+                }
+                crossterm::event::KeyCode::PageDown if self.show_pf_states => {
+                    let count = self.debug_info.as_ref().map_or(0, |i| i.pf_states.len());
+                    self.pf_state_scroll =
+                        (self.pf_state_scroll + PF_STATE_PAGE).min(count.saturating_sub(1));
+                    return;
+                }
+                crossterm::event::KeyCode::PageUp if self.show_pf_states => {
+                    self.pf_state_scroll = self.pf_state_scroll.saturating_sub(PF_STATE_PAGE);
+                    return;
+                }
+                _ => {}
+            }
+        }
+
+        match self.state {
+            AppState::Menu => self.handle_menu_key(key),
+            AppState::SelectingVpn => self.handle_vpn_select_key(key),
+            AppState::SelectingLan => self.handle_lan_select_key(key),
+            AppState::Active => self.handle_active_key(key),
+            AppState::EditingDns => self.handle_dns_edit_key(key),
+            AppState::SelectingProfile => self.handle_profile_select_key(key),
+            AppState::NamingProfile => self.handle_profile_name_key(key),
+            AppState::NamingLabel => self.handle_label_key(key),
+            AppState::NoInterfaces => self.handle_no_interfaces_key(key),
+        }
+    }
+
+    /// `E` (previous error) and `L` (log level) on the expanded log. Only for
+    /// states where letters aren't typed into a field.
+    fn handle_log_shortcut_key(&mut self, key: crossterm::event::KeyCode) -> bool {
+        use crossterm::event::KeyCode;
+
+        if !self.logs_expanded {
+            return false;
+        }
+        match key {
+            KeyCode::Char('E') => self.select_previous_error(self.logs.len()),
+            KeyCode::Char('L') => self.cycle_log_level(),
+            _ => return false,
+        }
+        true
+    }
+
+    fn handle_no_interfaces_key(&mut self, key: crossterm::event::KeyCode) {
+        use crossterm::event::KeyCode;
+
+        if self.handle_log_shortcut_key(key) {
+            return;
+        }
+        match key {
+            KeyCode::Char('r') | KeyCode::Enter => self.refresh_interfaces_async(),
+            KeyCode::Char('l') => self.toggle_logs_expanded(),
+            KeyCode::Esc => self.state = AppState::Menu,
+            KeyCode::Char('q') => self.quit(),
+            _ => {}
+        }
+    }
+
+    fn handle_log_select_key(&mut self, key: crossterm::event::KeyCode) {
+        use crossterm::event::KeyCode;
+
+        if self.show_log_detail {
+            if matches!(key, KeyCode::Esc | KeyCode::Enter) {
+                self.show_log_detail = false;
+            }
+            return;
+        }
+
+        let Some(selected) = self.selected_log else {
+            return;
+        };
+
+        match key {
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.selected_log = Some(selected.saturating_sub(1));
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected_log = Some((selected + 1).min(self.logs.len().saturating_sub(1)));
+            }
+            KeyCode::Enter => {
+                self.show_log_detail = true;
+            }
+            KeyCode::Char('E') => {
+                self.select_previous_error(selected);
+            }
+            KeyCode::Char('L') => self.cycle_log_level(),
+            KeyCode::Esc | KeyCode::Tab => {
+                self.selected_log = None;
+            }
+            KeyCode::Char('l') => {
+                self.toggle_logs_expanded();
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_menu_key(&mut self, key: crossterm::event::KeyCode) {
+        use crossterm::event::KeyCode;
+
+        if self.handle_log_shortcut_key(key) {
+            return;
+        }
+        // The debug panel hides the menu; don't act on it blindly
+        if self.show_debug
+            && matches!(
+                key,
+                KeyCode::Up
+                    | KeyCode::Down
+                    | KeyCode::Enter
+                    | KeyCode::Char('k' | 'j' | '1' | '2' | '3')
+            )
+        {
+            return;
+        }
+        let items = self.menu_items();
+
+        match key {
+            KeyCode::Up | KeyCode::Char('k') if self.selected_menu_item > 0 => {
+                self.selected_menu_item -= 1;
+            }
+            KeyCode::Down | KeyCode::Char('j')
+                if self.selected_menu_item < items.len().saturating_sub(1) =>
+            {
+                self.selected_menu_item += 1;
+            }
+            KeyCode::Enter => {
+                if let Some(item) = items.get(self.selected_menu_item) {
+                    match item {
+                        MenuItem::StartSharing => self.start_interface_selection(),
+                        MenuItem::Profiles => self.open_profile_picker(),
+                        MenuItem::CleanUpLeftovers => self.clean_up_leftovers_async(),
+                        MenuItem::StopSharing => self.stop_sharing_async(),
+                        MenuItem::RestartSharing => self.restart_sharing_async(),
+                        MenuItem::ToggleDhcp => self.toggle_dhcp_preference(),
+                        MenuItem::ToggleNatPmp => self.toggle_natpmp_preference(),
+                        MenuItem::SetDns => self.start_dns_edit(),
+                        MenuItem::Quit => self.quit(),
+                    }
+                }
+            }
+            KeyCode::Char('1') => {
+                if let Some(MenuItem::StartSharing) = items.first() {
+                    self.start_interface_selection();
+                } else if let Some(MenuItem::StopSharing) = items.first() {
+                    self.stop_sharing_async();
+                }
+            }
+            KeyCode::Char('2') if items.len() > 1 => match items[1] {
+                MenuItem::Profiles => self.open_profile_picker(),
+                MenuItem::Quit => self.quit(),
+                MenuItem::StopSharing => self.stop_sharing_async(),
+                MenuItem::RestartSharing => self.restart_sharing_async(),
+                _ => {}
+            },
+            KeyCode::Char('3') if items.len() > 2 => {
+                if let MenuItem::Quit = items[2] {
+                    self.quit();
+                }
+            }
+            KeyCode::Char('q') => self.quit(),
+            KeyCode::Char('N') => self.start_label_edit(),
+            KeyCode::Char('r') => {
+                if let Some((vpn_name, lan_name, lan_ip)) = self.start_retry.take() {
+                    self.start_sharing_async(vpn_name, lan_name, lan_ip, None);
+                }
+            }
+            // Also before sharing, to check what a previous run left behind
+            KeyCode::Char('d') => {
+                self.toggle_debug();
+            }
+            KeyCode::Esc if self.show_debug => {
+                self.show_debug = false;
+                self.stash_debug_info();
+            }
+            KeyCode::Char('l') => {
+                self.toggle_logs_expanded();
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_vpn_select_key(&mut self, key: crossterm::event::KeyCode) {
+        use crossterm::event::KeyCode;
+
+        if self.handle_interface_filter_key(key) || self.handle_log_shortcut_key(key) {
+            return;
+        }
+
+        let visible = self.filtered_indices(&self.vpn_interfaces);
+        match key {
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.selected_vpn = step_selection(self.selected_vpn, &visible, false);
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected_vpn = step_selection(self.selected_vpn, &visible, true);
+            }
+            KeyCode::Char(' ') => {
+                let Some(vpn) = self.selected_vpn.and_then(|i| self.vpn_interfaces.get(i)) else {
+                    return;
+                };
+                let name = vpn.name.clone();
+                if let Some(pos) = self.extra_uplinks.iter().position(|n| *n == name) {
+                    self.extra_uplinks.remove(pos);
+                } else {
+                    self.extra_uplinks.push(name);
+                }
+            }
+            KeyCode::Char('f') => {
+                let Some(vpn) = self.selected_vpn.and_then(|i| self.vpn_interfaces.get(i)) else {
+                    return;
+                };
+                let name = vpn.name.clone();
+                if self.failover_vpn.as_ref() == Some(&name) {
+                    self.failover_vpn = None;
+                } else {
+                    self.failover_vpn = Some(name);
+                }
+            }
+            KeyCode::Enter => {
+                if let Some(vpn_idx) = self.selected_vpn {
+                    if let Some(vpn) = self.vpn_interfaces.get(vpn_idx) {
+                        let name = vpn.name.clone();
+                        // The highlighted interface is the primary uplink
+                        self.extra_uplinks.retain(|n| *n != name);
+                        if self.failover_vpn.as_ref() == Some(&name) {
+                            self.failover_vpn = None;
+                        }
+                        if !self.extra_uplinks.is_empty() {
+                            self.log_info_cat(
+                                LogCategory::Network,
+                                format!(
+                                    "Balancing across {} and {}; {} is primary (NAT-PMP)",
+                                    name,
+                                    self.extra_uplinks.join(", "),
+                                    name
+                                ),
+                            );
+                        }
+                        self.clear_interface_filter();
+                        // Discover DNS for selected VPN (async)
+                        self.discover_dns_async(name);
+                    }
+                }
+            }
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.state = AppState::Menu;
+                self.log_info("Cancelled interface selection");
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_lan_select_key(&mut self, key: crossterm::event::KeyCode) {
+        use crossterm::event::KeyCode;
+
+        if self.handle_interface_filter_key(key) || self.handle_log_shortcut_key(key) {
+            return;
+        }
+
+        let visible = self.filtered_indices(&self.lan_interfaces);
+        match key {
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.selected_lan = step_selection(self.selected_lan, &visible, false);
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected_lan = step_selection(self.selected_lan, &visible, true);
+            }
+            KeyCode::Enter => {
+                self.start_selected_sharing();
+            }
+            KeyCode::Char('e') => self.cycle_natpmp_external_ip(),
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.state = AppState::SelectingVpn;
+                self.log_info("Back to VPN selection");
+            }
+            KeyCode::Backspace => {
+                self.state = AppState::SelectingVpn;
+                self.log_info("Back to VPN selection");
+            }
+            _ => {}
+        }
+    }
+
+    /// Advertise the selected VPN's next address over NAT-PMP, and remember it.
+    fn cycle_natpmp_external_ip(&mut self) {
+        let addresses = self.selected_vpn_addresses();
+        if addresses.len() < 2 {
+            return;
+        }
+        let next = self
+            .natpmp_external_ip
+            .and_then(|ip| addresses.iter().position(|a| *a == ip))
+            .map_or(0, |i| (i + 1) % addresses.len());
+        let ip = addresses[next];
+
+        self.natpmp_external_ip = Some(ip);
+        self.natpmp_external_preference = Some(ip);
+        self.save_preferences();
+        self.log_info_cat(
+            LogCategory::NatPmp,
+            format!("NAT-PMP will advertise {} as the external address", ip),
+        );
+    }
+
+    /// Indices of `interfaces` that match the list filter, in list order.
+    pub fn filtered_indices(&self, interfaces: &[InterfaceInfo]) -> Vec<usize> {
+        interfaces
+            .iter()
+            .enumerate()
+            .filter(|(_, iface)| iface.matches_filter(&self.filter_buffer))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Filter keys shared by both interface lists. Returns true if the key was consumed.
+    ///
+    /// `/` starts typing; while typing, characters and Backspace edit the
+    /// filter and arrows/Enter still work on the list. Esc clears the filter.
+    fn handle_interface_filter_key(&mut self, key: crossterm::event::KeyCode) -> bool {
+        use crossterm::event::KeyCode;
+
+        match key {
+            KeyCode::Char('/') if !self.filtering => {
+                self.filtering = true;
+            }
+            KeyCode::Esc if self.filtering || !self.filter_buffer.is_empty() => {
+                self.clear_interface_filter();
+            }
+            KeyCode::Char(c) if self.filtering => {
+                self.filter_buffer.push(c);
+                self.snap_selection_to_filter();
+            }
+            KeyCode::Backspace if self.filtering => {
+                self.filter_buffer.pop();
+                self.snap_selection_to_filter();
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// Keep the current list's selection on a visible entry after the filter changed.
+    fn snap_selection_to_filter(&mut self) {
+        let (interfaces, selected) = match self.state {
+            AppState::SelectingVpn => (&self.vpn_interfaces, self.selected_vpn),
+            AppState::SelectingLan => (&self.lan_interfaces, self.selected_lan),
+            _ => return,
+        };
+        let visible = self.filtered_indices(interfaces);
+        let snapped = match selected {
+            Some(i) if visible.contains(&i) => Some(i),
+            _ => visible.first().copied(),
+        };
+        match self.state {
+            AppState::SelectingVpn => self.selected_vpn = snapped,
+            _ => self.selected_lan = snapped,
+        }
+    }
+
+    /// Drop the list filter and bring back a selection if filtering had emptied it.
+    fn clear_interface_filter(&mut self) {
+        self.filter_buffer.clear();
+        self.filtering = false;
+        if self.selected_vpn.is_none() && !self.vpn_interfaces.is_empty() {
+            self.selected_vpn = Some(0);
+        }
+        if self.selected_lan.is_none() && !self.lan_interfaces.is_empty() {
+            self.selected_lan = Some(0);
+        }
+    }
+
+    fn handle_active_key(&mut self, key: crossterm::event::KeyCode) {
+        use crossterm::event::KeyCode;
+
+        if self.handle_log_shortcut_key(key) {
+            return;
+        }
+        match key {
+            KeyCode::Char('s') | KeyCode::Enter => {
+                self.stop_sharing_async();
+            }
+            KeyCode::Char('q') => {
+                self.should_quit = true;
+                self.stop_sharing_async();
+            }
+            KeyCode::Char('r') => {
+                self.restart_sharing_async();
+            }
+            KeyCode::Char('R') => {
+                self.reload_rules_async();
+            }
+            KeyCode::Char('t') => {
+                self.probe_forwards_async();
+            }
+            KeyCode::Char('T') => {
+                self.probe_client_dns_async();
+            }
+            KeyCode::Char('a') => {
+                self.profile_name_input.clear();
+                self.state = AppState::NamingProfile;
+            }
+            KeyCode::Char('N') => self.start_label_edit(),
+            KeyCode::Char('y') => {
+                self.copy_client_setup();
+            }
+            KeyCode::Char('h') => {
+                self.toggle_live_dhcp();
+            }
+            KeyCode::Char('H') => {
+                self.toggle_health_pause();
+            }
+            KeyCode::Char('n') => {
+                self.toggle_live_natpmp();
+            }
+            KeyCode::Char('d') => {
+                self.toggle_debug();
+            }
+            KeyCode::Char('k') if self.natpmp_conflict.is_some() => {
+                self.retry_natpmp_after_conflict();
+            }
+            KeyCode::Char('l') => {
+                self.toggle_logs_expanded();
+            }
+            KeyCode::Char('v') => {
+                self.diagnose_async();
+            }
+            KeyCode::Esc => {
+                if self.diagnostic_facts.is_some() {
+                    self.diagnostic_facts = None;
+                } else if self.probe_report.is_some() {
+                    self.probe_report = None;
+                } else if self.dns_probe_report.is_some() {
+                    self.dns_probe_report = None;
+                } else if self.show_debug {
+                    self.show_debug = false;
+                    self.stash_debug_info();
+                } else {
+                    self.state = AppState::Menu;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Copy the join snippet for another Mac to the clipboard.
+    fn copy_client_setup(&mut self) {
+        let snippet = self.client_setup_command();
+        if snippet.is_empty() {
             return;
-        };
-
-        let tx = self.op_tx.clone();
-        let vpn_name = session.vpn_name.clone();
-
-        // Bump the timer regardless of outcome
-        self.next_health_check = Some(Instant::now() + HEALTH_CHECK_INTERVAL);
-
-        tokio::spawn(async move {
-            let status =
-                tokio::time::timeout(TIMEOUT_HEALTH_CHECK, health::check_health(&vpn_name))
-                    .await
-                    .unwrap_or(HealthStatus::Healthy); // Timeout = assume OK
-
-            let _ = tx.send(AsyncOpResult::HealthCheck { status });
-        });
+        }
+        match clipboard::copy(&snippet) {
+            Ok(()) => self.log_success(format!(
+                "Copied join commands for another Mac (edit SERVICE if it isn't {})",
+                self.client_setup_service()
+            )),
+            Err(e) => {
+                self.log_warning(format!("Couldn't copy join commands: {}", e));
+                for line in snippet.lines() {
+                    self.log_info(line.to_string());
+                }
+            }
+        }
     }
 
-    /// Toggle debug panel visibility.
-    fn toggle_debug(&mut self) {
-        self.show_debug = !self.show_debug;
-        if self.show_debug {
-            self.fetch_debug_info_async();
-        } else {
-            self.debug_info = None;
+    /// The debug panel as a plain-text bug report: version, what's shared,
+    /// the detected interfaces, then the status summary and pf rules.
+    fn debug_report(&self, info: &DebugInfo) -> String {
+        let mut report = format!("tunshare {}\n", env!("CARGO_PKG_VERSION"));
+        match &self.session {
+            Some(session) => report.push_str(&format!(
+                "Sharing: {} -> {}\n",
+                session.vpn_name, session.lan_name
+            )),
+            None => report.push_str("Sharing: no\n"),
+        }
+        report.push_str("\nInterfaces\n");
+        for (kind, interfaces) in [("VPN", &self.vpn_interfaces), ("LAN", &self.lan_interfaces)] {
+            for interface in interfaces {
+                let addresses: Vec<String> = interface
+                    .ipv4_addresses
+                    .iter()
+                    .map(|ip| ip.to_string())
+                    .collect();
+                report.push_str(&format!(
+                    "  {} {}: {}{}\n",
+                    kind,
+                    interface.display_label(),
+                    if addresses.is_empty() {
+                        "no IPv4".to_string()
+                    } else {
+                        addresses.join(", ")
+                    },
+                    if interface.is_up { "" } else { " (down)" }
+                ));
+            }
         }
+        report.push('\n');
+        report.push_str(&info.to_report_string());
+        report
     }
 
-    /// Toggle DHCP server preference (only when sharing is inactive).
-    fn toggle_dhcp_preference(&mut self) {
-        // Only allow toggling if dnsmasq is installed
-        if !self.dnsmasq_installed {
-            self.log_warning("Cannot toggle DHCP: dnsmasq not installed");
+    /// Copy the debug report to the clipboard, for pasting into a bug report.
+    fn copy_debug_report(&mut self) {
+        let Some(info) = self.debug_info.as_ref() else {
+            self.log_warning("No debug info yet; press f to fetch it");
             return;
+        };
+        let report = self.debug_report(info);
+        match clipboard::copy(&report) {
+            Ok(()) => self.log_success(format!("Copied debug report ({} bytes)", report.len())),
+            Err(e) => self.log_warning(format!("Couldn't copy debug report: {}", e)),
         }
+    }
 
-        self.dhcp_enabled = !self.dhcp_enabled;
-        if self.dhcp_enabled {
-            self.log_info("DHCP server enabled");
-        } else {
-            self.log_info("DHCP server disabled (manual router config required)");
+    /// Apply a new custom DNS server (None = auto-detect), keeping the old ones for undo.
+    /// Replaces any `--dns` servers for the rest of the run.
+    ///
+    /// Returns false, changing nothing, if `custom` isn't an IP address (a
+    /// preset from the config file can hold anything).
+    fn commit_dns_change(&mut self, custom: Option<String>) -> bool {
+        if let Some(server) = custom
+            .as_deref()
+            .filter(|s| !config::is_valid_dns_server(s))
+        {
+            self.log_error_cat(
+                LogCategory::Dns,
+                format!("{:?} is not an IP address; DNS left unchanged", server),
+            );
+            return false;
         }
+        let previous = self
+            .dns
+            .custom
+            .iter()
+            .chain(&self.dns.extra)
+            .cloned()
+            .collect();
+        if custom.iter().ne(self.dns.custom_servers().iter()) {
+            self.dns_undo = Some((previous, Instant::now()));
+        }
+        self.dns.custom = custom;
+        self.dns.extra.clear();
+        self.dns.cli.clear();
         self.save_preferences();
+        true
     }
 
-    /// Toggle NAT-PMP server preference (only when sharing is inactive).
-    fn toggle_natpmp_preference(&mut self) {
-        self.natpmp_enabled = !self.natpmp_enabled;
-        if self.natpmp_enabled {
-            self.log_info("NAT-PMP server enabled");
-        } else {
-            self.log_info("NAT-PMP server disabled");
-        }
+    /// Restore the DNS setting from before the last change.
+    fn undo_dns_change(&mut self) {
+        let Some((previous, _)) = self.dns_undo.take() else {
+            return;
+        };
+        let mut previous = previous.into_iter();
+        self.dns.custom = previous.next();
+        self.dns.extra = previous.collect();
         self.save_preferences();
+        self.log_info_cat(
+            LogCategory::Dns,
+            format!(
+                "DNS change undone, back to {}",
+                self.dns.custom.as_deref().unwrap_or("auto-detect")
+            ),
+        );
     }
 
-    /// Start NAT-PMP server (async).
-    fn start_natpmp_async(&mut self, vpn_name: String, lan_name: String, lan_ip: Ipv4Addr) {
-        self.log_info("Starting NAT-PMP server...");
-        self.set_pending_op(PendingOp::StartingNatPmp);
-
-        let tx = self.op_tx.clone();
-
-        tokio::spawn(async move {
-            let lan_network = NatPmpServer::network_from_ip(lan_ip);
-            let server = NatPmpServer::new(&vpn_name, &lan_name, &lan_network);
-
-            let result = tokio::time::timeout(TIMEOUT_START_NATPMP, server.start()).await;
-
-            let (result, server) = match result {
-                Ok(inner) => {
-                    let server = if inner.is_ok() { Some(server) } else { None };
-                    (inner, server)
-                }
-                Err(_) => (
-                    Err(crate::error::TunshareError::CommandFailed {
-                        command: "start_natpmp".into(),
-                        message: "operation timed out".into(),
-                    }),
-                    None,
-                ),
-            };
-
-            let _ = tx.send(AsyncOpResult::NatPmpStarted { result, server });
-        });
+    /// Toast text while a DNS change can still be undone.
+    pub fn dns_undo_toast(&self) -> Option<String> {
+        let (_, changed) = self.dns_undo.as_ref()?;
+        let remaining = DNS_UNDO_WINDOW.saturating_sub(changed.elapsed());
+        let current = self.dns.custom.as_deref().unwrap_or("auto-detect");
+        Some(format!(
+            "DNS changed to {} \u{2014} press u to undo ({}s)",
+            current,
+            remaining.as_secs() + 1
+        ))
     }
 
-    /// Handle keyboard input.
-    pub fn handle_key(&mut self, key: crossterm::event::KeyCode) {
-        // While an operation is pending, only allow quit and cancel
-        if self.pending_op.is_some() {
-            match key {
-                crossterm::event::KeyCode::Char('q') => {
-                    self.should_quit = true;
-                }
-                crossterm::event::KeyCode::Esc => {
-                    self.cancel_pending_op();
-                }
-                _ => {}
-            }
+    /// Open the profile picker.
+    fn open_profile_picker(&mut self) {
+        if self.refuse_read_only() {
             return;
         }
-
-        match self.state {
-            AppState::Menu => self.handle_menu_key(key),
-            AppState::SelectingVpn => self.handle_vpn_select_key(key),
-            AppState::SelectingLan => self.handle_lan_select_key(key),
-            AppState::Active => self.handle_active_key(key),
-            AppState::EditingDns => self.handle_dns_edit_key(key),
+        if self.profiles.is_empty() {
+            self.log_info("No profiles yet. While sharing, press a to save the setup as one.");
         }
+        self.selected_profile = 0;
+        self.state = AppState::SelectingProfile;
     }
 
-    fn handle_menu_key(&mut self, key: crossterm::event::KeyCode) {
+    fn handle_profile_select_key(&mut self, key: crossterm::event::KeyCode) {
         use crossterm::event::KeyCode;
 
-        let items = self.menu_items();
-
+        if self.handle_log_shortcut_key(key) {
+            return;
+        }
         match key {
             KeyCode::Up | KeyCode::Char('k') => {
-                if self.selected_menu_item > 0 {
-                    self.selected_menu_item -= 1;
-                }
+                self.selected_profile = self.selected_profile.saturating_sub(1);
             }
             KeyCode::Down | KeyCode::Char('j') => {
-                if self.selected_menu_item < items.len().saturating_sub(1) {
-                    self.selected_menu_item += 1;
-                }
+                self.selected_profile =
+                    (self.selected_profile + 1).min(self.profiles.len().saturating_sub(1));
             }
             KeyCode::Enter => {
-                if let Some(item) = items.get(self.selected_menu_item) {
-                    match item {
-                        MenuItem::StartSharing => self.start_interface_selection(),
-                        MenuItem::StopSharing => self.stop_sharing_async(),
-                        MenuItem::ToggleDhcp => self.toggle_dhcp_preference(),
-                        MenuItem::ToggleNatPmp => self.toggle_natpmp_preference(),
-                        MenuItem::SetDns => self.start_dns_edit(),
-                        MenuItem::Quit => self.quit(),
-                    }
-                }
-            }
-            KeyCode::Char('1') => {
-                if let Some(MenuItem::StartSharing) = items.first() {
-                    self.start_interface_selection();
-                } else if let Some(MenuItem::StopSharing) = items.first() {
-                    self.stop_sharing_async();
-                }
-            }
-            KeyCode::Char('2') => {
-                if items.len() > 1 {
-                    match items[1] {
-                        MenuItem::Quit => self.quit(),
-                        MenuItem::StopSharing => self.stop_sharing_async(),
-                        _ => {}
-                    }
+                if let Some(profile) = self.profiles.get(self.selected_profile).cloned() {
+                    self.log_info(format!("Starting profile \"{}\"", profile.name));
+                    self.state = AppState::Menu;
+                    self.launching_profile = Some(profile);
+                    self.refresh_interfaces_async();
                 }
             }
-            KeyCode::Char('q') => self.quit(),
-            KeyCode::Char('d') if self.is_sharing() => {
-                self.toggle_debug();
+            KeyCode::Char('x') | KeyCode::Delete if self.selected_profile < self.profiles.len() => {
+                let removed = self.profiles.remove(self.selected_profile);
+                self.selected_profile = self
+                    .selected_profile
+                    .min(self.profiles.len().saturating_sub(1));
+                self.save_preferences();
+                self.log_info(format!("Deleted profile \"{}\"", removed.name));
             }
-            KeyCode::Char('l') => {
-                self.logs_expanded = !self.logs_expanded;
+            KeyCode::Esc | KeyCode::Char('q') => {
+                self.state = AppState::Menu;
             }
             _ => {}
         }
     }
 
-    fn handle_vpn_select_key(&mut self, key: crossterm::event::KeyCode) {
+    /// Open the label prompt, starting from the current label.
+    fn start_label_edit(&mut self) {
+        self.label_input = self.session_label.clone().unwrap_or_default();
+        self.state = AppState::NamingLabel;
+    }
+
+    /// Handle key input in the label prompt. The label lasts for this run;
+    /// `session_label` in the config file sets the default.
+    fn handle_label_key(&mut self, key: crossterm::event::KeyCode) {
         use crossterm::event::KeyCode;
 
         match key {
-            KeyCode::Up | KeyCode::Char('k') => {
-                if let Some(idx) = self.selected_vpn {
-                    if idx > 0 {
-                        self.selected_vpn = Some(idx - 1);
-                    }
-                }
+            KeyCode::Char(c) if !c.is_control() && self.label_input.chars().count() < 24 => {
+                self.label_input.push(c);
             }
-            KeyCode::Down | KeyCode::Char('j') => {
-                if let Some(idx) = self.selected_vpn {
-                    if idx < self.vpn_interfaces.len().saturating_sub(1) {
-                        self.selected_vpn = Some(idx + 1);
-                    }
-                }
+            KeyCode::Backspace => {
+                self.label_input.pop();
             }
             KeyCode::Enter => {
-                if let Some(vpn_idx) = self.selected_vpn {
-                    if let Some(vpn) = self.vpn_interfaces.get(vpn_idx) {
-                        // Discover DNS for selected VPN (async)
-                        self.discover_dns_async(vpn.name.clone());
-                    }
+                let label = self.label_input.trim().to_string();
+                self.session_label = Some(label).filter(|l| !l.is_empty());
+                match &self.session_label {
+                    Some(label) => self.log_info(format!("Instance labeled {}", label)),
+                    None => self.log_info("Instance label cleared"),
                 }
+                self.close_label_edit();
             }
-            KeyCode::Esc | KeyCode::Char('q') => {
-                self.state = AppState::Menu;
-                self.log_info("Cancelled interface selection");
-            }
+            KeyCode::Esc => self.close_label_edit(),
             _ => {}
         }
     }
 
-    fn handle_lan_select_key(&mut self, key: crossterm::event::KeyCode) {
+    /// Leave the label prompt for the screen it was opened from.
+    fn close_label_edit(&mut self) {
+        self.label_input.clear();
+        self.state = if self.is_sharing() {
+            AppState::Active
+        } else {
+            AppState::Menu
+        };
+    }
+
+    fn handle_profile_name_key(&mut self, key: crossterm::event::KeyCode) {
         use crossterm::event::KeyCode;
 
         match key {
-            KeyCode::Up | KeyCode::Char('k') => {
-                if let Some(idx) = self.selected_lan {
-                    if idx > 0 {
-                        self.selected_lan = Some(idx - 1);
-                    }
-                }
+            KeyCode::Char(c) if !c.is_control() && self.profile_name_input.chars().count() < 32 => {
+                self.profile_name_input.push(c);
             }
-            KeyCode::Down | KeyCode::Char('j') => {
-                if let Some(idx) = self.selected_lan {
-                    if idx < self.lan_interfaces.len().saturating_sub(1) {
-                        self.selected_lan = Some(idx + 1);
-                    }
-                }
+            KeyCode::Backspace => {
+                self.profile_name_input.pop();
             }
             KeyCode::Enter => {
-                if let Some(vpn_idx) = self.selected_vpn {
-                    if let Some(lan_idx) = self.selected_lan {
-                        if let (Some(vpn), Some(lan)) = (
-                            self.vpn_interfaces.get(vpn_idx),
-                            self.lan_interfaces.get(lan_idx),
-                        ) {
-                            self.start_sharing_async(
-                                vpn.name.clone(),
-                                lan.name.clone(),
-                                lan.ipv4_address,
-                            );
-                        }
-                    }
+                let name = self.profile_name_input.trim().to_string();
+                if !name.is_empty() {
+                    self.save_current_as_profile(name);
                 }
+                self.profile_name_input.clear();
+                self.state = AppState::Active;
             }
-            KeyCode::Esc | KeyCode::Char('q') => {
-                self.state = AppState::SelectingVpn;
-                self.log_info("Back to VPN selection");
-            }
-            KeyCode::Backspace => {
-                self.state = AppState::SelectingVpn;
-                self.log_info("Back to VPN selection");
+            KeyCode::Esc => {
+                self.profile_name_input.clear();
+                self.state = AppState::Active;
             }
             _ => {}
         }
     }
 
-    fn handle_active_key(&mut self, key: crossterm::event::KeyCode) {
+    /// Save the active interfaces and settings as a profile, replacing one with the same name.
+    fn save_current_as_profile(&mut self, name: String) {
+        let Some(session) = self.session.as_ref() else {
+            return;
+        };
+
+        let profile = Profile {
+            name: name.clone(),
+            vpn: session.vpn_name.clone(),
+            lan: session.lan_name.clone(),
+            dns: self.dns.custom.clone(),
+            dns_extra: self.dns.extra.clone(),
+            dhcp_enabled: self.dhcp_enabled,
+            natpmp_enabled: self.natpmp_enabled,
+            failover_vpn: session.failover_vpn.clone(),
+        };
+        let summary = format!("{} -> {}", profile.vpn, profile.lan);
+
+        if let Some(existing) = self.profiles.iter_mut().find(|p| p.name == name) {
+            *existing = profile;
+        } else {
+            self.profiles.push(profile);
+        }
+        self.save_preferences();
+        self.log_success(format!("Saved profile \"{}\" ({})", name, summary));
+    }
+
+    /// Apply a profile once interfaces are detected and start sharing from it.
+    ///
+    /// Returns false (leaving the wizard to take over) if a referenced
+    /// interface isn't present.
+    fn launch_profile(&mut self, profile: Profile) -> bool {
+        let vpn_idx = self
+            .vpn_interfaces
+            .iter()
+            .position(|i| i.name == profile.vpn);
+        let lan_idx = self
+            .lan_interfaces
+            .iter()
+            .position(|i| i.name == profile.lan);
+
+        let (Some(vpn_idx), Some(lan_idx)) = (vpn_idx, lan_idx) else {
+            let missing: Vec<&str> = [
+                (vpn_idx.is_none(), profile.vpn.as_str()),
+                (lan_idx.is_none(), profile.lan.as_str()),
+            ]
+            .into_iter()
+            .filter_map(|(missing, name)| missing.then_some(name))
+            .collect();
+            self.log_error_cat(
+                LogCategory::Network,
+                format!(
+                    "Profile \"{}\": interface not found: {}",
+                    profile.name,
+                    missing.join(", ")
+                ),
+            );
+            return false;
+        };
+
+        self.selected_vpn = Some(vpn_idx);
+        self.selected_lan = Some(lan_idx);
+        self.apply_profile(&profile);
+        self.natpmp_external_ip = natpmp::select_external_ip(
+            self.selected_vpn_addresses(),
+            self.natpmp_external_preference,
+        );
+
+        if !self.dns.custom_servers().is_empty() {
+            self.start_selected_sharing();
+        } else {
+            // Auto-detected DNS needs discovery before DHCP can advertise it
+            self.launching_profile = Some(profile);
+            self.discover_dns_async(self.vpn_interfaces[vpn_idx].name.clone());
+        }
+        true
+    }
+
+    /// Switch to a profile's settings for the session it starts, remembering
+    /// the user's own so they can be put back.
+    fn apply_profile(&mut self, profile: &Profile) {
+        if self.profile_restore.is_none() {
+            self.profile_restore = Some(ProfileRestore {
+                dns_custom: self.dns.custom.clone(),
+                dns_extra: self.dns.extra.clone(),
+                failover_vpn: self.failover_vpn.clone(),
+                dhcp_enabled: self.dhcp_enabled,
+                natpmp_enabled: self.natpmp_enabled,
+            });
+        }
+        self.dns.custom = profile.dns.clone();
+        self.dns.extra = profile.dns_extra.clone();
+        self.failover_vpn = profile.failover_vpn.clone();
+        self.dhcp_enabled = profile.dhcp_enabled && self.dhcp_backend.is_some();
+        self.natpmp_enabled = profile.natpmp_enabled;
+    }
+
+    /// Put back the settings a launched profile replaced, if any.
+    fn end_profile_session(&mut self) {
+        if let Some(saved) = self.profile_restore.take() {
+            self.dns.custom = saved.dns_custom;
+            self.dns.extra = saved.dns_extra;
+            self.failover_vpn = saved.failover_vpn;
+            self.dhcp_enabled = saved.dhcp_enabled;
+            self.natpmp_enabled = saved.natpmp_enabled;
+        }
+    }
+
+    /// Start sharing between the currently selected VPN and LAN interfaces.
+    ///
+    /// A LAN with a public address needs a second confirmation (Enter again on
+    /// the LAN list), since NATing onto it exposes the whole segment.
+    fn start_selected_sharing(&mut self) {
+        let (Some(vpn), Some(lan)) = (
+            self.selected_vpn.and_then(|i| self.vpn_interfaces.get(i)),
+            self.selected_lan.and_then(|i| self.lan_interfaces.get(i)),
+        ) else {
+            return;
+        };
+        let (vpn_name, lan_name, lan_ip) = (vpn.name.clone(), lan.name.clone(), lan.ipv4_address);
+        let lan_label = lan.display_label();
+
+        if lan_ip.is_none() {
+            let address = self.lan_gateway_ip.unwrap_or(ifaddr::DEFAULT_GATEWAY);
+            if self.lan_address_confirmation.take() != self.selected_lan {
+                self.log_warning_cat(
+                    LogCategory::Network,
+                    format!("{} has no IPv4 address", lan_label),
+                );
+                self.log_warning_cat(
+                    LogCategory::Network,
+                    format!(
+                        "Press Enter again to assign {} to it (removed when sharing stops), or pick another interface",
+                        address
+                    ),
+                );
+                self.lan_address_confirmation = self.selected_lan;
+                self.state = AppState::SelectingLan;
+                return;
+            }
+            self.clear_interface_filter();
+            self.assign_lan_address_async(vpn_name, lan_name, address);
+            return;
+        }
+
+        let public = lan_ip.is_some() && !lan.is_private_ipv4();
+        if public && self.public_lan_confirmation.take() != self.selected_lan {
+            self.log_warning_cat(
+                LogCategory::Network,
+                format!(
+                    "{} has a public address ({}), not a private LAN range",
+                    lan_label,
+                    lan_ip.map(|ip| ip.to_string()).unwrap_or_default()
+                ),
+            );
+            self.log_warning_cat(
+                LogCategory::Network,
+                "Press Enter again to share onto it anyway, or pick another interface",
+            );
+            self.public_lan_confirmation = self.selected_lan;
+            self.state = AppState::SelectingLan;
+            return;
+        }
+        self.public_lan_confirmation = None;
+        self.clear_interface_filter();
+        self.start_sharing_async(vpn_name, lan_name, lan_ip, None);
+    }
+
+    /// Assign `address` to a LAN interface that has no IPv4, then start sharing onto it.
+    fn assign_lan_address_async(&mut self, vpn_name: String, lan_name: String, address: Ipv4Net) {
+        if self.pending_op.is_some() || self.outside_active_hours() {
+            return;
+        }
+
+        self.log_info_cat(
+            LogCategory::Network,
+            format!("Assigning {} to {}", address, lan_name),
+        );
+        self.set_pending_op(PendingOp::AssigningLanAddress);
+
+        let tx = self.op_tx.clone();
+        tokio::spawn(async move {
+            let result = ifaddr::assign(&lan_name, address).await;
+            let _ = tx.send(AsyncOpResult::LanAddressAssigned {
+                result,
+                vpn_name,
+                lan_name,
+                address,
+            });
+        });
+    }
+
+    /// Start editing DNS.
+    fn start_dns_edit(&mut self) {
+        self.dns.input_buffer = self.dns.custom.clone().unwrap_or_default();
+        self.dns.edit_mode = DnsEditMode::SelectingPreset;
+        // Pre-select current DNS in the preset list (+1 because 0 is Auto-detect).
+        // On auto-detect, start on the preset picked last time, if any.
+        self.dns.preset_selected = dns::preset_selection(
+            &self.dns.presets,
+            self.dns.custom.as_deref(),
+            self.dns.last_preset,
+        );
+        self.state = AppState::EditingDns;
+    }
+
+    /// Handle key input during DNS editing (dispatches by mode).
+    fn handle_dns_edit_key(&mut self, key: crossterm::event::KeyCode) {
+        match self.dns.edit_mode {
+            DnsEditMode::SelectingPreset => self.handle_dns_preset_key(key),
+            DnsEditMode::CustomInput => self.handle_dns_custom_input_key(key),
+            DnsEditMode::Ordering => self.handle_dns_ordering_key(key),
+        }
+    }
+
+    /// Handle a Shift+arrow key: moves the highlighted server while ordering
+    /// DNS servers, otherwise behaves like the plain arrow.
+    pub fn handle_shifted_arrow(&mut self, key: crossterm::event::KeyCode) {
         use crossterm::event::KeyCode;
 
+        let ordering = self.state == AppState::EditingDns
+            && self.dns.edit_mode == DnsEditMode::Ordering
+            && self.pending_op.is_none();
         match key {
-            KeyCode::Char('s') | KeyCode::Enter => {
-                self.stop_sharing_async();
-            }
-            KeyCode::Char('q') => {
-                self.should_quit = true;
-                self.stop_sharing_async();
-            }
-            KeyCode::Char('d') => {
-                self.toggle_debug();
+            KeyCode::Up if ordering => self.move_dns_server(true),
+            KeyCode::Down if ordering => self.move_dns_server(false),
+            _ => self.handle_key(key),
+        }
+    }
+
+    /// Handle key input while ordering DNS servers. Changes apply immediately.
+    fn handle_dns_ordering_key(&mut self, key: crossterm::event::KeyCode) {
+        use crossterm::event::KeyCode;
+
+        let listed = self.dns.listed();
+        let selected = self.dns.order_selected;
+        match key {
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.dns.order_selected = selected.saturating_sub(1);
             }
-            KeyCode::Char('l') => {
-                self.logs_expanded = !self.logs_expanded;
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.dns.order_selected = (selected + 1).min(listed.len().saturating_sub(1));
             }
-            KeyCode::Esc => {
-                if self.show_debug {
-                    self.show_debug = false;
-                    self.debug_info = None;
+            KeyCode::Char('K') => self.move_dns_server(true),
+            KeyCode::Char('J') => self.move_dns_server(false),
+            KeyCode::Char(' ') => {
+                let Some(server) = listed.get(selected).cloned() else {
+                    return;
+                };
+                if let Some(pos) = self.dns.disabled.iter().position(|s| *s == server) {
+                    self.dns.disabled.remove(pos);
+                    self.log_info_cat(LogCategory::Dns, format!("DNS server {} enabled", server));
+                } else if listed
+                    .iter()
+                    .filter(|s| !self.dns.disabled.contains(s))
+                    .count()
+                    <= 1
+                {
+                    self.log_warning_cat(
+                        LogCategory::Dns,
+                        "At least one DNS server has to stay enabled",
+                    );
+                    return;
                 } else {
-                    self.state = AppState::Menu;
+                    self.dns.disabled.push(server.clone());
+                    self.log_info_cat(LogCategory::Dns, format!("DNS server {} disabled", server));
                 }
+                self.save_preferences();
+            }
+            KeyCode::Enter => {
+                self.state = AppState::Menu;
+            }
+            KeyCode::Esc => {
+                self.dns.edit_mode = DnsEditMode::SelectingPreset;
             }
             _ => {}
         }
     }
 
-    /// Start editing DNS.
-    fn start_dns_edit(&mut self) {
-        self.dns.input_buffer = self.dns.custom.clone().unwrap_or_default();
-        self.dns.edit_mode = DnsEditMode::SelectingPreset;
-        // Pre-select current DNS in the preset list
-        self.dns.preset_selected = if self.dns.custom.is_none() {
-            0 // Auto-detect
-        } else if let Some(ref dns) = self.dns.custom {
-            // Check if the custom DNS matches a preset
-            DNS_PRESETS
-                .iter()
-                .position(|p| p.ip == dns.as_str())
-                .map(|i| i + 1) // +1 because 0 is Auto-detect
-                .unwrap_or(DNS_PRESETS.len() + 1) // Custom...
-        } else {
-            0
-        };
-        self.state = AppState::EditingDns;
-    }
-
-    /// Handle key input during DNS editing (dispatches by mode).
-    fn handle_dns_edit_key(&mut self, key: crossterm::event::KeyCode) {
-        match self.dns.edit_mode {
-            DnsEditMode::SelectingPreset => self.handle_dns_preset_key(key),
-            DnsEditMode::CustomInput => self.handle_dns_custom_input_key(key),
+    /// Swap the highlighted DNS server with its neighbour and persist the order.
+    fn move_dns_server(&mut self, up: bool) {
+        let listed = self.dns.listed();
+        let selected = self.dns.order_selected;
+        if let Some(order) = dns::move_server(&listed, &self.dns.order, selected, up) {
+            self.dns.order = order;
+            self.dns.order_selected = if up { selected - 1 } else { selected + 1 };
+            self.save_preferences();
         }
     }
 
     /// Total number of items in the preset list (Auto-detect + presets + Custom...).
     fn dns_preset_count(&self) -> usize {
-        1 + DNS_PRESETS.len() + 1
+        1 + self.dns.presets.len() + 1
     }
 
     /// Handle key input in preset selection mode.
@@ -1408,30 +5175,31 @@ impl App {
 
         let count = self.dns_preset_count();
         match key {
-            KeyCode::Up | KeyCode::Char('k') => {
-                if self.dns.preset_selected > 0 {
-                    self.dns.preset_selected -= 1;
-                }
+            KeyCode::Up | KeyCode::Char('k') if self.dns.preset_selected > 0 => {
+                self.dns.preset_selected -= 1;
             }
-            KeyCode::Down | KeyCode::Char('j') => {
-                if self.dns.preset_selected < count - 1 {
-                    self.dns.preset_selected += 1;
-                }
+            KeyCode::Down | KeyCode::Char('j') if self.dns.preset_selected < count - 1 => {
+                self.dns.preset_selected += 1;
             }
             KeyCode::Enter => {
                 let idx = self.dns.preset_selected;
                 if idx == 0 {
                     // Auto-detect
-                    self.dns.custom = None;
-                    self.log_info("DNS reset to auto-detect");
-                    self.save_preferences();
+                    self.commit_dns_change(None);
+                    self.log_info_cat(LogCategory::Dns, "DNS reset to auto-detect");
                     self.state = AppState::Menu;
-                } else if idx <= DNS_PRESETS.len() {
+                } else if idx <= self.dns.presets.len() {
                     // A preset
-                    let preset = &DNS_PRESETS[idx - 1];
-                    self.dns.custom = Some(preset.ip.to_string());
-                    self.log_success(format!("DNS set to {} ({})", preset.ip, preset.name));
-                    self.save_preferences();
+                    let preset = self.dns.presets[idx - 1].clone();
+                    let last_preset = self.dns.last_preset.replace(idx - 1);
+                    if !self.commit_dns_change(Some(preset.primary.clone())) {
+                        self.dns.last_preset = last_preset;
+                        return;
+                    }
+                    self.log_success_cat(
+                        LogCategory::Dns,
+                        format!("DNS set to {} ({})", preset.primary, preset.name),
+                    );
                     self.state = AppState::Menu;
                 } else {
                     // Custom...
@@ -1439,6 +5207,10 @@ impl App {
                     self.dns.input_buffer = self.dns.custom.clone().unwrap_or_default();
                 }
             }
+            KeyCode::Char('o') => {
+                self.dns.order_selected = 0;
+                self.dns.edit_mode = DnsEditMode::Ordering;
+            }
             KeyCode::Esc => {
                 self.dns.input_buffer.clear();
                 self.state = AppState::Menu;
@@ -1452,11 +5224,9 @@ impl App {
         use crossterm::event::KeyCode;
 
         match key {
-            KeyCode::Char(c) => {
-                // Only allow digits, dots, and colons (for IPv6)
-                if c.is_ascii_digit() || c == '.' || c == ':' {
-                    self.dns.input_buffer.push(c);
-                }
+            // Only allow digits, dots, and colons (for IPv6)
+            KeyCode::Char(c) if c.is_ascii_digit() || c == '.' || c == ':' => {
+                self.dns.input_buffer.push(c);
             }
             KeyCode::Backspace => {
                 self.dns.input_buffer.pop();
@@ -1464,16 +5234,28 @@ impl App {
             KeyCode::Enter => {
                 let input = self.dns.input_buffer.trim().to_string();
                 if input.is_empty() {
-                    self.dns.custom = None;
-                    self.log_info("DNS reset to auto-detect");
-                } else if input.parse::<IpAddr>().is_ok() {
-                    self.dns.custom = Some(input.clone());
-                    self.log_success(format!("Custom DNS set to {}", input));
+                    self.commit_dns_change(None);
+                    self.log_info_cat(LogCategory::Dns, "DNS reset to auto-detect");
+                } else if self.dns_unanswered.take().as_ref() == Some(&input) {
+                    // Second try after a timeout: the user vouches for it
+                    if let Some(i) = dns::preset_index(&self.dns.presets, &input) {
+                        self.dns.last_preset = Some(i);
+                    }
+                    self.commit_dns_change(Some(input.clone()));
+                    self.log_warning_cat(
+                        LogCategory::Dns,
+                        format!("Custom DNS set to {} (untested)", input),
+                    );
+                } else if config::is_valid_dns_server(&input) {
+                    // Applied once it answers; see AsyncOpResult::DnsTested
+                    self.test_dns_server_async(input);
                 } else {
-                    self.log_warning(format!("Invalid IP address: {}", input));
+                    self.log_warning_cat(
+                        LogCategory::Dns,
+                        format!("Invalid IP address: {}", input),
+                    );
                 }
                 self.dns.input_buffer.clear();
-                self.save_preferences();
                 self.state = AppState::Menu;
             }
             KeyCode::Esc => {
@@ -1486,9 +5268,44 @@ impl App {
 
     /// Start the interface selection flow.
     fn start_interface_selection(&mut self) {
+        if self.refuse_read_only() {
+            return;
+        }
         self.refresh_interfaces_async();
     }
 
+    /// Effective DNS servers that are IPv4 addresses (advertised over DHCPv4).
+    pub fn effective_dns_v4(&self) -> Vec<String> {
+        dns::split_by_family(&self.dns.effective()).0
+    }
+
+    /// Effective DNS servers that are IPv6 addresses (advertised over DHCPv6).
+    pub fn effective_dns_v6(&self) -> Vec<String> {
+        dns::split_by_family(&self.dns.effective()).1
+    }
+
+    /// Which interfaces the last detection found none of, if any.
+    pub fn missing_interfaces(&self) -> Option<MissingInterfaces> {
+        match (
+            self.vpn_interfaces.is_empty(),
+            self.lan_interfaces.is_empty(),
+        ) {
+            (true, true) => Some(MissingInterfaces::Both),
+            (true, false) => Some(MissingInterfaces::Vpn),
+            (false, true) => Some(MissingInterfaces::Lan),
+            (false, false) => None,
+        }
+    }
+
+    /// In read-only mode, log why an action that needs root is unavailable.
+    /// Returns true if the action must not run.
+    fn refuse_read_only(&mut self) -> bool {
+        if self.read_only {
+            self.log_warning("Sharing requires sudo (running read-only)");
+        }
+        self.read_only
+    }
+
     /// Quit the application.
     fn quit(&mut self) {
         if self.is_sharing() {
@@ -1499,50 +5316,265 @@ impl App {
         }
     }
 
+    /// Handle a termination signal (SIGTERM/SIGINT) delivered outside the TUI.
+    ///
+    /// Goes through the same path as quitting from the menu so that the async
+    /// stop runs and tears down NAT-PMP, DHCP, pf, and IP forwarding in order.
+    pub fn request_shutdown(&mut self, signal: &str) {
+        self.log_warning(format!("Received {}, shutting down...", signal));
+        self.quit();
+    }
+
     /// Get the help text for current state.
     pub fn help_text(&self) -> &'static str {
         if self.pending_op.is_some() {
             return "Esc: Cancel  q: Force quit";
         }
+        if self.show_log_detail {
+            return "Esc: Close";
+        }
+        if self.selected_log.is_some() {
+            return "↑/↓: Select  E: Previous error  L: Log level  Enter: Full message  Esc: Done";
+        }
 
         match self.state {
-            AppState::Menu if self.is_sharing() => {
-                "↑/↓: Navigate  Enter: Select  d: Debug  l: Logs  q: Quit"
-            }
-            AppState::Menu => "↑/↓: Navigate  Enter: Select  l: Logs  q: Quit",
-            AppState::SelectingVpn => "↑/↓: Navigate  Enter: Select  Esc: Cancel",
-            AppState::SelectingLan => "↑/↓: Navigate  Enter: Select  ←: Back  Esc: Cancel",
-            AppState::Active if self.show_debug => "d: Hide debug  s: Stop  l: Logs  q: Quit",
-            AppState::Active => "s: Stop  d: Debug  l: Logs  q: Quit",
+            AppState::Menu if self.stop_summary.is_some() => "Enter: Close  q: Quit",
+            AppState::Active if self.diagnostic_facts.is_some() => {
+                "Esc: Close  v: Re-check  r: Restart  q: Quit"
+            }
+            AppState::Active if self.probe_report.is_some() => "Esc: Close  s: Stop  q: Quit",
+            AppState::Active if self.dns_probe_report.is_some() => {
+                "Esc: Close  T: Re-test  s: Stop  q: Quit"
+            }
+            AppState::Active | AppState::Menu if self.show_debug && self.show_anchor_preview => {
+                "↑/↓: Select mapping  x: Remove mapping  m: Hide mappings  f: Refresh  d: Hide debug  q: Quit"
+            }
+            AppState::Active | AppState::Menu if self.show_debug && self.show_pf_states => {
+                "PgUp/PgDn: Scroll states  p: Hide states  f: Refresh  Y: Copy report  d: Hide debug  q: Quit"
+            }
+            AppState::Active if self.show_debug => {
+                "d: Hide debug  f: Refresh  c: Diff  p: States  m: Mapping rules  Y: Copy report  s: Stop  r: Restart  l: Logs  q: Quit"
+            }
+            AppState::Menu if self.show_debug => {
+                "d: Hide debug  f: Refresh  c: Diff  p: States  m: Mapping rules  Y: Copy report  l: Logs  q: Quit"
+            }
+            AppState::Menu if self.start_retry.is_some() => {
+                "r: Retry start  ↑/↓: Navigate  Enter: Select  N: Label  d: Debug  l: Logs  q: Quit"
+            }
+            AppState::Menu => {
+                "↑/↓: Navigate  Enter: Select  N: Label  d: Debug  l: Logs  q: Quit"
+            }
+            AppState::SelectingVpn | AppState::SelectingLan if self.filtering => {
+                "Type to filter  ↑/↓: Navigate  Enter: Select  Esc: Clear filter"
+            }
+            AppState::SelectingVpn => {
+                "↑/↓: Navigate  Enter: Select  Space: Add uplink  f: Failover  /: Filter  Esc: Cancel"
+            }
+            AppState::SelectingLan
+                if self.public_lan_confirmation.is_some()
+                    && self.public_lan_confirmation == self.selected_lan =>
+            {
+                "Enter: Share onto public address anyway  ↑/↓: Pick another  Esc: Cancel"
+            }
+            AppState::SelectingLan
+                if self.lan_address_confirmation.is_some()
+                    && self.lan_address_confirmation == self.selected_lan =>
+            {
+                "Enter: Assign gateway address and share  ↑/↓: Pick another  Esc: Cancel"
+            }
+            AppState::SelectingLan if self.selected_vpn_addresses().len() > 1 => {
+                "↑/↓: Navigate  Enter: Select  /: Filter  e: NAT-PMP address  ←: Back  Esc: Cancel"
+            }
+            AppState::SelectingLan => {
+                "↑/↓: Navigate  Enter: Select  /: Filter  ←: Back  Esc: Cancel"
+            }
+            AppState::Active if self.natpmp_conflict.is_some() => {
+                "k: Stop conflicting daemon  s: Stop  r: Restart  d: Debug  l: Logs  q: Quit"
+            }
+            AppState::Active if self.health_paused() => {
+                "H: Resume health  s: Stop  r: Restart  R: Reload rules  h: DHCP  n: NAT-PMP  v: Verify  a: Save profile  N: Label  y: Copy join cmd  d: Debug  l: Logs  q: Quit"
+            }
+            AppState::Active => {
+                "s: Stop  r: Restart  R: Reload rules  h: DHCP  H: Pause health  n: NAT-PMP  v: Verify  a: Save profile  N: Label  y: Copy join cmd  d: Debug  l: Logs  q: Quit"
+            }
+            AppState::SelectingProfile if self.profiles.is_empty() => "Esc: Back",
+            AppState::SelectingProfile => "↑/↓: Navigate  Enter: Start  x: Delete  Esc: Back",
+            AppState::NamingProfile => "Enter: Save profile  Esc: Cancel",
+            AppState::NamingLabel => "Enter: Set label (empty clears it)  Esc: Cancel",
+            AppState::NoInterfaces => "r: Rescan  Esc: Back to menu  l: Logs  q: Quit",
             AppState::EditingDns => match self.dns.edit_mode {
-                DnsEditMode::SelectingPreset => "↑/↓: Navigate  Enter: Select  Esc: Cancel",
+                DnsEditMode::SelectingPreset => {
+                    "↑/↓: Navigate  Enter: Select  o: Order servers  Esc: Cancel"
+                }
                 DnsEditMode::CustomInput => "Enter: Save  Esc: Back  (empty = auto-detect)",
+                DnsEditMode::Ordering => {
+                    "↑/↓: Navigate  Shift+↑/↓: Move  Space: On/off  Enter: Done  Esc: Back"
+                }
             },
         }
     }
 
     // Persistence
 
+    /// Move the session's interfaces to the front of the recently-used lists.
+    fn remember_interfaces(&mut self) {
+        let Some(session) = self.session.as_ref() else {
+            return;
+        };
+        config::mru_push(&mut self.mru_vpn, &session.vpn_name);
+        config::mru_push(&mut self.mru_lan, &session.lan_name);
+        self.save_preferences();
+    }
+
     /// Save current preferences to config file.
     fn save_preferences(&self) {
+        // Best-effort, like the rest of the config handling
+        if let Some(path) = &self.config_path {
+            let _ = self.preferences().save_to(path);
+        }
+    }
+
+    /// The config `save_preferences` writes.
+    fn preferences(&self) -> Config {
+        // A running profile's settings aren't the user's preferences
+        let (dhcp_enabled, natpmp_enabled, custom_dns, custom_dns_extra) =
+            match &self.profile_restore {
+                Some(saved) => (
+                    saved.dhcp_enabled,
+                    saved.natpmp_enabled,
+                    saved.dns_custom.clone(),
+                    saved.dns_extra.clone(),
+                ),
+                None => (
+                    self.dhcp_enabled,
+                    self.natpmp_enabled,
+                    self.dns.custom.clone(),
+                    self.dns.extra.clone(),
+                ),
+            };
         Config {
-            dhcp_enabled: self.dhcp_enabled,
-            natpmp_enabled: self.natpmp_enabled,
-            custom_dns: self.dns.custom.clone(),
+            schema_version: config::SCHEMA_VERSION,
+            dhcp_enabled,
+            natpmp_enabled,
+            custom_dns,
+            custom_dns_extra,
+            dns_order: self.dns.order.clone(),
+            dns_disabled: self.dns.disabled.clone(),
+            dns_presets: self.dns_presets.clone(),
+            dns_presets_replace: self.dns_presets_replace,
+            last_dns_preset: self
+                .dns
+                .last_preset
+                .and_then(|i| self.dns.presets.get(i))
+                .map(|preset| preset.name.clone()),
+            mru_vpn: self.mru_vpn.clone(),
+            mru_lan: self.mru_lan.clone(),
+            no_nat_destinations: self.no_nat_destinations.clone(),
+            logs_expanded_default: self.logs_expanded,
+            log_level: self.log_level_preference,
+            natpmp_anchor: self.natpmp_anchor.clone(),
+            natpmp_preserve_existing: self.natpmp_preserve_existing,
+            dhcp_backend: self.dhcp_backend_preference,
+            tunnel_probe_attempts: self.tunnel_probe_attempts,
+            tunnel_probe_interval_ms: self.tunnel_probe_interval.as_millis() as u64,
+            command_retry_attempts: self.command_retry.attempts,
+            command_retry_backoff_ms: self.command_retry.backoff.as_millis() as u64,
+            profiles: self.profiles.clone(),
+            natpmp_allowed_port_range: self.natpmp_port_range,
+            colorblind_mode: self.colorblind_mode,
+            reduced_motion: self.reduced_motion_preference,
+            session_label: self.session_label_preference.clone(),
+            leave_forwarding_enabled: self.leave_forwarding_enabled,
+            active_hours: self.active_hours.clone(),
+            sysctls: self.sysctls.clone(),
+            natpmp_external_address: self.natpmp_external_preference,
+            dhcp_auto_restart: self.dhcp_auto_restart,
+            persistent_dhcp: self.persistent_dhcp,
+            connectivity_check_domains: self.connectivity_check_domains.clone(),
+            connectivity_check_resolver: self.connectivity_check_resolver.clone(),
+            bell_on_down: self.bell_on_down,
+            bell_on_recovery: self.bell_on_recovery,
+            failover_prefer_primary: self.failover_prefer_primary,
+            dhcp_mac_allowlist: self.dhcp_mac_allowlist.clone(),
+            dhcp_boot: self.dhcp_boot.clone(),
+            lan_gateway_ip: self.lan_gateway_ip,
+            override_mss: self.override_mss,
+            pf_template: self.pf_template.clone(),
+        }
+    }
+
+    /// Select the most recent error logged before index `before`, wrapping
+    /// around to the newest one. Does nothing if there are no errors.
+    fn select_previous_error(&mut self, before: usize) {
+        let is_error = |entry: &LogEntry| entry.level == LogEntryLevel::Error;
+        let found = self
+            .logs
+            .iter()
+            .take(before)
+            .rposition(is_error)
+            .or_else(|| self.logs.iter().rposition(is_error));
+        if found.is_some() {
+            self.selected_log = found;
+        }
+    }
+
+    /// Toggle the log panel expansion and remember it for the next launch.
+    fn toggle_logs_expanded(&mut self) {
+        self.logs_expanded = !self.logs_expanded;
+        if !self.logs_expanded {
+            self.selected_log = None;
+            self.show_log_detail = false;
         }
-        .save();
+        self.save_preferences();
+    }
+
+    /// Cycle the lowest recorded log level (info → success → warning) and remember it.
+    /// Only affects new entries; what's already in the log stays. The log title
+    /// shows the level while it's above info.
+    fn cycle_log_level(&mut self) {
+        self.min_log_level = self.min_log_level.next_threshold();
+        self.log_level_preference = self.min_log_level;
+        self.save_preferences();
     }
 
     // Logging helpers
 
     /// Append a log entry, evicting the oldest if at capacity.
+    /// Entries below `min_log_level` are dropped.
     fn push_log(&mut self, entry: LogEntry) {
+        if !entry.level.is_recorded(self.min_log_level) {
+            return;
+        }
+        if let Some(sink) = self.event_sink {
+            sink.write(&Record::from_log(&entry));
+        } else if self.headless {
+            eprintln!(
+                "{} {:<7} {}",
+                entry.time.format("%H:%M:%S"),
+                entry.level.label(),
+                entry.message
+            );
+        }
         if self.logs.len() >= MAX_LOG_ENTRIES {
             self.logs.pop_front();
+            // Indices shift down by one; keep pointing at the same entry if it survived
+            if let Some(selected) = self.selected_log {
+                self.selected_log = Some(selected.checked_sub(1).unwrap_or_else(|| {
+                    self.show_log_detail = false;
+                    0
+                }));
+            }
         }
         self.logs.push_back(entry);
     }
 
+    /// Write `event` to the JSON event log, if there is one.
+    fn emit(&self, level: LogEntryLevel, event: Event) {
+        if let Some(sink) = self.event_sink {
+            sink.write(&Record::new(level, event));
+        }
+    }
+
     fn log_info(&mut self, msg: impl Into<String>) {
         self.push_log(LogEntry::info(msg));
     }
@@ -1558,11 +5590,107 @@ impl App {
     fn log_error(&mut self, msg: impl Into<String>) {
         self.push_log(LogEntry::error(msg));
     }
+
+    fn log_info_cat(&mut self, category: LogCategory, msg: impl Into<String>) {
+        self.push_log(LogEntry::info(msg).with_category(category));
+    }
+
+    fn log_success_cat(&mut self, category: LogCategory, msg: impl Into<String>) {
+        self.push_log(LogEntry::success(msg).with_category(category));
+    }
+
+    fn log_warning_cat(&mut self, category: LogCategory, msg: impl Into<String>) {
+        self.push_log(LogEntry::warning(msg).with_category(category));
+    }
+
+    fn log_error_cat(&mut self, category: LogCategory, msg: impl Into<String>) {
+        self.push_log(LogEntry::error(msg).with_category(category));
+    }
+}
+
+/// A step of starting sharing, run by [`bring_up`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StartStep {
+    EnableForwarding,
+    LoadRules,
+    CheckTunnel,
+    /// Undoes `LoadRules`.
+    RemoveRules,
+    /// Undoes `EnableForwarding`.
+    RestoreForwarding,
+}
+
+/// Start sharing: enable forwarding, load the rules, then check the tunnel
+/// carries traffic, each step run by `run` on `state`.
+///
+/// When a step fails, whatever the earlier ones did is undone (the rules
+/// removed, forwarding put back) and the first error is returned, along with
+/// any undo step that failed too.
+async fn bring_up<S, F, Fut>(state: S, mut run: F) -> (S, Result<()>, Vec<TunshareError>)
+where
+    F: FnMut(StartStep, S) -> Fut,
+    Fut: Future<Output = (S, Result<()>)>,
+{
+    let mut rollback_errors = Vec::new();
+
+    let (state, result) = run(StartStep::EnableForwarding, state).await;
+    if result.is_err() {
+        return (state, result, rollback_errors);
+    }
+
+    // The VPN can vanish right here; forwarding must not stay on
+    let (state, result) = run(StartStep::LoadRules, state).await;
+    if result.is_err() {
+        let (state, undone) = run(StartStep::RestoreForwarding, state).await;
+        rollback_errors.extend(undone.err());
+        return (state, result, rollback_errors);
+    }
+
+    let (state, result) = run(StartStep::CheckTunnel, state).await;
+    if result.is_err() {
+        let (state, undone) = run(StartStep::RemoveRules, state).await;
+        rollback_errors.extend(undone.err());
+        let (state, undone) = run(StartStep::RestoreForwarding, state).await;
+        rollback_errors.extend(undone.err());
+        return (state, result, rollback_errors);
+    }
+
+    (state, result, rollback_errors)
+}
+
+/// Run `probe` until traffic gets through the tunnel, up to `attempts` times.
+///
+/// Reports each failed probe that will be retried over `tx`. Returns true
+/// immediately when `attempts` is 0.
+async fn wait_for_tunnel<F, Fut>(
+    attempts: u32,
+    interval: Duration,
+    tx: &mpsc::UnboundedSender<AsyncOpResult>,
+    mut probe: F,
+) -> bool
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = bool>,
+{
+    if attempts == 0 {
+        return true;
+    }
+
+    for attempt in 1..=attempts {
+        if probe().await {
+            return true;
+        }
+        if attempt < attempts {
+            let _ = tx.send(AsyncOpResult::TunnelSettling { attempt, attempts });
+            tokio::time::sleep(interval).await;
+        }
+    }
+    false
 }
 
 impl Default for App {
     fn default() -> Self {
-        Self::new()
+        Self::new(&Args::default())
     }
 }
 
@@ -1571,5 +5699,639 @@ impl Drop for App {
         // SharingSession::drop handles all cleanup in the correct order.
         // Dropping `self.session` triggers it automatically.
         drop(self.session.take());
+
+        // A DHCP server kept between shares doesn't outlive the app
+        if let Some(kept) = self.kept_dhcp.take() {
+            kept.stop_sync();
+        }
+    }
+}
+
+/// Move a list selection one visible entry up or down, staying put at the ends.
+/// A selection that isn't visible jumps to the first visible entry.
+fn step_selection(selected: Option<usize>, visible: &[usize], down: bool) -> Option<usize> {
+    let Some(pos) = selected.and_then(|i| visible.iter().position(|&v| v == i)) else {
+        return visible.first().copied();
+    };
+    let pos = if down {
+        (pos + 1).min(visible.len() - 1)
+    } else {
+        pos.saturating_sub(1)
+    };
+    Some(visible[pos])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An app on default settings that touches neither the system nor the config file.
+    fn test_app() -> App {
+        let args = Args {
+            read_only: true,
+            ..Args::default()
+        };
+        App::with_config(&args, Config::default(), None)
+    }
+
+    #[test]
+    fn test_dns_overrides_vpn() {
+        let mut dns = DnsConfig::new(None, Vec::new(), Vec::new(), Vec::new(), Vec::new(), None);
+        assert!(!dns.overrides_vpn());
+
+        // Custom servers alone override nothing
+        dns.custom = Some("1.1.1.1".to_string());
+        assert!(!dns.overrides_vpn());
+
+        dns.vpn_servers = vec!["10.8.0.1".to_string()];
+        assert!(dns.overrides_vpn());
+
+        dns.custom = None;
+        assert!(!dns.overrides_vpn());
+        dns.cli = vec!["9.9.9.9".to_string()];
+        assert!(dns.overrides_vpn());
+    }
+
+    /// Run `bring_up` with steps that record themselves and fail when listed in `failing`.
+    async fn bring_up_with(failing: &[StartStep]) -> (Vec<StartStep>, Result<()>, usize) {
+        let (calls, result, rollback_errors) =
+            bring_up(Vec::new(), |step, mut calls: Vec<StartStep>| {
+                calls.push(step);
+                let result = if !failing.contains(&step) {
+                    Ok(())
+                } else if step == StartStep::LoadRules {
+                    // The VPN vanishing mid-start
+                    Err(TunshareError::PfInterfaceMissing {
+                        iface: "utun4".to_string(),
+                        stderr: "no IP address found for utun4".to_string(),
+                    })
+                } else {
+                    Err(TunshareError::FirewallError(format!("{:?} failed", step)))
+                };
+                async move { (calls, result) }
+            })
+            .await;
+        (calls, result, rollback_errors.len())
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_tunnel_retries() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let interval = Duration::from_millis(1);
+        let settling = |rx: &mut mpsc::UnboundedReceiver<AsyncOpResult>| {
+            std::iter::from_fn(|| rx.try_recv().ok())
+                .map(|result| match result {
+                    AsyncOpResult::TunnelSettling { attempt, attempts } => (attempt, attempts),
+                    _ => panic!("unexpected result"),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Gets through on the third probe, after reporting the first two
+        let mut probes = 0;
+        let probe = || {
+            probes += 1;
+            std::future::ready(probes == 3)
+        };
+        assert!(wait_for_tunnel(3, interval, &tx, probe).await);
+        assert_eq!(probes, 3);
+        assert_eq!(settling(&mut rx), [(1, 3), (2, 3)]);
+
+        // Gives up without reporting the last failure as settling
+        let mut probes = 0;
+        let probe = || {
+            probes += 1;
+            std::future::ready(false)
+        };
+        assert!(!wait_for_tunnel(2, interval, &tx, probe).await);
+        assert_eq!(probes, 2);
+        assert_eq!(settling(&mut rx), [(1, 2)]);
+
+        // Disabled: no probe at all
+        assert!(
+            wait_for_tunnel(0, interval, &tx, || -> std::future::Ready<bool> {
+                panic!("probed")
+            })
+            .await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_rule_load_rolls_back() {
+        use StartStep::*;
+
+        let (calls, result, _) = bring_up_with(&[]).await;
+        assert!(result.is_ok());
+        assert_eq!(calls, [EnableForwarding, LoadRules, CheckTunnel]);
+
+        // The VPN vanishing mid-start must still undo IP forwarding
+        let (calls, result, rollback_failures) = bring_up_with(&[LoadRules]).await;
+        assert!(matches!(
+            result,
+            Err(TunshareError::PfInterfaceMissing { .. })
+        ));
+        assert_eq!(calls, [EnableForwarding, LoadRules, RestoreForwarding]);
+        assert_eq!(rollback_failures, 0);
+
+        // A dead tunnel takes the rules out too
+        let (calls, result, _) = bring_up_with(&[CheckTunnel]).await;
+        assert!(result.is_err());
+        let all = [
+            EnableForwarding,
+            LoadRules,
+            CheckTunnel,
+            RemoveRules,
+            RestoreForwarding,
+        ];
+        assert_eq!(calls, all);
+
+        // Undo failures are reported, not lost, and the rest still runs
+        let (calls, result, rollback_failures) =
+            bring_up_with(&[CheckTunnel, RemoveRules, RestoreForwarding]).await;
+        assert!(
+            matches!(result, Err(TunshareError::FirewallError(ref e)) if e == "CheckTunnel failed")
+        );
+        assert_eq!(calls, all);
+        assert_eq!(rollback_failures, 2);
+
+        // Nothing to undo when forwarding can't be enabled
+        let (calls, _, _) = bring_up_with(&[EnableForwarding]).await;
+        assert_eq!(calls, [EnableForwarding]);
+    }
+
+    #[test]
+    fn test_late_dns_test_result_is_discarded() {
+        let late = AsyncOpResult::DnsTested {
+            server: "192.0.2.53".to_string(),
+            result: DnsProbeResult::Answered {
+                latency: Duration::from_millis(20),
+                answers: 1,
+            },
+        };
+        assert!(result_matches(&late, Some(PendingOp::TestingDns)));
+
+        // Cancelled with Esc, or something else started since: never reaches
+        // the handler, so the custom DNS setting is left alone
+        assert!(!result_matches(&late, None));
+        assert!(!result_matches(&late, Some(PendingOp::FetchingDebugInfo)));
+
+        let mut app = test_app();
+        app.handle_async_result(late);
+        assert_eq!(app.dns.custom, None);
+    }
+
+    #[test]
+    fn test_unanswered_dns_can_be_used_anyway() {
+        use crossterm::event::KeyCode;
+
+        let server = "192.0.2.53".to_string();
+        let mut app = test_app();
+
+        // A refusal leaves DNS as it was, with no way around it
+        app.set_pending_op(PendingOp::TestingDns);
+        app.handle_async_result(AsyncOpResult::DnsTested {
+            server: server.clone(),
+            result: DnsProbeResult::Rejected("REFUSED"),
+        });
+        assert_eq!(app.dns.custom, None);
+        assert_eq!(app.dns_unanswered, None);
+
+        // A timeout too, but entering the same server again takes it untested
+        app.set_pending_op(PendingOp::TestingDns);
+        app.handle_async_result(AsyncOpResult::DnsTested {
+            server: server.clone(),
+            result: DnsProbeResult::TimedOut,
+        });
+        assert_eq!(app.dns.custom, None);
+
+        app.state = AppState::EditingDns;
+        app.dns.edit_mode = DnsEditMode::CustomInput;
+        app.dns.input_buffer = server.clone();
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(app.pending_op, None);
+        assert_eq!(app.dns.custom, Some(server));
+    }
+
+    #[test]
+    fn test_profile_settings_last_for_its_session() {
+        let mut app = test_app();
+        app.dns.custom = Some("9.9.9.9".to_string());
+        app.natpmp_enabled = true;
+
+        let profile = Profile {
+            name: "travel".to_string(),
+            vpn: "utun4".to_string(),
+            lan: "en5".to_string(),
+            dns: Some("1.1.1.1".to_string()),
+            dns_extra: vec!["1.0.0.1".to_string()],
+            dhcp_enabled: false,
+            natpmp_enabled: false,
+            failover_vpn: Some("utun5".to_string()),
+        };
+        app.apply_profile(&profile);
+        assert_eq!(app.dns.custom.as_deref(), Some("1.1.1.1"));
+        assert_eq!(app.dns.extra, ["1.0.0.1"]);
+        assert!(!app.natpmp_enabled);
+
+        // Saving mid-session keeps the user's own settings
+        let saved = app.preferences();
+        assert_eq!(saved.custom_dns.as_deref(), Some("9.9.9.9"));
+        assert!(saved.natpmp_enabled);
+
+        app.end_profile_session();
+        assert_eq!(app.dns.custom.as_deref(), Some("9.9.9.9"));
+        assert!(app.dns.extra.is_empty());
+        assert!(app.natpmp_enabled);
+        assert_eq!(app.failover_vpn, None);
+        assert_eq!(app.preferences().custom_dns.as_deref(), Some("9.9.9.9"));
+    }
+
+    #[test]
+    fn test_save_profile() {
+        use crossterm::event::KeyCode;
+
+        let mut app = test_app();
+        app.session = Some(SharingSession::new(
+            Firewall::new(),
+            IpForwarding::new(),
+            "utun4".to_string(),
+            "en5".to_string(),
+            Ipv4Addr::new(192, 168, 2, 1),
+        ));
+        app.dns.custom = Some("1.1.1.1".to_string());
+        app.dns.extra = vec!["1.0.0.1".to_string()];
+
+        // The name limit counts characters, not bytes
+        app.state = AppState::NamingProfile;
+        for _ in 0..40 {
+            app.handle_profile_name_key(KeyCode::Char('é'));
+        }
+        assert_eq!(app.profile_name_input.chars().count(), 32);
+        app.handle_profile_name_key(KeyCode::Enter);
+
+        let profile = &app.profiles[0];
+        assert_eq!(profile.name, "é".repeat(32));
+        assert_eq!(profile.dns.as_deref(), Some("1.1.1.1"));
+        assert_eq!(profile.dns_extra, ["1.0.0.1"]);
+    }
+
+    #[tokio::test]
+    async fn test_profile_launch_picks_its_vpn_address() {
+        let interface = |name: &str, addresses: Vec<Ipv4Addr>| InterfaceInfo {
+            ipv4_address: addresses.first().copied(),
+            ipv4_addresses: addresses,
+            ..crate::system::network::iface(name)
+        };
+        let mut app = test_app();
+        app.vpn_interfaces = vec![
+            interface("utun3", vec![Ipv4Addr::new(10, 8, 0, 2)]),
+            interface(
+                "utun4",
+                vec![Ipv4Addr::new(10, 9, 0, 2), Ipv4Addr::new(10, 9, 0, 3)],
+            ),
+        ];
+        app.lan_interfaces = vec![interface("en5", vec![Ipv4Addr::new(192, 168, 2, 1)])];
+        app.selected_vpn = Some(0);
+        app.natpmp_external_ip = Some(Ipv4Addr::new(10, 8, 0, 2));
+        app.natpmp_external_preference = Some(Ipv4Addr::new(10, 9, 0, 3));
+
+        // Custom DNS starts sharing right away, without a discovery round
+        let profile = Profile {
+            name: "work".to_string(),
+            vpn: "utun4".to_string(),
+            lan: "en5".to_string(),
+            dns: Some("1.1.1.1".to_string()),
+            dns_extra: Vec::new(),
+            dhcp_enabled: false,
+            natpmp_enabled: true,
+            failover_vpn: None,
+        };
+        assert!(app.launch_profile(profile));
+        assert_eq!(app.natpmp_external_ip, Some(Ipv4Addr::new(10, 9, 0, 3)));
+    }
+
+    #[test]
+    fn test_dhcp_restart_backoff() {
+        let mut app = test_app();
+        app.dhcp_backend = None;
+        app.session = Some(SharingSession::new(
+            Firewall::new(),
+            IpForwarding::new(),
+            "utun4".to_string(),
+            "en5".to_string(),
+            Ipv4Addr::new(192, 168, 2, 1),
+        ));
+        app.next_health_check = Some(Instant::now());
+
+        // Each restart waits twice as long as the one before
+        for (restarts, delay) in [(1, 2), (2, 4), (3, 8)] {
+            let before = Instant::now();
+            app.schedule_dhcp_restart();
+            let session = app.session.as_ref().unwrap();
+            assert_eq!(session.dhcp_restarts, restarts);
+            let wait = session.dhcp_restart_at.unwrap() - before;
+            assert!(wait >= Duration::from_secs(delay) && wait < Duration::from_secs(delay + 1));
+        }
+
+        // Then it gives up
+        app.session.as_mut().unwrap().dhcp_restart_at = None;
+        app.schedule_dhcp_restart();
+        let session = app.session.as_ref().unwrap();
+        assert_eq!(session.dhcp_restarts, DHCP_MAX_RESTARTS);
+        assert_eq!(session.dhcp_restart_at, None);
+        assert!(app
+            .logs
+            .iter()
+            .any(|entry| entry.message.starts_with("Giving up on DHCP")));
+
+        // Taking over by hand starts the count again
+        app.session.as_mut().unwrap().dhcp_restart_at = Some(Instant::now());
+        app.toggle_live_dhcp();
+        let session = app.session.as_ref().unwrap();
+        assert_eq!(session.dhcp_restarts, 0);
+        assert_eq!(session.dhcp_restart_at, None);
+        app.schedule_dhcp_restart();
+        let wait = app.session.as_ref().unwrap().dhcp_restart_at.unwrap() - Instant::now();
+        assert!(wait <= DHCP_RESTART_BACKOFF);
+    }
+
+    #[test]
+    fn test_log_shortcuts_leave_text_fields_alone() {
+        use crossterm::event::KeyCode;
+
+        let mut app = test_app();
+        app.logs_expanded = true;
+        app.state = AppState::NamingLabel;
+        let level = app.min_log_level;
+
+        app.handle_key(KeyCode::Char('E'));
+        app.handle_key(KeyCode::Char('L'));
+        assert_eq!(app.label_input, "EL");
+        assert_eq!(app.min_log_level, level);
+        assert_eq!(app.selected_log, None);
+    }
+
+    #[test]
+    fn test_invalid_active_hours_refuse_sharing() {
+        let args = Args {
+            read_only: true,
+            ..Args::default()
+        };
+        let config = Config {
+            active_hours: Some(("8am".to_string(), "20:00".to_string())),
+            ..Config::default()
+        };
+        let mut app = App::with_config(&args, config, None);
+        assert_eq!(app.active_hours(), None);
+        assert!(app
+            .logs
+            .iter()
+            .any(|entry| entry.level == LogEntryLevel::Warning
+                && entry.message.contains("active_hours")));
+        assert!(app.outside_active_hours());
+
+        // Unset allows any time
+        let mut app = test_app();
+        assert!(!app.outside_active_hours());
+    }
+
+    #[test]
+    fn test_preset_with_bad_address_is_refused() {
+        use crossterm::event::KeyCode;
+
+        let args = Args {
+            read_only: true,
+            ..Args::default()
+        };
+        let config = Config {
+            dns_presets: vec![DnsPreset {
+                name: "Typo".to_string(),
+                primary: "1.1.1".to_string(),
+                secondary: None,
+            }],
+            dns_presets_replace: true,
+            ..Config::default()
+        };
+        let mut app = App::with_config(&args, config, None);
+        app.state = AppState::EditingDns;
+        app.dns.preset_selected = 1;
+        app.handle_key(KeyCode::Enter);
+        assert_eq!(app.dns.custom, None);
+        assert_eq!(app.dns.last_preset, None);
+        assert_eq!(app.preferences().last_dns_preset, None);
+    }
+
+    #[test]
+    fn test_last_dns_preset_is_kept_by_name() {
+        let args = Args {
+            read_only: true,
+            ..Args::default()
+        };
+        let home = DnsPreset {
+            name: "Home".to_string(),
+            primary: "192.168.1.53".to_string(),
+            secondary: None,
+        };
+        let config = Config {
+            dns_presets: vec![home.clone()],
+            last_dns_preset: Some("Home".to_string()),
+            ..Config::default()
+        };
+        let app = App::with_config(&args, config, None);
+        let i = app.dns.last_preset.unwrap();
+        assert_eq!(app.dns.presets[i], home);
+        assert_eq!(app.preferences().last_dns_preset.as_deref(), Some("Home"));
+    }
+
+    #[tokio::test]
+    async fn test_kept_dhcp_lifecycle() {
+        use std::os::unix::process::ExitStatusExt;
+
+        // Stands in for the DHCP daemon
+        let mut daemon = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let pid = daemon.id();
+
+        let mut app = test_app();
+        app.persistent_dhcp = true;
+        app.natpmp_enabled = false;
+        app.dhcp_backend = Some(DhcpBackend::Dnsmasq);
+        let gateway = Ipv4Addr::new(192, 168, 2, 1);
+        let session = || {
+            SharingSession::new(
+                Firewall::new(),
+                IpForwarding::new(),
+                "utun4".to_string(),
+                "en5".to_string(),
+                gateway,
+            )
+        };
+        let stopped = || AsyncOpResult::SharingStopped {
+            result: Ok(()),
+            firewall: Firewall::new(),
+            ip_forwarding: IpForwarding::new(),
+            traffic: None,
+            nat_counters: None,
+            residue: Vec::new(),
+            nat_removed: true,
+        };
+
+        // The test never yields, so the spawned start and cleanup tasks don't run
+        app.session = Some(session());
+        app.start_dhcp_async("en5".to_string(), session().lan_net());
+        app.handle_async_result(AsyncOpResult::DhcpStarted {
+            result: Ok(()),
+            pid: Some(pid),
+            skipped: Vec::new(),
+        });
+
+        // Stop: left running for the next share
+        app.stop_sharing_async();
+        app.handle_async_result(stopped());
+        assert_eq!(app.kept_dhcp.as_ref().map(|kept| kept.pid), Some(pid));
+        assert!(daemon.try_wait().unwrap().is_none());
+
+        // Start: taken over as is
+        app.session = Some(session());
+        app.start_dhcp_async("en5".to_string(), session().lan_net());
+        assert!(app.kept_dhcp.is_none());
+        let started = app.op_rx.try_recv().unwrap();
+        assert!(matches!(started, AsyncOpResult::DhcpStarted { pid: Some(p), .. } if p == pid));
+        app.handle_async_result(started);
+        assert_eq!(app.session.as_ref().and_then(|s| s.dhcp_pid), Some(pid));
+
+        // Stop again, then quit: the app takes the daemon down with it
+        app.stop_sharing_async();
+        app.handle_async_result(stopped());
+        assert!(app.kept_dhcp.is_some());
+        drop(app);
+        assert_eq!(daemon.wait().unwrap().signal(), Some(libc::SIGTERM));
+    }
+
+    #[test]
+    fn test_debug_panel_hides_menu_keys() {
+        use crossterm::event::KeyCode;
+
+        let mut app = test_app();
+        app.state = AppState::Menu;
+        app.show_debug = true;
+        for key in [
+            KeyCode::Down,
+            KeyCode::Char('j'),
+            KeyCode::Enter,
+            KeyCode::Char('1'),
+        ] {
+            app.handle_key(key);
+        }
+        assert_eq!(app.selected_menu_item, 0);
+        assert_eq!(app.state, AppState::Menu);
+        assert!(app.show_debug);
+
+        // Closing the panel brings the menu back under the keys
+        app.handle_key(KeyCode::Esc);
+        app.handle_key(KeyCode::Down);
+        assert_eq!(app.selected_menu_item, 1);
+    }
+
+    #[test]
+    fn test_reloaded_settings_kept_only_on_success() {
+        let mut app = test_app();
+        app.no_nat_destinations = vec!["10.0.0.0/8".to_string()];
+        app.override_mss = Some(1400);
+        let reloaded = |result| AsyncOpResult::RulesReloaded {
+            result,
+            firewall: Firewall::new(),
+            ip_forwarding: IpForwarding::new(),
+            no_nat_destinations: vec!["172.16.0.0/12".to_string()],
+            override_mss: Some(1300),
+            pf_template: Some(PathBuf::from("/etc/tunshare.pf")),
+        };
+
+        // The previous rules stay loaded, and so do their settings
+        app.handle_async_result(reloaded(Err(TunshareError::FirewallError(
+            "syntax error".into(),
+        ))));
+        assert_eq!(app.no_nat_destinations, vec!["10.0.0.0/8".to_string()]);
+        assert_eq!(app.override_mss, Some(1400));
+        assert_eq!(app.pf_template, None);
+
+        app.handle_async_result(reloaded(Ok(())));
+        assert_eq!(app.no_nat_destinations, vec!["172.16.0.0/12".to_string()]);
+        assert_eq!(app.override_mss, Some(1300));
+        assert_eq!(app.pf_template, Some(PathBuf::from("/etc/tunshare.pf")));
+
+        // Settings the template leaves out are called out
+        app.warn_template_ignores(&app.no_nat_destinations.clone(), None);
+        assert!(app.logs.iter().any(|entry| entry.message
+            == "no_nat_destinations not applied: the pf template replaces the generated rules"));
+    }
+
+    #[tokio::test]
+    async fn test_samplers_resume_after_pending_op() {
+        use crossterm::event::KeyCode;
+
+        let short = Duration::from_millis(50);
+        let mut app = test_app();
+        let mut gate = app.sampler_pause.gate();
+
+        // Held while the op runs, released when its result arrives
+        app.set_pending_op(PendingOp::ReloadingRules);
+        assert!(tokio::time::timeout(short, gate.wait()).await.is_err());
+        app.handle_async_result(AsyncOpResult::RulesReloaded {
+            result: Ok(()),
+            firewall: Firewall::new(),
+            ip_forwarding: IpForwarding::new(),
+            no_nat_destinations: Vec::new(),
+            override_mss: None,
+            pf_template: None,
+        });
+        assert!(tokio::time::timeout(short, gate.wait()).await.is_ok());
+
+        // ... or when the user cancels it
+        app.set_pending_op(PendingOp::StartingSharing);
+        assert!(tokio::time::timeout(short, gate.wait()).await.is_err());
+        app.handle_key(KeyCode::Esc);
+        assert_eq!(app.pending_op, None);
+        assert!(tokio::time::timeout(short, gate.wait()).await.is_ok());
+    }
+
+    #[test]
+    fn test_client_setup_command() {
+        let mut app = test_app();
+        assert_eq!(app.client_setup_command(), "");
+
+        let mut session = SharingSession::new(
+            Firewall::new(),
+            IpForwarding::new(),
+            "utun4".to_string(),
+            "bridge0".to_string(),
+            Ipv4Addr::new(192, 168, 2, 1),
+        );
+        session.lan_subnet = Some("192.168.2.0/23".parse().unwrap());
+        session.accounted_clients = vec![Ipv4Addr::new(192, 168, 3, 254)];
+        app.session = Some(session);
+        app.dns.custom = Some("1.1.1.1".to_string());
+
+        // Outside the .100-.150 DHCP range, with the LAN's own prefix
+        let snippet = app.client_setup_command();
+        assert!(snippet.starts_with("SERVICE=\"Wi-Fi\""));
+        assert!(
+            snippet.contains("-setmanual \"$SERVICE\" 192.168.3.253 255.255.254.0 192.168.2.1\n")
+        );
+        assert!(snippet.contains("-setdnsservers \"$SERVICE\" 1.1.1.1\n"));
+
+        app.lan_interfaces = vec![InterfaceInfo {
+            ipv4_address: Some(Ipv4Addr::new(192, 168, 2, 1)),
+            ipv4_addresses: vec![Ipv4Addr::new(192, 168, 2, 1)],
+            netmask: Some(Ipv4Addr::new(255, 255, 254, 0)),
+            description: Some("Thunderbolt Bridge".to_string()),
+            members: vec!["en1".to_string(), "en2".to_string()],
+            ..crate::system::network::iface("bridge0")
+        }];
+        assert!(app
+            .client_setup_command()
+            .starts_with("SERVICE=\"Thunderbolt Bridge\""));
     }
 }
@@ -0,0 +1,323 @@
+//! Command-line arguments.
+
+use crate::config;
+use crate::ui::status::LogLevel;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+/// Printed for `--help` and after argument errors.
+pub const USAGE: &str = "\
+Usage: sudo tunshare [OPTIONS]
+       tunshare config <validate|show>
+       tunshare config export <FILE>
+       tunshare config import <FILE> [--replace]
+
+Commands:
+  config validate   Check the config file and list any problems
+  config show       Print the effective config (file merged with defaults)
+  config export     Write the effective config, with its schema version, to FILE
+  config import     Merge the config in FILE into this one (imported settings
+                    win, profiles are added by name); --replace overwrites it
+
+Options:
+      --demo      Show the UI without root; sharing is disabled
+      --dry-run   Same as --demo
+      --dns <IP>  Use this DNS server for this run, over the discovered and
+                  configured ones; repeat for more servers
+      --save      Also save the --dns servers as the custom DNS
+      --label <NAME>
+                  Name this instance in the header and status output,
+                  e.g. Work (overrides session_label in the config file)
+      --http <ADDR>
+                  Serve a JSON control API on a loopback address,
+                  e.g. 127.0.0.1:8123 (GET /status, POST /start, POST /stop)
+      --no-animation
+                  Show a still glyph instead of the spinner while waiting
+      --log-level <LEVEL>
+                  Lowest level recorded in the log: info, success or warning
+                  (errors and warnings are always recorded)
+      --log-format <FORMAT>
+                  human (default) or json: also write events and log entries
+                  as one JSON object per line, to stdout with --headless,
+                  otherwise to stderr, which must be redirected
+      --headless  Run without the TUI, controlled through --http; the log
+                  goes to stderr (or JSON to stdout with --log-format json)
+  -h, --help      Print this help";
+
+/// Subcommands that run instead of the TUI.
+// Named after the full command line, so every `config` subcommand shares the prefix
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// `config validate`
+    ConfigValidate,
+    /// `config show`
+    ConfigShow,
+    /// `config export <file>`
+    ConfigExport(PathBuf),
+    /// `config import <file> [--replace]`
+    ConfigImport { file: PathBuf, replace: bool },
+}
+
+/// Parsed command-line arguments.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Args {
+    /// Run without root: everything is browsable, nothing touches the system.
+    pub read_only: bool,
+    /// Print usage and exit.
+    pub help: bool,
+    /// Subcommand to run instead of the TUI.
+    pub command: Option<Command>,
+    /// Lowest level recorded in the log (None = use the config file).
+    pub log_level: Option<LogLevel>,
+    /// Loopback address to serve the HTTP control API on.
+    pub http: Option<SocketAddr>,
+    /// Don't animate the loading indicator (overrides the config file).
+    pub no_animation: bool,
+    /// DNS servers for this run, in order (empty = use the config file).
+    pub dns: Vec<String>,
+    /// Save `dns` as the custom DNS instead of using it for this run only.
+    pub save: bool,
+    /// Name for this instance (None = use the config file).
+    pub label: Option<String>,
+    /// Write events and the log as JSON lines (`--log-format json`).
+    pub json_log: bool,
+    /// Run without the TUI, driven by the HTTP control API.
+    pub headless: bool,
+}
+
+impl Args {
+    /// Parse arguments (without the program name).
+    pub fn parse<I, S>(args: I) -> std::result::Result<Self, String>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut parsed = Args::default();
+        let mut invalid_dns = Vec::new();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_ref() {
+                "--demo" | "--dry-run" => parsed.read_only = true,
+                "-h" | "--help" => parsed.help = true,
+                "--no-animation" => parsed.no_animation = true,
+                "--headless" => parsed.headless = true,
+                "--dns" => {
+                    let Some(server) = args.next() else {
+                        return Err("--dns needs a server address, e.g. 1.1.1.1".into());
+                    };
+                    let server = server.as_ref().trim().to_string();
+                    if config::is_valid_dns_server(&server) {
+                        parsed.dns.push(server);
+                    } else {
+                        invalid_dns.push(server);
+                    }
+                }
+                "--save" => parsed.save = true,
+                "--label" => {
+                    let label = args.next().map(|l| l.as_ref().trim().to_string());
+                    match label {
+                        Some(label) if !label.is_empty() => parsed.label = Some(label),
+                        _ => return Err("--label needs a name, e.g. Work".into()),
+                    }
+                }
+                "--http" => {
+                    let Some(addr) = args.next() else {
+                        return Err("--http needs an address, e.g. 127.0.0.1:8123".into());
+                    };
+                    let addr: SocketAddr = addr
+                        .as_ref()
+                        .parse()
+                        .map_err(|_| format!("invalid --http address: {}", addr.as_ref()))?;
+                    if !addr.ip().is_loopback() {
+                        return Err(format!(
+                            "--http only binds loopback addresses (e.g. 127.0.0.1), not {}",
+                            addr.ip()
+                        ));
+                    }
+                    parsed.http = Some(addr);
+                }
+                "--log-level" => {
+                    let Some(name) = args.next() else {
+                        return Err("--log-level needs a level: info, success or warning".into());
+                    };
+                    parsed.log_level = Some(
+                        LogLevel::parse(name.as_ref())
+                            .ok_or_else(|| format!("unknown log level: {}", name.as_ref()))?,
+                    );
+                }
+                "--log-format" => {
+                    parsed.json_log = match args.next().as_ref().map(AsRef::as_ref) {
+                        Some("human") => false,
+                        Some("json") => true,
+                        Some(other) => return Err(format!("unknown log format: {}", other)),
+                        None => return Err("--log-format needs a format: human or json".into()),
+                    };
+                }
+                "config" if parsed.command.is_none() => {
+                    parsed.command = match args.next().as_ref().map(AsRef::as_ref) {
+                        Some("validate") => Some(Command::ConfigValidate),
+                        Some("show") => Some(Command::ConfigShow),
+                        Some("export") => match args.next() {
+                            Some(file) => Some(Command::ConfigExport(file.as_ref().into())),
+                            None => return Err("config export needs a file to write".into()),
+                        },
+                        Some("import") => match args.next() {
+                            Some(file) => Some(Command::ConfigImport {
+                                file: file.as_ref().into(),
+                                replace: false,
+                            }),
+                            None => return Err("config import needs a file to read".into()),
+                        },
+                        Some(other) => return Err(format!("unknown config command: {}", other)),
+                        None => {
+                            return Err(
+                                "config needs a command: validate, show, export or import".into()
+                            )
+                        }
+                    };
+                }
+                "--replace" => match parsed.command {
+                    Some(Command::ConfigImport {
+                        ref mut replace, ..
+                    }) => *replace = true,
+                    _ => return Err("--replace only applies to config import".into()),
+                },
+                other => return Err(format!("unknown argument: {}", other)),
+            }
+        }
+        // Report every bad server at once rather than one per attempt
+        if !invalid_dns.is_empty() {
+            return Err(format!(
+                "invalid --dns server (not an IP address): {}",
+                invalid_dns.join(", ")
+            ));
+        }
+        if parsed.save && parsed.dns.is_empty() {
+            return Err("--save only applies to --dns".into());
+        }
+        if parsed.headless && parsed.http.is_none() {
+            return Err(
+                "--headless needs --http to be controlled, e.g. --http 127.0.0.1:8123".into(),
+            );
+        }
+        Ok(parsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(Args::parse(Vec::<String>::new()), Ok(Args::default()));
+        assert!(Args::parse(["--demo"]).unwrap().read_only);
+        assert!(Args::parse(["--dry-run"]).unwrap().read_only);
+        assert!(Args::parse(["-h"]).unwrap().help);
+        assert!(Args::parse(["--no-animation"]).unwrap().no_animation);
+        assert!(Args::parse(["--demo", "--bogus"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_log_level() {
+        assert_eq!(
+            Args::parse(["--log-level", "success"]).unwrap().log_level,
+            Some(LogLevel::Success)
+        );
+        assert!(Args::parse(["--log-level"]).is_err());
+        assert!(Args::parse(["--log-level", "verbose"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_log_format() {
+        assert!(!Args::parse(Vec::<String>::new()).unwrap().json_log);
+        assert!(Args::parse(["--log-format", "json"]).unwrap().json_log);
+        assert!(!Args::parse(["--log-format", "human"]).unwrap().json_log);
+        assert!(Args::parse(["--log-format"]).is_err());
+        assert!(Args::parse(["--log-format", "xml"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_headless() {
+        let args = Args::parse([
+            "--headless",
+            "--http",
+            "127.0.0.1:8123",
+            "--log-format",
+            "json",
+        ])
+        .unwrap();
+        assert!(args.headless && args.json_log);
+        assert!(Args::parse(["--headless"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_dns() {
+        let args = Args::parse(["--dns", "1.1.1.1", "--dns", "2606:4700::1111"]).unwrap();
+        assert_eq!(args.dns, ["1.1.1.1", "2606:4700::1111"]);
+        assert!(!args.save);
+        assert!(Args::parse(["--dns", "9.9.9.9", "--save"]).unwrap().save);
+
+        assert_eq!(
+            Args::parse(["--dns", "1.1.1", "--dns", "8.8.8.8", "--dns", "dns.google"]),
+            Err("invalid --dns server (not an IP address): 1.1.1, dns.google".to_string())
+        );
+        assert!(Args::parse(["--dns"]).is_err());
+        assert!(Args::parse(["--save"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_label() {
+        assert_eq!(
+            Args::parse(["--label", " Work "]).unwrap().label.as_deref(),
+            Some("Work")
+        );
+        assert_eq!(Args::parse(Vec::<String>::new()).unwrap().label, None);
+        assert!(Args::parse(["--label"]).is_err());
+        assert!(Args::parse(["--label", " "]).is_err());
+    }
+
+    #[test]
+    fn test_parse_http() {
+        assert_eq!(
+            Args::parse(["--http", "127.0.0.1:8123"]).unwrap().http,
+            Some("127.0.0.1:8123".parse().unwrap())
+        );
+        assert!(Args::parse(["--http", "0.0.0.0:8123"]).is_err());
+        assert!(Args::parse(["--http", "8123"]).is_err());
+        assert!(Args::parse(["--http"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_config_commands() {
+        assert_eq!(
+            Args::parse(["config", "validate"]).unwrap().command,
+            Some(Command::ConfigValidate)
+        );
+        assert_eq!(
+            Args::parse(["config", "show"]).unwrap().command,
+            Some(Command::ConfigShow)
+        );
+        assert_eq!(
+            Args::parse(["config", "export", "backup.json"])
+                .unwrap()
+                .command,
+            Some(Command::ConfigExport("backup.json".into()))
+        );
+        assert_eq!(
+            Args::parse(["config", "import", "backup.json", "--replace"])
+                .unwrap()
+                .command,
+            Some(Command::ConfigImport {
+                file: "backup.json".into(),
+                replace: true
+            })
+        );
+        assert!(Args::parse(["config", "export"]).is_err());
+        assert!(Args::parse(["--replace"]).is_err());
+        assert!(Args::parse(["config"]).is_err());
+        assert!(Args::parse(["config", "edit"]).is_err());
+        assert!(Args::parse(["config", "show", "config", "show"]).is_err());
+    }
+}
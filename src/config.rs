@@ -1,21 +1,63 @@
 //! User preferences persistence.
 //!
-//! Saves/loads a small JSON config to `~/.config/tunshare/config.json`.
+//! Saves/loads a small JSON config to `~/.config/tunshare/config.json`
+//! (overridable via `TUNSHARE_CONFIG` or `XDG_CONFIG_HOME`).
 //! Failures are silently ignored (log at most) — the app always has sensible defaults.
 
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
 use std::fs;
-use std::path::PathBuf;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
 
+use ipnet::Ipv4Net;
 use serde::{Deserialize, Serialize};
 
+use crate::schedule;
+use crate::system::dhcp::{DhcpBackend, DhcpBoot};
+use crate::system::dns::DnsPreset;
+use crate::ui::status::LogLevel;
+
+/// Environment variable that overrides the config file path entirely.
+const CONFIG_PATH_ENV: &str = "TUNSHARE_CONFIG";
+
+/// Bounds for `override_mss`: the IPv4 minimum, and a full 1500-byte Ethernet MTU.
+const MIN_MSS: u16 = 536;
+const MAX_MSS: u16 = 1460;
+
+/// Version of the config file layout written by this build. Files without a
+/// `schema_version` predate it and are version 0; [`Config::migrate`] brings
+/// older files up to date.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Settings tied to this machine's interfaces, kept as they are when
+/// another machine's config is merged in.
+const LOCAL_KEYS: &[&str] = &["mru_vpn", "mru_lan", "last_dns_preset", "session_label"];
+
+/// List settings that are combined on merge rather than replaced.
+const LIST_KEYS: &[&str] = &[
+    "no_nat_destinations",
+    "connectivity_check_domains",
+    "dhcp_mac_allowlist",
+    "dns_presets",
+    "dns_order",
+    "dns_disabled",
+];
+
 /// Persisted user preferences.
 ///
 /// Every field has a serde default so that adding new fields later
 /// doesn't break old config files.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Config {
+    /// Layout version the file was written with (0 = before versioning).
+    #[serde(default)]
+    pub schema_version: u32,
+
     /// Whether to auto-start DHCP when sharing begins.
-    /// Stored as user *intent* — the app still checks for dnsmasq at runtime.
+    /// Stored as user *intent* — the app still checks for a DHCP daemon at runtime.
     #[serde(default = "default_true")]
     pub dhcp_enabled: bool,
 
@@ -26,28 +68,396 @@ pub struct Config {
     /// Custom DNS server override (None = auto-detect from VPN/system).
     #[serde(default)]
     pub custom_dns: Option<String>,
+
+    /// Further custom DNS servers, after `custom_dns` (saved by `--dns ... --save`).
+    #[serde(default)]
+    pub custom_dns_extra: Vec<String>,
+
+    /// Destinations LAN clients reach directly, without NAT through the VPN
+    /// (e.g. `10.0.50.0/24`). Kept as written, so saving never loses an entry;
+    /// ones that don't parse as IPv4 CIDR are ignored (see [`parse_cidrs`]).
+    #[serde(default)]
+    pub no_nat_destinations: Vec<String>,
+
+    /// Whether the log panel starts expanded.
+    #[serde(default)]
+    pub logs_expanded_default: bool,
+
+    /// Lowest log level recorded in the log panel. Capped at `warning`, so
+    /// warnings and errors are always recorded.
+    #[serde(default)]
+    pub log_level: LogLevel,
+
+    /// pf anchor for NAT-PMP mapping rules.
+    #[serde(default = "default_natpmp_anchor")]
+    pub natpmp_anchor: String,
+
+    /// Keep rules already in the NAT-PMP anchor when the server starts,
+    /// instead of flushing them as leftovers. For an anchor shared with
+    /// another instance.
+    #[serde(default)]
+    pub natpmp_preserve_existing: bool,
+
+    /// DHCP daemon to use (None = auto-detect: dnsmasq, then ISC dhcpd).
+    #[serde(default)]
+    pub dhcp_backend: Option<DhcpBackend>,
+
+    /// How many times to probe the tunnel after loading rules before giving up
+    /// (0 = don't wait for the tunnel).
+    #[serde(default = "default_tunnel_probe_attempts")]
+    pub tunnel_probe_attempts: u32,
+
+    /// Delay between tunnel probes, in milliseconds.
+    #[serde(default = "default_tunnel_probe_interval_ms")]
+    pub tunnel_probe_interval_ms: u64,
+
+    /// Tries for read-only system commands (`ifconfig`, `scutil`, `pfctl -s`)
+    /// that fail transiently, e.g. right after a network change (1 = no retries).
+    #[serde(default = "default_command_retry_attempts")]
+    pub command_retry_attempts: u32,
+
+    /// Wait before retrying a read-only command, in milliseconds; each later
+    /// retry waits one more step.
+    #[serde(default = "default_command_retry_backoff_ms")]
+    pub command_retry_backoff_ms: u64,
+
+    /// Named sharing setups that can be started without the interface wizard.
+    #[serde(default)]
+    pub profiles: Vec<Profile>,
+
+    /// External ports NAT-PMP clients may map, as `[low, high]` (inclusive).
+    #[serde(default = "default_natpmp_port_range")]
+    pub natpmp_allowed_port_range: (u16, u16),
+
+    /// Use blue/orange instead of green/red for statuses.
+    #[serde(default)]
+    pub colorblind_mode: bool,
+
+    /// Show a still glyph instead of the animated spinner while waiting.
+    #[serde(default)]
+    pub reduced_motion: bool,
+
+    /// After failing over, move back to the primary VPN once it's up again.
+    #[serde(default = "default_true")]
+    pub failover_prefer_primary: bool,
+
+    /// Keep IP forwarding on after sharing stops, even if it was off before.
+    #[serde(default)]
+    pub leave_forwarding_enabled: bool,
+
+    /// Daily window sharing may run in, as local `["HH:MM", "HH:MM"]`, e.g.
+    /// `["22:00", "06:00"]` past midnight. Outside it sharing is stopped and
+    /// won't start. None = any time.
+    #[serde(default)]
+    pub active_hours: Option<(String, String)>,
+
+    /// Extra `net.*` sysctls set while sharing and restored when it stops,
+    /// e.g. `{"net.inet.ip.redirect": 0}`. Forwarding is always managed.
+    #[serde(default)]
+    pub sysctls: BTreeMap<String, i64>,
+
+    /// VPN address NAT-PMP advertises when the VPN interface has several
+    /// (None, or not on the interface = its first address).
+    #[serde(default)]
+    pub natpmp_external_address: Option<Ipv4Addr>,
+
+    /// Restart the DHCP daemon if it exits while sharing.
+    #[serde(default = "default_true")]
+    pub dhcp_auto_restart: bool,
+
+    /// Leave the DHCP daemon running when sharing stops, so clients keep their
+    /// leases across stop/start (it's still stopped when tunshare exits).
+    #[serde(default)]
+    pub persistent_dhcp: bool,
+
+    /// Short name shown in the header and reported to external tools, to
+    /// tell several instances apart (e.g. "Work").
+    #[serde(default)]
+    pub session_label: Option<String>,
+
+    /// Domains devices probe for internet access (e.g. `captive.apple.com`),
+    /// resolved through `connectivity_check_resolver` by dnsmasq. Empty = off.
+    #[serde(default)]
+    pub connectivity_check_domains: Vec<String>,
+
+    /// Resolver for `connectivity_check_domains`.
+    #[serde(default = "default_connectivity_check_resolver")]
+    pub connectivity_check_resolver: String,
+
+    /// Ring the terminal bell when the connection goes down.
+    #[serde(default)]
+    pub bell_on_down: bool,
+
+    /// Ring the terminal bell when a down connection recovers.
+    #[serde(default)]
+    pub bell_on_recovery: bool,
+
+    /// Only clients with these MAC addresses get DHCP leases. Empty = everyone.
+    #[serde(default)]
+    pub dhcp_mac_allowlist: Vec<String>,
+
+    /// Netboot options for PXE clients: TFTP server and boot file. None = off.
+    #[serde(default)]
+    pub dhcp_boot: Option<DhcpBoot>,
+
+    /// Address (with prefix) assigned to a LAN interface that has no IPv4,
+    /// e.g. `192.168.42.1/24`. None = offer `192.168.42.1/24`.
+    #[serde(default)]
+    pub lan_gateway_ip: Option<Ipv4Net>,
+
+    /// TCP MSS clamp for shared traffic, replacing the default of 1400. For
+    /// VPNs whose path MTU is smaller than the tunnel reports. None = default.
+    #[serde(default)]
+    pub override_mss: Option<u16>,
+
+    /// A complete pf ruleset loaded instead of the generated one, with
+    /// `{vpn}`, `{lan}`, `{lan_subnet}` and `{dns}` filled in. Relative paths
+    /// are resolved against the config file's directory. None = generate.
+    #[serde(default)]
+    pub pf_template: Option<PathBuf>,
+
+    /// Preferred order of DNS servers; listed ones come first when present.
+    #[serde(default)]
+    pub dns_order: Vec<String>,
+
+    /// DNS servers left out even when discovered.
+    #[serde(default)]
+    pub dns_disabled: Vec<String>,
+
+    /// Extra presets for the DNS editor, e.g. a local resolver. One named
+    /// like a built-in replaces it.
+    #[serde(default)]
+    pub dns_presets: Vec<DnsPreset>,
+
+    /// List only `dns_presets` in the DNS editor, without the built-ins.
+    #[serde(default)]
+    pub dns_presets_replace: bool,
+
+    /// Name of the DNS preset picked last, highlighted when the DNS menu
+    /// opens on auto-detect.
+    #[serde(default, deserialize_with = "deserialize_preset_name")]
+    pub last_dns_preset: Option<String>,
+
+    /// VPN interfaces shared from recently, most recent first (at most [`MRU_LIMIT`]).
+    #[serde(default)]
+    pub mru_vpn: Vec<String>,
+
+    /// LAN interfaces shared onto recently, most recent first (at most [`MRU_LIMIT`]).
+    #[serde(default)]
+    pub mru_lan: Vec<String>,
+}
+
+/// A named sharing setup: which interfaces to share between, and with what settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    /// VPN interface to share from (e.g. `utun4`).
+    pub vpn: String,
+    /// LAN interface to share to (e.g. `en0`).
+    pub lan: String,
+    /// Custom DNS server (None = auto-detect).
+    #[serde(default)]
+    pub dns: Option<String>,
+    /// Further custom DNS servers, after `dns`.
+    #[serde(default)]
+    pub dns_extra: Vec<String>,
+    #[serde(default = "default_true")]
+    pub dhcp_enabled: bool,
+    #[serde(default = "default_true")]
+    pub natpmp_enabled: bool,
+    /// VPN interface NAT moves to while `vpn` is down (None = no failover).
+    #[serde(default)]
+    pub failover_vpn: Option<String>,
+}
+
+/// A problem found by [`Config::validate`] or [`Config::check`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    /// Field path, e.g. `custom_dns` or `profiles[1].dns`.
+    pub field: String,
+    pub message: String,
+    /// The offending value as written, used to find its line.
+    pub value: Option<String>,
+    /// 1-based line in the config file, when known.
+    pub line: Option<usize>,
+}
+
+impl ConfigError {
+    fn new(field: impl Into<String>, value: Option<&str>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+            value: value.map(str::to_string),
+            line: None,
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "line {}: ", line)?;
+        }
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// Most interface names kept in `mru_vpn` and `mru_lan`.
+pub const MRU_LIMIT: usize = 8;
+
+/// Move `name` to the front of a most-recently-used list, dropping its
+/// earlier entry and anything past [`MRU_LIMIT`].
+pub fn mru_push(list: &mut Vec<String>, name: &str) {
+    list.retain(|entry| entry != name);
+    list.insert(0, name.to_string());
+    list.truncate(MRU_LIMIT);
+}
+
+/// Whether `server` is usable as a DNS server address (IPv4 or IPv6).
+pub fn is_valid_dns_server(server: &str) -> bool {
+    server.parse::<IpAddr>().is_ok()
 }
 
 fn default_true() -> bool {
     true
 }
 
+fn default_tunnel_probe_attempts() -> u32 {
+    3
+}
+
+fn default_tunnel_probe_interval_ms() -> u64 {
+    1000
+}
+
+fn default_command_retry_attempts() -> u32 {
+    crate::system::exec::DEFAULT_ATTEMPTS
+}
+
+fn default_command_retry_backoff_ms() -> u64 {
+    crate::system::exec::DEFAULT_BACKOFF_MS
+}
+
+fn default_natpmp_port_range() -> (u16, u16) {
+    crate::system::natpmp::DEFAULT_PORT_RANGE
+}
+
+fn default_connectivity_check_resolver() -> String {
+    "1.1.1.1".to_string()
+}
+
+fn default_natpmp_anchor() -> String {
+    crate::system::natpmp::DEFAULT_ANCHOR_NAME.to_string()
+}
+
+/// The networks in a list of CIDR strings, skipping invalid entries, with
+/// host bits cleared (`192.168.7.9/16` is `192.168.0.0/16`).
+pub fn parse_cidrs(entries: &[String]) -> Vec<Ipv4Net> {
+    entries
+        .iter()
+        .filter_map(|entry| entry.trim().parse::<Ipv4Net>().ok())
+        .map(|net| net.trunc())
+        .collect()
+}
+
+/// Read `last_dns_preset`. Files from before it held a name stored the
+/// preset's position in the list, which may point elsewhere once presets
+/// change, so a number reads as no preset.
+fn deserialize_preset_name<'de, D>(deserializer: D) -> std::result::Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::String(name) => Some(name),
+        _ => None,
+    })
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
+            schema_version: SCHEMA_VERSION,
             dhcp_enabled: true,
             natpmp_enabled: true,
             custom_dns: None,
+            custom_dns_extra: Vec::new(),
+            no_nat_destinations: Vec::new(),
+            logs_expanded_default: false,
+            log_level: LogLevel::Info,
+            natpmp_anchor: default_natpmp_anchor(),
+            natpmp_preserve_existing: false,
+            dhcp_backend: None,
+            tunnel_probe_attempts: default_tunnel_probe_attempts(),
+            tunnel_probe_interval_ms: default_tunnel_probe_interval_ms(),
+            command_retry_attempts: default_command_retry_attempts(),
+            command_retry_backoff_ms: default_command_retry_backoff_ms(),
+            profiles: Vec::new(),
+            natpmp_allowed_port_range: default_natpmp_port_range(),
+            colorblind_mode: false,
+            reduced_motion: false,
+            failover_prefer_primary: true,
+            leave_forwarding_enabled: false,
+            active_hours: None,
+            sysctls: BTreeMap::new(),
+            natpmp_external_address: None,
+            dhcp_auto_restart: true,
+            persistent_dhcp: false,
+            session_label: None,
+            connectivity_check_domains: Vec::new(),
+            connectivity_check_resolver: default_connectivity_check_resolver(),
+            bell_on_down: false,
+            bell_on_recovery: false,
+            dhcp_mac_allowlist: Vec::new(),
+            dhcp_boot: None,
+            lan_gateway_ip: None,
+            override_mss: None,
+            pf_template: None,
+            dns_order: Vec::new(),
+            dns_disabled: Vec::new(),
+            dns_presets: Vec::new(),
+            dns_presets_replace: false,
+            last_dns_preset: None,
+            mru_vpn: Vec::new(),
+            mru_lan: Vec::new(),
         }
     }
 }
 
 impl Config {
-    /// Config file path: `~/.config/tunshare/config.json`.
+    /// Config file path, in order of precedence:
+    /// 1. `$TUNSHARE_CONFIG` (a file path)
+    /// 2. `$XDG_CONFIG_HOME/tunshare/config.json` (absolute paths only, per the XDG spec)
+    /// 3. The platform config dir (`~/Library/Application Support` on macOS)
     ///
     /// Returns `None` if the home/config directory can't be determined.
     pub fn path() -> Option<PathBuf> {
-        dirs::config_dir().map(|d| d.join("tunshare").join("config.json"))
+        Self::path_from(env::var_os(CONFIG_PATH_ENV), env::var_os("XDG_CONFIG_HOME"))
+    }
+
+    /// [`Config::path`] for the given `TUNSHARE_CONFIG` and `XDG_CONFIG_HOME` values.
+    fn path_from(override_path: Option<OsString>, xdg: Option<OsString>) -> Option<PathBuf> {
+        if let Some(path) = override_path.filter(|p| !p.is_empty()) {
+            return Some(PathBuf::from(path));
+        }
+
+        let xdg = xdg.map(PathBuf::from).filter(|p| p.is_absolute());
+
+        xdg.or_else(dirs::config_dir)
+            .map(|d| d.join("tunshare").join("config.json"))
+    }
+
+    /// Where a `pf_template` path points: `~/` expands to the home directory
+    /// and relative paths sit next to the config file.
+    pub fn resolve_pf_template(path: &Path) -> Option<PathBuf> {
+        if let Ok(rest) = path.strip_prefix("~") {
+            return dirs::home_dir().map(|home| home.join(rest));
+        }
+        if path.is_absolute() {
+            return Some(path.to_path_buf());
+        }
+        Self::path()
+            .and_then(|config| config.parent().map(Path::to_path_buf))
+            .map(|dir| dir.join(path))
     }
 
     /// Load config from disk, falling back to defaults on any error.
@@ -60,24 +470,782 @@ impl Config {
             return Self::default();
         };
 
-        serde_json::from_str(&contents).unwrap_or_default()
+        serde_json::from_str(&contents)
+            .map(Config::migrate)
+            .unwrap_or_default()
     }
 
-    /// Save config to disk. Creates parent directories if needed.
-    /// Logs nothing and never panics — this is best-effort.
-    pub fn save(&self) {
-        let Some(path) = Self::path() else {
-            return;
+    /// Bring a config read from an older file up to [`SCHEMA_VERSION`].
+    /// Newer versions are left alone; unknown fields were already ignored.
+    pub fn migrate(mut self) -> Self {
+        // 0 -> 1: only the version field was added
+        if self.schema_version < SCHEMA_VERSION {
+            self.schema_version = SCHEMA_VERSION;
+        }
+        self
+    }
+
+    /// Check field values that parse but can't be used.
+    pub fn validate(&self) -> std::result::Result<(), Vec<ConfigError>> {
+        let mut errors = Vec::new();
+        for (i, cidr) in self.no_nat_destinations.iter().enumerate() {
+            if cidr.trim().parse::<Ipv4Net>().is_err() {
+                errors.push(ConfigError::new(
+                    format!("no_nat_destinations[{}]", i),
+                    Some(cidr),
+                    format!("{:?} is not an IPv4 CIDR range; it is ignored", cidr),
+                ));
+            }
+        }
+
+        let mut check_dns = |field: String, dns: &Option<String>| {
+            if let Some(dns) = dns.as_deref().filter(|d| !is_valid_dns_server(d)) {
+                errors.push(ConfigError::new(
+                    field,
+                    Some(dns),
+                    format!("{:?} is not an IP address", dns),
+                ));
+            }
         };
 
-        if let Some(parent) = path.parent() {
-            let _ = fs::create_dir_all(parent);
+        check_dns("custom_dns".to_string(), &self.custom_dns);
+        for (i, dns) in self.custom_dns_extra.iter().enumerate() {
+            check_dns(format!("custom_dns_extra[{}]", i), &Some(dns.clone()));
+        }
+        for (i, profile) in self.profiles.iter().enumerate() {
+            check_dns(format!("profiles[{}].dns", i), &profile.dns);
+            for (j, dns) in profile.dns_extra.iter().enumerate() {
+                check_dns(
+                    format!("profiles[{}].dns_extra[{}]", i, j),
+                    &Some(dns.clone()),
+                );
+            }
+        }
+        for (i, preset) in self.dns_presets.iter().enumerate() {
+            check_dns(
+                format!("dns_presets[{}].primary", i),
+                &Some(preset.primary.clone()),
+            );
+            check_dns(format!("dns_presets[{}].secondary", i), &preset.secondary);
+        }
+        for (i, preset) in self.dns_presets.iter().enumerate() {
+            if preset.name.trim().is_empty() {
+                errors.push(ConfigError::new(
+                    format!("dns_presets[{}].name", i),
+                    None,
+                    "preset name is empty",
+                ));
+            }
+        }
+
+        for (i, profile) in self.profiles.iter().enumerate() {
+            if profile.name.trim().is_empty() {
+                errors.push(ConfigError::new(
+                    format!("profiles[{}].name", i),
+                    None,
+                    "profile name is empty",
+                ));
+            } else if self.profiles[..i].iter().any(|p| p.name == profile.name) {
+                errors.push(ConfigError::new(
+                    format!("profiles[{}].name", i),
+                    Some(&profile.name),
+                    format!("duplicate profile name {:?}", profile.name),
+                ));
+            }
+        }
+
+        if !is_valid_dns_server(&self.connectivity_check_resolver) {
+            errors.push(ConfigError::new(
+                "connectivity_check_resolver",
+                Some(&self.connectivity_check_resolver),
+                format!(
+                    "{:?} is not an IP address",
+                    self.connectivity_check_resolver
+                ),
+            ));
+        }
+        for (i, domain) in self.connectivity_check_domains.iter().enumerate() {
+            if domain.trim().is_empty() || domain.contains(['/', ' ']) {
+                errors.push(ConfigError::new(
+                    format!("connectivity_check_domains[{}]", i),
+                    Some(domain),
+                    format!("{:?} is not a domain name", domain),
+                ));
+            }
+        }
+
+        for (i, mac) in self.dhcp_mac_allowlist.iter().enumerate() {
+            if crate::system::dhcp::normalize_mac(mac).is_none() {
+                errors.push(ConfigError::new(
+                    format!("dhcp_mac_allowlist[{}]", i),
+                    Some(mac),
+                    format!("{:?} is not a MAC address like aa:bb:cc:dd:ee:ff", mac),
+                ));
+            }
+        }
+
+        if let Some(boot) = &self.dhcp_boot {
+            if !boot.next_server.is_private() {
+                errors.push(ConfigError::new(
+                    "dhcp_boot.next_server",
+                    Some(&boot.next_server.to_string()),
+                    "must be a private (RFC 1918) address clients can reach",
+                ));
+            }
+            if let Some(problem) = boot.filename_problem() {
+                errors.push(ConfigError::new(
+                    "dhcp_boot.filename",
+                    Some(boot.filename.as_str()).filter(|name| !name.trim().is_empty()),
+                    problem,
+                ));
+            }
+        }
+
+        if let Some(gateway) = self.lan_gateway_ip {
+            let value = gateway.to_string();
+            let problem = if !gateway.addr().is_private() {
+                Some("must be a private (RFC 1918) address")
+            } else if gateway.prefix_len() > 30 {
+                Some("the prefix must leave room for clients (at most /30)")
+            } else if gateway.addr() == gateway.network() || gateway.addr() == gateway.broadcast() {
+                Some("must be a host address, not the network or broadcast address")
+            } else {
+                None
+            };
+            if let Some(problem) = problem {
+                errors.push(ConfigError::new("lan_gateway_ip", Some(&value), problem));
+            }
+        }
+
+        if let Some(mss) = self.override_mss {
+            if !(MIN_MSS..=MAX_MSS).contains(&mss) {
+                errors.push(ConfigError::new(
+                    "override_mss",
+                    Some(&mss.to_string()),
+                    format!("must be between {} and {}", MIN_MSS, MAX_MSS),
+                ));
+            }
+        }
+
+        for key in self.sysctls.keys() {
+            let problem = if key == crate::system::sysctl::FORWARDING {
+                Some("IP forwarding is always managed; use leave_forwarding_enabled")
+            } else if !crate::system::sysctl::is_net_key(key) {
+                Some("only net.* sysctl names are allowed")
+            } else {
+                None
+            };
+            if let Some(problem) = problem {
+                errors.push(ConfigError::new(format!("sysctls.{}", key), None, problem));
+            }
+        }
+
+        if !crate::system::NatPmpServer::is_valid_anchor_name(&self.natpmp_anchor) {
+            errors.push(ConfigError::new(
+                "natpmp_anchor",
+                Some(&self.natpmp_anchor),
+                "only letters, digits, '-' and '_' are allowed",
+            ));
+        }
+
+        let (low, high) = self.natpmp_allowed_port_range;
+        if low > high {
+            errors.push(ConfigError::new(
+                "natpmp_allowed_port_range",
+                None,
+                format!("low port {} is above high port {}", low, high),
+            ));
+        } else if high < 1024 {
+            errors.push(ConfigError::new(
+                "natpmp_allowed_port_range",
+                None,
+                "the range must reach 1024 or above",
+            ));
+        }
+
+        if let Some((start, end)) = &self.active_hours {
+            for (field, time) in [("active_hours[0]", start), ("active_hours[1]", end)] {
+                if schedule::parse_time(time).is_none() {
+                    errors.push(ConfigError::new(
+                        field,
+                        Some(time),
+                        format!("{:?} is not a 24-hour HH:MM time", time),
+                    ));
+                }
+            }
+            if start.trim() == end.trim() {
+                errors.push(ConfigError::new(
+                    "active_hours",
+                    None,
+                    "start and end are the same, so the window is empty",
+                ));
+            }
+        }
+
+        if self.tunnel_probe_attempts > 0 && self.tunnel_probe_interval_ms == 0 {
+            errors.push(ConfigError::new(
+                "tunnel_probe_interval_ms",
+                None,
+                "must be above 0 while tunnel probes are enabled",
+            ));
+        }
+
+        if self.command_retry_attempts == 0 {
+            errors.push(ConfigError::new(
+                "command_retry_attempts",
+                Some("0"),
+                "must be at least 1 (1 = no retries)",
+            ));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
         }
+    }
 
-        let Ok(json) = serde_json::to_string_pretty(self) else {
-            return;
+    /// Parse and validate config file contents, reporting every problem with
+    /// the line it's on. Unlike [`Config::load`], entries that would be
+    /// silently dropped (invalid CIDRs) are reported too.
+    pub fn check(contents: &str) -> std::result::Result<Self, Vec<ConfigError>> {
+        let config: Config = serde_json::from_str(contents)
+            .map(Config::migrate)
+            .map_err(|e| {
+                vec![ConfigError {
+                    line: Some(e.line()),
+                    ..ConfigError::new("config", None, e.to_string())
+                }]
+            })?;
+
+        let mut errors = config.validate().err().unwrap_or_default();
+        if errors.is_empty() {
+            return Ok(config);
+        }
+        for error in &mut errors {
+            error.line = line_of(contents, error);
+        }
+        errors.sort_by_key(|e| e.line);
+        Err(errors)
+    }
+
+    /// Re-read the config file while running. Unlike [`Config::load`], a file
+    /// with problems is reported rather than replaced by defaults, so a typo
+    /// can't silently drop settings. A missing file gives the defaults.
+    pub fn reload() -> std::result::Result<Self, Vec<ConfigError>> {
+        match Self::path().and_then(|path| fs::read_to_string(path).ok()) {
+            Some(contents) => Self::check(&contents),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Parse an exported config for import. Like [`Config::check`], but a
+    /// file from a newer tunshare is refused instead of losing its settings.
+    pub fn import(contents: &str) -> std::result::Result<Self, Vec<ConfigError>> {
+        let raw: serde_json::Value = serde_json::from_str(contents).unwrap_or_default();
+        if let Some(version) = raw
+            .get("schema_version")
+            .and_then(|v| v.as_u64())
+            .filter(|&v| v > u64::from(SCHEMA_VERSION))
+        {
+            return Err(vec![ConfigError::new(
+                "schema_version",
+                Some(&version.to_string()),
+                format!(
+                    "written by a newer tunshare (this one reads up to {})",
+                    SCHEMA_VERSION
+                ),
+            )]);
+        }
+        Self::check(contents)
+    }
+
+    /// Combine `imported` into this config, returning the result and any
+    /// conflicts. Imported settings win; lists are combined; profiles are
+    /// added by name, and an existing profile with the same name but
+    /// different settings is kept and reported. Interface history
+    /// ([`LOCAL_KEYS`]) stays as it is.
+    pub fn merge(&self, imported: &Config) -> (Config, Vec<String>) {
+        let mut conflicts = Vec::new();
+        let mut merged = imported.clone();
+
+        let mut profiles = self.profiles.clone();
+        for profile in &imported.profiles {
+            match profiles.iter().find(|p| p.name == profile.name) {
+                None => profiles.push(profile.clone()),
+                Some(existing) if existing == profile => {}
+                Some(_) => conflicts.push(format!(
+                    "profile {:?} differs from the existing one, which was kept",
+                    profile.name
+                )),
+            }
+        }
+        merged.profiles = profiles;
+
+        // Go through JSON so lists and local keys are handled by name
+        let (Ok(serde_json::Value::Object(current)), Ok(serde_json::Value::Object(mut out))) =
+            (serde_json::to_value(self), serde_json::to_value(&merged))
+        else {
+            return (merged, conflicts);
         };
+        for key in LOCAL_KEYS {
+            if let Some(value) = current.get(*key) {
+                out.insert(key.to_string(), value.clone());
+            }
+        }
+        for key in LIST_KEYS {
+            let (Some(serde_json::Value::Array(mine)), Some(serde_json::Value::Array(theirs))) =
+                (current.get(*key), out.get(*key))
+            else {
+                continue;
+            };
+            let mut combined = mine.clone();
+            combined.extend(theirs.iter().filter(|v| !mine.contains(v)).cloned());
+            out.insert(key.to_string(), serde_json::Value::Array(combined));
+        }
+
+        match serde_json::from_value(serde_json::Value::Object(out)) {
+            Ok(config) => (config, conflicts),
+            Err(_) => (merged, conflicts),
+        }
+    }
+
+    /// Write config to `path` as pretty JSON, creating parent directories.
+    /// Used by `config export` and `config import`, which report failures,
+    /// and for saving preferences, which ignores them.
+    pub fn save_to(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        fs::write(path, json)
+    }
+}
+
+/// Line of the offending value, falling back to the line of its top-level key.
+fn line_of(contents: &str, error: &ConfigError) -> Option<usize> {
+    let key = error.field.split(['[', '.']).next().unwrap_or_default();
+    let needles = [
+        error.value.as_ref().map(|v| format!("{:?}", v)),
+        Some(format!("{:?}", key)),
+    ];
+    needles.into_iter().flatten().find_map(|needle| {
+        contents
+            .lines()
+            .position(|line| line.contains(&needle))
+            .map(|i| i + 1)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_round_trip() {
+        let config = Config {
+            schema_version: SCHEMA_VERSION,
+            dhcp_enabled: false,
+            natpmp_enabled: true,
+            custom_dns: Some("1.1.1.1".to_string()),
+            custom_dns_extra: vec!["1.0.0.1".to_string()],
+            no_nat_destinations: vec!["10.0.50.0/24".to_string()],
+            logs_expanded_default: true,
+            log_level: LogLevel::Warning,
+            natpmp_anchor: "custom-natpmp".to_string(),
+            natpmp_preserve_existing: true,
+            dhcp_backend: Some(DhcpBackend::IscDhcpd),
+            tunnel_probe_attempts: 5,
+            tunnel_probe_interval_ms: 500,
+            command_retry_attempts: 1,
+            command_retry_backoff_ms: 50,
+            profiles: vec![Profile {
+                name: "travel".to_string(),
+                vpn: "utun4".to_string(),
+                lan: "en5".to_string(),
+                dns: Some("9.9.9.9".to_string()),
+                dns_extra: vec!["149.112.112.112".to_string()],
+                dhcp_enabled: true,
+                natpmp_enabled: false,
+                failover_vpn: Some("utun5".to_string()),
+            }],
+            natpmp_allowed_port_range: (1024, 49151),
+            colorblind_mode: true,
+            reduced_motion: true,
+            failover_prefer_primary: false,
+            leave_forwarding_enabled: true,
+            active_hours: Some(("22:00".to_string(), "06:00".to_string())),
+            sysctls: BTreeMap::from([("net.inet.ip.redirect".to_string(), 0)]),
+            natpmp_external_address: Some(Ipv4Addr::new(203, 0, 113, 7)),
+            dhcp_auto_restart: false,
+            persistent_dhcp: true,
+            session_label: Some("Work".to_string()),
+            connectivity_check_domains: vec!["captive.apple.com".to_string()],
+            connectivity_check_resolver: "9.9.9.9".to_string(),
+            bell_on_down: true,
+            bell_on_recovery: true,
+            dhcp_mac_allowlist: vec!["aa:bb:cc:00:11:22".to_string()],
+            dhcp_boot: Some(DhcpBoot {
+                next_server: Ipv4Addr::new(10, 42, 0, 5),
+                filename: "pxelinux.0".to_string(),
+            }),
+            lan_gateway_ip: Some("10.42.0.1/24".parse().unwrap()),
+            override_mss: Some(1240),
+            pf_template: Some(PathBuf::from("/etc/tunshare/template.pf")),
+            dns_order: vec!["10.8.0.2".to_string(), "10.8.0.1".to_string()],
+            dns_disabled: vec!["10.8.0.3".to_string()],
+            dns_presets: vec![DnsPreset {
+                name: "Home".to_string(),
+                primary: "192.168.1.53".to_string(),
+                secondary: None,
+            }],
+            dns_presets_replace: true,
+            last_dns_preset: Some("Home".to_string()),
+            mru_vpn: vec!["utun4".to_string(), "utun3".to_string()],
+            mru_lan: vec!["en5".to_string()],
+        };
+
+        let json = serde_json::to_string_pretty(&config).unwrap();
+        let loaded: Config = serde_json::from_str(&json).unwrap();
+
+        assert!(!loaded.dhcp_enabled);
+        assert!(loaded.natpmp_enabled);
+        assert_eq!(loaded.custom_dns.as_deref(), Some("1.1.1.1"));
+        assert_eq!(loaded.custom_dns_extra, ["1.0.0.1"]);
+        assert_eq!(loaded.no_nat_destinations, config.no_nat_destinations);
+        assert!(loaded.logs_expanded_default);
+        assert_eq!(loaded.log_level, LogLevel::Warning);
+        assert!(json.contains("\"warning\""));
+        assert_eq!(loaded.natpmp_anchor, "custom-natpmp");
+        assert!(loaded.natpmp_preserve_existing);
+        assert_eq!(loaded.dhcp_backend, Some(DhcpBackend::IscDhcpd));
+        assert!(json.contains("\"isc_dhcpd\""));
+        assert_eq!(loaded.tunnel_probe_attempts, 5);
+        assert_eq!(loaded.tunnel_probe_interval_ms, 500);
+        assert_eq!(loaded.command_retry_attempts, 1);
+        assert_eq!(loaded.command_retry_backoff_ms, 50);
+        assert_eq!(loaded.profiles, config.profiles);
+        assert_eq!(loaded.natpmp_allowed_port_range, (1024, 49151));
+        assert!(loaded.colorblind_mode);
+        assert!(loaded.reduced_motion);
+        assert!(!loaded.failover_prefer_primary);
+        assert!(loaded.leave_forwarding_enabled);
+        assert_eq!(loaded.active_hours, config.active_hours);
+        assert_eq!(loaded.sysctls, config.sysctls);
+        assert_eq!(
+            loaded.natpmp_external_address,
+            Some(Ipv4Addr::new(203, 0, 113, 7))
+        );
+        assert!(!loaded.dhcp_auto_restart);
+        assert!(loaded.persistent_dhcp);
+        assert_eq!(loaded.session_label.as_deref(), Some("Work"));
+        assert_eq!(loaded.connectivity_check_domains, ["captive.apple.com"]);
+        assert_eq!(loaded.connectivity_check_resolver, "9.9.9.9");
+        assert!(loaded.bell_on_down);
+        assert!(loaded.bell_on_recovery);
+        assert_eq!(loaded.dhcp_mac_allowlist, ["aa:bb:cc:00:11:22"]);
+        assert_eq!(loaded.dhcp_boot, config.dhcp_boot);
+        assert_eq!(loaded.lan_gateway_ip, config.lan_gateway_ip);
+        assert_eq!(loaded.override_mss, Some(1240));
+        assert_eq!(loaded.pf_template, config.pf_template);
+        assert_eq!(loaded.dns_order, ["10.8.0.2", "10.8.0.1"]);
+        assert_eq!(loaded.dns_disabled, ["10.8.0.3"]);
+        assert_eq!(loaded.dns_presets, config.dns_presets);
+        assert!(loaded.dns_presets_replace);
+        assert_eq!(loaded.last_dns_preset.as_deref(), Some("Home"));
+
+        // Older files saved the preset's position, which is dropped
+        let old: Config = serde_json::from_str(r#"{"last_dns_preset": 2}"#).unwrap();
+        assert_eq!(old.last_dns_preset, None);
+        assert_eq!(loaded.mru_vpn, ["utun4", "utun3"]);
+        assert_eq!(loaded.mru_lan, ["en5"]);
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let config = Config {
+            custom_dns: Some("9.9.9.9".to_string()),
+            natpmp_allowed_port_range: (2000, 3000),
+            no_nat_destinations: vec!["10.0.50.0/24".to_string()],
+            profiles: vec![Profile {
+                name: "office".to_string(),
+                vpn: "utun4".to_string(),
+                lan: "en5".to_string(),
+                dns: None,
+                dns_extra: Vec::new(),
+                dhcp_enabled: false,
+                natpmp_enabled: true,
+                failover_vpn: None,
+            }],
+            ..Config::default()
+        };
+
+        let path = env::temp_dir().join(format!("tunshare-export-{}.json", std::process::id()));
+        config.save_to(&path).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(contents.contains("\"schema_version\": 1"));
+        assert_eq!(Config::import(&contents), Ok(config));
+    }
+
+    #[test]
+    fn test_migrate_and_newer_schema() {
+        // Files from before versioning are version 0 and get migrated
+        let config = Config::check(r#"{"custom_dns": "1.1.1.1"}"#).unwrap();
+        assert_eq!(config.schema_version, SCHEMA_VERSION);
+
+        let errors = Config::import(r#"{"schema_version": 99}"#).unwrap_err();
+        assert_eq!(errors[0].field, "schema_version");
+        assert!(Config::import(r#"{"custom_dns": "nope"}"#).is_err());
+    }
+
+    #[test]
+    fn test_merge_reports_profile_conflicts() {
+        let profile = |name: &str, lan: &str| Profile {
+            name: name.to_string(),
+            vpn: "utun4".to_string(),
+            lan: lan.to_string(),
+            dns: None,
+            dns_extra: Vec::new(),
+            dhcp_enabled: true,
+            natpmp_enabled: true,
+            failover_vpn: None,
+        };
+        let current = Config {
+            profiles: vec![profile("home", "en0"), profile("office", "en5")],
+            dns_order: vec!["10.8.0.1".to_string()],
+            mru_vpn: vec!["utun3".to_string()],
+            bell_on_down: false,
+            ..Config::default()
+        };
+        let imported = Config {
+            profiles: vec![
+                profile("home", "en0"),
+                profile("office", "en7"),
+                profile("lab", "en8"),
+            ],
+            dns_order: vec!["10.8.0.2".to_string(), "10.8.0.1".to_string()],
+            mru_vpn: vec!["utun9".to_string()],
+            bell_on_down: true,
+            ..Config::default()
+        };
+
+        let (merged, conflicts) = current.merge(&imported);
+        let names: Vec<&str> = merged.profiles.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["home", "office", "lab"]);
+        assert_eq!(merged.profiles[1].lan, "en5");
+        assert_eq!(conflicts.len(), 1);
+        assert!(conflicts[0].contains("\"office\""));
+
+        assert!(merged.bell_on_down);
+        assert_eq!(merged.dns_order, ["10.8.0.1", "10.8.0.2"]);
+        assert_eq!(merged.mru_vpn, ["utun3"]);
+    }
+
+    #[test]
+    fn test_mru_push() {
+        let mut mru = Vec::new();
+        mru_push(&mut mru, "utun3");
+        mru_push(&mut mru, "utun4");
+        assert_eq!(mru, ["utun4", "utun3"]);
+
+        // Re-using an entry moves it to the front without duplicating it
+        mru_push(&mut mru, "utun3");
+        assert_eq!(mru, ["utun3", "utun4"]);
+
+        for i in 0..MRU_LIMIT {
+            mru_push(&mut mru, &format!("utun{}", 10 + i));
+        }
+        assert_eq!(mru.len(), MRU_LIMIT);
+        assert_eq!(mru[0], format!("utun{}", 10 + MRU_LIMIT - 1));
+        assert!(!mru.contains(&"utun4".to_string()));
+    }
+
+    #[test]
+    fn test_path_honors_env_override() {
+        let path = Config::path_from(
+            Some("/tmp/tunshare-test/custom.json".into()),
+            Some("/tmp/xdg".into()),
+        );
+        assert_eq!(path, Some(PathBuf::from("/tmp/tunshare-test/custom.json")));
+
+        // Empty override falls through to XDG_CONFIG_HOME
+        let path = Config::path_from(Some("".into()), Some("/tmp/xdg".into()));
+        assert_eq!(path, Some(PathBuf::from("/tmp/xdg/tunshare/config.json")));
+    }
+
+    #[test]
+    fn test_missing_fields_use_defaults() {
+        let loaded: Config = serde_json::from_str("{}").unwrap();
+        assert!(loaded.dhcp_enabled);
+        assert!(loaded.natpmp_enabled);
+        assert!(!loaded.logs_expanded_default);
+        assert_eq!(loaded.log_level, LogLevel::Info);
+        assert_eq!(loaded.tunnel_probe_attempts, 3);
+        assert_eq!(loaded.tunnel_probe_interval_ms, 1000);
+        assert_eq!(loaded.command_retry_attempts, 3);
+        assert!(loaded.profiles.is_empty());
+    }
+
+    #[test]
+    fn test_profile_defaults() {
+        let json = r#"{"profiles": [{"name": "home", "vpn": "utun3", "lan": "en0"}]}"#;
+        let config: Config = serde_json::from_str(json).unwrap();
+
+        let profile = &config.profiles[0];
+        assert_eq!(profile.name, "home");
+        assert_eq!(profile.dns, None);
+        assert!(profile.dhcp_enabled);
+        assert!(profile.natpmp_enabled);
+    }
+
+    #[test]
+    fn test_no_nat_destinations_skips_invalid_entries() {
+        let json = r#"{"no_nat_destinations": ["10.0.50.0/24", "not-a-cidr", "192.168.7.9/16", "10.1.2.3"]}"#;
+        let config: Config = serde_json::from_str(json).unwrap();
+
+        assert_eq!(
+            parse_cidrs(&config.no_nat_destinations),
+            vec![
+                "10.0.50.0/24".parse::<Ipv4Net>().unwrap(),
+                "192.168.0.0/16".parse::<Ipv4Net>().unwrap(),
+            ]
+        );
+        assert!(config.dhcp_enabled);
+
+        // Saving writes every entry back as it was
+        let saved: Config = serde_json::from_str(&serde_json::to_string(&config).unwrap()).unwrap();
+        assert_eq!(
+            saved.no_nat_destinations,
+            ["10.0.50.0/24", "not-a-cidr", "192.168.7.9/16", "10.1.2.3"]
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(Config::default().validate(), Ok(()));
+
+        let config = Config {
+            custom_dns: Some("1.1.1".to_string()),
+            custom_dns_extra: vec!["8.8.8.8".to_string(), "dns.google".to_string()],
+            natpmp_anchor: "bad anchor".to_string(),
+            natpmp_allowed_port_range: (5000, 4000),
+            active_hours: Some(("8am".to_string(), "20:00".to_string())),
+            lan_gateway_ip: Some("192.168.42.0/24".parse().unwrap()),
+            override_mss: Some(9000),
+            dhcp_boot: Some(DhcpBoot {
+                next_server: Ipv4Addr::new(8, 8, 8, 8),
+                filename: " ".to_string(),
+            }),
+            sysctls: BTreeMap::from([
+                ("kern.maxfiles".to_string(), 1),
+                ("net.inet.ip.forwarding".to_string(), 1),
+                ("net.inet.ip.redirect".to_string(), 0),
+            ]),
+            profiles: vec![
+                Profile {
+                    name: "home".to_string(),
+                    vpn: "utun4".to_string(),
+                    lan: "en0".to_string(),
+                    dns: Some("2606:4700::1111".to_string()),
+                    dns_extra: Vec::new(),
+                    dhcp_enabled: true,
+                    natpmp_enabled: true,
+                    failover_vpn: None,
+                },
+                Profile {
+                    name: "home".to_string(),
+                    vpn: "utun5".to_string(),
+                    lan: "en0".to_string(),
+                    dns: None,
+                    dns_extra: Vec::new(),
+                    dhcp_enabled: true,
+                    natpmp_enabled: true,
+                    failover_vpn: None,
+                },
+            ],
+            ..Config::default()
+        };
+        let fields: Vec<String> = config
+            .validate()
+            .unwrap_err()
+            .into_iter()
+            .map(|e| e.field)
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                "custom_dns",
+                "custom_dns_extra[1]",
+                "profiles[1].name",
+                "dhcp_boot.next_server",
+                "dhcp_boot.filename",
+                "lan_gateway_ip",
+                "override_mss",
+                "sysctls.kern.maxfiles",
+                "sysctls.net.inet.ip.forwarding",
+                "natpmp_anchor",
+                "natpmp_allowed_port_range",
+                "active_hours[0]"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_lan_gateway_ip() {
+        let validate = |gateway: &str| {
+            Config {
+                lan_gateway_ip: Some(gateway.parse().unwrap()),
+                ..Config::default()
+            }
+            .validate()
+        };
+
+        // Smaller than a /24, and inside the usual .100-.150 pool: the DHCP
+        // range follows the subnet and skips the gateway
+        for gateway in ["192.168.2.1/25", "192.168.2.120/24", "10.42.0.1/30"] {
+            assert_eq!(validate(gateway), Ok(()), "{}", gateway);
+
+            let lan: Ipv4Net = gateway.parse().unwrap();
+            let (start, end) = crate::system::DhcpServer::calculate_dhcp_range(lan);
+            let (start, end): (Ipv4Addr, Ipv4Addr) = (start.parse().unwrap(), end.parse().unwrap());
+            assert!(lan.hosts().any(|ip| ip == start), "{}", gateway);
+            assert!(lan.hosts().any(|ip| ip == end), "{}", gateway);
+            assert!(!(start..=end).contains(&lan.addr()), "{}", gateway);
+        }
+
+        assert!(validate("192.168.2.1/31").is_err());
+        assert!(validate("192.168.2.127/25").is_err());
+        assert!(validate("8.8.8.1/24").is_err());
+    }
+
+    #[test]
+    fn test_check_reports_lines() {
+        let contents = r#"{
+  "custom_dns": "8.8.8.8",
+  "no_nat_destinations": [
+    "10.0.50.0/24",
+    "10.0.999.0/24"
+  ],
+  "profiles": [
+    { "name": "home", "vpn": "utun4", "lan": "en0", "dns": "9.9.9" }
+  ]
+}"#;
+        let errors = Config::check(contents).unwrap_err();
+        let lines: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            lines,
+            [
+                "line 5: no_nat_destinations[1]: \"10.0.999.0/24\" is not an IPv4 CIDR range; it is ignored",
+                "line 8: profiles[0].dns: \"9.9.9\" is not an IP address",
+            ]
+        );
+
+        // Syntax errors carry serde's line
+        let errors = Config::check("{\n  \"dhcp_enabled\": yes\n}").unwrap_err();
+        assert_eq!(errors[0].line, Some(2));
 
-        let _ = fs::write(&path, json);
+        assert!(Config::check("{}").is_ok());
     }
 }
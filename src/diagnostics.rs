@@ -0,0 +1,245 @@
+//! Expected-vs-actual checklist for an active share.
+//!
+//! Unlike the debug panel, which dumps raw pf and interface state, this
+//! compares what tunshare set up against what the system reports and says
+//! what to do about each mismatch.
+
+use crate::system::exec::RetryPolicy;
+use crate::system::{process, Firewall, IpForwarding, NatPmpServer};
+
+/// What the system reported when the checks ran. `None` = couldn't be read.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SystemFacts {
+    pub pf_enabled: Option<bool>,
+    /// `pfctl -sn` output (translation rules and anchors).
+    pub nat_rules: Option<String>,
+    pub ip_forwarding: Option<bool>,
+    /// Whether the DHCP daemon we started is still alive (None = no pid known).
+    pub dhcp_alive: Option<bool>,
+    /// Process bound to the NAT-PMP port, if any.
+    pub natpmp_port_holder: Option<(u32, String)>,
+}
+
+/// What tunshare believes it has set up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expectations {
+    pub vpn_name: String,
+    pub dhcp: bool,
+    pub natpmp: bool,
+    pub natpmp_anchor: String,
+    /// Our own pid, to tell our NAT-PMP socket from someone else's.
+    pub own_pid: u32,
+}
+
+/// One checklist row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub check: &'static str,
+    pub expected: String,
+    pub actual: String,
+    pub passed: bool,
+    /// What to try when the check fails.
+    pub hint: Option<&'static str>,
+}
+
+impl Diagnostic {
+    fn new(
+        check: &'static str,
+        expected: impl Into<String>,
+        actual: impl Into<String>,
+        passed: bool,
+        hint: &'static str,
+    ) -> Self {
+        Self {
+            check,
+            expected: expected.into(),
+            actual: actual.into(),
+            passed,
+            hint: (!passed).then_some(hint),
+        }
+    }
+}
+
+/// Read the facts the checklist is built from.
+pub async fn gather(dhcp_pid: Option<u32>, retry: RetryPolicy) -> SystemFacts {
+    let ip_fwd = IpForwarding::new();
+    let (pf_enabled, nat_rules, ip_forwarding, natpmp_port_holder) = tokio::join!(
+        Firewall::is_enabled(),
+        Firewall::get_current_rules(retry),
+        ip_fwd.get_state(),
+        NatPmpServer::port_holder()
+    );
+
+    SystemFacts {
+        pf_enabled: pf_enabled.ok(),
+        nat_rules: nat_rules.ok(),
+        ip_forwarding: ip_forwarding.ok(),
+        dhcp_alive: dhcp_pid.map(process::is_alive),
+        natpmp_port_holder,
+    }
+}
+
+/// Compare `facts` against `expect`, one row per check.
+/// DHCP and NAT-PMP rows are only included when those servers should be running.
+pub fn evaluate(expect: &Expectations, facts: &SystemFacts) -> Vec<Diagnostic> {
+    let on_off = |state: Option<bool>| match state {
+        Some(true) => "on",
+        Some(false) => "off",
+        None => "unknown",
+    };
+    let present = |found: Option<bool>| match found {
+        Some(true) => "present",
+        Some(false) => "missing",
+        None => "unknown",
+    };
+
+    let nat_rule = facts.nat_rules.as_ref().map(|rules| {
+        let prefix = format!("nat on {} ", expect.vpn_name);
+        rules
+            .lines()
+            .any(|line| line.trim_start().starts_with(&prefix))
+    });
+
+    let mut checks = vec![
+        Diagnostic::new(
+            "pf enabled",
+            "on",
+            on_off(facts.pf_enabled),
+            facts.pf_enabled == Some(true),
+            "Another tool disabled pf; press r to restart sharing",
+        ),
+        Diagnostic::new(
+            "NAT rule",
+            format!("nat on {}", expect.vpn_name),
+            present(nat_rule),
+            nat_rule == Some(true),
+            "The ruleset was replaced (e.g. by a VPN client or pfctl -f); press r to reload it",
+        ),
+        Diagnostic::new(
+            "IP forwarding",
+            "on",
+            on_off(facts.ip_forwarding),
+            facts.ip_forwarding == Some(true),
+            "Something turned forwarding off; press r, or run sysctl -w net.inet.ip.forwarding=1",
+        ),
+    ];
+
+    if expect.dhcp {
+        checks.push(Diagnostic::new(
+            "DHCP server",
+            "running",
+            match facts.dhcp_alive {
+                Some(true) => "running",
+                Some(false) => "exited",
+                None => "unknown",
+            },
+            facts.dhcp_alive == Some(true),
+            "Press h twice to restart it; check the log for why it exited",
+        ));
+    }
+
+    if expect.natpmp {
+        let anchor = facts.nat_rules.as_ref().map(|rules| {
+            let reference = format!("rdr-anchor \"{}\"", expect.natpmp_anchor);
+            rules.contains(&reference)
+        });
+        checks.push(Diagnostic::new(
+            "NAT-PMP anchor",
+            format!("rdr-anchor \"{}\"", expect.natpmp_anchor),
+            present(anchor),
+            anchor == Some(true),
+            "Port mappings won't apply without it; press r to reload the ruleset",
+        ));
+
+        let (actual, passed) = match &facts.natpmp_port_holder {
+            Some((pid, _)) if *pid == expect.own_pid => ("bound by tunshare".to_string(), true),
+            Some((pid, name)) => (format!("bound by {} ({})", name, pid), false),
+            None => ("not bound".to_string(), false),
+        };
+        checks.push(Diagnostic::new(
+            "UDP 5351",
+            "bound by tunshare",
+            actual,
+            passed,
+            "Press n twice to restart NAT-PMP; stop any other NAT-PMP/UPnP daemon first",
+        ));
+    }
+
+    checks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expectations() -> Expectations {
+        Expectations {
+            vpn_name: "utun4".into(),
+            dhcp: true,
+            natpmp: true,
+            natpmp_anchor: "tunshare-natpmp".into(),
+            own_pid: 100,
+        }
+    }
+
+    #[test]
+    fn test_evaluate_all_passing() {
+        let facts = SystemFacts {
+            pf_enabled: Some(true),
+            nat_rules: Some(
+                "nat on utun4 inet from 192.168.2.0/24 to any -> (utun4) static-port\n\
+                 rdr-anchor \"tunshare-natpmp\" all\n"
+                    .into(),
+            ),
+            ip_forwarding: Some(true),
+            dhcp_alive: Some(true),
+            natpmp_port_holder: Some((100, "tunshare".into())),
+        };
+
+        let checks = evaluate(&expectations(), &facts);
+        assert_eq!(checks.len(), 6);
+        assert!(checks.iter().all(|c| c.passed && c.hint.is_none()));
+    }
+
+    #[test]
+    fn test_evaluate_failures_have_hints() {
+        let facts = SystemFacts {
+            pf_enabled: Some(true),
+            nat_rules: Some("nat on utun9 inet from any to any -> (utun9)\n".into()),
+            ip_forwarding: None,
+            dhcp_alive: Some(false),
+            natpmp_port_holder: Some((42, "miniupnpd".into())),
+        };
+
+        let checks = evaluate(&expectations(), &facts);
+        let failed: Vec<&str> = checks
+            .iter()
+            .filter(|c| !c.passed)
+            .map(|c| c.check)
+            .collect();
+        assert_eq!(
+            failed,
+            [
+                "NAT rule",
+                "IP forwarding",
+                "DHCP server",
+                "NAT-PMP anchor",
+                "UDP 5351"
+            ]
+        );
+        assert!(checks
+            .iter()
+            .filter(|c| !c.passed)
+            .all(|c| c.hint.is_some()));
+        assert_eq!(checks[2].actual, "unknown");
+        assert_eq!(checks[5].actual, "bound by miniupnpd (42)");
+
+        // Servers that aren't supposed to run aren't checked
+        let minimal = Expectations {
+            dhcp: false,
+            natpmp: false,
+            ..expectations()
+        };
+        assert_eq!(evaluate(&minimal, &facts).len(), 3);
+    }
+}
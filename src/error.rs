@@ -1,5 +1,6 @@
 //! Error types for VPN sharing operations.
 
+use std::path::Path;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -20,11 +21,75 @@ pub enum TunshareError {
     #[error("Failed to parse output: {0}")]
     ParseError(String),
 
+    #[error("UDP port {port} is already in use")]
+    PortInUse {
+        port: u16,
+        /// Process holding the port as `(pid, command name)`, if it could be identified.
+        holder: Option<(u32, String)>,
+    },
+
+    #[error("VPN tunnel not ready: {0}")]
+    TunnelNotReady(String),
+
     #[error("Firewall error: {0}")]
     FirewallError(String),
 
+    #[error(
+        "pf rejected the rules: syntax error{}",
+        line.map(|l| format!(" on line {}", l)).unwrap_or_default()
+    )]
+    PfSyntaxError {
+        line: Option<u32>,
+        /// pfctl's full output, for the log.
+        stderr: String,
+    },
+
+    #[error("pf refused access to /dev/pf")]
+    PfPermission { stderr: String },
+
+    #[error("pf can't find an IP address for {iface}")]
+    PfInterfaceMissing { iface: String, stderr: String },
+
+    #[error("NAT-PMP port range {low}-{high} has no ports above 1023")]
+    InvalidPortRange { low: u16, high: u16 },
+
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 }
 
+impl TunshareError {
+    /// What the user can do about a failure, for the errors that have an obvious fix.
+    /// `pf_template` is the template the rules came from, if one is configured.
+    pub fn hint(&self, pf_template: Option<&Path>) -> Option<String> {
+        match self {
+            TunshareError::PfSyntaxError { .. } => Some(match pf_template {
+                Some(path) => format!(
+                    "check the pf template {}; the rules it produced are in the debug panel",
+                    path.display()
+                ),
+                None => "tunshare generated rules pf can't parse; please report this with the debug panel's rules"
+                    .to_string(),
+            }),
+            TunshareError::PfPermission { .. } | TunshareError::PermissionDenied => {
+                Some("pf needs root: run tunshare with sudo".to_string())
+            }
+            TunshareError::PfInterfaceMissing { iface, .. } => Some(format!(
+                "{} has no address yet; wait for it to connect and try again",
+                iface
+            )),
+            _ => None,
+        }
+    }
+
+    /// pfctl's own output behind a recognized pf failure.
+    pub fn pfctl_output(&self) -> Option<&str> {
+        match self {
+            TunshareError::PfSyntaxError { stderr, .. }
+            | TunshareError::PfPermission { stderr }
+            | TunshareError::PfInterfaceMissing { stderr, .. } => Some(stderr),
+            _ => None,
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, TunshareError>;
@@ -0,0 +1,206 @@
+//! Machine-readable event log for `--log-format json`.
+//!
+//! Every record is one JSON object on one line, with the time, the level and
+//! the event name, plus that event's own fields:
+//!
+//! ```text
+//! {"event":"sharing_started","gateway":"192.168.2.1","lan":"en5","level":"success","ts":"...","vpn":"utun4",...}
+//! {"client":"192.168.2.100","event":"client_connected","lan":"en5","level":"info","ts":"..."}
+//! {"category":"dhcp","event":"log","level":"warning","message":"...","ts":"..."}
+//! ```
+//!
+//! Log entries without an event of their own are written as `log` records
+//! (`error` for errors), so nothing in the Activity log is lost. Headless runs
+//! write records to stdout; with the TUI on the terminal they go to stderr,
+//! and only when it's redirected.
+
+use std::io::Write;
+use std::net::Ipv4Addr;
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+
+use crate::app::LogEntry;
+use crate::ui::status::LogLevel;
+
+/// Something that happened, with the details a log shipper would index.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// Sharing is up, DHCP and NAT-PMP included.
+    SharingStarted {
+        vpn: String,
+        lan: String,
+        gateway: Ipv4Addr,
+        label: Option<String>,
+        dhcp: bool,
+        natpmp: bool,
+    },
+    /// Sharing was stopped (or restarted).
+    SharingStopped {
+        vpn: String,
+        lan: String,
+        duration_secs: u64,
+        peak_clients: usize,
+        /// Cleanup succeeded and nothing was left behind.
+        clean: bool,
+    },
+    /// A LAN client was seen for the first time this session.
+    ClientConnected { client: Ipv4Addr, lan: String },
+    /// An error-level log entry.
+    Error {
+        category: &'static str,
+        message: String,
+    },
+    /// Any other log entry.
+    Log {
+        category: &'static str,
+        message: String,
+    },
+}
+
+/// An [`Event`] with its time and level, as written out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub ts: DateTime<Local>,
+    pub level: LogLevel,
+    pub event: Event,
+}
+
+impl Record {
+    /// `event` happening now.
+    pub fn new(level: LogLevel, event: Event) -> Self {
+        Self {
+            ts: Local::now(),
+            level,
+            event,
+        }
+    }
+
+    /// A log entry as a `log` (or `error`) record.
+    pub fn from_log(entry: &LogEntry) -> Self {
+        let category = entry.category.name();
+        let message = entry.message.clone();
+        let event = match entry.level {
+            LogLevel::Error => Event::Error { category, message },
+            _ => Event::Log { category, message },
+        };
+        Self {
+            ts: entry.time,
+            level: entry.level,
+            event,
+        }
+    }
+
+    /// The record as one line of JSON (without the newline).
+    pub fn to_json_line(&self) -> String {
+        let mut value = serde_json::to_value(&self.event).unwrap_or_default();
+        if let Some(fields) = value.as_object_mut() {
+            fields.insert("ts".into(), self.ts.to_rfc3339().into());
+            fields.insert("level".into(), self.level.label().into());
+        }
+        value.to_string()
+    }
+}
+
+/// Where records are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventSink {
+    Stdout,
+    Stderr,
+}
+
+impl EventSink {
+    /// Write `record` as a line, ignoring a closed pipe.
+    pub fn write(self, record: &Record) {
+        let line = format!("{}\n", record.to_json_line());
+        let _ = match self {
+            EventSink::Stdout => {
+                let mut stdout = std::io::stdout().lock();
+                stdout
+                    .write_all(line.as_bytes())
+                    .and_then(|()| stdout.flush())
+            }
+            EventSink::Stderr => std::io::stderr().write_all(line.as_bytes()),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::status::LogCategory;
+
+    fn parse(record: &Record) -> serde_json::Value {
+        let line = record.to_json_line();
+        assert!(!line.contains('\n'));
+        serde_json::from_str(&line).unwrap()
+    }
+
+    #[test]
+    fn test_sharing_started_record() {
+        let value = parse(&Record::new(
+            LogLevel::Success,
+            Event::SharingStarted {
+                vpn: "utun4".to_string(),
+                lan: "en5".to_string(),
+                gateway: Ipv4Addr::new(192, 168, 2, 1),
+                label: Some("Work".to_string()),
+                dhcp: true,
+                natpmp: false,
+            },
+        ));
+
+        assert_eq!(value["event"], "sharing_started");
+        assert_eq!(value["level"], "success");
+        assert_eq!(value["vpn"], "utun4");
+        assert_eq!(value["lan"], "en5");
+        assert_eq!(value["gateway"], "192.168.2.1");
+        assert_eq!(value["label"], "Work");
+        assert_eq!(value["dhcp"], true);
+        assert_eq!(value["natpmp"], false);
+        assert!(DateTime::parse_from_rfc3339(value["ts"].as_str().unwrap()).is_ok());
+    }
+
+    #[test]
+    fn test_event_records() {
+        let stopped = parse(&Record::new(
+            LogLevel::Info,
+            Event::SharingStopped {
+                vpn: "utun4".to_string(),
+                lan: "en5".to_string(),
+                duration_secs: 3725,
+                peak_clients: 3,
+                clean: true,
+            },
+        ));
+        assert_eq!(stopped["event"], "sharing_stopped");
+        assert_eq!(stopped["duration_secs"], 3725);
+        assert_eq!(stopped["peak_clients"], 3);
+
+        let client = parse(&Record::new(
+            LogLevel::Info,
+            Event::ClientConnected {
+                client: Ipv4Addr::new(192, 168, 2, 100),
+                lan: "en5".to_string(),
+            },
+        ));
+        assert_eq!(client["event"], "client_connected");
+        assert_eq!(client["client"], "192.168.2.100");
+
+        // Log entries keep their level and category; errors get their own event
+        let entry =
+            LogEntry::error("pfctl failed:\n\"syntax error\"").with_category(LogCategory::Firewall);
+        let error = parse(&Record::from_log(&entry));
+        assert_eq!(error["event"], "error");
+        assert_eq!(error["level"], "error");
+        assert_eq!(error["category"], "firewall");
+        assert_eq!(error["message"], entry.message.as_str());
+
+        let entry = LogEntry::warning("DHCP restarted").with_category(LogCategory::Dhcp);
+        let log = parse(&Record::from_log(&entry));
+        assert_eq!(log["event"], "log");
+        assert_eq!(log["level"], "warning");
+        assert_eq!(log["ts"], entry.time.to_rfc3339());
+    }
+}
@@ -1,9 +1,26 @@
 //! Connection health monitoring.
 //!
 //! Periodic checks that verify the VPN sharing setup is still working:
-//! VPN interface up, IP forwarding enabled.
+//! VPN interface up, IP forwarding enabled, traffic routed through the VPN.
 
+use crate::system::network;
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::time::Duration;
+use tokio::net::TcpSocket;
 use tokio::process::Command;
+use tokio::sync::watch;
+
+/// Public address used to check where internet-bound traffic is routed.
+/// VPNs that add `0/1` + `128/1` routes leave the default route untouched,
+/// so the default route alone isn't conclusive.
+const ROUTE_PROBE_ADDRESS: &str = "1.1.1.1";
+
+/// Where the tunnel probe connects: HTTPS on the same public address, which
+/// VPNs let through even when they drop ICMP.
+const TUNNEL_PROBE_TARGET: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(1, 1, 1, 1), 443);
+
+/// How long a single tunnel reachability probe may take.
+pub const TUNNEL_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
 
 /// Overall health status of the active sharing session.
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
@@ -17,27 +34,155 @@ pub enum HealthStatus {
     Down(String),
 }
 
+/// Whether moving from `prev` to `next` should ring the terminal bell:
+/// on entering `Down` (if `on_down`), or on leaving it for `Healthy` (if `on_recovery`).
+pub fn rings_bell(
+    prev: &HealthStatus,
+    next: &HealthStatus,
+    on_down: bool,
+    on_recovery: bool,
+) -> bool {
+    match (prev, next) {
+        (HealthStatus::Down(_), HealthStatus::Down(_)) => false,
+        (_, HealthStatus::Down(_)) => on_down,
+        (HealthStatus::Down(_), HealthStatus::Healthy) => on_recovery,
+        _ => false,
+    }
+}
+
+/// Which VPN to NAT through after a health check, when a failover VPN is set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailoverAction {
+    /// Keep the current uplink.
+    Stay,
+    /// Move NAT onto the failover VPN.
+    ToStandby,
+    /// Move NAT back onto the primary VPN.
+    ToPrimary,
+}
+
+/// Decide whether to switch uplinks.
+///
+/// `status` is the health of the uplink in use (the failover one when
+/// `on_standby`), and `other_up` whether the other one's interface is up.
+/// A down primary hands over to an up standby. From the standby, sharing goes
+/// back once the primary is up again if `prefer_primary`, and always when the
+/// standby itself goes down.
+pub fn failover_action(
+    status: &HealthStatus,
+    on_standby: bool,
+    other_up: bool,
+    prefer_primary: bool,
+) -> FailoverAction {
+    let down = matches!(status, HealthStatus::Down(_));
+    match (on_standby, other_up) {
+        (false, true) if down => FailoverAction::ToStandby,
+        (true, true) if down || prefer_primary => FailoverAction::ToPrimary,
+        _ => FailoverAction::Stay,
+    }
+}
+
+/// Pauses background samplers while a heavy operation (start, stop, ...) runs,
+/// so their `ifconfig`/`pfctl` calls don't compete with it for its timeout.
+pub struct SamplerPause {
+    tx: watch::Sender<bool>,
+}
+
+impl SamplerPause {
+    pub fn new() -> Self {
+        let (tx, _) = watch::channel(false);
+        Self { tx }
+    }
+
+    /// Pause or resume the samplers.
+    pub fn set(&self, paused: bool) {
+        self.tx
+            .send_if_modified(|current| std::mem::replace(current, paused) != paused);
+    }
+
+    /// A handle a sampler task waits on before shelling out.
+    pub fn gate(&self) -> SamplerGate {
+        SamplerGate(self.tx.subscribe())
+    }
+}
+
+/// Sampler-side view of a [`SamplerPause`].
+pub struct SamplerGate(watch::Receiver<bool>);
+
+impl SamplerGate {
+    /// Return once the samplers aren't paused (immediately if they aren't).
+    pub async fn wait(&mut self) {
+        // An error means the app is gone; nothing left to yield to
+        let _ = self.0.wait_for(|paused| !*paused).await;
+    }
+}
+
 /// Run health checks against the active sharing session.
 ///
 /// Checks (in order of severity):
 /// 1. VPN interface is still UP (critical — if down, all traffic fails)
 /// 2. IP forwarding is still enabled (warning — can be re-enabled)
-pub async fn check_health(vpn_name: &str) -> HealthStatus {
+/// 3. Internet traffic is routed via the VPN (warning — split tunnel, clients leak)
+///
+/// `extra_uplinks` are further VPN interfaces traffic is balanced across; one
+/// of them going down degrades the share (part of the connections fail).
+pub async fn check_health(vpn_name: &str, extra_uplinks: &[String]) -> HealthStatus {
     // Check VPN interface
     if !is_interface_up(vpn_name).await {
         return HealthStatus::Down(format!("VPN interface {} is no longer up", vpn_name));
     }
+    for uplink in extra_uplinks {
+        if !is_interface_up(uplink).await {
+            return HealthStatus::Degraded(format!("uplink {} is no longer up", uplink));
+        }
+    }
 
     // Check IP forwarding
     if !is_ip_forwarding_enabled().await {
         return HealthStatus::Degraded("IP forwarding was disabled externally".to_string());
     }
 
+    // Check that the VPN carries the default route
+    if let Some(default_if) = network::route_interface("default").await {
+        if default_if != vpn_name
+            && network::route_interface(ROUTE_PROBE_ADDRESS)
+                .await
+                .as_deref()
+                != Some(vpn_name)
+        {
+            return HealthStatus::Degraded(format!(
+                "split tunnel: default route is {}, not {}",
+                default_if, vpn_name
+            ));
+        }
+    }
+
     HealthStatus::Healthy
 }
 
+/// Check that traffic actually gets through the VPN tunnel.
+///
+/// Opens a TCP connection from the VPN's address (`vpn_ip`), so it succeeds
+/// only once the tunnel carries traffic regardless of how the routing table
+/// looks. A refused connection still made the round trip and counts too.
+pub async fn probe_tunnel(vpn_ip: Option<Ipv4Addr>) -> bool {
+    let connect = async {
+        let socket = TcpSocket::new_v4()?;
+        if let Some(ip) = vpn_ip {
+            socket.bind(SocketAddrV4::new(ip, 0).into())?;
+        }
+        socket.connect(TUNNEL_PROBE_TARGET.into()).await
+    };
+
+    match tokio::time::timeout(TUNNEL_PROBE_TIMEOUT, connect).await {
+        Ok(Ok(_)) => true,
+        Ok(Err(e)) => e.kind() == std::io::ErrorKind::ConnectionRefused,
+        Err(_) => false,
+    }
+}
+
 /// Check whether a network interface has the UP flag.
-async fn is_interface_up(interface: &str) -> bool {
+pub async fn is_interface_up(interface: &str) -> bool {
     let Ok(output) = Command::new("ifconfig").arg(interface).output().await else {
         // Can't run ifconfig — assume OK rather than false-alarming
         return true;
@@ -67,3 +212,99 @@ async fn is_ip_forwarding_enabled() -> bool {
     let stdout = String::from_utf8_lossy(&output.stdout);
     stdout.trim() == "1"
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sampler_gate_waits_while_paused() {
+        let pause = SamplerPause::new();
+        let mut gate = pause.gate();
+        let short = Duration::from_millis(50);
+
+        // Not paused: passes straight through
+        assert!(tokio::time::timeout(short, gate.wait()).await.is_ok());
+
+        pause.set(true);
+        let waiting = tokio::spawn(async move {
+            gate.wait().await;
+        });
+        tokio::time::sleep(short).await;
+        assert!(!waiting.is_finished());
+
+        // Resuming (op completed or cancelled) releases the sampler
+        pause.set(false);
+        tokio::time::timeout(Duration::from_secs(1), waiting)
+            .await
+            .expect("sampler should resume")
+            .unwrap();
+    }
+
+    #[test]
+    fn test_failover_action() {
+        let healthy = HealthStatus::Healthy;
+        let degraded = HealthStatus::Degraded("split tunnel".into());
+        let down = HealthStatus::Down("utun4 gone".into());
+
+        // On the primary: only a down primary with an up standby switches
+        assert_eq!(
+            failover_action(&down, false, true, true),
+            FailoverAction::ToStandby
+        );
+        assert_eq!(
+            failover_action(&down, false, false, true),
+            FailoverAction::Stay
+        );
+        assert_eq!(
+            failover_action(&degraded, false, true, true),
+            FailoverAction::Stay
+        );
+        assert_eq!(
+            failover_action(&healthy, false, true, false),
+            FailoverAction::Stay
+        );
+
+        // On the standby: back to a recovered primary if preferred
+        assert_eq!(
+            failover_action(&healthy, true, true, true),
+            FailoverAction::ToPrimary
+        );
+        assert_eq!(
+            failover_action(&healthy, true, true, false),
+            FailoverAction::Stay
+        );
+        assert_eq!(
+            failover_action(&healthy, true, false, true),
+            FailoverAction::Stay
+        );
+        // ...and always if the standby goes down while the primary is back
+        assert_eq!(
+            failover_action(&down, true, true, false),
+            FailoverAction::ToPrimary
+        );
+        assert_eq!(
+            failover_action(&down, true, false, true),
+            FailoverAction::Stay
+        );
+    }
+
+    #[test]
+    fn test_rings_bell() {
+        let healthy = HealthStatus::Healthy;
+        let degraded = HealthStatus::Degraded("forwarding off".into());
+        let down = HealthStatus::Down("utun4 gone".into());
+        let down_again = HealthStatus::Down("still gone".into());
+
+        assert!(rings_bell(&healthy, &down, true, false));
+        assert!(rings_bell(&degraded, &down, true, false));
+        assert!(!rings_bell(&healthy, &down, false, true));
+        // Only the transition rings, not every failing check
+        assert!(!rings_bell(&down, &down_again, true, true));
+
+        assert!(rings_bell(&down, &healthy, false, true));
+        assert!(!rings_bell(&down, &healthy, true, false));
+        assert!(!rings_bell(&down, &degraded, true, true));
+        assert!(!rings_bell(&healthy, &degraded, true, true));
+    }
+}
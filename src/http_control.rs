@@ -0,0 +1,442 @@
+//! Loopback-only HTTP control endpoint for home automation.
+//!
+//! Enabled with `--http 127.0.0.1:PORT`. A tiny JSON API, one request per
+//! connection:
+//!
+//! ```text
+//! GET  /status                          -> 200 {"ok": true, "sharing": false, ...}
+//! POST /start {"vpn": "utun4", "lan": "en5"} -> 202 {"ok": true, "message": "..."}
+//! POST /stop                            -> 202 {"ok": true, "message": "..."}
+//! ```
+//!
+//! Like the status socket, commands are answered by the app's main loop, so
+//! start/stop go through exactly the same path as the keyboard. Only loopback
+//! addresses can be bound, and connections from anywhere else are dropped.
+//!
+//! Loopback alone doesn't stop a web page in the user's browser from sending
+//! requests here, so requests carrying an `Origin` header (browsers add it to
+//! cross-site POSTs) and requests whose `Host` isn't the loopback address
+//! itself (DNS rebinding) are refused with 403.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use serde::Deserialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, oneshot};
+
+/// How long a connection waits for the main loop to answer.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(2);
+/// Largest request (head plus body) accepted.
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+/// How long a client gets to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A command sent over HTTP.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlCommand {
+    /// `GET /status`
+    Status,
+    /// `POST /start` with the interfaces to share between.
+    Start { vpn: String, lan: String },
+    /// `POST /stop`
+    Stop,
+}
+
+/// Body of `POST /start`.
+#[derive(Debug, Deserialize)]
+struct StartBody {
+    vpn: String,
+    lan: String,
+}
+
+/// HTTP status and JSON body to send back.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControlResponse {
+    pub status: u16,
+    pub body: serde_json::Value,
+}
+
+impl ControlResponse {
+    pub fn ok(body: serde_json::Value) -> Self {
+        Self { status: 200, body }
+    }
+
+    /// 202: the command was started; watch `/status` for the outcome.
+    pub fn accepted(message: impl Into<String>) -> Self {
+        Self {
+            status: 202,
+            body: serde_json::json!({ "ok": true, "message": message.into() }),
+        }
+    }
+
+    pub fn error(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: serde_json::json!({ "ok": false, "error": message.into() }),
+        }
+    }
+
+    /// The full HTTP/1.1 response.
+    fn to_http(&self) -> String {
+        let reason = match self.status {
+            200 => "OK",
+            202 => "Accepted",
+            400 => "Bad Request",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            409 => "Conflict",
+            413 => "Payload Too Large",
+            _ => "Service Unavailable",
+        };
+        let body = self.body.to_string();
+        format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.status,
+            reason,
+            body.len(),
+            body
+        )
+    }
+}
+
+/// A routed command waiting for the main loop to answer it.
+pub struct ControlRequest {
+    pub command: ControlCommand,
+    pub reply: oneshot::Sender<ControlResponse>,
+}
+
+/// Map a request to a command, or the error response to send instead.
+pub fn route(
+    method: &str,
+    path: &str,
+    body: &str,
+) -> std::result::Result<ControlCommand, ControlResponse> {
+    // Query strings are ignored
+    let path = path.split('?').next().unwrap_or_default();
+    match (method, path) {
+        ("GET", "/status") => Ok(ControlCommand::Status),
+        ("POST", "/start") => serde_json::from_str::<StartBody>(body)
+            .map(|start| ControlCommand::Start {
+                vpn: start.vpn,
+                lan: start.lan,
+            })
+            .map_err(|e| {
+                ControlResponse::error(400, format!("expected {{\"vpn\", \"lan\"}}: {}", e))
+            }),
+        ("POST", "/stop") => Ok(ControlCommand::Stop),
+        (_, "/status" | "/start" | "/stop") => Err(ControlResponse::error(
+            405,
+            format!("{} not allowed on {}", method, path),
+        )),
+        _ => Err(ControlResponse::error(
+            404,
+            format!("no such endpoint: {}", path),
+        )),
+    }
+}
+
+/// The parts of a request head tunshare looks at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RequestHead {
+    method: String,
+    path: String,
+    content_length: usize,
+    host: Option<String>,
+    origin: Option<String>,
+}
+
+/// Parse the request head (up to the blank line).
+fn parse_head(head: &str) -> Option<RequestHead> {
+    let mut lines = head.lines();
+    let mut request_line = lines.next()?.split_whitespace();
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.to_string();
+
+    let headers: Vec<(&str, &str)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim(), value.trim()))
+        .collect();
+    let header = |wanted: &str| {
+        headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(wanted))
+            .map(|(_, value)| value.to_string())
+    };
+
+    let content_length = match header("content-length") {
+        Some(value) => value.parse().ok()?,
+        None => 0,
+    };
+    Some(RequestHead {
+        method,
+        path,
+        content_length,
+        host: header("host"),
+        origin: header("origin"),
+    })
+}
+
+/// Refuse requests a web page could have made: any with an `Origin` header,
+/// and any whose `Host` isn't a loopback name with the port we listen on.
+fn check_request_origin(head: &RequestHead, port: u16) -> std::result::Result<(), ControlResponse> {
+    if head.origin.is_some() {
+        return Err(ControlResponse::error(
+            403,
+            "requests from web pages are refused",
+        ));
+    }
+    let allowed = head.host.as_deref().is_some_and(|host| {
+        let (name, host_port) = match host.rsplit_once(':') {
+            Some((name, p)) if !name.is_empty() && !p.contains(']') => (name, p.parse().ok()),
+            _ => (host, Some(80)),
+        };
+        let name = name.to_ascii_lowercase();
+        host_port == Some(port) && ["127.0.0.1", "localhost", "[::1]"].contains(&name.as_str())
+    });
+    if allowed {
+        Ok(())
+    } else {
+        Err(ControlResponse::error(
+            403,
+            "Host must be 127.0.0.1, localhost or [::1] with the listening port",
+        ))
+    }
+}
+
+/// Refuse anything but a loopback address, so the endpoint is never exposed
+/// to the LAN being shared.
+pub fn check_loopback(addr: SocketAddr) -> std::io::Result<()> {
+    if addr.ip().is_loopback() {
+        Ok(())
+    } else {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!("{} is not a loopback address", addr.ip()),
+        ))
+    }
+}
+
+/// Bind `addr` (loopback only) and start accepting connections.
+///
+/// Returns the receiving end for commands, which the main loop drains.
+pub fn start(addr: SocketAddr) -> std::io::Result<mpsc::UnboundedReceiver<ControlRequest>> {
+    check_loopback(addr)?;
+    let listener = std::net::TcpListener::bind(addr)?;
+    listener.set_nonblocking(true)?;
+    let listener = TcpListener::from_std(listener)?;
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    let port = listener.local_addr()?.port();
+
+    tokio::spawn(async move {
+        while let Ok((stream, peer)) = listener.accept().await {
+            // Bound to loopback, so this shouldn't happen; drop it if it does
+            if !peer.ip().is_loopback() {
+                continue;
+            }
+            tokio::spawn(handle_connection(stream, port, tx.clone()));
+        }
+    });
+
+    Ok(rx)
+}
+
+/// Read one request, hand it to the main loop, and write the response.
+async fn handle_connection(
+    mut stream: TcpStream,
+    port: u16,
+    tx: mpsc::UnboundedSender<ControlRequest>,
+) {
+    let response = match tokio::time::timeout(READ_TIMEOUT, read_request(&mut stream)).await {
+        Ok(Ok((head, body))) => match check_request_origin(&head, port)
+            .and_then(|()| route(&head.method, &head.path, &body))
+        {
+            Ok(command) => {
+                let (reply_tx, reply_rx) = oneshot::channel();
+                if tx
+                    .send(ControlRequest {
+                        command,
+                        reply: reply_tx,
+                    })
+                    .is_err()
+                {
+                    return; // App is gone
+                }
+                match tokio::time::timeout(COMMAND_TIMEOUT, reply_rx).await {
+                    Ok(Ok(response)) => response,
+                    _ => ControlResponse::error(503, "no response from tunshare"),
+                }
+            }
+            Err(response) => response,
+        },
+        Ok(Err(response)) => response,
+        Err(_) => ControlResponse::error(400, "timed out reading the request"),
+    };
+
+    let _ = stream.write_all(response.to_http().as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+/// Read the head and body of one request.
+async fn read_request(
+    stream: &mut TcpStream,
+) -> std::result::Result<(RequestHead, String), ControlResponse> {
+    let too_large = || ControlResponse::error(413, "request too large");
+    let malformed = || ControlResponse::error(400, "malformed request");
+
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    let head_end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos;
+        }
+        if buf.len() > MAX_REQUEST_BYTES {
+            return Err(too_large());
+        }
+        let n = stream.read(&mut chunk).await.map_err(|_| malformed())?;
+        if n == 0 {
+            return Err(malformed());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&buf[..head_end]).to_string();
+    let head = parse_head(&head).ok_or_else(malformed)?;
+    let content_length = head.content_length;
+    // Content-Length comes from the client, so the sum must not overflow
+    match (head_end + 4).checked_add(content_length) {
+        Some(total) if total <= MAX_REQUEST_BYTES => {}
+        _ => return Err(too_large()),
+    }
+
+    let mut body = buf[head_end + 4..].to_vec();
+    while body.len() < content_length {
+        let n = stream.read(&mut chunk).await.map_err(|_| malformed())?;
+        if n == 0 {
+            return Err(malformed());
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    body.truncate(content_length);
+
+    Ok((head, String::from_utf8_lossy(&body).to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route() {
+        assert_eq!(route("GET", "/status", ""), Ok(ControlCommand::Status));
+        assert_eq!(route("GET", "/status?x=1", ""), Ok(ControlCommand::Status));
+        assert_eq!(route("POST", "/stop", ""), Ok(ControlCommand::Stop));
+        assert_eq!(
+            route("POST", "/start", r#"{"vpn": "utun4", "lan": "en5"}"#),
+            Ok(ControlCommand::Start {
+                vpn: "utun4".to_string(),
+                lan: "en5".to_string()
+            })
+        );
+
+        let status = |result: std::result::Result<ControlCommand, ControlResponse>| {
+            result.unwrap_err().status
+        };
+        assert_eq!(status(route("POST", "/start", r#"{"vpn": "utun4"}"#)), 400);
+        assert_eq!(status(route("POST", "/start", "")), 400);
+        assert_eq!(status(route("GET", "/start", "")), 405);
+        assert_eq!(status(route("DELETE", "/status", "")), 405);
+        assert_eq!(status(route("GET", "/", "")), 404);
+    }
+
+    #[test]
+    fn test_parse_head() {
+        let head = "POST /start HTTP/1.1\r\nHost: localhost:8123\r\ncontent-length: 31";
+        assert_eq!(
+            parse_head(head),
+            Some(RequestHead {
+                method: "POST".to_string(),
+                path: "/start".to_string(),
+                content_length: 31,
+                host: Some("localhost:8123".to_string()),
+                origin: None,
+            })
+        );
+        let head = parse_head("GET /status HTTP/1.1\r\nORIGIN: https://example.com").unwrap();
+        assert_eq!(head.content_length, 0);
+        assert_eq!(head.host, None);
+        assert_eq!(head.origin.as_deref(), Some("https://example.com"));
+        assert_eq!(
+            parse_head("POST /start HTTP/1.1\r\nContent-Length: lots"),
+            None
+        );
+        assert_eq!(parse_head(""), None);
+    }
+
+    #[test]
+    fn test_check_request_origin() {
+        let request = |host: Option<&str>, origin: Option<&str>| RequestHead {
+            method: "POST".to_string(),
+            path: "/stop".to_string(),
+            content_length: 0,
+            host: host.map(str::to_string),
+            origin: origin.map(str::to_string),
+        };
+        let check = |host, origin| check_request_origin(&request(host, origin), 8123);
+
+        assert!(check(Some("127.0.0.1:8123"), None).is_ok());
+        assert!(check(Some("LOCALHOST:8123"), None).is_ok());
+        assert!(check(Some("[::1]:8123"), None).is_ok());
+
+        // Cross-site request from a web page
+        let refused = check(Some("127.0.0.1:8123"), Some("https://evil.example"));
+        assert_eq!(refused.unwrap_err().status, 403);
+        assert!(check(Some("127.0.0.1:8123"), Some("null")).is_err());
+
+        // DNS rebinding: the page's own name resolved to loopback
+        assert!(check(Some("evil.example:8123"), None).is_err());
+        assert!(check(Some("127.0.0.1:9999"), None).is_err());
+        assert!(check(Some("127.0.0.1"), None).is_err());
+        assert!(check(Some("[::1]"), None).is_err());
+        assert!(check(None, None).is_err());
+    }
+
+    #[test]
+    fn test_binding_is_loopback_only() {
+        assert!(check_loopback("127.0.0.1:8123".parse().unwrap()).is_ok());
+        assert!(check_loopback("[::1]:8123".parse().unwrap()).is_ok());
+        assert!(check_loopback("0.0.0.0:8123".parse().unwrap()).is_err());
+        assert!(check_loopback("192.168.2.1:8123".parse().unwrap()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_request_rejects_huge_content_length() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        let (mut server, _) = listener.accept().await.unwrap();
+
+        client
+            .write_all(
+                b"POST /start HTTP/1.1\r\nHost: 127.0.0.1\r\nContent-Length: 18446744073709551615\r\n\r\n",
+            )
+            .await
+            .unwrap();
+
+        let response = read_request(&mut server).await.unwrap_err();
+        assert_eq!(response.status, 413);
+    }
+
+    #[test]
+    fn test_response_to_http() {
+        let http = ControlResponse::error(404, "no such endpoint: /").to_http();
+        let (head, body) = http.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(head.contains(&format!("Content-Length: {}", body.len())));
+        let value: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(value["ok"], false);
+    }
+}
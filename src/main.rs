@@ -4,16 +4,22 @@
 //! Uses macOS's pf (packet filter) firewall for NAT.
 
 mod app;
+mod cli;
 mod config;
+mod diagnostics;
 mod error;
+mod event_log;
 mod health;
+mod http_control;
+mod schedule;
 mod session;
+mod status_socket;
 mod system;
 mod ui;
 
-use std::io;
+use std::io::{self, Write};
 use std::panic;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
 use crossterm::{
@@ -24,28 +30,66 @@ use crossterm::{
 use ratatui::{
     backend::CrosstermBackend,
     layout::{Constraint, Direction, Layout},
-    Terminal,
+    Frame, Terminal,
 };
+use tokio::signal::unix::{signal, SignalKind};
 
-use app::{App, AppState};
+use app::{App, AppState, StopSummary};
+use cli::{Args, Command};
+use config::Config;
 use ui::{
     debug::render_debug_panel,
-    interface_select::{render_lan_selection, render_vpn_selection},
+    interface_select::{render_lan_selection, render_missing_interfaces, render_vpn_selection},
     main_menu::{
-        render_connection_info, render_dns_edit, render_header, render_main_menu, render_separator,
+        render_connection_info, render_dns_edit, render_header, render_label_input,
+        render_main_menu, render_profile_name_input, render_profile_picker, render_separator,
+    },
+    mini::render_mini,
+    status::{
+        render_diagnostics, render_dns_probe_report, render_help, render_loading_indicator,
+        render_log_detail, render_probe_report, render_status_panel, render_stop_summary,
+        render_toast, render_too_small, LayoutMode,
     },
-    status::{render_help, render_loading_indicator, render_status_panel},
 };
 
+/// How long to wait for the async stop after a termination signal before
+/// giving up and relying on the synchronous `Drop` cleanup.
+/// Slightly longer than the stop operation's own timeout so it can report back.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(12);
+
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Check for root privileges
-    if !is_root() {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("Error: {}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    if args.help {
+        println!("{}", cli::USAGE);
+        return Ok(());
+    }
+    if let Some(command) = args.command.clone() {
+        std::process::exit(run_command(command));
+    }
+
+    // Check for root privileges (read-only runs never touch the system)
+    if !args.read_only && !is_root() {
         eprintln!("Error: This program must be run as root (sudo).");
-        eprintln!("Usage: sudo tunshare");
+        eprintln!("Usage: sudo tunshare  (or tunshare --demo to look around without sharing)");
         std::process::exit(1);
     }
 
+    if args.headless {
+        let result = run_headless(&args).await;
+        status_socket::cleanup();
+        if let Ok(Some(summary)) = &result {
+            eprintln!("{}", summary);
+        }
+        return result.map(|_| ());
+    }
+
     // Set up panic hook to restore terminal on panic
     let original_hook = panic::take_hook();
     panic::set_hook(Box::new(move |panic_info| {
@@ -56,20 +100,165 @@ async fn main() -> Result<()> {
     }));
 
     // Run the app
-    let result = run_app().await;
+    let result = run_app(&args).await;
+
+    status_socket::cleanup();
 
     // Restore terminal on exit
     disable_raw_mode()?;
     execute!(io::stdout(), LeaveAlternateScreen)?;
 
-    result
+    // The TUI is gone, so the summary of a session stopped by quitting goes to stderr
+    if let Ok(Some(summary)) = &result {
+        eprintln!("{}", summary);
+    }
+
+    result.map(|_| ())
+}
+
+/// Run a non-TUI subcommand, returning the exit code.
+fn run_command(command: Command) -> i32 {
+    let Some(path) = Config::path() else {
+        eprintln!("Error: can't determine the config file location");
+        return 1;
+    };
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => Some(contents),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => {
+            eprintln!("Error: can't read {}: {}", path.display(), e);
+            return 1;
+        }
+    };
+
+    match command {
+        Command::ConfigValidate => {
+            let Some(contents) = contents else {
+                println!("{}: not found, defaults will be used", path.display());
+                return 0;
+            };
+            match Config::check(&contents) {
+                Ok(_) => {
+                    println!("{}: OK", path.display());
+                    0
+                }
+                Err(errors) => {
+                    for error in &errors {
+                        eprintln!("{}: {}", path.display(), error);
+                    }
+                    1
+                }
+            }
+        }
+        Command::ConfigShow => {
+            let config = effective_config(&path, contents.as_deref());
+            match serde_json::to_string_pretty(&config) {
+                Ok(json) => {
+                    println!("{}", json);
+                    0
+                }
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    1
+                }
+            }
+        }
+        Command::ConfigExport(file) => {
+            let config = effective_config(&path, contents.as_deref());
+            match config.save_to(&file) {
+                Ok(()) => {
+                    println!(
+                        "Exported {} (schema version {}) to {}",
+                        path.display(),
+                        config.schema_version,
+                        file.display()
+                    );
+                    0
+                }
+                Err(e) => {
+                    eprintln!("Error: can't write {}: {}", file.display(), e);
+                    1
+                }
+            }
+        }
+        Command::ConfigImport { file, replace } => {
+            let imported = match std::fs::read_to_string(&file) {
+                Ok(imported) => imported,
+                Err(e) => {
+                    eprintln!("Error: can't read {}: {}", file.display(), e);
+                    return 1;
+                }
+            };
+            let imported = match Config::import(&imported) {
+                Ok(config) => config,
+                Err(errors) => {
+                    for error in &errors {
+                        eprintln!("{}: {}", file.display(), error);
+                    }
+                    eprintln!("Nothing imported");
+                    return 1;
+                }
+            };
+
+            let current = effective_config(&path, contents.as_deref());
+            let (config, conflicts) = if replace {
+                (imported, Vec::new())
+            } else {
+                current.merge(&imported)
+            };
+            for conflict in &conflicts {
+                eprintln!("conflict: {}", conflict);
+            }
+            if let Err(e) = config.save_to(&path) {
+                eprintln!("Error: can't write {}: {}", path.display(), e);
+                return 1;
+            }
+
+            let added = config.profiles.len().saturating_sub(current.profiles.len());
+            println!(
+                "{} {} from {} ({} profile(s) {})",
+                if replace { "Replaced" } else { "Merged" },
+                path.display(),
+                file.display(),
+                if replace {
+                    config.profiles.len()
+                } else {
+                    added
+                },
+                if replace { "in total" } else { "added" }
+            );
+            0
+        }
+    }
+}
+
+/// The config the app would run with: the file if it checks out, otherwise
+/// what [`Config::load`] falls back to, with the problems printed as warnings.
+fn effective_config(path: &std::path::Path, contents: Option<&str>) -> Config {
+    match contents.map(Config::check) {
+        None => Config::default(),
+        Some(Ok(config)) => config,
+        Some(Err(errors)) => {
+            for error in &errors {
+                eprintln!("warning: {}: {}", path.display(), error);
+            }
+            Config::load()
+        }
+    }
 }
 
 fn is_root() -> bool {
     unsafe { libc::geteuid() == 0 }
 }
 
-async fn run_app() -> Result<()> {
+/// Run the TUI until quit. Returns the summary of a session stopped on the way out.
+async fn run_app(args: &Args) -> Result<Option<StopSummary>> {
+    // Install signal handlers before touching the terminal so a kill during
+    // setup still goes through the normal exit path.
+    let mut sigterm =
+        signal(SignalKind::terminate()).context("Failed to install SIGTERM handler")?;
+    let mut sigint = signal(SignalKind::interrupt()).context("Failed to install SIGINT handler")?;
+
     // Setup terminal
     enable_raw_mode().context("Failed to enable raw mode")?;
     let mut stdout = io::stdout();
@@ -79,92 +268,39 @@ async fn run_app() -> Result<()> {
     let mut terminal = Terminal::new(backend).context("Failed to create terminal")?;
 
     // Create app state
-    let mut app = App::new();
+    let mut app = App::new(args);
 
     // Main loop using tokio for non-blocking event polling
-    let mut interval = tokio::time::interval(Duration::from_millis(50));
+    let mut interval = tokio::time::interval(app.repaint_interval());
+
+    // Set once a termination signal arrives; bounds how long we wait for cleanup.
+    let mut shutdown_deadline: Option<Instant> = None;
 
     loop {
         // Poll for async operation results
         app.poll_async_results();
 
-        // Draw UI
-        terminal.draw(|frame| {
-            let size = frame.area();
-
-            // Calculate log panel height based on expansion state
-            let log_height = if app.logs_expanded { 12 } else { 4 };
-
-            // Main layout - new structure
-            let chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([
-                    Constraint::Length(1),          // Header (single line)
-                    Constraint::Length(1),          // Separator
-                    Constraint::Min(12),            // Main content
-                    Constraint::Length(log_height), // Logs (collapsed/expanded)
-                    Constraint::Length(1),          // Help
-                ])
-                .split(size);
-
-            // Render header (single line)
-            render_header(frame, chunks[0], &app);
-
-            // Render separator
-            render_separator(frame, chunks[1]);
-
-            // Render main content based on state
-            match app.state {
-                AppState::Menu => {
-                    if app.is_sharing() {
-                        if !app.show_debug {
-                            render_connection_info(frame, chunks[2], &app);
-                        }
-                    } else {
-                        render_main_menu(frame, chunks[2], &app);
-                    }
-                }
-                AppState::SelectingVpn => {
-                    render_vpn_selection(frame, chunks[2], &app);
-                }
-                AppState::SelectingLan => {
-                    render_lan_selection(frame, chunks[2], &app);
-                }
-                AppState::Active => {
-                    if !app.show_debug {
-                        render_connection_info(frame, chunks[2], &app);
-                    }
-                }
-                AppState::EditingDns => {
-                    render_main_menu(frame, chunks[2], &app);
-                    render_dns_edit(frame, chunks[2], &app);
-                }
-            }
-
-            // Render loading indicator if operation is pending
-            if let Some(pending_op) = &app.pending_op {
-                render_loading_indicator(
-                    frame,
-                    chunks[2],
-                    pending_op.display(),
-                    app.pending_elapsed(),
-                );
-            }
+        // Redraw less often on battery
+        if interval.period() != app.repaint_interval() {
+            interval = tokio::time::interval(app.repaint_interval());
+        }
 
-            // Render debug panel overlay if enabled
-            if app.show_debug {
-                if let Some(debug_info) = &app.debug_info {
-                    render_debug_panel(frame, chunks[2], debug_info);
-                }
-            }
+        // Exit once quitting and no operation (e.g. the stop) is still in flight.
+        // After a signal, stop waiting at the deadline and let Drop clean up.
+        if app.should_quit
+            && (app.pending_op.is_none() || shutdown_deadline.is_some_and(|d| Instant::now() >= d))
+        {
+            break;
+        }
 
-            // Render logs (with expansion state)
-            let log_lines = chunks[3].height.saturating_sub(1) as usize;
-            render_status_panel(frame, chunks[3], &app.logs, log_lines, app.logs_expanded);
+        // Ring the bell outside the frame so it doesn't disturb rendering
+        if std::mem::take(&mut app.bell_pending) {
+            let _ = terminal.backend_mut().write_all(b"\x07");
+            let _ = terminal.backend_mut().flush();
+        }
 
-            // Render help
-            render_help(frame, chunks[4], app.help_text());
-        })?;
+        // Draw UI
+        terminal.draw(|frame| draw(frame, &app))?;
 
         // Handle events with non-blocking poll
         tokio::select! {
@@ -181,17 +317,248 @@ async fn run_app() -> Result<()> {
                                 break;
                             }
 
-                            app.handle_key(key.code);
-
-                            if app.should_quit && app.pending_op.is_none() {
-                                break;
+                            if key.modifiers.contains(event::KeyModifiers::SHIFT)
+                                && matches!(key.code, KeyCode::Up | KeyCode::Down)
+                            {
+                                app.handle_shifted_arrow(key.code);
+                            } else {
+                                app.handle_key(key.code);
                             }
                         }
                     }
                 }
             }
+            _ = sigterm.recv() => {
+                if shutdown_deadline.is_some() {
+                    break; // Second signal: stop waiting
+                }
+                app.request_shutdown("SIGTERM");
+                shutdown_deadline = Some(Instant::now() + SHUTDOWN_GRACE);
+            }
+            _ = sigint.recv() => {
+                if shutdown_deadline.is_some() {
+                    break;
+                }
+                app.request_shutdown("SIGINT");
+                shutdown_deadline = Some(Instant::now() + SHUTDOWN_GRACE);
+            }
+        }
+    }
+
+    Ok(app.stop_summary.take().map(|(summary, _)| summary))
+}
+
+/// Run without the TUI until a termination signal; sharing is started and
+/// stopped through the HTTP control API. Returns the summary of a session
+/// stopped on the way out.
+async fn run_headless(args: &Args) -> Result<Option<StopSummary>> {
+    let mut sigterm =
+        signal(SignalKind::terminate()).context("Failed to install SIGTERM handler")?;
+    let mut sigint = signal(SignalKind::interrupt()).context("Failed to install SIGINT handler")?;
+
+    let mut app = App::new(args);
+    let mut interval = tokio::time::interval(app.repaint_interval());
+    let mut shutdown_deadline: Option<Instant> = None;
+
+    loop {
+        app.poll_async_results();
+
+        if interval.period() != app.repaint_interval() {
+            interval = tokio::time::interval(app.repaint_interval());
+        }
+
+        if app.should_quit
+            && (app.pending_op.is_none() || shutdown_deadline.is_some_and(|d| Instant::now() >= d))
+        {
+            break;
+        }
+
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = sigterm.recv() => {
+                if shutdown_deadline.is_some() {
+                    break;
+                }
+                app.request_shutdown("SIGTERM");
+                shutdown_deadline = Some(Instant::now() + SHUTDOWN_GRACE);
+            }
+            _ = sigint.recv() => {
+                if shutdown_deadline.is_some() {
+                    break;
+                }
+                app.request_shutdown("SIGINT");
+                shutdown_deadline = Some(Instant::now() + SHUTDOWN_GRACE);
+            }
+        }
+    }
+
+    Ok(app.stop_summary.take().map(|(summary, _)| summary))
+}
+
+/// Draw one frame of the UI.
+fn draw(frame: &mut Frame, app: &App) {
+    let size = frame.area();
+
+    // The layout below assumes some room; below that, draw a compact
+    // version, and below even that, just say so
+    match LayoutMode::for_area(size) {
+        LayoutMode::TooSmall => {
+            render_too_small(frame, size);
+            return;
+        }
+        LayoutMode::Mini => {
+            render_mini(frame, size, app);
+            return;
+        }
+        LayoutMode::Full => {}
+    }
+
+    // Calculate log panel height based on expansion state
+    let log_height = if app.logs_expanded { 12 } else { 4 };
+
+    // Main layout - new structure
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),          // Header (single line)
+            Constraint::Length(1),          // Separator
+            Constraint::Min(12),            // Main content
+            Constraint::Length(log_height), // Logs (collapsed/expanded)
+            Constraint::Length(1),          // Help
+        ])
+        .split(size);
+
+    // Render header (single line)
+    render_header(frame, chunks[0], app);
+
+    // Render separator
+    render_separator(frame, chunks[1]);
+
+    // Render main content based on state
+    match app.state {
+        AppState::Menu => {
+            if app.is_sharing() {
+                if !app.show_debug {
+                    render_connection_info(frame, chunks[2], app);
+                }
+            } else if !app.show_debug {
+                render_main_menu(frame, chunks[2], app);
+            }
+        }
+        AppState::SelectingVpn => {
+            render_vpn_selection(frame, chunks[2], app);
+        }
+        AppState::SelectingLan => {
+            render_lan_selection(frame, chunks[2], app);
+        }
+        AppState::Active => {
+            if !app.show_debug {
+                render_connection_info(frame, chunks[2], app);
+            }
         }
+        AppState::EditingDns => {
+            render_main_menu(frame, chunks[2], app);
+            render_dns_edit(frame, chunks[2], app);
+        }
+        AppState::SelectingProfile => {
+            render_main_menu(frame, chunks[2], app);
+            render_profile_picker(frame, chunks[2], app);
+        }
+        AppState::NamingProfile => {
+            render_connection_info(frame, chunks[2], app);
+            render_profile_name_input(frame, chunks[2], app);
+        }
+        AppState::NamingLabel => {
+            if app.is_sharing() {
+                render_connection_info(frame, chunks[2], app);
+            } else {
+                render_main_menu(frame, chunks[2], app);
+            }
+            render_label_input(frame, chunks[2], app);
+        }
+        AppState::NoInterfaces => {
+            render_missing_interfaces(frame, chunks[2], app);
+        }
+    }
+
+    // Render loading indicator if operation is pending
+    if let Some(pending_op) = app.pending_op {
+        render_loading_indicator(
+            frame,
+            chunks[2],
+            pending_op.display(),
+            app.pending_elapsed(),
+            app.reduced_motion,
+        );
     }
 
-    Ok(())
+    // Render debug panel overlay if enabled
+    if app.show_debug {
+        if let Some(debug_info) = &app.debug_info {
+            let prev = app.debug_info_prev.as_ref().filter(|_| app.show_rule_diff);
+            let state_scroll = app.show_pf_states.then_some(app.pf_state_scroll);
+            let anchor_preview = app.show_anchor_preview.then(|| {
+                (
+                    app.natpmp_anchor_preview().unwrap_or_default(),
+                    app.selected_mapping,
+                )
+            });
+            render_debug_panel(
+                frame,
+                chunks[2],
+                debug_info,
+                prev,
+                state_scroll,
+                anchor_preview
+                    .as_ref()
+                    .map(|(rules, selected)| (rules.as_str(), *selected)),
+            );
+        }
+    }
+
+    // Render the summary of the session that just stopped
+    if let Some((summary, _)) = &app.stop_summary {
+        render_stop_summary(frame, chunks[2], summary);
+    }
+
+    // Render port forward test results
+    if let Some(report) = &app.probe_report {
+        render_probe_report(frame, chunks[2], report);
+    }
+
+    // Render client DNS test results
+    if let Some(report) = &app.dns_probe_report {
+        render_dns_probe_report(frame, chunks[2], report);
+    }
+
+    // Render the expected-vs-actual checklist
+    if app.diagnostic_facts.is_some() {
+        render_diagnostics(frame, chunks[2], &app.diagnose());
+    }
+
+    // Render logs (with expansion state)
+    let log_lines = chunks[3].height.saturating_sub(1) as usize;
+    render_status_panel(
+        frame,
+        chunks[3],
+        &app.logs,
+        log_lines,
+        app.logs_expanded,
+        app.selected_log,
+        app.min_log_level,
+    );
+
+    // Render full text of the selected log entry
+    if app.show_log_detail {
+        if let Some(entry) = app.selected_log.and_then(|i| app.logs.get(i)) {
+            render_log_detail(frame, chunks[2], entry);
+        }
+    }
+
+    // Render help, or the DNS undo toast while it's up
+    if let Some(toast) = app.dns_undo_toast() {
+        render_toast(frame, chunks[4], &toast);
+    } else {
+        render_help(frame, chunks[4], app.help_text());
+    }
 }
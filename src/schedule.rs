@@ -0,0 +1,103 @@
+//! Daily time window sharing is allowed in (`active_hours`).
+//!
+//! Times are local wall-clock times, so the window follows DST changes and
+//! the Mac's time zone.
+
+use chrono::NaiveTime;
+
+/// Time of day sharing may run, e.g. 08:00–20:00 or, past midnight,
+/// 22:00–06:00. The start is inside the window, the end isn't.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActiveHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl ActiveHours {
+    /// Parse a `("HH:MM", "HH:MM")` pair. None if either time is malformed
+    /// or the window is empty (start = end).
+    pub fn parse(start: &str, end: &str) -> Option<Self> {
+        let start = parse_time(start)?;
+        let end = parse_time(end)?;
+        (start != end).then_some(Self { start, end })
+    }
+
+    /// Whether `time` falls in the window.
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            // Wraps past midnight
+            time >= self.start || time < self.end
+        }
+    }
+
+    /// Whether the current local time falls in the window.
+    pub fn contains_now(&self) -> bool {
+        self.contains(chrono::Local::now().time())
+    }
+}
+
+impl std::fmt::Display for ActiveHours {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}\u{2013}{}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M")
+        )
+    }
+}
+
+/// Parse a 24-hour `HH:MM` time.
+pub fn parse_time(text: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(text.trim(), "%H:%M").ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(text: &str) -> NaiveTime {
+        parse_time(text).unwrap()
+    }
+
+    #[test]
+    fn test_contains() {
+        let day = ActiveHours::parse("08:00", "20:00").unwrap();
+        assert!(day.contains(at("08:00")));
+        assert!(day.contains(at("12:30")));
+        assert!(day.contains(at("19:59")));
+        assert!(!day.contains(at("20:00")));
+        assert!(!day.contains(at("07:59")));
+        assert!(!day.contains(at("23:00")));
+        assert_eq!(day.to_string(), "08:00\u{2013}20:00");
+    }
+
+    #[test]
+    fn test_contains_wraps_past_midnight() {
+        let night = ActiveHours::parse("22:00", "06:00").unwrap();
+        assert!(night.contains(at("22:00")));
+        assert!(night.contains(at("23:59")));
+        assert!(night.contains(at("00:00")));
+        assert!(night.contains(at("05:59")));
+        assert!(!night.contains(at("06:00")));
+        assert!(!night.contains(at("12:00")));
+        assert!(!night.contains(at("21:59")));
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            ActiveHours::parse(" 9:05", "17:30"),
+            Some(ActiveHours {
+                start: at("09:05"),
+                end: at("17:30"),
+            })
+        );
+        assert_eq!(ActiveHours::parse("08:00", "08:00"), None);
+        assert_eq!(ActiveHours::parse("24:00", "06:00"), None);
+        assert_eq!(ActiveHours::parse("8am", "6pm"), None);
+        assert_eq!(ActiveHours::parse("08:00", ""), None);
+    }
+}
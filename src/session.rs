@@ -1,9 +1,13 @@
 //! Active sharing session — owns all state that exists while VPN sharing is running.
 
+use ipnet::Ipv4Net;
 use std::net::Ipv4Addr;
+use std::time::Instant;
 
 use crate::health::HealthStatus;
-use crate::system::{DhcpServer, Firewall, IpForwarding, NatPmpServer};
+use crate::system::ifaddr;
+use crate::system::natpmp::{self, MappingKey, MappingSnapshot};
+use crate::system::{DhcpServer, Firewall, IpForwarding, NatPmpServer, NatTotals};
 
 /// Represents an active VPN sharing session.
 ///
@@ -18,23 +22,55 @@ pub struct SharingSession {
     firewall: Option<Firewall>,
     ip_forwarding: Option<IpForwarding>,
 
-    /// VPN interface name (e.g. "utun4").
+    /// VPN interface name (e.g. "utun4"); the primary uplink when balancing.
     pub vpn_name: String,
+    /// Further VPN interfaces LAN traffic is balanced across (empty = just `vpn_name`).
+    pub extra_uplinks: Vec<String>,
+    /// VPN interface NAT moves to while `vpn_name` is down.
+    pub failover_vpn: Option<String>,
+    /// Whether NAT currently goes through `failover_vpn`.
+    pub on_standby: bool,
     /// LAN interface name (e.g. "en0").
     pub lan_name: String,
     /// LAN gateway IP (e.g. 192.168.2.1).
     pub lan_ip: Ipv4Addr,
+    /// VPN interface address when sharing started.
+    pub vpn_ip: Option<Ipv4Addr>,
+    /// LAN subnet when sharing started.
+    pub lan_subnet: Option<Ipv4Net>,
+    /// LAN broadcast address when sharing started.
+    pub lan_broadcast: Option<Ipv4Addr>,
+    /// Gateway address tunshare assigned to the LAN interface (removed on drop).
+    pub assigned_lan_address: Option<Ipv4Net>,
 
     /// Whether the DHCP server is running.
     pub dhcp_active: bool,
     /// DHCP range being served (start, end).
     pub dhcp_range: Option<(String, String)>,
+    /// PID of the DHCP daemon, watched for unexpected exits.
+    pub dhcp_pid: Option<u32>,
+    /// `DhcpServer::settings()` of the daemon, to reuse it after a stop.
+    pub dhcp_settings: Option<String>,
+    /// Automatic DHCP restarts so far this session.
+    pub dhcp_restarts: u32,
+    /// When the next automatic DHCP restart is due (None = none scheduled).
+    pub dhcp_restart_at: Option<Instant>,
     /// Whether the NAT-PMP server is running.
     pub natpmp_active: bool,
     /// Handle to the running NAT-PMP server (for shutdown signaling).
     natpmp_server: Option<NatPmpServer>,
     /// Connection health status (updated by periodic checks).
     pub health_status: HealthStatus,
+    /// LAN clients that currently have a labeled accounting rule loaded.
+    pub accounted_clients: Vec<Ipv4Addr>,
+    /// Most LAN clients seen at once during this session.
+    pub peak_clients: usize,
+    /// LAN clients seen in leases, pf states or the ARP table at the last sync.
+    pub connected_clients: usize,
+    /// Traffic the NAT rules have forwarded this session.
+    pub forwarded: NatTotals,
+    /// When the session was created.
+    pub started_at: Instant,
 }
 
 impl SharingSession {
@@ -50,13 +86,44 @@ impl SharingSession {
             firewall: Some(firewall),
             ip_forwarding: Some(ip_forwarding),
             vpn_name,
+            extra_uplinks: Vec::new(),
+            failover_vpn: None,
+            on_standby: false,
             lan_name,
             lan_ip,
+            vpn_ip: None,
+            lan_subnet: None,
+            lan_broadcast: None,
+            assigned_lan_address: None,
             dhcp_active: false,
             dhcp_range: None,
+            dhcp_pid: None,
+            dhcp_settings: None,
+            dhcp_restarts: 0,
+            dhcp_restart_at: None,
             natpmp_active: false,
             natpmp_server: None,
             health_status: HealthStatus::default(),
+            accounted_clients: Vec::new(),
+            peak_clients: 0,
+            forwarded: NatTotals::default(),
+            connected_clients: 0,
+            started_at: Instant::now(),
+        }
+    }
+
+    /// The LAN gateway address with the subnet's prefix length, assuming a
+    /// /24 when the subnet isn't known.
+    pub fn lan_net(&self) -> Ipv4Net {
+        let prefix = self.lan_subnet.map_or(24, |subnet| subnet.prefix_len());
+        Ipv4Net::new(self.lan_ip, prefix).expect("prefix of a valid subnet")
+    }
+
+    /// The VPN interface NAT currently goes through.
+    pub fn active_vpn(&self) -> &str {
+        match self.failover_vpn {
+            Some(ref standby) if self.on_standby => standby,
+            _ => &self.vpn_name,
         }
     }
 
@@ -95,16 +162,47 @@ impl SharingSession {
     pub fn set_natpmp_server(&mut self, server: Option<NatPmpServer>) {
         self.natpmp_server = server;
     }
+
+    /// Ask the NAT-PMP server to re-read its external address right away.
+    pub fn refresh_natpmp_external_ip(&self) {
+        if let Some(ref server) = self.natpmp_server {
+            server.refresh_external_ip();
+        }
+    }
+
+    /// A NAT-PMP anchor reload failure not reported yet, if any.
+    pub fn natpmp_anchor_error(&mut self) -> Option<String> {
+        self.natpmp_server.as_mut()?.new_anchor_error()
+    }
+
+    /// Ask the NAT-PMP server to drop a mapping (no-op when it isn't running).
+    pub fn remove_natpmp_mapping(&self, key: MappingKey) {
+        if let Some(ref server) = self.natpmp_server {
+            server.remove_mapping(key);
+        }
+    }
+
+    /// Active NAT-PMP mappings (empty when the server isn't running).
+    pub fn natpmp_mappings(&self) -> Vec<MappingSnapshot> {
+        self.natpmp_server
+            .as_ref()
+            .map(|server| server.mappings())
+            .unwrap_or_default()
+    }
 }
 
 impl Drop for SharingSession {
     fn drop(&mut self) {
         // NAT-PMP first (before firewall so pf anchor flush works)
         if self.natpmp_active {
-            if let Some(ref server) = self.natpmp_server {
-                server.shutdown();
-            }
-            NatPmpServer::stop_sync();
+            let anchor = match self.natpmp_server {
+                Some(ref server) => {
+                    server.shutdown();
+                    server.rules_anchor()
+                }
+                None => natpmp::DEFAULT_ANCHOR_NAME.to_string(),
+            };
+            NatPmpServer::stop_sync(&anchor);
         }
 
         // DHCP
@@ -121,5 +219,10 @@ impl Drop for SharingSession {
         if let Some(ref mut fwd) = self.ip_forwarding {
             fwd.restore_sync();
         }
+
+        // LAN address last, once nothing is bound to it
+        if let Some(address) = self.assigned_lan_address.take() {
+            ifaddr::remove_sync(&self.lan_name, address);
+        }
     }
 }
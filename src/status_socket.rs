@@ -0,0 +1,280 @@
+//! Local status socket for external tooling.
+//!
+//! Serves line-delimited JSON over a Unix socket at [`STATUS_SOCKET_PATH`].
+//! Each request is one line, answered by one line:
+//!
+//! ```text
+//! -> {"query": "mappings"}
+//! <- {"ok": true, "mappings": [{"protocol": "tcp", "external_port": 51413,
+//!     "internal_ip": "192.168.2.100", "internal_port": 51413, "lifetime_remaining": 3540}]}
+//! -> {"query": "status"}
+//! <- {"ok": true, "label": "Work", "sharing": true}
+//! <- {"ok": false, "error": "unknown query: ..."}
+//! ```
+//!
+//! Queries are answered by the app's main loop (see `App::poll_async_results`),
+//! so responses always reflect the same state the TUI shows.
+
+use std::os::unix::fs::PermissionsExt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{mpsc, oneshot};
+
+/// Path of the status socket.
+pub const STATUS_SOCKET_PATH: &str = "/var/run/tunshare.sock";
+
+/// Group allowed to query the socket besides root: macOS administrators.
+const SOCKET_GROUP: &str = "admin";
+
+/// How long a connection waits for the main loop to answer a query.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+/// Longest request line accepted, newline included.
+const MAX_LINE_BYTES: u64 = 4 * 1024;
+
+/// Whether this process created the socket file (and so should remove it).
+static SOCKET_OWNED: AtomicBool = AtomicBool::new(false);
+
+/// A query sent by a status socket client.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "query", rename_all = "snake_case")]
+pub enum StatusQuery {
+    /// Active NAT-PMP port mappings.
+    Mappings,
+    /// Instance label and whether sharing is up.
+    Status,
+}
+
+/// One NAT-PMP mapping as reported over the socket.
+#[derive(Debug, Clone, Serialize)]
+pub struct MappingInfo {
+    pub protocol: String,
+    pub external_port: u16,
+    pub internal_ip: String,
+    pub internal_port: u16,
+    /// Seconds until the mapping expires unless the client renews it.
+    pub lifetime_remaining: u64,
+}
+
+/// Response to a [`StatusQuery`].
+#[derive(Debug, Clone)]
+pub enum StatusResponse {
+    Mappings(Vec<MappingInfo>),
+    Status {
+        label: Option<String>,
+        sharing: bool,
+    },
+    Error(String),
+}
+
+impl StatusResponse {
+    /// Serialize as a single newline-terminated JSON line.
+    pub fn to_line(&self) -> String {
+        let value = match self {
+            StatusResponse::Mappings(mappings) => {
+                serde_json::json!({ "ok": true, "mappings": mappings })
+            }
+            StatusResponse::Status { label, sharing } => {
+                serde_json::json!({ "ok": true, "label": label, "sharing": sharing })
+            }
+            StatusResponse::Error(error) => serde_json::json!({ "ok": false, "error": error }),
+        };
+        format!("{}\n", value)
+    }
+}
+
+/// A parsed query waiting for the main loop to answer it.
+pub struct StatusRequest {
+    pub query: StatusQuery,
+    pub reply: oneshot::Sender<StatusResponse>,
+}
+
+/// Parse one request line.
+pub fn parse_request(line: &str) -> std::result::Result<StatusQuery, String> {
+    serde_json::from_str(line.trim()).map_err(|e| format!("invalid request: {}", e))
+}
+
+/// Bind the status socket and start accepting connections.
+///
+/// Returns the receiving end for queries, which the main loop drains.
+/// Refuses to take over the socket of another running instance.
+pub fn start() -> std::io::Result<mpsc::UnboundedReceiver<StatusRequest>> {
+    if other_instance_running() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AddrInUse,
+            "another tunshare instance owns the status socket",
+        ));
+    }
+
+    // Stale socket from a previous run
+    let _ = std::fs::remove_file(STATUS_SOCKET_PATH);
+
+    let listener = UnixListener::bind(STATUS_SOCKET_PATH)?;
+    SOCKET_OWNED.store(true, Ordering::SeqCst);
+
+    // Root and admin-group monitors only; every query is read-only, but
+    // mappings still tell who on the LAN is reachable on which port
+    if let Some(gid) = group_id(SOCKET_GROUP) {
+        std::os::unix::fs::chown(STATUS_SOCKET_PATH, None, Some(gid))?;
+    }
+    std::fs::set_permissions(STATUS_SOCKET_PATH, std::fs::Permissions::from_mode(0o660))?;
+
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(handle_connection(stream, tx.clone()));
+        }
+    });
+
+    Ok(rx)
+}
+
+/// Whether another tunshare process is serving the status socket.
+pub fn other_instance_running() -> bool {
+    !SOCKET_OWNED.load(Ordering::SeqCst)
+        && std::os::unix::net::UnixStream::connect(STATUS_SOCKET_PATH).is_ok()
+}
+
+/// Id of the group called `name`, if there is one.
+fn group_id(name: &str) -> Option<u32> {
+    let name = std::ffi::CString::new(name).ok()?;
+    // getgrnam returns a pointer into static storage, read before any other call
+    let group = unsafe { libc::getgrnam(name.as_ptr()) };
+    (!group.is_null()).then(|| unsafe { (*group).gr_gid })
+}
+
+/// Remove the socket file on exit, unless another instance owns it.
+pub fn cleanup() {
+    if SOCKET_OWNED.load(Ordering::SeqCst) {
+        let _ = std::fs::remove_file(STATUS_SOCKET_PATH);
+    }
+}
+
+/// Answer requests on one connection until the client hangs up.
+async fn handle_connection(stream: UnixStream, tx: mpsc::UnboundedSender<StatusRequest>) {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        // Capped so a client can't grow the buffer by never sending a newline
+        let n = match (&mut reader)
+            .take(MAX_LINE_BYTES)
+            .read_line(&mut line)
+            .await
+        {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+        if n as u64 == MAX_LINE_BYTES && !line.ends_with('\n') {
+            let response = StatusResponse::Error("request too long".to_string());
+            let _ = writer.write_all(response.to_line().as_bytes()).await;
+            break;
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match parse_request(&line) {
+            Ok(query) => {
+                let (reply_tx, reply_rx) = oneshot::channel();
+                if tx
+                    .send(StatusRequest {
+                        query,
+                        reply: reply_tx,
+                    })
+                    .is_err()
+                {
+                    break; // App is gone
+                }
+                match tokio::time::timeout(QUERY_TIMEOUT, reply_rx).await {
+                    Ok(Ok(response)) => response,
+                    _ => StatusResponse::Error("no response from tunshare".to_string()),
+                }
+            }
+            Err(e) => StatusResponse::Error(e),
+        };
+
+        if writer
+            .write_all(response.to_line().as_bytes())
+            .await
+            .is_err()
+        {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        assert_eq!(
+            parse_request(r#"{"query": "mappings"}"#),
+            Ok(StatusQuery::Mappings)
+        );
+        assert_eq!(
+            parse_request("  {\"query\":\"mappings\"}\r\n"),
+            Ok(StatusQuery::Mappings)
+        );
+        assert_eq!(
+            parse_request(r#"{"query": "status"}"#),
+            Ok(StatusQuery::Status)
+        );
+        assert!(parse_request(r#"{"query": "reboot"}"#).is_err());
+        assert!(parse_request("mappings").is_err());
+        assert!(parse_request("{}").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_long_line_is_refused() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let task = tokio::spawn(handle_connection(server, tx));
+
+        let (reader, mut writer) = client.into_split();
+        writer
+            .write_all(&vec![b'x'; MAX_LINE_BYTES as usize + 1])
+            .await
+            .unwrap();
+
+        let mut reply = String::new();
+        BufReader::new(reader).read_line(&mut reply).await.unwrap();
+        let value: serde_json::Value = serde_json::from_str(&reply).unwrap();
+        assert_eq!(value["ok"], false);
+        assert_eq!(value["error"], "request too long");
+        task.await.unwrap();
+    }
+
+    #[test]
+    fn test_response_line() {
+        let response = StatusResponse::Mappings(vec![MappingInfo {
+            protocol: "tcp".to_string(),
+            external_port: 51413,
+            internal_ip: "192.168.2.100".to_string(),
+            internal_port: 51413,
+            lifetime_remaining: 3540,
+        }]);
+        let line = response.to_line();
+        assert!(line.ends_with('\n'));
+
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["ok"], true);
+        assert_eq!(value["mappings"][0]["external_port"], 51413);
+        assert_eq!(value["mappings"][0]["internal_ip"], "192.168.2.100");
+
+        let line = StatusResponse::Status {
+            label: Some("Work".to_string()),
+            sharing: true,
+        }
+        .to_line();
+        assert_eq!(line, "{\"label\":\"Work\",\"ok\":true,\"sharing\":true}\n");
+    }
+}
@@ -0,0 +1,81 @@
+//! Connected-client estimate from the ARP table.
+//!
+//! Works whether or not DHCP is on: any device that talked to the gateway
+//! recently has an ARP entry, including ones configured by hand.
+
+use ipnet::Ipv4Net;
+use std::net::Ipv4Addr;
+use tokio::process::Command;
+
+/// Addresses in `subnet` with a resolved entry in `arp -an` output, sorted.
+///
+/// Lines look like `? (192.168.2.100) at aa:bb:cc:dd:ee:ff on en5 ifscope [ethernet]`.
+/// Unresolved (`(incomplete)`) entries, the subnet's network and broadcast
+/// addresses, and `gateway` itself are skipped.
+pub fn parse_arp_clients(output: &str, subnet: Ipv4Net, gateway: Ipv4Addr) -> Vec<Ipv4Addr> {
+    let mut clients: Vec<Ipv4Addr> = output
+        .lines()
+        .filter_map(|line| {
+            let (_, rest) = line.split_once('(')?;
+            let (ip, rest) = rest.split_once(')')?;
+            let mac = rest
+                .trim_start()
+                .strip_prefix("at ")?
+                .split_whitespace()
+                .next()?;
+            if mac.starts_with('(') {
+                return None;
+            }
+            ip.parse::<Ipv4Addr>().ok()
+        })
+        .filter(|ip| {
+            subnet.contains(ip)
+                && *ip != gateway
+                && *ip != subnet.network()
+                && *ip != subnet.broadcast()
+        })
+        .collect();
+    clients.sort();
+    clients.dedup();
+    clients
+}
+
+/// LAN clients with an ARP entry (`arp -an`). Empty if `arp` can't be run.
+pub async fn arp_clients(lan_subnet: Ipv4Net, gateway: Ipv4Addr) -> Vec<Ipv4Addr> {
+    let Ok(output) = Command::new("arp").arg("-an").output().await else {
+        return Vec::new();
+    };
+    parse_arp_clients(
+        &String::from_utf8_lossy(&output.stdout),
+        lan_subnet,
+        gateway,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_arp_clients() {
+        let output = "\
+? (192.168.2.1) at 3c:22:fb:00:00:01 on en5 ifscope permanent [ethernet]
+? (192.168.2.100) at aa:bb:cc:dd:ee:ff on en5 ifscope [ethernet]
+? (192.168.2.101) at (incomplete) on en5 ifscope [ethernet]
+? (192.168.2.120) at 11:22:33:44:55:66 on en5 ifscope [ethernet]
+? (192.168.2.255) at ff:ff:ff:ff:ff:ff on en5 ifscope [ethernet]
+? (192.168.1.1) at 00:11:22:33:44:55 on en0 ifscope [ethernet]
+? (224.0.0.251) at 1:0:5e:0:0:fb on en5 ifscope permanent [ethernet]
+? (192.168.2.100) at aa:bb:cc:dd:ee:ff on en5 ifscope [ethernet]
+";
+        let subnet: Ipv4Net = "192.168.2.0/24".parse().unwrap();
+        assert_eq!(
+            parse_arp_clients(output, subnet, Ipv4Addr::new(192, 168, 2, 1)),
+            [
+                Ipv4Addr::new(192, 168, 2, 100),
+                Ipv4Addr::new(192, 168, 2, 120)
+            ]
+        );
+        assert!(parse_arp_clients("", subnet, Ipv4Addr::new(192, 168, 2, 1)).is_empty());
+    }
+}
@@ -0,0 +1,33 @@
+//! Copying text to the macOS pasteboard.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::error::{Result, TunshareError};
+
+/// Put `text` on the pasteboard via `pbcopy`.
+pub fn copy(text: &str) -> Result<()> {
+    let failed = |message: String| TunshareError::CommandFailed {
+        command: "pbcopy".into(),
+        message,
+    };
+
+    let mut child = Command::new("pbcopy")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| failed(e.to_string()))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(text.as_bytes())
+            .map_err(|e| failed(e.to_string()))?;
+    }
+
+    let status = child.wait().map_err(|e| failed(e.to_string()))?;
+    if !status.success() {
+        return Err(failed(format!("exited with {}", status)));
+    }
+    Ok(())
+}
@@ -1,89 +1,489 @@
-//! DHCP server management using dnsmasq for automatic router configuration.
+//! DHCP server management for automatic router configuration.
+//!
+//! Drives an external DHCP daemon: dnsmasq (preferred) or ISC dhcpd.
 
 use crate::error::{Result, TunshareError};
+use crate::system::{dns, process};
+use ipnet::Ipv4Net;
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::fs;
 use std::net::Ipv4Addr;
 use std::path::Path;
 use std::process::Command as SyncCommand;
+use std::sync::Mutex;
 use tokio::process::Command;
 
 const DNSMASQ_CONF_PATH: &str = "/tmp/tunshare-dnsmasq.conf";
 const DNSMASQ_PID_PATH: &str = "/tmp/tunshare-dnsmasq.pid";
 const DNSMASQ_LEASE_PATH: &str = "/tmp/tunshare-dnsmasq.leases";
+const DHCPD_CONF_PATH: &str = "/tmp/tunshare-dhcpd.conf";
+const DHCPD_PID_PATH: &str = "/tmp/tunshare-dhcpd.pid";
+const DHCPD_LEASE_PATH: &str = "/tmp/tunshare-dhcpd.leases";
 
-/// DHCP server manager using dnsmasq.
+/// Command line of the last daemon we launched, for the debug panel.
+static LAST_COMMAND: Mutex<Option<String>> = Mutex::new(None);
+
+/// Which DHCP daemon serves the LAN. Both are external; there is no
+/// built-in server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DhcpBackend {
+    /// dnsmasq (`brew install dnsmasq`).
+    Dnsmasq,
+    /// ISC dhcpd (`brew install isc-dhcp`).
+    IscDhcpd,
+}
+
+impl DhcpBackend {
+    /// Backends in auto-detect preference order.
+    const AUTO_ORDER: [DhcpBackend; 2] = [DhcpBackend::Dnsmasq, DhcpBackend::IscDhcpd];
+
+    /// Path of the daemon binary, if installed.
+    pub fn find_binary(self) -> Option<String> {
+        match self {
+            DhcpBackend::Dnsmasq => find_binary("dnsmasq"),
+            DhcpBackend::IscDhcpd => find_binary("dhcpd"),
+        }
+    }
+
+    /// Whether this backend can be used on this system.
+    pub fn is_available(self) -> bool {
+        self.find_binary().is_some()
+    }
+
+    /// Pick the backend to use: `preferred` if it's available, otherwise the
+    /// first installed one. `None` if no DHCP daemon is installed.
+    pub fn resolve(preferred: Option<DhcpBackend>) -> Option<DhcpBackend> {
+        preferred
+            .filter(|b| b.is_available())
+            .or_else(|| Self::AUTO_ORDER.into_iter().find(|b| b.is_available()))
+    }
+}
+
+impl fmt::Display for DhcpBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DhcpBackend::Dnsmasq => f.write_str("dnsmasq"),
+            DhcpBackend::IscDhcpd => f.write_str("ISC dhcpd"),
+        }
+    }
+}
+
+/// Netboot (PXE) options handed to clients: the TFTP server and the boot file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DhcpBoot {
+    /// TFTP server clients load `filename` from (`next-server`).
+    pub next_server: Ipv4Addr,
+    /// Boot file path on that server, e.g. `pxelinux.0`.
+    pub filename: String,
+}
+
+impl fmt::Display for DhcpBoot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} from {}", self.filename, self.next_server)
+    }
+}
+
+impl DhcpBoot {
+    /// Why `filename` can't go into the daemon configs, if it can't.
+    /// Commas split dnsmasq's `dhcp-boot`, quotes end dhcpd's string, and
+    /// line breaks would start a new config line.
+    pub fn filename_problem(&self) -> Option<&'static str> {
+        if self.filename.trim().is_empty() {
+            Some("boot file name is empty")
+        } else if self.filename.contains([',', '"']) {
+            Some("commas and quotes aren't allowed")
+        } else if self.filename.chars().any(char::is_control) {
+            Some("control characters aren't allowed")
+        } else {
+            None
+        }
+    }
+}
+
+/// A dnsmasq release, as reported by `dnsmasq --version`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DnsmasqVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl DnsmasqVersion {
+    /// First release with `tag:` filters (used by the MAC allowlist).
+    const TAGS: DnsmasqVersion = DnsmasqVersion::new(2, 52);
+    /// First release with DHCPv6 (`option6:` DNS servers).
+    const DHCPV6: DnsmasqVersion = DnsmasqVersion::new(2, 60);
+
+    pub const fn new(major: u32, minor: u32) -> Self {
+        Self { major, minor }
+    }
+}
+
+impl fmt::Display for DnsmasqVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// Version from the `dnsmasq --version` banner
+/// (`Dnsmasq version 2.90  Copyright (c) ...`). Test builds like `2.81test3`
+/// count as their base release.
+fn parse_dnsmasq_version(banner: &str) -> Option<DnsmasqVersion> {
+    let mut words = banner.lines().next()?.split_whitespace();
+    words.find(|w| w.eq_ignore_ascii_case("version"))?;
+    let (major, rest) = words.next()?.split_once('.')?;
+    let minor: String = rest.chars().take_while(char::is_ascii_digit).collect();
+    Some(DnsmasqVersion::new(
+        major.parse().ok()?,
+        minor.parse().ok()?,
+    ))
+}
+
+/// Find a daemon binary in the usual Homebrew locations or on PATH.
+fn find_binary(name: &str) -> Option<String> {
+    // Check common Homebrew locations first (sudo might not have them in PATH)
+    let common_dirs = [
+        "/opt/homebrew/sbin",
+        "/usr/local/sbin",
+        "/opt/homebrew/bin",
+        "/usr/local/bin",
+    ];
+
+    for dir in common_dirs {
+        let path = format!("{}/{}", dir, name);
+        if Path::new(&path).exists() {
+            return Some(path);
+        }
+    }
+
+    // Fall back to which command
+    SyncCommand::new("which")
+        .arg(name)
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .and_then(|o| String::from_utf8(o.stdout).ok())
+        .map(|s| s.trim().to_string())
+}
+
+/// A DHCP daemon left running after sharing stopped (`persistent_dhcp`),
+/// so the next share can take it over without clients losing their leases.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeptDhcp {
+    /// PID of the daemon.
+    pub pid: u32,
+    /// Its process name when it was kept, to tell it from a process that
+    /// later reused the PID.
+    pub name: String,
+    /// `DhcpServer::settings()` it was started with.
+    pub settings: String,
+}
+
+impl KeptDhcp {
+    /// Whether the daemon is still running: its PID exists and still
+    /// belongs to a process of the same name.
+    pub fn is_running(&self) -> bool {
+        process::is_alive(self.pid) && process::name(self.pid).as_deref() == Some(&*self.name)
+    }
+
+    /// Whether a share that would start a daemon with `settings` can take
+    /// this one over as is.
+    pub fn reusable(&self, settings: &str, running: bool) -> bool {
+        running && self.settings == settings
+    }
+
+    /// Stop the daemon, if it is still running, and remove the files it used.
+    pub fn stop_sync(&self) {
+        if self.is_running() {
+            if let Ok(pid) = i32::try_from(self.pid) {
+                unsafe { libc::kill(pid, libc::SIGTERM) };
+            }
+        }
+        DhcpServer::remove_files();
+    }
+}
+
+/// Whether stopping sharing should leave the DHCP daemon running.
+///
+/// Only with `persistent_dhcp`, never on the way out of the app, and not
+/// when the gateway address it hands out is about to be removed.
+pub fn keep_between_shares(persistent: bool, quitting: bool, lan_address_assigned: bool) -> bool {
+    persistent && !quitting && !lan_address_assigned
+}
+
+/// DHCP server manager.
 pub struct DhcpServer {
+    /// Daemon used to serve DHCP.
+    backend: DhcpBackend,
     /// Whether the DHCP server is running.
     running: bool,
     /// The LAN interface to serve DHCP on.
     interface: String,
-    /// The gateway IP (Mac mini's LAN IP).
-    gateway_ip: Ipv4Addr,
-    /// DNS servers to advertise to clients.
+    /// The gateway IP (Mac mini's LAN IP) with the LAN's prefix length.
+    lan: Ipv4Net,
+    /// IPv4 DNS servers to advertise to clients.
     dns_servers: Vec<String>,
+    /// IPv6 DNS servers to advertise over DHCPv6 (dnsmasq only).
+    dns_servers_v6: Vec<String>,
+    /// PID of the daemon we started, read from its pid file.
+    pid: Option<u32>,
+    /// Connectivity-check domains resolved through `check_resolver` (dnsmasq only).
+    check_domains: Vec<String>,
+    /// Resolver for `check_domains`.
+    check_resolver: String,
+    /// Only these MACs (normalized) get leases; empty = everyone.
+    mac_allowlist: Vec<String>,
+    /// Netboot options for PXE clients (None = not offered).
+    boot: Option<DhcpBoot>,
+    /// Installed dnsmasq release, read on start (None = unknown, assume current).
+    dnsmasq_version: Option<DnsmasqVersion>,
 }
 
 impl DhcpServer {
     /// Create a new DHCP server instance.
-    pub fn new(interface: &str, gateway_ip: Ipv4Addr, dns_servers: Vec<String>) -> Self {
+    pub fn new(
+        backend: DhcpBackend,
+        interface: &str,
+        lan: Ipv4Net,
+        dns_servers: Vec<String>,
+    ) -> Self {
         Self {
+            backend,
             running: false,
             interface: interface.to_string(),
-            gateway_ip,
+            lan,
             dns_servers,
+            dns_servers_v6: Vec::new(),
+            pid: None,
+            check_domains: Vec::new(),
+            check_resolver: String::new(),
+            mac_allowlist: Vec::new(),
+            boot: None,
+            dnsmasq_version: None,
         }
     }
 
-    /// Check if dnsmasq is installed and return its path.
-    pub fn find_dnsmasq() -> Option<String> {
-        // Check common Homebrew locations first (sudo might not have them in PATH)
-        let common_paths = [
-            "/opt/homebrew/sbin/dnsmasq",
-            "/usr/local/sbin/dnsmasq",
-            "/opt/homebrew/bin/dnsmasq",
-            "/usr/local/bin/dnsmasq",
-        ];
+    /// Version of the installed dnsmasq, or None if it isn't installed or
+    /// the banner couldn't be parsed.
+    pub async fn dnsmasq_version() -> Option<DnsmasqVersion> {
+        let path = DhcpBackend::Dnsmasq.find_binary()?;
+        let output = Command::new(path).arg("--version").output().await.ok()?;
+        parse_dnsmasq_version(&String::from_utf8_lossy(&output.stdout))
+    }
 
-        for path in common_paths {
-            if Path::new(path).exists() {
-                return Some(path.to_string());
-            }
+    /// Whether the detected dnsmasq is at least `required`. An unknown
+    /// version is assumed to be recent rather than disabling features.
+    fn dnsmasq_supports(&self, required: DnsmasqVersion) -> bool {
+        self.dnsmasq_version.is_none_or(|v| v >= required)
+    }
+
+    /// Requested features left out of the config because the installed
+    /// dnsmasq is too old, as messages for the log. Valid after `start`.
+    pub fn skipped_features(&self) -> Vec<String> {
+        let (DhcpBackend::Dnsmasq, Some(found)) = (self.backend, self.dnsmasq_version) else {
+            return Vec::new();
+        };
+        let mut skipped = Vec::new();
+        if !self.dns_servers_v6.is_empty() && found < DnsmasqVersion::DHCPV6 {
+            skipped.push(format!(
+                "Not advertising IPv6 DNS servers: DHCPv6 needs dnsmasq {} or newer (found {})",
+                DnsmasqVersion::DHCPV6,
+                found
+            ));
         }
+        skipped
+    }
 
-        // Fall back to which command
-        SyncCommand::new("which")
-            .arg("dnsmasq")
-            .output()
-            .ok()
-            .filter(|o| o.status.success())
-            .and_then(|o| String::from_utf8(o.stdout).ok())
-            .map(|s| s.trim().to_string())
+    /// Also advertise these IPv6 DNS servers (DHCPv6 option 23).
+    /// Only dnsmasq supports this; the IPv4 list passed to `new` stays separate.
+    pub fn with_dns_v6(mut self, servers: &[String]) -> Self {
+        self.dns_servers_v6 = servers.to_vec();
+        self
+    }
+
+    /// Only hand out leases to clients with these MAC addresses.
+    /// Malformed entries are dropped; an empty list allows everyone.
+    pub fn with_mac_allowlist(mut self, macs: &[String]) -> Self {
+        self.mac_allowlist = macs.iter().filter_map(|m| normalize_mac(m)).collect();
+        self
+    }
+
+    /// Point netboot clients at a TFTP server and boot file.
+    pub fn with_boot(mut self, boot: Option<DhcpBoot>) -> Self {
+        self.boot = boot;
+        self
+    }
+
+    /// Netboot options that are safe to write into a config.
+    fn valid_boot(&self) -> Option<&DhcpBoot> {
+        self.boot
+            .as_ref()
+            .filter(|boot| boot.filename_problem().is_none())
+    }
+
+    /// dnsmasq line for the netboot options (empty if there are none).
+    fn dnsmasq_boot(&self) -> String {
+        self.valid_boot().map_or(String::new(), |boot| {
+            format!(
+                "# Netboot: boot file and TFTP server\ndhcp-boot={},,{}\n",
+                boot.filename, boot.next_server
+            )
+        })
+    }
+
+    /// dnsmasq lines restricting leases to the allowlist (empty if there is none).
+    fn dnsmasq_allowlist(&self) -> String {
+        if self.mac_allowlist.is_empty() {
+            return String::new();
+        }
+        // Listed hosts get the "known" tag; everyone else is ignored
+        let mut lines = vec!["# Only allowlisted clients get leases".to_string()];
+        lines.extend(
+            self.mac_allowlist
+                .iter()
+                .map(|mac| format!("dhcp-host={}", mac)),
+        );
+        lines.push("dhcp-ignore=tag:!known".to_string());
+        lines.join("\n") + "\n"
+    }
+
+    /// ISC dhcpd host declarations for the allowlist (empty if there is none).
+    fn isc_allowlist(&self) -> String {
+        self.mac_allowlist
+            .iter()
+            .enumerate()
+            .map(|(i, mac)| {
+                format!(
+                    "\nhost tunshare-allowed-{} {{\n  hardware ethernet {};\n}}\n",
+                    i, mac
+                )
+            })
+            .collect()
     }
 
-    /// Check if dnsmasq is installed.
-    pub fn is_dnsmasq_installed() -> bool {
-        Self::find_dnsmasq().is_some()
+    /// Have dnsmasq serve DNS to clients and resolve `domains` (captive portal
+    /// and connectivity probes) through `resolver`, everything else through the
+    /// usual servers. Ignored by ISC dhcpd, which can't relay DNS.
+    pub fn with_connectivity_check(mut self, domains: &[String], resolver: &str) -> Self {
+        self.check_domains = domains.to_vec();
+        self.check_resolver = resolver.to_string();
+        self
     }
 
-    /// Calculate DHCP range from gateway IP.
-    /// Given gateway 192.168.2.1, returns ("192.168.2.100", "192.168.2.150").
-    pub fn calculate_dhcp_range(gateway_ip: Ipv4Addr) -> (String, String) {
-        let o = gateway_ip.octets();
+    /// PID of the running daemon (None before `start`, or if it wrote no pid file).
+    pub fn pid(&self) -> Option<u32> {
+        self.pid
+    }
+
+    /// Calculate the DHCP range for the LAN `lan` (the gateway IP with the
+    /// LAN's prefix length).
+    ///
+    /// Normally .100-.150 of the gateway's /24, e.g. 192.168.2.100-150 for
+    /// 192.168.2.1/24. When that doesn't fit the subnet or would hand out the
+    /// gateway itself, up to 51 addresses from the start of the larger run of
+    /// hosts beside the gateway.
+    pub fn calculate_dhcp_range(lan: Ipv4Net) -> (String, String) {
+        let gateway = u32::from(lan.addr());
+        let (first, last) = match lan.prefix_len() {
+            31.. => (u32::from(lan.network()), u32::from(lan.broadcast())),
+            _ => (u32::from(lan.network()) + 1, u32::from(lan.broadcast()) - 1),
+        };
+
+        let base = gateway & !0xff;
+        let (start, end) = if first <= base + 100
+            && base + 150 <= last
+            && !(base + 100..=base + 150).contains(&gateway)
+        {
+            (base + 100, base + 150)
+        } else {
+            let below = gateway.saturating_sub(first);
+            let above = last.saturating_sub(gateway);
+            let (start, end) = match (below, above) {
+                (0, 0) => (gateway, gateway),
+                _ if above >= below => (gateway + 1, last),
+                _ => (first, gateway - 1),
+            };
+            (start, end.min(start + 50))
+        };
+
         (
-            format!("{}.{}.{}.100", o[0], o[1], o[2]),
-            format!("{}.{}.{}.150", o[0], o[1], o[2]),
+            Ipv4Addr::from(start).to_string(),
+            Ipv4Addr::from(end).to_string(),
         )
     }
 
-    /// Generate dnsmasq configuration.
+    /// DNS servers to advertise, falling back to the gateway.
+    fn advertised_dns(&self) -> Vec<String> {
+        if self.dns_servers.is_empty() {
+            // Use gateway as DNS if no VPN DNS available
+            vec![self.lan.addr().to_string()]
+        } else {
+            self.dns_servers.clone()
+        }
+    }
+
+    /// IPv6 DNS servers clients can reach: link-local and `%scope`d ones only
+    /// work from this Mac (they still serve as upstreams when relaying).
+    fn advertised_dns_v6(&self) -> Vec<String> {
+        self.dns_servers_v6
+            .iter()
+            .filter(|server| !dns::is_link_scoped(server))
+            .cloned()
+            .collect()
+    }
+
+    /// DNS servers clients are told to use, IPv4 first. With connectivity
+    /// checks on, dnsmasq relays and clients only see the gateway.
+    pub fn client_dns(&self) -> Vec<String> {
+        match self.backend {
+            DhcpBackend::Dnsmasq if !self.check_domains.is_empty() => {
+                vec![self.lan.addr().to_string()]
+            }
+            DhcpBackend::Dnsmasq if self.dnsmasq_supports(DnsmasqVersion::DHCPV6) => self
+                .advertised_dns()
+                .into_iter()
+                .chain(self.advertised_dns_v6())
+                .collect(),
+            _ => self.advertised_dns(),
+        }
+    }
+
+    /// Generate dnsmasq configuration, in config-file syntax.
     fn generate_config(&self) -> String {
-        let (range_start, range_end) = Self::calculate_dhcp_range(self.gateway_ip);
+        let (range_start, range_end) = Self::calculate_dhcp_range(self.lan);
 
-        let dns_option = if self.dns_servers.is_empty() {
-            // Use gateway as DNS if no VPN DNS available
-            format!("dhcp-option=6,{}", self.gateway_ip)
+        // Relaying DNS is only needed to route connectivity checks separately
+        let relay = !self.check_domains.is_empty();
+        let dns_service = if relay {
+            let mut lines =
+                vec!["# Relay DNS so connectivity checks use their own resolver".to_string()];
+            lines.extend(
+                self.dns_servers
+                    .iter()
+                    .chain(&self.dns_servers_v6)
+                    .map(|s| format!("server={}", s)),
+            );
+            lines.extend(connectivity_server_args(
+                &self.check_domains,
+                &self.check_resolver,
+            ));
+            lines.join("\n")
+        } else {
+            "# Don't provide DNS service (just DHCP)\nport=0".to_string()
+        };
+        let advertised_v6 = self.advertised_dns_v6();
+        let dns_option = if relay {
+            format!("dhcp-option=6,{}", self.lan.addr())
+        } else if advertised_v6.is_empty() || !self.dnsmasq_supports(DnsmasqVersion::DHCPV6) {
+            format!("dhcp-option=6,{}", self.advertised_dns().join(","))
         } else {
-            format!("dhcp-option=6,{}", self.dns_servers.join(","))
+            let v6: Vec<String> = advertised_v6.iter().map(|s| format!("[{}]", s)).collect();
+            format!(
+                "dhcp-option=6,{}\ndhcp-option=option6:dns-server,{}",
+                self.advertised_dns().join(","),
+                v6.join(",")
+            )
         };
 
         format!(
@@ -98,12 +498,11 @@ bind-interfaces
 # Don't use /etc/resolv.conf
 no-resolv
 
-# Don't provide DNS service (just DHCP)
-port=0
+{dns_service}
 
 # DHCP range and lease time
 dhcp-range={range_start},{range_end},12h
-
+{allowlist}{boot}
 # Gateway (option 3) - Mac mini's LAN IP
 dhcp-option=3,{gateway}
 
@@ -120,37 +519,143 @@ pid-file={pid_file}
 dhcp-authoritative
 "#,
             interface = self.interface,
-            gateway = self.gateway_ip,
+            gateway = self.lan.addr(),
             range_start = range_start,
             range_end = range_end,
+            dns_service = dns_service,
+            allowlist = self.dnsmasq_allowlist(),
+            boot = self.dnsmasq_boot(),
             dns_option = dns_option,
             lease_file = DNSMASQ_LEASE_PATH,
             pid_file = DNSMASQ_PID_PATH,
         )
     }
 
+    /// Everything the daemon is started with, to tell whether a running one
+    /// can be reused.
+    pub fn settings(&self) -> String {
+        match self.backend {
+            DhcpBackend::IscDhcpd => format!(
+                "{} {}\n{}",
+                self.backend,
+                self.interface,
+                self.generate_isc_config()
+            ),
+            _ => format!("{}\n{}", self.backend, self.generate_config()),
+        }
+    }
+
+    /// Full command line of the DHCP daemon started last in this process,
+    /// whether or not it came up.
+    pub fn last_command() -> Option<String> {
+        LAST_COMMAND.lock().ok().and_then(|command| command.clone())
+    }
+
+    /// Generate ISC dhcpd configuration. dhcpd only serves an interface
+    /// whose network has a matching `subnet` declaration, so it carries the
+    /// LAN's real netmask.
+    fn generate_isc_config(&self) -> String {
+        let (range_start, range_end) = Self::calculate_dhcp_range(self.lan);
+
+        format!(
+            r#"# DHCP configuration - generated by tunshare
+# Interface: {interface}
+# Gateway: {gateway}
+
+authoritative;
+default-lease-time 43200;
+max-lease-time 86400;
+
+subnet {network} netmask {netmask} {{
+  range {range_start} {range_end};
+  option routers {gateway};
+  option domain-name-servers {dns};{deny_unknown}{boot}
+}}
+{hosts}"#,
+            interface = self.interface,
+            gateway = self.lan.addr(),
+            network = self.lan.network(),
+            netmask = self.lan.netmask(),
+            dns = self.advertised_dns().join(", "),
+            deny_unknown = if self.mac_allowlist.is_empty() {
+                ""
+            } else {
+                "\n  deny unknown-clients;"
+            },
+            boot = self.valid_boot().map_or(String::new(), |boot| format!(
+                "\n  next-server {};\n  filename \"{}\";",
+                boot.next_server, boot.filename
+            )),
+            hosts = self.isc_allowlist(),
+        )
+    }
+
     /// Start the DHCP server.
     pub async fn start(&mut self) -> Result<()> {
         if self.running {
             return Ok(());
         }
 
-        let dnsmasq_path = Self::find_dnsmasq().ok_or_else(|| TunshareError::CommandFailed {
-            command: "dnsmasq".into(),
-            message: "dnsmasq is not installed. Install with: brew install dnsmasq".into(),
-        })?;
+        if let Some(problem) = self.boot.as_ref().and_then(DhcpBoot::filename_problem) {
+            return Err(TunshareError::CommandFailed {
+                command: "dhcp".into(),
+                message: format!("invalid netboot file name: {problem}"),
+            });
+        }
 
         // Stop any existing instance first
         Self::stop().await.ok();
 
-        // Generate and write configuration
-        let config = self.generate_config();
-        fs::write(DNSMASQ_CONF_PATH, &config).map_err(TunshareError::Io)?;
+        let pid_path = match self.backend {
+            DhcpBackend::Dnsmasq => {
+                self.dnsmasq_version = Self::dnsmasq_version().await;
+                self.start_dnsmasq().await?;
+                DNSMASQ_PID_PATH
+            }
+            DhcpBackend::IscDhcpd => {
+                self.start_isc().await?;
+                DHCPD_PID_PATH
+            }
+        };
+
+        // Both daemons write the pid file before the launching process exits
+        self.pid = fs::read_to_string(pid_path)
+            .ok()
+            .and_then(|contents| parse_pid(&contents));
+        self.running = true;
+        Ok(())
+    }
+
+    async fn start_dnsmasq(&self) -> Result<()> {
+        let dnsmasq_path =
+            DhcpBackend::Dnsmasq
+                .find_binary()
+                .ok_or_else(|| TunshareError::CommandFailed {
+                    command: "dnsmasq".into(),
+                    message: "dnsmasq is not installed. Install with: brew install dnsmasq".into(),
+                })?;
+
+        // Leaving the allowlist out would hand leases to everyone, so refuse instead
+        if !self.mac_allowlist.is_empty() && !self.dnsmasq_supports(DnsmasqVersion::TAGS) {
+            return Err(TunshareError::CommandFailed {
+                command: "dnsmasq".into(),
+                message: format!(
+                    "the MAC allowlist needs dnsmasq {} or newer (found {}). Upgrade with: brew upgrade dnsmasq",
+                    DnsmasqVersion::TAGS,
+                    self.dnsmasq_version.map(|v| v.to_string()).unwrap_or_default()
+                ),
+            });
+        }
+
+        // Settings go in a file rather than on the command line, where
+        // anyone could read them (the MAC allowlist) with ps
+        fs::write(DNSMASQ_CONF_PATH, self.generate_config()).map_err(TunshareError::Io)?;
 
         // Start dnsmasq (it will daemonize itself)
-        let conf_arg = format!("--conf-file={}", DNSMASQ_CONF_PATH);
+        let args = [format!("--conf-file={}", DNSMASQ_CONF_PATH)];
+        record_command(&dnsmasq_path, &args);
         let output = Command::new(&dnsmasq_path)
-            .arg(&conf_arg)
+            .args(&args)
             .output()
             .await
             .map_err(|e| TunshareError::CommandFailed {
@@ -166,7 +671,51 @@ dhcp-authoritative
             });
         }
 
-        self.running = true;
+        Ok(())
+    }
+
+    async fn start_isc(&self) -> Result<()> {
+        let dhcpd_path =
+            DhcpBackend::IscDhcpd
+                .find_binary()
+                .ok_or_else(|| TunshareError::CommandFailed {
+                    command: "dhcpd".into(),
+                    message: "dhcpd is not installed. Install with: brew install isc-dhcp".into(),
+                })?;
+
+        fs::write(DHCPD_CONF_PATH, self.generate_isc_config()).map_err(TunshareError::Io)?;
+        // dhcpd refuses to start without an existing lease file
+        fs::write(DHCPD_LEASE_PATH, "").map_err(TunshareError::Io)?;
+
+        // dhcpd daemonizes itself
+        let args = [
+            "-q",
+            "-cf",
+            DHCPD_CONF_PATH,
+            "-lf",
+            DHCPD_LEASE_PATH,
+            "-pf",
+            DHCPD_PID_PATH,
+            &self.interface,
+        ];
+        record_command(&dhcpd_path, &args);
+        let output = Command::new(&dhcpd_path)
+            .args(args)
+            .output()
+            .await
+            .map_err(|e| TunshareError::CommandFailed {
+                command: "dhcpd".into(),
+                message: e.to_string(),
+            })?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(TunshareError::CommandFailed {
+                command: "dhcpd".into(),
+                message: format!("Failed to start DHCP server: {}", stderr),
+            });
+        }
+
         Ok(())
     }
 
@@ -182,24 +731,65 @@ dhcp-authoritative
         Ok(())
     }
 
+    /// PIDs of DHCP daemons we started that are still running, going by
+    /// their pid files (e.g. left behind by a crashed run).
+    pub fn running_pids() -> Vec<u32> {
+        [DNSMASQ_PID_PATH, DHCPD_PID_PATH]
+            .iter()
+            .filter_map(|path| fs::read_to_string(path).ok())
+            .filter_map(|contents| parse_pid(&contents))
+            .filter(|&pid| process::is_alive(pid))
+            .collect()
+    }
+
+    /// Client addresses currently holding a lease from our DHCP daemon.
+    ///
+    /// Returns an empty list if DHCP isn't running or the lease file is unreadable.
+    pub fn leased_clients() -> Vec<Ipv4Addr> {
+        let mut clients: Vec<Ipv4Addr> = fs::read_to_string(DNSMASQ_LEASE_PATH)
+            .map(|contents| parse_leases(&contents))
+            .unwrap_or_default();
+        if let Ok(contents) = fs::read_to_string(DHCPD_LEASE_PATH) {
+            clients.extend(parse_isc_leases(&contents));
+        }
+        clients
+    }
+
     /// Synchronous stop. Single source of truth for DHCP cleanup.
+    ///
+    /// Stops whichever backend is running, so callers don't need to know which one started.
     pub fn stop_sync() {
-        // Try to read PID and kill the process
-        if Path::new(DNSMASQ_PID_PATH).exists() {
-            if let Ok(pid_str) = fs::read_to_string(DNSMASQ_PID_PATH) {
-                if let Ok(pid) = pid_str.trim().parse::<i32>() {
-                    let _ = SyncCommand::new("kill").arg(pid.to_string()).output();
+        for (daemon, conf_path, pid_path) in [
+            ("dnsmasq", DNSMASQ_CONF_PATH, DNSMASQ_PID_PATH),
+            ("dhcpd", DHCPD_CONF_PATH, DHCPD_PID_PATH),
+        ] {
+            // Try to read PID and kill the process
+            if Path::new(pid_path).exists() {
+                if let Ok(pid_str) = fs::read_to_string(pid_path) {
+                    if let Ok(pid) = pid_str.trim().parse::<i32>() {
+                        let _ = SyncCommand::new("kill").arg(pid.to_string()).output();
+                    }
                 }
             }
-        }
 
-        // Also try pkill as a fallback
-        let _ = SyncCommand::new("pkill")
-            .args(["-f", &format!("dnsmasq.*{}", DNSMASQ_CONF_PATH)])
-            .output();
+            // Also try pkill as a fallback
+            let _ = SyncCommand::new("pkill")
+                .args(["-f", &format!("{}.*{}", daemon, conf_path)])
+                .output();
+        }
+        Self::remove_files();
+    }
 
-        // Clean up files
-        for path in [DNSMASQ_CONF_PATH, DNSMASQ_PID_PATH, DNSMASQ_LEASE_PATH] {
+    /// Remove both backends' config, pid and lease files.
+    fn remove_files() {
+        for path in [
+            DNSMASQ_CONF_PATH,
+            DNSMASQ_PID_PATH,
+            DNSMASQ_LEASE_PATH,
+            DHCPD_CONF_PATH,
+            DHCPD_PID_PATH,
+            DHCPD_LEASE_PATH,
+        ] {
             if Path::new(path).exists() {
                 let _ = fs::remove_file(path);
             }
@@ -207,27 +797,125 @@ dhcp-authoritative
     }
 }
 
+/// Remember `program args..` as the last command, quoted for a shell.
+fn record_command<S: AsRef<str>>(program: &str, args: &[S]) {
+    if let Ok(mut last) = LAST_COMMAND.lock() {
+        *last = Some(command_line(program, args));
+    }
+}
+
+/// `program` and `args` joined into one line that can be pasted into a shell.
+fn command_line<S: AsRef<str>>(program: &str, args: &[S]) -> String {
+    std::iter::once(program)
+        .chain(args.iter().map(AsRef::as_ref))
+        .map(shell_quote)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Single-quote `arg` unless it's made only of characters a shell leaves alone.
+fn shell_quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=,@%+".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// dnsmasq `server=/domain/resolver` lines sending each domain to `resolver`.
+/// Blank entries are skipped and leading dots dropped.
+fn connectivity_server_args(domains: &[String], resolver: &str) -> Vec<String> {
+    domains
+        .iter()
+        .map(|d| d.trim().trim_start_matches('.'))
+        .filter(|d| !d.is_empty())
+        .map(|d| format!("server=/{}/{}", d, resolver))
+        .collect()
+}
+
+/// Normalize a MAC address to lowercase, colon-separated form.
+/// Accepts `:` or `-` separators; None if it isn't six hex octets.
+pub fn normalize_mac(mac: &str) -> Option<String> {
+    let octets: Vec<&str> = mac.trim().split([':', '-']).collect();
+    let valid = octets.len() == 6
+        && octets
+            .iter()
+            .all(|o| o.len() == 2 && o.chars().all(|c| c.is_ascii_hexdigit()));
+    valid.then(|| octets.join(":").to_lowercase())
+}
+
+/// Parse a daemon pid file (a single number, usually newline-terminated).
+fn parse_pid(contents: &str) -> Option<u32> {
+    contents.trim().parse().ok().filter(|pid| *pid > 0)
+}
+
+/// Parse a dnsmasq lease file into the leased client addresses.
+///
+/// Each line is: `expiry mac ip hostname client-id`.
+fn parse_leases(contents: &str) -> Vec<Ipv4Addr> {
+    contents
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(2)?.parse().ok())
+        .collect()
+}
+
+/// Parse an ISC dhcpd lease file into the leased client addresses.
+///
+/// Leases are blocks starting with `lease <ip> {`.
+fn parse_isc_leases(contents: &str) -> Vec<Ipv4Addr> {
+    let mut clients: Vec<Ipv4Addr> = contents
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("lease "))
+        .filter_map(|rest| rest.split_whitespace().next()?.parse().ok())
+        .collect();
+    // dhcpd appends a new block on every renewal
+    clients.sort();
+    clients.dedup();
+    clients
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_calculate_dhcp_range() {
-        let range = DhcpServer::calculate_dhcp_range(Ipv4Addr::new(192, 168, 2, 1));
-        assert_eq!(
-            range,
-            ("192.168.2.100".to_string(), "192.168.2.150".to_string())
-        );
+        let range = |lan: &str| {
+            let (start, end) = DhcpServer::calculate_dhcp_range(lan.parse().unwrap());
+            format!("{}-{}", start, end)
+        };
 
-        let range = DhcpServer::calculate_dhcp_range(Ipv4Addr::new(10, 0, 0, 1));
-        assert_eq!(range, ("10.0.0.100".to_string(), "10.0.0.150".to_string()));
+        assert_eq!(range("192.168.2.1/24"), "192.168.2.100-192.168.2.150");
+        assert_eq!(range("10.0.0.1/24"), "10.0.0.100-10.0.0.150");
+        // Wider LANs keep the gateway's /24 pool
+        assert_eq!(range("192.168.3.1/23"), "192.168.3.100-192.168.3.150");
+        // The usual pool is outside a smaller subnet
+        assert_eq!(range("192.168.2.1/25"), "192.168.2.2-192.168.2.52");
+        assert_eq!(range("192.168.2.129/25"), "192.168.2.130-192.168.2.180");
+        assert_eq!(range("192.168.2.250/25"), "192.168.2.129-192.168.2.179");
+        assert_eq!(range("192.168.2.1/30"), "192.168.2.2-192.168.2.2");
+        // The gateway is never handed out
+        assert_eq!(range("192.168.2.120/24"), "192.168.2.121-192.168.2.171");
+    }
+
+    #[test]
+    fn test_parse_pid() {
+        assert_eq!(parse_pid("4242\n"), Some(4242));
+        assert_eq!(parse_pid(""), None);
+        assert_eq!(parse_pid("0"), None);
+        assert_eq!(parse_pid("not a pid"), None);
     }
 
     #[test]
     fn test_generate_config() {
         let server = DhcpServer::new(
+            DhcpBackend::Dnsmasq,
             "en0",
-            Ipv4Addr::new(192, 168, 2, 1),
+            "192.168.2.1/24".parse().unwrap(),
             vec!["10.8.0.1".to_string()],
         );
         let config = server.generate_config();
@@ -236,5 +924,364 @@ mod tests {
         assert!(config.contains("dhcp-range=192.168.2.100,192.168.2.150"));
         assert!(config.contains("dhcp-option=3,192.168.2.1"));
         assert!(config.contains("dhcp-option=6,10.8.0.1"));
+        assert!(config.contains("port=0"));
+    }
+
+    #[test]
+    fn test_command_line() {
+        assert_eq!(
+            command_line(
+                "/opt/homebrew/sbin/dnsmasq",
+                &["--conf-file=/tmp/tunshare-dnsmasq.conf"]
+            ),
+            "/opt/homebrew/sbin/dnsmasq --conf-file=/tmp/tunshare-dnsmasq.conf"
+        );
+        assert_eq!(command_line("dhcpd", &["-q", "en 5"]), "dhcpd -q 'en 5'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    }
+
+    #[test]
+    fn test_dhcp_boot_args() {
+        let server = |backend, boot| {
+            DhcpServer::new(
+                backend,
+                "en5",
+                "192.168.2.1/24".parse().unwrap(),
+                vec!["10.8.0.1".to_string()],
+            )
+            .with_boot(boot)
+        };
+        let boot = DhcpBoot {
+            next_server: Ipv4Addr::new(192, 168, 2, 10),
+            filename: "pxelinux.0".to_string(),
+        };
+
+        let config = server(DhcpBackend::Dnsmasq, Some(boot.clone())).generate_config();
+        assert!(config.contains("\ndhcp-boot=pxelinux.0,,192.168.2.10\n"));
+        assert!(!server(DhcpBackend::Dnsmasq, None)
+            .generate_config()
+            .contains("dhcp-boot"));
+
+        let isc = server(DhcpBackend::IscDhcpd, Some(boot.clone())).generate_isc_config();
+        assert!(isc.contains("  next-server 192.168.2.10;\n  filename \"pxelinux.0\";\n}"));
+
+        // A name that would break out of its config line is never written
+        for filename in ["", "a,b", "a\"b", "a\ndhcp-range=10.0.0.2,10.0.0.9"] {
+            let bad = DhcpBoot {
+                filename: filename.to_string(),
+                ..boot.clone()
+            };
+            assert!(bad.filename_problem().is_some(), "{filename:?}");
+            assert!(!server(DhcpBackend::Dnsmasq, Some(bad.clone()))
+                .generate_config()
+                .contains("dhcp-boot"));
+            assert!(!server(DhcpBackend::IscDhcpd, Some(bad))
+                .generate_isc_config()
+                .contains("filename"));
+        }
+    }
+
+    #[test]
+    fn test_kept_dhcp_lifecycle() {
+        let server = |dns: &str| {
+            DhcpServer::new(
+                DhcpBackend::Dnsmasq,
+                "en5",
+                "192.168.2.1/24".parse().unwrap(),
+                vec![dns.to_string()],
+            )
+        };
+
+        // Stop: kept with persistent_dhcp, but never when quitting or when the
+        // gateway address goes away with the share
+        assert!(keep_between_shares(true, false, false));
+        assert!(!keep_between_shares(false, false, false));
+        assert!(!keep_between_shares(true, true, false));
+        assert!(!keep_between_shares(true, false, true));
+
+        // Next start: taken over only if unchanged and still running
+        let kept = KeptDhcp {
+            pid: 4242,
+            name: "dnsmasq".to_string(),
+            settings: server("10.8.0.1").settings(),
+        };
+        assert!(kept.reusable(&server("10.8.0.1").settings(), true));
+        assert!(!kept.reusable(&server("10.8.0.1").settings(), false));
+        assert!(!kept.reusable(&server("1.1.1.1").settings(), true));
+
+        // A live PID now held by some other program isn't the kept daemon
+        let own = KeptDhcp {
+            pid: std::process::id(),
+            name: process::name(std::process::id()).unwrap(),
+            settings: String::new(),
+        };
+        assert!(own.is_running());
+        let reused = KeptDhcp {
+            name: "dnsmasq".to_string(),
+            ..own
+        };
+        assert!(!reused.is_running());
+    }
+
+    #[test]
+    fn test_generate_config_dns_per_family() {
+        let server = DhcpServer::new(
+            DhcpBackend::Dnsmasq,
+            "en0",
+            "192.168.2.1/24".parse().unwrap(),
+            vec!["10.8.0.1".to_string()],
+        )
+        .with_dns_v6(&[
+            "2606:4700:4700::1111".to_string(),
+            "fe80::1%utun4".to_string(),
+        ]);
+        let config = server.generate_config();
+
+        assert!(config.contains("dhcp-option=6,10.8.0.1\n"));
+        // The VPN's link-local resolver is only reachable from this Mac
+        assert!(config.contains("dhcp-option=option6:dns-server,[2606:4700:4700::1111]\n"));
+        assert!(!config.contains("fe80"));
+
+        // Without IPv6 servers nothing DHCPv6-related is emitted
+        let v4_only = DhcpServer::new(
+            DhcpBackend::Dnsmasq,
+            "en0",
+            "192.168.2.1/24".parse().unwrap(),
+            vec!["10.8.0.1".to_string()],
+        );
+        assert!(!v4_only.generate_config().contains("option6"));
+    }
+
+    #[test]
+    fn test_client_dns() {
+        let gateway: Ipv4Net = "192.168.2.1/24".parse().unwrap();
+        let v6 = ["2606:4700:4700::1111".to_string()];
+        let server = |backend| {
+            DhcpServer::new(backend, "en0", gateway, vec!["10.8.0.1".to_string()]).with_dns_v6(&v6)
+        };
+
+        assert_eq!(
+            server(DhcpBackend::Dnsmasq).client_dns(),
+            ["10.8.0.1", "2606:4700:4700::1111"]
+        );
+        // ISC dhcpd only hands out IPv4 servers
+        assert_eq!(server(DhcpBackend::IscDhcpd).client_dns(), ["10.8.0.1"]);
+        // Relaying: clients ask the gateway
+        let relay = server(DhcpBackend::Dnsmasq)
+            .with_connectivity_check(&["captive.apple.com".to_string()], "192.168.1.1");
+        assert_eq!(relay.client_dns(), ["192.168.2.1"]);
+        // Nothing to advertise: the gateway is the fallback
+        let empty = DhcpServer::new(DhcpBackend::IscDhcpd, "en0", gateway, Vec::new());
+        assert_eq!(empty.client_dns(), ["192.168.2.1"]);
+    }
+
+    #[test]
+    fn test_parse_dnsmasq_version() {
+        let banner = "Dnsmasq version 2.90  Copyright (c) 2000-2024 Simon Kelley
+                      Compile time options: IPv6 GNU-getopt no-DBus no-UBus no-i18n no-IDN DHCP DHCPv6
+";
+        assert_eq!(
+            parse_dnsmasq_version(banner),
+            Some(DnsmasqVersion::new(2, 90))
+        );
+        assert_eq!(
+            parse_dnsmasq_version("Dnsmasq version 2.81test3  Copyright"),
+            Some(DnsmasqVersion::new(2, 81))
+        );
+        assert_eq!(parse_dnsmasq_version("dnsmasq: command not found"), None);
+        assert_eq!(parse_dnsmasq_version(""), None);
+        assert!(DnsmasqVersion::new(2, 9) < DnsmasqVersion::new(2, 60));
+    }
+
+    #[test]
+    fn test_old_dnsmasq_skips_dhcpv6() {
+        let mut server = DhcpServer::new(
+            DhcpBackend::Dnsmasq,
+            "en0",
+            "192.168.2.1/24".parse().unwrap(),
+            vec!["10.8.0.1".to_string()],
+        )
+        .with_dns_v6(&["2606:4700:4700::1111".to_string()]);
+
+        server.dnsmasq_version = Some(DnsmasqVersion::new(2, 55));
+        assert!(!server.generate_config().contains("option6"));
+        assert_eq!(server.skipped_features().len(), 1);
+
+        server.dnsmasq_version = Some(DnsmasqVersion::new(2, 90));
+        assert!(server.generate_config().contains("option6:dns-server"));
+        assert!(server.skipped_features().is_empty());
+    }
+
+    #[test]
+    fn test_normalize_mac() {
+        assert_eq!(
+            normalize_mac("AA-BB-CC-00-11-22").as_deref(),
+            Some("aa:bb:cc:00:11:22")
+        );
+        assert_eq!(
+            normalize_mac(" aa:bb:cc:00:11:22 ").as_deref(),
+            Some("aa:bb:cc:00:11:22")
+        );
+        assert_eq!(normalize_mac("aa:bb:cc:00:11"), None);
+        assert_eq!(normalize_mac("aa:bb:cc:00:11:zz"), None);
+        assert_eq!(normalize_mac("aabb.cc00.1122"), None);
+    }
+
+    #[test]
+    fn test_mac_allowlist_config() {
+        let macs = [
+            "AA:BB:CC:00:11:22".to_string(),
+            "de-ad-be-ef-00-01".to_string(),
+            "not-a-mac".to_string(),
+        ];
+        let server = DhcpServer::new(
+            DhcpBackend::Dnsmasq,
+            "en0",
+            "192.168.2.1/24".parse().unwrap(),
+            vec!["10.8.0.1".to_string()],
+        )
+        .with_mac_allowlist(&macs);
+
+        let config = server.generate_config();
+        assert!(config.contains("dhcp-host=aa:bb:cc:00:11:22\n"));
+        assert!(config.contains("dhcp-host=de:ad:be:ef:00:01\n"));
+        assert!(config.contains("dhcp-ignore=tag:!known"));
+        assert_eq!(config.matches("dhcp-host=").count(), 2);
+
+        let isc = server.generate_isc_config();
+        assert!(isc.contains("deny unknown-clients;"));
+        assert!(isc.contains("hardware ethernet de:ad:be:ef:00:01;"));
+
+        // No allowlist: everyone gets a lease
+        let open = DhcpServer::new(
+            DhcpBackend::Dnsmasq,
+            "en0",
+            "192.168.2.1/24".parse().unwrap(),
+            Vec::new(),
+        );
+        assert!(!open.generate_config().contains("dhcp-ignore"));
+        assert!(!open.generate_isc_config().contains("unknown-clients"));
+    }
+
+    #[test]
+    fn test_connectivity_server_args() {
+        let domains = [
+            "captive.apple.com".to_string(),
+            " .connectivitycheck.gstatic.com".to_string(),
+            "".to_string(),
+        ];
+        assert_eq!(
+            connectivity_server_args(&domains, "1.1.1.1"),
+            [
+                "server=/captive.apple.com/1.1.1.1",
+                "server=/connectivitycheck.gstatic.com/1.1.1.1",
+            ]
+        );
+
+        let server = DhcpServer::new(
+            DhcpBackend::Dnsmasq,
+            "en0",
+            "192.168.2.1/24".parse().unwrap(),
+            vec!["10.8.0.1".to_string()],
+        )
+        .with_connectivity_check(&domains[..1], "1.1.1.1");
+        let config = server.generate_config();
+        assert!(!config.contains("port=0"));
+        assert!(config.contains("server=10.8.0.1\n"));
+        assert!(config.contains("server=/captive.apple.com/1.1.1.1"));
+        // Clients ask dnsmasq, which relays
+        assert!(config.contains("dhcp-option=6,192.168.2.1"));
+    }
+
+    #[test]
+    fn test_generate_isc_config() {
+        let server = DhcpServer::new(
+            DhcpBackend::IscDhcpd,
+            "en0",
+            "192.168.2.1/24".parse().unwrap(),
+            vec!["10.8.0.1".to_string(), "1.1.1.1".to_string()],
+        );
+        let config = server.generate_isc_config();
+
+        assert!(config.contains("subnet 192.168.2.0 netmask 255.255.255.0 {"));
+        assert!(config.contains("range 192.168.2.100 192.168.2.150;"));
+        assert!(config.contains("option routers 192.168.2.1;"));
+        assert!(config.contains("option domain-name-servers 10.8.0.1, 1.1.1.1;"));
+    }
+
+    #[test]
+    fn test_generate_isc_config_non_24_subnet() {
+        let server = DhcpServer::new(
+            DhcpBackend::IscDhcpd,
+            "en5",
+            "192.168.3.1/23".parse().unwrap(),
+            vec!["10.8.0.1".to_string()],
+        );
+        let config = server.generate_isc_config();
+        assert!(config.contains("subnet 192.168.2.0 netmask 255.255.254.0 {"));
+        assert!(config.contains("range 192.168.3.100 192.168.3.150;"));
+
+        let server = DhcpServer::new(
+            DhcpBackend::IscDhcpd,
+            "en5",
+            "192.168.2.129/25".parse().unwrap(),
+            vec!["10.8.0.1".to_string()],
+        );
+        let config = server.generate_isc_config();
+        assert!(config.contains("subnet 192.168.2.128 netmask 255.255.255.128 {"));
+        assert!(config.contains("range 192.168.2.130 192.168.2.180;"));
+    }
+
+    #[test]
+    fn test_generate_isc_config_falls_back_to_gateway_dns() {
+        let server = DhcpServer::new(
+            DhcpBackend::IscDhcpd,
+            "en0",
+            "10.0.0.1/24".parse().unwrap(),
+            Vec::new(),
+        );
+        let config = server.generate_isc_config();
+
+        assert!(config.contains("subnet 10.0.0.0 netmask 255.255.255.0 {"));
+        assert!(config.contains("option domain-name-servers 10.0.0.1;"));
+    }
+
+    #[test]
+    fn test_parse_isc_leases() {
+        let contents = "\
+lease 192.168.2.101 {
+  starts 4 2024/06/13 10:00:00;
+  hardware ethernet aa:bb:cc:dd:ee:02;
+}
+lease 192.168.2.100 {
+  starts 4 2024/06/13 10:00:00;
+}
+lease 192.168.2.101 {
+  starts 4 2024/06/13 16:00:00;
+}
+";
+        assert_eq!(
+            parse_isc_leases(contents),
+            vec![
+                Ipv4Addr::new(192, 168, 2, 100),
+                Ipv4Addr::new(192, 168, 2, 101)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_leases() {
+        let contents = "\
+1718000000 aa:bb:cc:dd:ee:01 192.168.2.100 phone 01:aa:bb:cc:dd:ee:01
+1718000500 aa:bb:cc:dd:ee:02 192.168.2.101 * *
+garbage
+";
+        assert_eq!(
+            parse_leases(contents),
+            vec![
+                Ipv4Addr::new(192, 168, 2, 100),
+                Ipv4Addr::new(192, 168, 2, 101)
+            ]
+        );
     }
 }
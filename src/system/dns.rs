@@ -1,16 +1,24 @@
-//! VPN DNS server discovery via scutil --dns.
+//! VPN DNS server discovery via scutil --dns and /etc/resolver.
 
 use crate::error::{Result, TunshareError};
-use tokio::process::Command;
+use crate::system::exec::{self, RetryPolicy};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::path::Path;
+
+/// Read when `scutil` has no answer; macOS still maintains it for Unix tools.
+const RESOLV_CONF_PATH: &str = "/etc/resolv.conf";
+/// Per-domain resolver files (see `man 5 resolver`), which some VPN clients
+/// fill in instead of registering with configd.
+const RESOLVER_DIR: &str = "/etc/resolver";
 
 /// Discover DNS servers associated with a VPN interface.
 ///
 /// Parses `scutil --dns` output to find resolver configurations
-/// that are associated with the given VPN interface.
-pub async fn discover_vpn_dns(vpn_interface: &str) -> Result<Vec<String>> {
-    let output = Command::new("scutil")
-        .arg("--dns")
-        .output()
+/// that are associated with the given VPN interface, then adds servers from
+/// `/etc/resolver` files bound to it (scutil's come first).
+pub async fn discover_vpn_dns(vpn_interface: &str, retry: RetryPolicy) -> Result<Vec<String>> {
+    let output = exec::output_with_retry(retry, "scutil", &["--dns"])
         .await
         .map_err(|e| TunshareError::CommandFailed {
             command: "scutil --dns".into(),
@@ -18,80 +26,208 @@ pub async fn discover_vpn_dns(vpn_interface: &str) -> Result<Vec<String>> {
         })?;
 
     let stdout = String::from_utf8_lossy(&output.stdout);
-    let dns_servers = parse_dns_for_interface(&stdout, vpn_interface);
+    let mut dns_servers = parse_dns_for_interface(&stdout, vpn_interface);
+
+    let dir = Path::new(RESOLVER_DIR);
+    let iface = vpn_interface.to_string();
+    let from_files = tokio::task::spawn_blocking(move || parse_resolver_dir(dir, &iface))
+        .await
+        .unwrap_or_default();
+    for server in from_files {
+        if !dns_servers.contains(&server) {
+            dns_servers.push(server);
+        }
+    }
 
     Ok(dns_servers)
 }
 
+/// Nameservers from the resolver files in `dir` whose `interface` line
+/// names `interface`, in file-name order without duplicates.
+///
+/// Unreadable files (and a missing directory) are skipped, as are
+/// nameservers that aren't IP addresses.
+fn parse_resolver_dir(dir: &Path, interface: &str) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .collect();
+    paths.sort();
+
+    let mut servers: Vec<String> = Vec::new();
+    for path in paths {
+        let Ok(contents) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        for server in parse_resolver_file(&contents, interface) {
+            if !servers.contains(&server) {
+                servers.push(server);
+            }
+        }
+    }
+    servers
+}
+
+/// Nameservers in one resolver file, if its `interface` line names `interface`.
+fn parse_resolver_file(contents: &str, interface: &str) -> Vec<String> {
+    let bound = contents.lines().any(|line| {
+        let line = line.split(['#', ';']).next().unwrap_or_default();
+        let mut fields = line.split_whitespace();
+        fields.next() == Some("interface") && fields.next() == Some(interface)
+    });
+    if !bound {
+        return Vec::new();
+    }
+    parse_resolv_conf(contents)
+        .into_iter()
+        .filter(|server| family(server).is_some())
+        .collect()
+}
+
+/// One resolver block from `scutil --dns`.
+#[derive(Debug, Default)]
+struct Resolver {
+    nameservers: Vec<String>,
+    /// Whether the block mentions the interface (`if_index : N (utun3)` or `interface`).
+    matches_interface: bool,
+    /// Listed under "DNS configuration (for scoped queries)" or flagged `Scoped`.
+    scoped: bool,
+    /// `order` field; lower is consulted first.
+    order: Option<u32>,
+}
+
 /// Parse scutil --dns output looking for DNS servers associated with interface.
+///
+/// Both IPv4 and IPv6 nameservers are returned (tell them apart with
+/// [`family`]); values that aren't IP addresses are skipped. Link-local
+/// servers are kept for this Mac's own lookups; [`is_link_scoped`] picks
+/// them out before anything is advertised to clients.
+///
+/// Servers come back in resolver-priority order: the interface's scoped
+/// resolver first, then other matching resolvers by their `order` field
+/// (blocks without one keep their listed order, after those with one).
+/// Duplicates are dropped, keeping the first (highest-priority) occurrence.
 fn parse_dns_for_interface(output: &str, interface: &str) -> Vec<String> {
-    let mut dns_servers = Vec::new();
-    let mut in_relevant_resolver = false;
-    let mut current_nameservers: Vec<String> = Vec::new();
+    let if_marker = format!("({})", interface);
+    let mut resolvers: Vec<Resolver> = Vec::new();
+    let mut in_scoped_section = false;
 
     for line in output.lines() {
         let trimmed = line.trim();
 
+        if trimmed.starts_with("DNS configuration") {
+            in_scoped_section = trimmed.contains("scoped queries");
+            continue;
+        }
+
         // New resolver block
         if trimmed.starts_with("resolver #") {
-            // Save previous if it was relevant
-            if in_relevant_resolver {
-                dns_servers.append(&mut current_nameservers);
-            }
-            in_relevant_resolver = false;
-            current_nameservers.clear();
+            resolvers.push(Resolver {
+                scoped: in_scoped_section,
+                ..Resolver::default()
+            });
+            continue;
         }
 
-        // Check if this resolver is for our interface
+        let Some(resolver) = resolvers.last_mut() else {
+            continue;
+        };
+
         // Look for "if_index : N (utun3)" pattern
-        if trimmed.starts_with("if_index") && trimmed.contains(&format!("({})", interface)) {
-            in_relevant_resolver = true;
+        if trimmed.starts_with("if_index") && trimmed.contains(&if_marker) {
+            resolver.matches_interface = true;
         }
 
         // Also check for interface field directly
         if trimmed.starts_with("interface") && trimmed.contains(interface) {
-            in_relevant_resolver = true;
+            resolver.matches_interface = true;
         }
 
-        // Collect nameservers
+        if trimmed.starts_with("flags") && trimmed.contains("Scoped") {
+            resolver.scoped = true;
+        }
+
+        if let Some(value) = field_value(trimmed, "order") {
+            resolver.order = value.parse().ok();
+        }
+
+        // Format: "nameserver[0] : 10.8.0.1" or "nameserver[0] : fd00::1"
         if trimmed.starts_with("nameserver[") {
-            // Format: "nameserver[0] : 10.8.0.1"
             if let Some(pos) = trimmed.find(" : ") {
                 let server = trimmed[pos + 3..].trim().to_string();
-                if !server.is_empty() {
-                    current_nameservers.push(server);
+                if family(&server).is_some() {
+                    resolver.nameservers.push(server);
                 }
             }
         }
     }
 
-    // Don't forget the last resolver block
-    if in_relevant_resolver {
-        dns_servers.extend(current_nameservers);
-    }
+    let mut relevant: Vec<&Resolver> = resolvers.iter().filter(|r| r.matches_interface).collect();
+    // Stable sort, so equal keys keep the order scutil listed them in
+    relevant.sort_by_key(|r| (!r.scoped, r.order.is_none(), r.order));
 
-    // Deduplicate
-    dns_servers.sort();
-    dns_servers.dedup();
+    let mut dns_servers: Vec<String> = Vec::new();
+    for server in relevant.iter().flat_map(|r| &r.nameservers) {
+        if !dns_servers.contains(server) {
+            dns_servers.push(server.clone());
+        }
+    }
 
     dns_servers
 }
 
+/// Value of a `key : value` line, if the line is exactly that key.
+fn field_value<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let (name, value) = line.split_once(':')?;
+    (name.trim() == key).then(|| value.trim())
+}
+
 /// Get the default DNS servers (from system configuration).
-pub async fn get_default_dns() -> Result<Vec<String>> {
-    let output = Command::new("scutil")
-        .arg("--dns")
-        .output()
+///
+/// Falls back to `/etc/resolv.conf` when `scutil --dns` fails or lists none.
+pub async fn get_default_dns(retry: RetryPolicy) -> Result<Vec<String>> {
+    let scutil = exec::output_with_retry(retry, "scutil", &["--dns"])
         .await
         .map_err(|e| TunshareError::CommandFailed {
             command: "scutil --dns".into(),
             message: e.to_string(),
-        })?;
+        })
+        .map(|output| parse_default_dns(&String::from_utf8_lossy(&output.stdout)));
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let dns_servers = parse_default_dns(&stdout);
+    if matches!(&scutil, Ok(servers) if !servers.is_empty()) {
+        return scutil;
+    }
 
-    Ok(dns_servers)
+    let fallback = tokio::fs::read_to_string(RESOLV_CONF_PATH)
+        .await
+        .map(|contents| parse_resolv_conf(&contents))
+        .unwrap_or_default();
+    if fallback.is_empty() {
+        scutil
+    } else {
+        Ok(fallback)
+    }
+}
+
+/// Parse `nameserver` lines from resolv.conf contents, in order, without duplicates.
+fn parse_resolv_conf(contents: &str) -> Vec<String> {
+    let mut servers: Vec<String> = Vec::new();
+    for line in contents.lines() {
+        // Comments start with '#' or ';', including trailing ones
+        let line = line.split(['#', ';']).next().unwrap_or_default();
+        let mut fields = line.split_whitespace();
+        if fields.next() != Some("nameserver") {
+            continue;
+        }
+        if let Some(server) = fields.next() {
+            if !servers.iter().any(|s| s == server) {
+                servers.push(server.to_string());
+            }
+        }
+    }
+    servers
 }
 
 /// Parse default DNS from scutil output (looks for the primary resolver).
@@ -123,10 +259,361 @@ fn parse_default_dns(output: &str) -> Vec<String> {
     dns_servers
 }
 
+/// Address family of a DNS server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpFamily {
+    V4,
+    V6,
+}
+
+/// Family of `server`, or None if it isn't an IP address.
+///
+/// A `%scope` suffix (as `scutil` prints for link-local servers) is ignored.
+pub fn family(server: &str) -> Option<IpFamily> {
+    let address = server.split('%').next().unwrap_or_default();
+    match address.parse::<IpAddr>().ok()? {
+        IpAddr::V4(_) => Some(IpFamily::V4),
+        IpAddr::V6(_) => Some(IpFamily::V6),
+    }
+}
+
+/// Whether `server` only works on one link: an IPv6 link-local address, or
+/// any address with a `%scope`. Such a server is usable from this Mac but
+/// means nothing to a client on the shared LAN.
+pub fn is_link_scoped(server: &str) -> bool {
+    let (address, scope) = match server.split_once('%') {
+        Some((address, _)) => (address, true),
+        None => (server, false),
+    };
+    match address.parse::<IpAddr>() {
+        Ok(IpAddr::V6(ip)) => scope || ip.is_unicast_link_local(),
+        Ok(IpAddr::V4(_)) => scope,
+        Err(_) => false,
+    }
+}
+
+/// Split DNS servers into (IPv4, IPv6), keeping their order.
+/// Entries that aren't IP addresses are dropped.
+pub fn split_by_family(servers: &[String]) -> (Vec<String>, Vec<String>) {
+    let mut v4 = Vec::new();
+    let mut v6 = Vec::new();
+    for server in servers {
+        match family(server) {
+            Some(IpFamily::V4) => v4.push(server.clone()),
+            Some(IpFamily::V6) => v6.push(server.clone()),
+            None => {}
+        }
+    }
+    (v4, v6)
+}
+
+/// A DNS service offered in the DNS editor: a built-in public one, or one
+/// from `dns_presets` in the config file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DnsPreset {
+    pub name: String,
+    /// Address set when the preset is picked.
+    pub primary: String,
+    /// The service's other address, recognised when entered by hand.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secondary: Option<String>,
+}
+
+impl DnsPreset {
+    fn new(name: &str, primary: &str, secondary: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            primary: primary.to_string(),
+            secondary: Some(secondary.to_string()),
+        }
+    }
+
+    /// Whether `server` is one of this service's addresses.
+    pub fn matches(&self, server: &str) -> bool {
+        server == self.primary || self.secondary.as_deref() == Some(server)
+    }
+}
+
+/// Well-known public DNS services.
+pub fn builtin_presets() -> Vec<DnsPreset> {
+    vec![
+        DnsPreset::new("Cloudflare", "1.1.1.1", "1.0.0.1"),
+        DnsPreset::new("Google", "8.8.8.8", "8.8.4.4"),
+        DnsPreset::new("Quad9", "9.9.9.9", "149.112.112.112"),
+        DnsPreset::new("OpenDNS", "208.67.222.222", "208.67.220.220"),
+    ]
+}
+
+/// The presets the DNS editor lists: the built-ins with `user`'s merged in
+/// (same name replaces, others are appended), or just `user` if `replace`.
+pub fn combine_presets(user: &[DnsPreset], replace: bool) -> Vec<DnsPreset> {
+    let mut presets = if replace {
+        Vec::new()
+    } else {
+        builtin_presets()
+    };
+    for preset in user {
+        match presets.iter_mut().find(|p| p.name == preset.name) {
+            Some(existing) => *existing = preset.clone(),
+            None => presets.push(preset.clone()),
+        }
+    }
+    presets
+}
+
+/// Index into `presets` of the service `server` belongs to.
+pub fn preset_index(presets: &[DnsPreset], server: &str) -> Option<usize> {
+    presets.iter().position(|p| p.matches(server.trim()))
+}
+
+/// Row to highlight when the DNS editor opens (0 = Auto-detect, 1..=N =
+/// presets, N+1 = Custom...): the preset `custom` belongs to, Custom... for
+/// other servers, and on auto-detect the preset picked last time, if any.
+pub fn preset_selection(
+    presets: &[DnsPreset],
+    custom: Option<&str>,
+    last_preset: Option<usize>,
+) -> usize {
+    match custom {
+        Some(server) => preset_index(presets, server)
+            .map(|i| i + 1)
+            .unwrap_or(presets.len() + 1),
+        None => last_preset
+            .filter(|&i| i < presets.len())
+            .map(|i| i + 1)
+            .unwrap_or(0),
+    }
+}
+
+/// `servers` with the ones named in `order` moved to the front, in that
+/// order. Servers `order` doesn't mention keep their relative order after them.
+pub fn apply_order(servers: &[String], order: &[String]) -> Vec<String> {
+    let mut arranged: Vec<String> = order
+        .iter()
+        .filter(|server| servers.contains(server))
+        .cloned()
+        .collect();
+    for server in servers {
+        if !arranged.contains(server) {
+            arranged.push(server.clone());
+        }
+    }
+    arranged
+}
+
+/// The servers to use: `servers` arranged by `order`, without `disabled` ones.
+/// If that would leave none, all of them are used rather than no DNS at all.
+pub fn select(servers: &[String], order: &[String], disabled: &[String]) -> Vec<String> {
+    let arranged = apply_order(servers, order);
+    let enabled: Vec<String> = arranged
+        .iter()
+        .filter(|server| !disabled.contains(server))
+        .cloned()
+        .collect();
+    if enabled.is_empty() {
+        arranged
+    } else {
+        enabled
+    }
+}
+
+/// Swap entry `index` of the arranged list `listed` with its neighbour above
+/// (`up`) or below, returning the order to persist. Servers in the previous
+/// `order` that aren't listed right now keep their place at the end, so a
+/// server that's only sometimes discovered doesn't lose its position.
+/// None if the entry is already at that edge.
+pub fn move_server(
+    listed: &[String],
+    order: &[String],
+    index: usize,
+    up: bool,
+) -> Option<Vec<String>> {
+    let other = if up { index.checked_sub(1)? } else { index + 1 };
+    if index >= listed.len() || other >= listed.len() {
+        return None;
+    }
+    let mut moved = listed.to_vec();
+    moved.swap(index, other);
+    for server in order {
+        if !moved.contains(server) {
+            moved.push(server.clone());
+        }
+    }
+    Some(moved)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn servers(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_apply_order_and_move() {
+        let discovered = servers(&["10.0.0.1", "10.0.0.2", "10.0.0.3"]);
+        assert_eq!(apply_order(&discovered, &[]), discovered);
+
+        // Ordered servers first; unknown entries in the order are skipped
+        let order = servers(&["10.0.0.3", "192.0.2.9"]);
+        assert_eq!(
+            apply_order(&discovered, &order),
+            servers(&["10.0.0.3", "10.0.0.1", "10.0.0.2"])
+        );
+
+        let listed = apply_order(&discovered, &order);
+        let moved = move_server(&listed, &order, 2, true).unwrap();
+        assert_eq!(
+            moved,
+            servers(&["10.0.0.3", "10.0.0.2", "10.0.0.1", "192.0.2.9"])
+        );
+        assert_eq!(
+            apply_order(&discovered, &moved),
+            servers(&["10.0.0.3", "10.0.0.2", "10.0.0.1"])
+        );
+
+        assert_eq!(move_server(&listed, &order, 0, true), None);
+        assert_eq!(move_server(&listed, &order, 2, false), None);
+    }
+
+    #[test]
+    fn test_preset_index_matches_secondary() {
+        let presets = builtin_presets();
+        assert_eq!(preset_index(&presets, "9.9.9.9"), Some(2));
+        assert_eq!(preset_index(&presets, "149.112.112.112"), Some(2));
+        assert_eq!(preset_index(&presets, "1.0.0.1"), Some(0));
+        assert_eq!(preset_index(&presets, " 8.8.4.4 "), Some(1));
+        assert_eq!(preset_index(&presets, "10.8.0.1"), None);
+    }
+
+    #[test]
+    fn test_user_presets() {
+        let user = [
+            DnsPreset {
+                name: "Home".to_string(),
+                primary: "192.168.1.53".to_string(),
+                secondary: None,
+            },
+            DnsPreset {
+                name: "Quad9".to_string(),
+                primary: "9.9.9.11".to_string(),
+                secondary: Some("149.112.112.11".to_string()),
+            },
+        ];
+
+        // Merged: same name replaces in place, new ones are appended
+        let presets = combine_presets(&user, false);
+        let names: Vec<&str> = presets.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["Cloudflare", "Google", "Quad9", "OpenDNS", "Home"]);
+        assert_eq!(presets[2].primary, "9.9.9.11");
+        assert_eq!(preset_index(&presets, "9.9.9.9"), None);
+
+        let replaced = combine_presets(&user, true);
+        assert_eq!(replaced, user);
+        assert_eq!(combine_presets(&[], false), builtin_presets());
+
+        // A custom DNS that is a user preset opens on that preset
+        assert_eq!(preset_selection(&presets, Some("192.168.1.53"), None), 5);
+        assert_eq!(preset_selection(&replaced, Some("149.112.112.11"), None), 2);
+        assert_eq!(preset_selection(&presets, Some("10.8.0.1"), None), 6);
+        assert_eq!(preset_selection(&presets, None, Some(4)), 5);
+        assert_eq!(preset_selection(&replaced, None, Some(4)), 0);
+        assert_eq!(preset_selection(&presets, None, None), 0);
+    }
+
+    #[test]
+    fn test_select_excludes_disabled() {
+        let discovered = servers(&["10.0.0.1", "10.0.0.2", "fd00::53"]);
+        let order = servers(&["fd00::53"]);
+        let disabled = servers(&["10.0.0.1"]);
+        assert_eq!(
+            select(&discovered, &order, &disabled),
+            servers(&["fd00::53", "10.0.0.2"])
+        );
+
+        // Never nothing: with every server off, all of them are used
+        assert_eq!(
+            select(&discovered, &order, &discovered),
+            servers(&["fd00::53", "10.0.0.1", "10.0.0.2"])
+        );
+        assert!(select(&[], &order, &disabled).is_empty());
+    }
+
+    #[test]
+    fn test_parse_resolv_conf() {
+        let contents = "\
+#
+# macOS Notice
+#
+search corp.example
+nameserver 10.8.0.1
+nameserver\t1.1.1.1  # trailing comment
+; nameserver 9.9.9.9
+nameserver 10.8.0.1
+nameserver
+options ndots:2
+";
+        assert_eq!(parse_resolv_conf(contents), ["10.8.0.1", "1.1.1.1"]);
+        assert!(parse_resolv_conf("").is_empty());
+    }
+
+    #[test]
+    fn test_parse_resolver_dir() {
+        let dir = std::env::temp_dir().join(format!("tunshare-resolver-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("corp.example"),
+            "# Added by the VPN client\n\
+             domain corp.example\n\
+             nameserver 10.8.0.53\n\
+             nameserver fd00::53\n\
+             nameserver bogus\n\
+             interface utun4\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("lab.example"),
+            "nameserver 10.8.0.53\nnameserver 10.8.0.54\ninterface utun4 # scoped\n",
+        )
+        .unwrap();
+        // Other tunnels' and unscoped files don't count
+        std::fs::write(
+            dir.join("other.example"),
+            "nameserver 10.9.0.1\ninterface utun5\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("local"), "nameserver 192.168.1.1\n").unwrap();
+
+        let servers = parse_resolver_dir(&dir, "utun4");
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(servers, ["10.8.0.53", "fd00::53", "10.8.0.54"]);
+
+        assert!(parse_resolver_dir(&dir, "utun4").is_empty());
+    }
+
+    #[test]
+    fn test_split_by_family() {
+        let servers: Vec<String> = [
+            "10.8.0.1",
+            "2606:4700:4700::1111",
+            "1.1.1.1",
+            "fe80::1%utun4",
+            "not-an-ip",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+        let (v4, v6) = split_by_family(&servers);
+        assert_eq!(v4, ["10.8.0.1", "1.1.1.1"]);
+        assert_eq!(v6, ["2606:4700:4700::1111", "fe80::1%utun4"]);
+
+        let (v4, v6) = split_by_family(&servers[..1]);
+        assert_eq!(v4, ["10.8.0.1"]);
+        assert!(v6.is_empty());
+    }
+
     #[test]
     fn test_parse_dns_for_interface() {
         let output = r#"
@@ -157,4 +644,78 @@ resolver #3
         let dns_en0 = parse_dns_for_interface(output, "en0");
         assert_eq!(dns_en0, vec!["192.168.1.1"]);
     }
+
+    #[test]
+    fn test_parse_dns_for_interface_priority() {
+        let output = r#"
+DNS configuration
+
+resolver #1
+  search domain[0] : corp.example
+  nameserver[0] : 1.1.1.1
+  nameserver[1] : 10.8.0.2
+  if_index : 23 (utun3)
+  flags    : Request A records
+  reach    : 0x00000002 (Reachable)
+  order    : 200000
+
+resolver #2
+  domain   : corp.example
+  nameserver[0] : 10.8.0.1
+  nameserver[1] : 10.8.0.2
+  if_index : 23 (utun3)
+  flags    : Request A records
+  reach    : 0x00000002 (Reachable)
+  order    : 100
+
+DNS configuration (for scoped queries)
+
+resolver #1
+  nameserver[0] : 10.8.0.53
+  if_index : 23 (utun3)
+  flags    : Scoped, Request A records
+  reach    : 0x00000002 (Reachable)
+"#;
+
+        let dns = parse_dns_for_interface(output, "utun3");
+        assert_eq!(dns, vec!["10.8.0.53", "10.8.0.1", "10.8.0.2", "1.1.1.1"]);
+    }
+
+    #[test]
+    fn test_parse_dns_for_interface_ipv6_only() {
+        let output = r#"
+DNS configuration
+
+resolver #1
+  nameserver[0] : 192.168.1.1
+  if_index : 5 (en0)
+  flags    : Request A records
+
+DNS configuration (for scoped queries)
+
+resolver #1
+  nameserver[0] : fd00::1
+  nameserver[1] : fe80::1%utun5
+  if_index : 25 (utun5)
+  flags    : Scoped, Request AAAA records
+  reach    : 0x00000002 (Reachable)
+"#;
+
+        let dns = parse_dns_for_interface(output, "utun5");
+        assert_eq!(dns, vec!["fd00::1", "fe80::1%utun5"]);
+        assert!(dns.iter().all(|s| family(s) == Some(IpFamily::V6)));
+
+        let (v4, v6) = split_by_family(&dns);
+        assert!(v4.is_empty());
+        assert_eq!(v6, dns);
+        assert_eq!(family("192.168.1.1"), Some(IpFamily::V4));
+        assert_eq!(family("not-an-ip"), None);
+
+        // The link-local one can't be handed to LAN clients
+        let usable: Vec<&String> = v6.iter().filter(|s| !is_link_scoped(s)).collect();
+        assert_eq!(usable, ["fd00::1"]);
+        assert!(is_link_scoped("fe80::1"));
+        assert!(!is_link_scoped("2606:4700:4700::1111"));
+        assert!(!is_link_scoped("192.168.1.1"));
+    }
 }
@@ -0,0 +1,130 @@
+//! Running read-only system commands, retrying transient failures.
+//!
+//! `ifconfig`, `scutil` and `pfctl -s` occasionally fail right after a
+//! network change while configd catches up. Reading again a moment later is
+//! harmless, so those reads go through [`output_with_retry`]. Commands that
+//! change state (loading rules, assigning addresses) are never retried here.
+
+use std::future::Future;
+use std::io;
+use std::process::Output;
+use std::time::Duration;
+
+use tokio::process::Command;
+
+/// Default number of tries for a read-only command.
+pub const DEFAULT_ATTEMPTS: u32 = 3;
+/// Default wait before the first retry, in milliseconds.
+pub const DEFAULT_BACKOFF_MS: u64 = 200;
+
+/// How often to try a command, and how long to wait before retrying.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Tries in total, including the first (at least 1).
+    pub attempts: u32,
+    /// Wait before the first retry; each later retry waits one more step.
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Wait before retry number `attempt` (1-based), saturating rather than
+    /// overflowing for a large configured backoff.
+    fn delay(&self, attempt: u32) -> Duration {
+        self.backoff.saturating_mul(attempt)
+    }
+}
+
+/// Run `op` until it succeeds or `policy.attempts` tries are used up,
+/// returning the last result. Only for operations that are safe to repeat.
+pub async fn run_with_retry<T, E, F, Fut>(policy: RetryPolicy, mut op: F) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut attempt = 1;
+    loop {
+        match op().await {
+            Err(_) if attempt < policy.attempts => {
+                tokio::time::sleep(policy.delay(attempt)).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Run a read-only command, retrying while it can't be spawned or exits
+/// unsuccessfully. If every try fails, the last output (or spawn error) is
+/// returned as-is, so callers see the same result as without retrying.
+pub async fn output_with_retry(
+    policy: RetryPolicy,
+    program: &str,
+    args: &[&str],
+) -> io::Result<Output> {
+    run_with_retry(policy, || async {
+        match Command::new(program).args(args).output().await {
+            Ok(output) if output.status.success() => Ok(output),
+            // Failed, but worth handing back if it was the last try
+            last => Err(last),
+        }
+    })
+    .await
+    .or_else(|last| last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[tokio::test]
+    async fn test_run_with_retry() {
+        let policy = RetryPolicy {
+            attempts: 3,
+            backoff: Duration::from_millis(1),
+        };
+
+        // Fails once, then succeeds
+        let calls = Cell::new(0);
+        let result: Result<&str, &str> = run_with_retry(policy, || {
+            calls.set(calls.get() + 1);
+            let call = calls.get();
+            async move {
+                if call == 1 {
+                    Err("busy")
+                } else {
+                    Ok("output")
+                }
+            }
+        })
+        .await;
+        assert_eq!(result, Ok("output"));
+        assert_eq!(calls.get(), 2);
+
+        // Gives up after the last attempt with its error
+        calls.set(0);
+        let result: Result<(), u32> = run_with_retry(policy, || {
+            calls.set(calls.get() + 1);
+            let call = calls.get();
+            async move { Err(call) }
+        })
+        .await;
+        assert_eq!(result, Err(3));
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn test_retry_delay_saturates() {
+        let policy = RetryPolicy {
+            attempts: 3,
+            backoff: Duration::from_millis(200),
+        };
+        assert_eq!(policy.delay(2), Duration::from_millis(400));
+
+        let huge = RetryPolicy {
+            attempts: 3,
+            backoff: Duration::MAX,
+        };
+        assert_eq!(huge.delay(2), Duration::MAX);
+    }
+}
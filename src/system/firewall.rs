@@ -1,12 +1,173 @@
 //! Packet filter (pf) firewall management.
 
 use crate::error::{Result, TunshareError};
+use crate::system::exec::{self, RetryPolicy};
+use crate::system::natpmp;
+use crate::system::process;
+use ipnet::Ipv4Net;
 use std::fs;
+use std::net::Ipv4Addr;
 use std::path::Path;
+use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
 
 const PF_CONF_PATH: &str = "/tmp/tunshare_pf.conf";
 const DEFAULT_PF_CONF: &str = "/etc/pf.conf";
+/// Anchor holding the per-client labeled pass rules used for accounting.
+pub const CLIENTS_ANCHOR_NAME: &str = "tunshare_clients";
+/// Anchor names tunshare always uses; leftovers are flushed by
+/// `Firewall::flush_all_tunshare_anchors`.
+const KNOWN_ANCHORS: &[&str] = &[CLIENTS_ANCHOR_NAME, natpmp::DEFAULT_ANCHOR_NAME];
+/// Generic anchor older releases loaded NAT-PMP mappings into. Other daemons
+/// use it too, so it's only flushed when its rules are recognizably ours.
+const LEGACY_NATPMP_ANCHOR: &str = "natpmp";
+/// Prefix of the pf label attached to each client's pass rule.
+const CLIENT_LABEL_PREFIX: &str = "client-";
+/// TCP MSS clamp for LAN traffic leaving through the VPN (1400 is safe for most VPNs).
+pub const DEFAULT_MSS: u16 = 1400;
+/// Table of destinations that bypass uplink balancing (the LAN and `no_nat` ranges).
+const DIRECT_TABLE_NAME: &str = "tunshare_direct";
+
+/// A VPN interface LAN traffic is shared through.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Uplink {
+    pub interface: String,
+    /// Next hop for `route-to` (the tunnel's peer address), if known.
+    pub gateway: Option<Ipv4Addr>,
+}
+
+impl Uplink {
+    /// pf `route-to` host: `(utun4 10.8.0.5)`, or just the interface.
+    fn route_host(&self) -> String {
+        match self.gateway {
+            Some(gateway) => format!("({} {})", self.interface, gateway),
+            None => self.interface.clone(),
+        }
+    }
+}
+
+/// A user-supplied pf ruleset loaded in place of `generate_rules`.
+///
+/// `{vpn}`, `{lan}`, `{lan_subnet}` and `{dns}` are filled in when rules are
+/// loaded; any other `{name}` is an error so typos don't reach pfctl.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PfTemplate {
+    pub text: String,
+    /// The LAN's subnet; `{lan_subnet}` falls back to `<lan>:network` if unknown.
+    pub lan_subnet: Option<Ipv4Net>,
+    /// DNS servers handed to clients, for `{dns}` (comma-separated).
+    pub dns: Vec<String>,
+}
+
+impl PfTemplate {
+    /// The ruleset for this primary uplink and LAN interface.
+    pub fn render(&self, vpn_if: &str, lan_if: &str) -> Result<String> {
+        let lan_subnet = match self.lan_subnet {
+            Some(subnet) => subnet.to_string(),
+            None => format!("{}:network", lan_if),
+        };
+        let values = [
+            ("vpn", vpn_if.to_string()),
+            ("lan", lan_if.to_string()),
+            ("lan_subnet", lan_subnet),
+            ("dns", self.dns.join(", ")),
+        ];
+        render_template(&self.text, &values).map_err(|unknown| {
+            TunshareError::FirewallError(format!(
+                "pf template has unknown placeholders: {}",
+                unknown
+                    .iter()
+                    .map(|name| format!("{{{}}}", name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+        })
+    }
+}
+
+/// Replace each `{name}` in `template` with its value from `values`.
+///
+/// Only braces around a bare identifier count as placeholders, so pf lists
+/// like `{ tcp, udp }` pass through. Returns the unknown names, each once.
+pub fn render_template(
+    template: &str,
+    values: &[(&str, String)],
+) -> std::result::Result<String, Vec<String>> {
+    let mut output = String::with_capacity(template.len());
+    let mut unknown: Vec<String> = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let name_len = after
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(after.len());
+        let name = &after[..name_len];
+        if name_len == 0 || !after[name_len..].starts_with('}') {
+            output.push('{');
+            rest = after;
+            continue;
+        }
+        match values.iter().find(|(key, _)| *key == name) {
+            Some((_, value)) => output.push_str(value),
+            None => {
+                if !unknown.iter().any(|u| u == name) {
+                    unknown.push(name.to_string());
+                }
+            }
+        }
+        rest = &after[name_len + 1..];
+    }
+    output.push_str(rest);
+
+    if unknown.is_empty() {
+        Ok(output)
+    } else {
+        Err(unknown)
+    }
+}
+
+/// Running totals of the NAT rules' `(packets, bytes)` counters.
+///
+/// Loading a ruleset (reload, uplink switch) starts pf's counters from zero,
+/// so a reading below the last one carries the last one over. Traffic between
+/// the last reading and a reload isn't counted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NatTotals {
+    carried: (u64, u64),
+    last: (u64, u64),
+}
+
+impl NatTotals {
+    /// Add a `Firewall::nat_counters` reading.
+    pub fn record(&mut self, reading: (u64, u64)) {
+        if reading.0 < self.last.0 || reading.1 < self.last.1 {
+            self.carried.0 += self.last.0;
+            self.carried.1 += self.last.1;
+        }
+        self.last = reading;
+    }
+
+    /// Forwarded `(packets, bytes)` so far.
+    pub fn total(&self) -> (u64, u64) {
+        (self.carried.0 + self.last.0, self.carried.1 + self.last.1)
+    }
+}
+
+/// One entry of the pf state table, as listed by `pfctl -ss`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PfState {
+    pub interface: String,
+    pub protocol: String,
+    /// Connection initiator (`addr:port`).
+    pub source: String,
+    /// Connection target (`addr:port`).
+    pub destination: String,
+    /// Translated address on the other side of NAT, if any.
+    pub nat: Option<String>,
+    /// Per-direction state, e.g. `ESTABLISHED:ESTABLISHED`.
+    pub state: String,
+}
 
 /// Manages pf firewall rules for VPN sharing.
 pub struct Firewall {
@@ -14,6 +175,18 @@ pub struct Firewall {
     rules_loaded: bool,
     /// The config file path we're using.
     config_path: String,
+    /// Anchor the NAT-PMP server loads its mapping rules into.
+    natpmp_anchor: String,
+    /// Also evaluate sub-anchors of `natpmp_anchor`, where servers that
+    /// preserve its existing rules keep theirs.
+    natpmp_sub_anchors: bool,
+    /// Whether pf was already on before `load_rules` enabled it; if so,
+    /// cleanup must leave it on.
+    was_pf_enabled: bool,
+    /// MSS clamp set by the user, used instead of `DEFAULT_MSS`.
+    mss_override: Option<u16>,
+    /// User ruleset loaded instead of the generated one.
+    template: Option<PfTemplate>,
 }
 
 impl Firewall {
@@ -21,9 +194,51 @@ impl Firewall {
         Self {
             rules_loaded: false,
             config_path: PF_CONF_PATH.to_string(),
+            natpmp_anchor: natpmp::DEFAULT_ANCHOR_NAME.to_string(),
+            natpmp_sub_anchors: false,
+            // Until we've checked, assume it's someone else's firewall
+            was_pf_enabled: true,
+            mss_override: None,
+            template: None,
         }
     }
 
+    /// Reference a different NAT-PMP anchor from the main ruleset
+    /// (must match `NatPmpServer::with_anchor`).
+    pub fn with_natpmp_anchor(mut self, anchor: &str) -> Self {
+        self.natpmp_anchor = anchor.to_string();
+        self
+    }
+
+    /// Also reference `<natpmp anchor>/*` from the main ruleset, for a NAT-PMP
+    /// server with `preserve_existing` (see `natpmp::instance_anchor`).
+    pub fn with_natpmp_sub_anchors(mut self, enabled: bool) -> Self {
+        self.natpmp_sub_anchors = enabled;
+        self
+    }
+
+    /// Clamp TCP MSS to `mss` instead of `DEFAULT_MSS` (None = default).
+    pub fn with_mss_override(mut self, mss: Option<u16>) -> Self {
+        self.mss_override = mss;
+        self
+    }
+
+    /// Load `template` instead of the generated ruleset (None = generate).
+    pub fn with_template(mut self, template: Option<PfTemplate>) -> Self {
+        self.template = template;
+        self
+    }
+
+    /// The template loaded instead of the generated ruleset, if any.
+    pub fn template(&self) -> Option<&PfTemplate> {
+        self.template.as_ref()
+    }
+
+    /// The MSS clamp `load_rules` writes: the override if set, else `DEFAULT_MSS`.
+    pub fn effective_mss(mss_override: Option<u16>) -> u16 {
+        mss_override.unwrap_or(DEFAULT_MSS)
+    }
+
     /// Generate pf rules for NAT from LAN to VPN.
     /// Rules must be in order: options, normalization, translation, filtering
     ///
@@ -33,7 +248,80 @@ impl Firewall {
     /// Note: NAT translation happens BEFORE filter rules, so outgoing filter rules
     /// must match the post-NAT source address (the VPN interface), not the original
     /// LAN address.
-    pub fn generate_rules(vpn_if: &str, lan_if: &str, mss: u16) -> String {
+    ///
+    /// `no_nat` destinations get `no nat` rules ahead of the catch-all nat rule,
+    /// since pf uses the first matching translation rule.
+    ///
+    /// The first uplink is the primary (`$ext_if`, also used for NAT-PMP). With
+    /// more than one, each gets its own scrub/nat/pass rules, and new LAN
+    /// connections are spread across them round-robin with `route-to`; NAT alone
+    /// can't balance since the routing table picks the outgoing interface.
+    pub fn generate_rules(
+        uplinks: &[Uplink],
+        lan_if: &str,
+        mss: u16,
+        no_nat: &[Ipv4Net],
+        natpmp_anchor: &str,
+        natpmp_sub_anchors: bool,
+    ) -> String {
+        let vpn_if = uplinks
+            .first()
+            .map(|uplink| uplink.interface.as_str())
+            .unwrap_or_default();
+        let extra = uplinks.get(1..).unwrap_or_default();
+
+        let no_nat_rules: String = no_nat
+            .iter()
+            .map(|dest| format!("no nat on $ext_if inet from $int_if:network to {dest}\n"))
+            .collect();
+
+        let (sub_rdr_anchor, sub_anchor) = if natpmp_sub_anchors {
+            (
+                format!("rdr-anchor \"{natpmp_anchor}/*\"\n"),
+                format!("anchor \"{natpmp_anchor}/*\"\n"),
+            )
+        } else {
+            Default::default()
+        };
+
+        let mut direct_table = String::new();
+        let mut extra_scrub = String::new();
+        let mut extra_nat = String::new();
+        let mut balance = String::new();
+        let mut extra_pass = String::new();
+        if !extra.is_empty() {
+            let direct: Vec<String> = std::iter::once("$int_if:network".to_string())
+                .chain(no_nat.iter().map(|dest| dest.to_string()))
+                .collect();
+            direct_table = format!(
+                "table <{DIRECT_TABLE_NAME}> const {{ {} }}\n",
+                direct.join(", ")
+            );
+            for uplink in extra {
+                let name = &uplink.interface;
+                extra_scrub.push_str(&format!(
+                    "scrub out on {name} inet proto tcp from $int_if:network to any max-mss {mss}\n"
+                ));
+                for dest in no_nat {
+                    extra_nat.push_str(&format!(
+                        "no nat on {name} inet from $int_if:network to {dest}\n"
+                    ));
+                }
+                extra_nat.push_str(&format!(
+                    "nat on {name} inet from $int_if:network to any -> ({name}) static-port\n"
+                ));
+                extra_pass.push_str(&format!(
+                    "pass out quick on {name} inet from ({name}) to any keep state\n"
+                ));
+            }
+            let hosts: Vec<String> = uplinks.iter().map(Uplink::route_host).collect();
+            balance = format!(
+                "# Spread new LAN connections across the VPN uplinks\n\
+                 pass in quick on $int_if route-to {{ {} }} round-robin inet from $int_if:network to ! <{DIRECT_TABLE_NAME}> keep state\n",
+                hosts.join(", ")
+            );
+        }
+
         format!(
             r#"# VPN Sharing pf rules - generated by tunshare
 # VPN interface: {vpn_if}
@@ -44,25 +332,152 @@ set skip on lo0
 
 ext_if = "{vpn_if}"
 int_if = "{lan_if}"
-
+{direct_table}
 # 2. Normalization (must come before translation)
 scrub in all no-df
 scrub out on $ext_if inet proto tcp from $int_if:network to any max-mss {mss}
-
+{extra_scrub}
 # 3. Translation - NAT LAN traffic through VPN
-nat on $ext_if inet from $int_if:network to any -> ($ext_if) static-port
-rdr-anchor "natpmp"
-
+{no_nat_rules}nat on $ext_if inet from $int_if:network to any -> ($ext_if) static-port
+{extra_nat}rdr-anchor "{natpmp_anchor}"
+{sub_rdr_anchor}
 # 4. Filtering
-# Allow all traffic on LAN interface (including DHCP from 0.0.0.0)
+# Per-client labeled rules for bandwidth accounting (must precede the catch-all)
+anchor "{clients_anchor}"
+{balance}# Allow all traffic on LAN interface (including DHCP from 0.0.0.0)
 pass quick on $int_if all keep state
 # Allow NAT'd traffic out (post-NAT, source is VPN interface address)
 pass out quick on $ext_if inet from ($ext_if) to any keep state
-anchor "natpmp"
-"#
+{extra_pass}anchor "{natpmp_anchor}"
+{sub_anchor}"#,
+            clients_anchor = CLIENTS_ANCHOR_NAME,
         )
     }
 
+    /// Generate the labeled per-client pass rules for the accounting anchor.
+    ///
+    /// Each client gets its own `quick` rule so pf attributes all of that
+    /// client's state traffic (both directions) to the rule's label.
+    pub fn generate_client_rules(lan_if: &str, clients: &[Ipv4Addr]) -> String {
+        let mut rules = String::new();
+        for client in clients {
+            rules.push_str(&format!(
+                "pass in quick on {lan_if} inet from {client} to any keep state label \"{CLIENT_LABEL_PREFIX}{client}\"\n"
+            ));
+        }
+        rules
+    }
+
+    /// Replace the accounting anchor's rules with one labeled rule per client.
+    pub async fn load_client_rules(lan_if: &str, clients: &[Ipv4Addr]) -> Result<()> {
+        let rules = Self::generate_client_rules(lan_if, clients);
+
+        let mut child = Command::new("pfctl")
+            .args(["-a", CLIENTS_ANCHOR_NAME, "-f", "-"])
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .map_err(|e| TunshareError::CommandFailed {
+                command: format!("pfctl -a {} -f -", CLIENTS_ANCHOR_NAME),
+                message: e.to_string(),
+            })?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(rules.as_bytes()).await;
+            let _ = stdin.shutdown().await;
+        }
+
+        let output = child
+            .wait_with_output()
+            .await
+            .map_err(|e| TunshareError::CommandFailed {
+                command: format!("pfctl -a {} -f -", CLIENTS_ANCHOR_NAME),
+                message: e.to_string(),
+            })?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(pfctl_failure("Failed to load client rules", &stderr));
+        }
+
+        Ok(())
+    }
+
+    /// Read per-client byte counters from the accounting anchor's labels.
+    ///
+    /// Returns `(client, bytes_in, bytes_out)` where "in" is traffic the client
+    /// sent (upload) and "out" is traffic sent back to it (download).
+    /// An empty list means no clients have been labeled yet.
+    pub async fn label_counters() -> Result<Vec<(String, u64, u64)>> {
+        let output = Command::new("pfctl")
+            .args(["-a", CLIENTS_ANCHOR_NAME, "-sl"])
+            .output()
+            .await
+            .map_err(|e| TunshareError::CommandFailed {
+                command: format!("pfctl -a {} -sl", CLIENTS_ANCHOR_NAME),
+                message: e.to_string(),
+            })?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(pfctl_failure("Failed to read client counters", &stderr));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Ok(parse_label_counters(&stdout))
+    }
+
+    /// Read the forwarded `(packets, bytes)` counted by our NAT rules.
+    ///
+    /// Only LAN traffic matches them, so unlike interface counters this
+    /// leaves out the Mac's own traffic through the VPN.
+    pub async fn nat_counters() -> Result<(u64, u64)> {
+        let output = Command::new("pfctl")
+            .args(["-v", "-s", "nat"])
+            .output()
+            .await
+            .map_err(|e| TunshareError::CommandFailed {
+                command: "pfctl -v -s nat".into(),
+                message: e.to_string(),
+            })?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Ok(parse_nat_counters(&stdout))
+    }
+
+    /// Parse `pfctl -ss` output into state records, skipping lines that aren't states.
+    ///
+    /// Lines look like `all tcp 10.8.0.6:62000 (192.168.2.50:62000) -> 1.1.1.1:443 ESTABLISHED:ESTABLISHED`,
+    /// where the parenthesized address is the pre-NAT one. `<-` marks states
+    /// created by inbound traffic, with the initiator on the right.
+    pub fn parse_states(states: &str) -> Vec<PfState> {
+        states.lines().filter_map(parse_state_line).collect()
+    }
+
+    /// Extract LAN client addresses (inside `lan_subnet`, other than the
+    /// gateway) from `pfctl -ss` output.
+    pub fn parse_state_clients(
+        states: &str,
+        lan_ip: Ipv4Addr,
+        lan_subnet: Ipv4Net,
+    ) -> Vec<Ipv4Addr> {
+        let mut clients: Vec<Ipv4Addr> = states
+            .split_whitespace()
+            .filter_map(|token| {
+                // Tokens look like "192.168.2.50:54321" or "(192.168.2.50:54321)"
+                let token = token.trim_matches(|c| c == '(' || c == ')');
+                let addr = token.split(':').next()?;
+                addr.parse::<Ipv4Addr>().ok()
+            })
+            .filter(|ip| lan_subnet.contains(ip) && *ip != lan_ip)
+            .collect();
+
+        clients.sort();
+        clients.dedup();
+        clients
+    }
+
     /// Validate a pf configuration file.
     pub async fn validate_rules(config_path: &str) -> Result<()> {
         let output = Command::new("pfctl")
@@ -80,38 +495,110 @@ anchor "natpmp"
         // Only treat as error if exit status is non-zero AND stderr contains actual errors
         if !output.status.success() {
             // Check for actual error indicators (not just warnings)
+            let error = pfctl_failure("Rule validation failed", &stderr);
             let has_error = stderr.contains("syntax error")
                 || stderr.contains("unknown")
                 || stderr.contains("invalid")
                 || stderr.contains("no valid")
-                || (stderr.contains("error") && !stderr.contains("0 errors"));
+                || (stderr.contains("error") && !stderr.contains("0 errors"))
+                || !matches!(error, TunshareError::FirewallError(_));
 
             if has_error {
-                return Err(TunshareError::FirewallError(format!(
-                    "Rule validation failed: {}",
-                    stderr.trim()
-                )));
+                return Err(error);
             }
         }
 
         Ok(())
     }
 
-    /// Load pf rules from the generated config.
-    pub async fn load_rules(&mut self, vpn_if: &str, lan_if: &str) -> Result<()> {
-        // Generate rules with default MSS (1400 is safe for most VPNs)
-        let rules = Self::generate_rules(vpn_if, lan_if, 1400);
+    /// The ruleset for this firewall's settings (MSS clamp, NAT-PMP anchor),
+    /// or the user's template filled in.
+    fn ruleset(&self, uplinks: &[Uplink], lan_if: &str, no_nat: &[Ipv4Net]) -> Result<String> {
+        if let Some(template) = &self.template {
+            let vpn_if = uplinks
+                .first()
+                .map(|uplink| uplink.interface.as_str())
+                .unwrap_or_default();
+            return template.render(vpn_if, lan_if);
+        }
+        let mss = Self::effective_mss(self.mss_override);
+        Ok(Self::generate_rules(
+            uplinks,
+            lan_if,
+            mss,
+            no_nat,
+            &self.natpmp_anchor,
+            self.natpmp_sub_anchors,
+        ))
+    }
+
+    /// Write `rules` to the config file and check them with `pfctl -n`.
+    async fn write_validated(&self, rules: &str) -> Result<()> {
+        fs::write(&self.config_path, rules).map_err(TunshareError::Io)?;
+        Self::validate_rules(&self.config_path).await
+    }
 
-        // Write to temp file
-        fs::write(&self.config_path, &rules).map_err(TunshareError::Io)?;
+    /// Load pf rules from the generated config. The first uplink is the primary.
+    pub async fn load_rules(
+        &mut self,
+        uplinks: &[Uplink],
+        lan_if: &str,
+        no_nat: &[Ipv4Net],
+    ) -> Result<()> {
+        let rules = self.ruleset(uplinks, lan_if, no_nat)?;
+        self.write_validated(&rules).await?;
 
-        // Validate first
-        Self::validate_rules(&self.config_path).await?;
+        // Remember whether pf was on so cleanup doesn't turn off someone
+        // else's firewall; if we can't tell, assume it was
+        self.was_pf_enabled = Self::is_enabled().await.unwrap_or(true);
 
         // Enable pf if not already enabled
         let _ = Command::new("pfctl").args(["-e"]).output().await;
 
-        // Load the rules
+        self.load_config_file().await?;
+        self.rules_loaded = true;
+        Ok(())
+    }
+
+    /// Regenerate and load the ruleset with new firewall-only settings (or a
+    /// re-read `template`) while sharing stays up.
+    ///
+    /// `pfctl -f` swaps the main ruleset in one transaction, so a rejected
+    /// ruleset leaves the old one in place. Anchors loaded separately (client
+    /// accounting, NAT-PMP mappings) keep their rules: the new ruleset refers
+    /// to them by the same names.
+    pub async fn reload_rules(
+        &mut self,
+        uplinks: &[Uplink],
+        lan_if: &str,
+        no_nat: &[Ipv4Net],
+        mss_override: Option<u16>,
+        template: Option<PfTemplate>,
+    ) -> Result<()> {
+        if !self.rules_loaded {
+            return Err(TunshareError::FirewallError(
+                "no rules are loaded to reload".into(),
+            ));
+        }
+
+        let previous = std::mem::replace(&mut self.mss_override, mss_override);
+        let previous_template = std::mem::replace(&mut self.template, template);
+        let result = match self.ruleset(uplinks, lan_if, no_nat) {
+            Ok(rules) => match self.write_validated(&rules).await {
+                Ok(()) => self.load_config_file().await,
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+        if result.is_err() {
+            self.mss_override = previous;
+            self.template = previous_template;
+        }
+        result
+    }
+
+    /// `pfctl -f` the written config file.
+    async fn load_config_file(&self) -> Result<()> {
         let output = Command::new("pfctl")
             .args(["-f", &self.config_path])
             .output()
@@ -131,47 +618,47 @@ anchor "natpmp"
                 || stderr.contains("rules loaded")
                 || stderr.contains("pf enabled");
 
+            let error = pfctl_failure("Failed to load rules", &stderr);
             let stderr_lower = stderr.to_lowercase();
             let has_real_error = stderr_lower.contains("syntax error")
                 || stderr_lower.contains("rules not loaded")
                 || stderr.contains("unknown")
                 || stderr.contains("invalid")
-                || stderr.contains("no valid");
+                || stderr.contains("no valid")
+                || !matches!(error, TunshareError::FirewallError(_));
 
             if has_real_error && !is_just_warning {
-                return Err(TunshareError::FirewallError(format!(
-                    "Failed to load rules: {}",
-                    stderr.trim()
-                )));
+                return Err(error);
             }
         }
 
-        self.rules_loaded = true;
         Ok(())
     }
 
     /// Stop sharing and restore default pf rules (async wrapper).
     /// Delegates to `cleanup_sync` via `spawn_blocking`.
-    pub async fn cleanup(&mut self) -> Result<()> {
+    ///
+    /// Failed steps are listed in the report; `Err` means cleanup couldn't run at all.
+    pub async fn cleanup(&mut self) -> Result<CleanupReport> {
         let config_path = self.config_path.clone();
-        tokio::task::spawn_blocking(move || cleanup_sync_impl(&config_path))
-            .await
-            .map_err(|e| TunshareError::CommandFailed {
-                command: "cleanup (spawn_blocking)".into(),
-                message: e.to_string(),
-            })??;
+        let was_pf_enabled = self.was_pf_enabled;
+        let report =
+            tokio::task::spawn_blocking(move || cleanup_sync_impl(&config_path, was_pf_enabled))
+                .await
+                .map_err(|e| TunshareError::CommandFailed {
+                    command: "cleanup (spawn_blocking)".into(),
+                    message: e.to_string(),
+                })?;
 
         self.rules_loaded = false;
-        Ok(())
+        Ok(report)
     }
 
     /// Get current pf rules (for debugging).
     /// Returns both NAT rules (-sn) and filter rules (-sr).
-    pub async fn get_current_rules() -> Result<String> {
+    pub async fn get_current_rules(retry: RetryPolicy) -> Result<String> {
         // Get NAT rules
-        let nat_output = Command::new("pfctl")
-            .args(["-sn"])
-            .output()
+        let nat_output = exec::output_with_retry(retry, "pfctl", &["-sn"])
             .await
             .map_err(|e| TunshareError::CommandFailed {
                 command: "pfctl -sn".into(),
@@ -179,9 +666,7 @@ anchor "natpmp"
             })?;
 
         // Get filter rules
-        let filter_output = Command::new("pfctl")
-            .args(["-sr"])
-            .output()
+        let filter_output = exec::output_with_retry(retry, "pfctl", &["-sr"])
             .await
             .map_err(|e| TunshareError::CommandFailed {
                 command: "pfctl -sr".into(),
@@ -206,10 +691,8 @@ anchor "natpmp"
     }
 
     /// Get current pf states (for debugging).
-    pub async fn get_current_states() -> Result<String> {
-        let output = Command::new("pfctl")
-            .args(["-ss"])
-            .output()
+    pub async fn get_current_states(retry: RetryPolicy) -> Result<String> {
+        let output = exec::output_with_retry(retry, "pfctl", &["-ss"])
             .await
             .map_err(|e| TunshareError::CommandFailed {
                 command: "pfctl -ss".into(),
@@ -234,6 +717,78 @@ anchor "natpmp"
         Ok(stdout.contains("Status: Enabled"))
     }
 
+    /// Flush every pf anchor tunshare may have left loaded: the known names,
+    /// `extra` (e.g. a configured NAT-PMP anchor), anything else named
+    /// `tunshare*`, and the legacy `natpmp` anchor if it only holds mappings
+    /// an older release wrote. If the main ruleset is still ours (a crashed
+    /// run), the default ruleset is reloaded as well.
+    ///
+    /// Returns the anchors flushed, plus `pf.conf` if the main ruleset was reloaded.
+    pub async fn flush_all_tunshare_anchors(
+        extra: &[&str],
+        retry: RetryPolicy,
+    ) -> Result<Vec<String>> {
+        // -v lists nested anchors too, like NAT-PMP's per-process ones
+        let output = Command::new("pfctl")
+            .args(["-vsA"])
+            .output()
+            .await
+            .map_err(|e| TunshareError::CommandFailed {
+                command: "pfctl -vsA".into(),
+                message: e.to_string(),
+            })?;
+        let listing = String::from_utf8_lossy(&output.stdout);
+        let mut anchors = leftover_anchors(&listing, extra);
+        anchors.retain(|anchor| !owned_by_other_process(anchor));
+        if listing
+            .lines()
+            .any(|name| name.trim() == LEGACY_NATPMP_ANCHOR)
+            && !anchors.iter().any(|name| name == LEGACY_NATPMP_ANCHOR)
+        {
+            let mut rules = String::new();
+            for show in ["-sn", "-sr"] {
+                if let Ok(output) = Command::new("pfctl")
+                    .args(["-a", LEGACY_NATPMP_ANCHOR, show])
+                    .output()
+                    .await
+                {
+                    rules.push_str(&String::from_utf8_lossy(&output.stdout));
+                }
+            }
+            if is_legacy_natpmp_rules(&rules) {
+                anchors.push(LEGACY_NATPMP_ANCHOR.to_string());
+            }
+        }
+
+        let mut cleaned = Vec::new();
+        for anchor in anchors {
+            let flushed = Command::new("pfctl")
+                .args(["-a", &anchor, "-F", "all"])
+                .output()
+                .await
+                .is_ok_and(|output| output.status.success());
+            if flushed {
+                cleaned.push(anchor);
+            }
+        }
+
+        // A normal stop replaces our main ruleset with pf.conf, so finding it
+        // means a run didn't stop cleanly
+        if is_tunshare_ruleset(&Self::get_current_rules(retry).await?) {
+            let report = tokio::task::spawn_blocking(|| cleanup_sync_impl(PF_CONF_PATH, true))
+                .await
+                .map_err(|e| TunshareError::CommandFailed {
+                    command: "flush_all_tunshare_anchors (spawn_blocking)".into(),
+                    message: e.to_string(),
+                })?;
+            if report.nat_removed {
+                cleaned.push(DEFAULT_PF_CONF.to_string());
+            }
+        }
+
+        Ok(cleaned)
+    }
+
     #[allow(dead_code)]
     pub fn is_loaded(&self) -> bool {
         self.rules_loaded
@@ -241,7 +796,7 @@ anchor "natpmp"
 
     /// Synchronous cleanup for use in Drop and async wrapper.
     pub fn cleanup_sync(&mut self) {
-        let _ = cleanup_sync_impl(&self.config_path);
+        cleanup_sync_impl(&self.config_path, self.was_pf_enabled);
         self.rules_loaded = false;
     }
 }
@@ -261,40 +816,854 @@ impl Drop for Firewall {
     }
 }
 
+/// Whether `rules` (from [`Firewall::get_current_rules`]) are tunshare's main
+/// ruleset, which references the accounting anchor.
+pub fn is_tunshare_ruleset(rules: &str) -> bool {
+    rules.contains(&format!("anchor \"{}\"", CLIENTS_ANCHOR_NAME))
+}
+
+/// Anchors in `pfctl -vsA` output that belong to tunshare, nested ones
+/// (`tunshare-natpmp/1234`) included.
+fn leftover_anchors(listing: &str, extra: &[&str]) -> Vec<String> {
+    listing
+        .lines()
+        .map(str::trim)
+        .filter(|name| {
+            let root = name.split('/').next().unwrap_or_default();
+            KNOWN_ANCHORS.contains(&root) || extra.contains(&root) || root.starts_with("tunshare")
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+/// Whether `anchor` is a per-process sub-anchor (`<anchor>/<pid>`) of
+/// another tunshare that is still running.
+fn owned_by_other_process(anchor: &str) -> bool {
+    let Some(pid) = anchor
+        .rsplit_once('/')
+        .and_then(|(_, pid)| pid.parse::<u32>().ok())
+    else {
+        return false;
+    };
+    process::is_other_instance(pid)
+}
+
+/// Whether `rules` (the legacy `natpmp` anchor, as pfctl shows it) are only
+/// the unlabeled redirect/pass pairs older tunshare releases loaded there.
+/// miniupnpd and friends label their rules, so those never match.
+fn is_legacy_natpmp_rules(rules: &str) -> bool {
+    let lines: Vec<&str> = rules
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    !lines.is_empty()
+        && lines.iter().all(|line| {
+            (line.starts_with("rdr pass on ") || line.starts_with("pass in quick on "))
+                && line.contains(" from any to ")
+                && !line.contains("label")
+        })
+}
+
+/// What [`Firewall::cleanup`] managed to undo.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CleanupReport {
+    /// Our NAT rules are gone (default ruleset reloaded, or pf disabled).
+    pub nat_removed: bool,
+    /// The client accounting anchor was flushed.
+    pub anchor_flushed: bool,
+    /// pf was turned off (only when it was off before we started).
+    pub pf_disabled: bool,
+    /// One entry per failed step.
+    pub errors: Vec<String>,
+}
+
+impl CleanupReport {
+    /// Whether every step succeeded.
+    pub fn is_clean(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Outcome of each cleanup step, summarized by [`CleanupSteps::report`].
+#[derive(Debug)]
+struct CleanupSteps {
+    /// `pfctl -f /etc/pf.conf`, if there is a default ruleset.
+    restore_rules: Option<std::result::Result<(), String>>,
+    /// `pfctl -d`, if it was attempted.
+    disable_pf: Option<std::result::Result<(), String>>,
+    flush_anchor: std::result::Result<(), String>,
+    remove_config: std::result::Result<(), String>,
+}
+
+impl CleanupSteps {
+    fn report(self) -> CleanupReport {
+        let ok = |step: &Option<std::result::Result<(), String>>| matches!(step, Some(Ok(())));
+        let pf_disabled = ok(&self.disable_pf);
+
+        let mut report = CleanupReport {
+            nat_removed: ok(&self.restore_rules) || pf_disabled,
+            anchor_flushed: self.flush_anchor.is_ok(),
+            pf_disabled,
+            errors: Vec::new(),
+        };
+
+        let failures = [
+            ("restore default rules", self.restore_rules),
+            ("disable pf", self.disable_pf),
+            ("flush client anchor", Some(self.flush_anchor)),
+            ("remove config file", Some(self.remove_config)),
+        ];
+        for (step, outcome) in failures {
+            if let Some(Err(e)) = outcome {
+                report.errors.push(format!("Failed to {}: {}", step, e));
+            }
+        }
+        report
+    }
+}
+
+/// Which pf teardown steps cleanup should run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CleanupPlan {
+    /// Reload `/etc/pf.conf` to replace our rules.
+    restore_rules: bool,
+    /// Turn pf off again.
+    disable_pf: bool,
+}
+
+impl CleanupPlan {
+    /// Only disable pf if we were the ones who enabled it; otherwise put the
+    /// default ruleset back and leave it running.
+    fn new(was_pf_enabled: bool, has_default_conf: bool) -> Self {
+        Self {
+            restore_rules: has_default_conf,
+            disable_pf: !was_pf_enabled,
+        }
+    }
+}
+
 /// Standalone sync cleanup logic. Single source of truth for both
 /// `cleanup_sync()` and `cleanup()` (via `spawn_blocking`).
-fn cleanup_sync_impl(config_path: &str) -> Result<()> {
+fn cleanup_sync_impl(config_path: &str, was_pf_enabled: bool) -> CleanupReport {
     use std::process::Command as SyncCommand;
 
-    let mut errors = Vec::new();
+    let run = |args: &[&str]| -> std::result::Result<std::process::Output, String> {
+        SyncCommand::new("pfctl")
+            .args(args)
+            .output()
+            .map_err(|e| e.to_string())
+    };
+
+    let plan = CleanupPlan::new(was_pf_enabled, Path::new(DEFAULT_PF_CONF).exists());
 
     // 1. Restore default pf rules (don't flush states - that kills VPN)
-    if Path::new(DEFAULT_PF_CONF).exists() {
-        let output = SyncCommand::new("pfctl")
-            .args(["-f", DEFAULT_PF_CONF])
-            .output();
-        if let Ok(output) = output {
-            if !output.status.success() {
-                let stderr = String::from_utf8_lossy(&output.stderr);
-                if !stderr.contains("rules loaded") && stderr.contains("error") {
-                    errors.push(format!("Failed to restore default rules: {}", stderr));
-                }
+    let restore_rules = if plan.restore_rules {
+        let restored = run(&["-f", DEFAULT_PF_CONF]).and_then(|output| {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            // pfctl reports warnings on stderr even when the load succeeds
+            if !output.status.success()
+                && !stderr.contains("rules loaded")
+                && stderr.contains("error")
+            {
+                Err(stderr.trim().to_string())
+            } else {
+                Ok(())
             }
+        });
+        Some(restored)
+    } else if !plan.disable_pf {
+        // pf stays on and there's nothing to reload over our rules
+        Some(Err(format!("{} not found", DEFAULT_PF_CONF)))
+    } else {
+        None
+    };
+
+    // 2. Turn pf back off if it was off before we started
+    let disable_pf = plan.disable_pf.then(|| {
+        run(&["-d"]).and_then(|output| {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            // Already off is fine
+            if output.status.success() || stderr.contains("not enabled") {
+                Ok(())
+            } else {
+                Err(stderr.trim().to_string())
+            }
+        })
+    });
+
+    // 3. Flush the client accounting anchor (restoring pf.conf drops the
+    //    reference to it, but the anchor's rules stay loaded in the kernel)
+    let flush_anchor = run(&["-a", CLIENTS_ANCHOR_NAME, "-F", "all"]).and_then(|output| {
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
         }
+    });
+
+    // 4. Remove our config file
+    let remove_config = if Path::new(config_path).exists() {
+        fs::remove_file(config_path).map_err(|e| e.to_string())
     } else {
-        let _ = SyncCommand::new("pfctl").args(["-d"]).output();
+        Ok(())
+    };
+
+    CleanupSteps {
+        restore_rules,
+        disable_pf,
+        flush_anchor,
+        remove_config,
     }
+    .report()
+}
+
+/// Parse `pfctl -sl` output into `(client, bytes_in, bytes_out)`, sorted by
+/// total traffic (highest first).
+///
+/// Each line is: `label evaluations packets bytes in_packets in_bytes out_packets out_bytes [states]`.
+fn parse_label_counters(output: &str) -> Vec<(String, u64, u64)> {
+    let mut counters: Vec<(String, u64, u64)> = output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 8 {
+                return None;
+            }
+            let client = fields[0].strip_prefix(CLIENT_LABEL_PREFIX)?;
+            let bytes_in = fields[5].parse::<u64>().ok()?;
+            let bytes_out = fields[7].parse::<u64>().ok()?;
+            Some((client.to_string(), bytes_in, bytes_out))
+        })
+        .collect();
+
+    counters.sort_by_key(|c| std::cmp::Reverse(c.1 + c.2));
+    counters
+}
 
-    // 2. Remove our config file
-    if Path::new(config_path).exists() {
-        if let Err(e) = fs::remove_file(config_path) {
-            errors.push(format!("Failed to remove config file: {}", e));
+/// Sum the `(packets, bytes)` of the `nat` rules in `pfctl -v -s nat` output.
+///
+/// Each rule is followed by indented counter lines such as
+/// `[ Evaluations: 120  Packets: 900  Bytes: 5000  States: 4 ]`. `no nat`
+/// and `rdr` rules don't forward through the VPN, so they're skipped.
+fn parse_nat_counters(output: &str) -> (u64, u64) {
+    let counter = |line: &str, name: &str| -> Option<u64> {
+        let mut fields = line.split_whitespace();
+        fields.find(|field| *field == name)?;
+        fields.next()?.trim_end_matches(']').parse().ok()
+    };
+
+    let mut totals = (0, 0);
+    let mut in_nat_rule = false;
+    for line in output.lines() {
+        let trimmed = line.trim_start();
+        if !trimmed.starts_with('[') {
+            in_nat_rule = trimmed.starts_with("nat ");
+            continue;
+        }
+        if in_nat_rule {
+            totals.0 += counter(trimmed, "Packets:").unwrap_or(0);
+            totals.1 += counter(trimmed, "Bytes:").unwrap_or(0);
         }
     }
+    totals
+}
 
-    if errors.is_empty() {
-        Ok(())
-    } else {
-        Err(TunshareError::FirewallError(errors.join("; ")))
+/// Parse a single `pfctl -ss` line (see [`Firewall::parse_states`]).
+fn parse_state_line(line: &str) -> Option<PfState> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    if tokens.len() < 6 {
+        return None;
+    }
+
+    let (interface, protocol, state) = (tokens[0], tokens[1], tokens[tokens.len() - 1]);
+    let endpoints = &tokens[2..tokens.len() - 1];
+
+    // Group into `addr [(addr)]` endpoints separated by arrows
+    let mut groups: Vec<(&str, Option<&str>)> = Vec::new();
+    let mut inbound = None;
+    for token in endpoints {
+        match *token {
+            "->" | "<-" => {
+                inbound.get_or_insert(*token == "<-");
+            }
+            t if t.starts_with('(') => {
+                let inner = t.trim_start_matches('(').trim_end_matches(')');
+                groups.last_mut()?.1 = Some(inner);
+            }
+            t => groups.push((t, None)),
+        }
+    }
+    if inbound? {
+        groups.reverse();
+    }
+    if groups.len() < 2 {
+        return None;
+    }
+
+    let (first, last) = (groups[0], groups[groups.len() - 1]);
+    let nat = match (first.1, last.1) {
+        (Some(_), _) => Some(first.0),
+        (None, Some(translated)) => Some(translated),
+        // Older pf lists the gateway as a middle hop: `lan -> gwy -> ext`
+        (None, None) if groups.len() > 2 => Some(groups[1].0),
+        (None, None) => None,
+    };
+
+    Some(PfState {
+        interface: interface.to_string(),
+        protocol: protocol.to_string(),
+        source: first.1.unwrap_or(first.0).to_string(),
+        destination: last.0.to_string(),
+        nat: nat.map(str::to_string),
+        state: state.to_string(),
+    })
+}
+
+/// Turn pfctl's stderr into a typed error for the failures it has a
+/// recognizable message for, or a plain `FirewallError` carrying the output.
+///
+/// ```text
+/// /etc/pf.anchors/tunshare:12: syntax error
+/// no IP address found for utun4
+/// pfctl: /dev/pf: Operation not permitted
+/// ```
+pub fn classify_pfctl_error(stderr: &str) -> TunshareError {
+    for line in stderr.lines().map(str::trim) {
+        if let Some(rest) = line.strip_suffix("syntax error") {
+            // "<file>:<line>: syntax error"
+            let number = rest
+                .trim_end()
+                .trim_end_matches(':')
+                .rsplit(':')
+                .next()
+                .and_then(|n| n.trim().parse().ok());
+            return TunshareError::PfSyntaxError {
+                line: number,
+                stderr: stderr.trim().to_string(),
+            };
+        }
+        if let Some(iface) = line.strip_prefix("no IP address found for ") {
+            return TunshareError::PfInterfaceMissing {
+                iface: iface.trim().to_string(),
+                stderr: stderr.trim().to_string(),
+            };
+        }
+        if line.contains("/dev/pf")
+            && (line.contains("Operation not permitted") || line.contains("Permission denied"))
+        {
+            return TunshareError::PfPermission {
+                stderr: stderr.trim().to_string(),
+            };
+        }
+    }
+    TunshareError::FirewallError(stderr.trim().to_string())
+}
+
+/// `classify_pfctl_error`, with `context` in front of unrecognized output.
+fn pfctl_failure(context: &str, stderr: &str) -> TunshareError {
+    match classify_pfctl_error(stderr) {
+        TunshareError::FirewallError(output) => {
+            TunshareError::FirewallError(format!("{}: {}", context, output))
+        }
+        error => error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_client_rules() {
+        let clients = [
+            Ipv4Addr::new(192, 168, 2, 50),
+            Ipv4Addr::new(192, 168, 2, 51),
+        ];
+        let rules = Firewall::generate_client_rules("en0", &clients);
+
+        assert_eq!(rules.lines().count(), 2);
+        assert!(rules.contains(
+            "pass in quick on en0 inet from 192.168.2.50 to any keep state label \"client-192.168.2.50\""
+        ));
+        assert!(rules.contains("label \"client-192.168.2.51\""));
+
+        assert!(Firewall::generate_client_rules("en0", &[]).is_empty());
+    }
+
+    #[test]
+    fn test_leftover_anchors() {
+        let listing = "  com.apple\n  com.apple/250.ApplicationFirewall\n  tunshare_clients\n  tunshare-natpmp\n  tunshare-natpmp/4242\n  my-natpmp\n  my-natpmp/4243\n  natpmp\n";
+        assert_eq!(
+            leftover_anchors(listing, &["my-natpmp"]),
+            [
+                "tunshare_clients",
+                "tunshare-natpmp",
+                "tunshare-natpmp/4242",
+                "my-natpmp",
+                "my-natpmp/4243"
+            ]
+        );
+        assert!(!owned_by_other_process("tunshare-natpmp"));
+        assert!(!owned_by_other_process(&format!(
+            "tunshare-natpmp/{}",
+            std::process::id()
+        )));
+        assert!(!owned_by_other_process(&format!(
+            "tunshare-natpmp/{}",
+            u32::MAX
+        )));
+        assert!(leftover_anchors("  com.apple\n", &[]).is_empty());
+
+        // The legacy anchor is only ours if it holds what old releases wrote
+        assert!(is_legacy_natpmp_rules(
+            "rdr pass on utun4 inet proto udp from any to any port = 51413 -> 192.168.2.100 port 51413\n\
+             pass in quick on utun4 inet proto udp from any to 192.168.2.100 port = 51413 keep state\n"
+        ));
+        assert!(!is_legacy_natpmp_rules(
+            "rdr pass on utun4 inet proto tcp from any to any port = 8080 label \"miniupnpd\" -> 192.168.2.100 port 80\n"
+        ));
+        assert!(!is_legacy_natpmp_rules("block drop in quick on en0 all\n"));
+        assert!(!is_legacy_natpmp_rules(""));
+    }
+
+    #[test]
+    fn test_render_template() {
+        let template = PfTemplate {
+            text: "ext_if = \"{vpn}\"\n\
+                   nat on $ext_if from {lan_subnet} to any -> ($ext_if)\n\
+                   pass in on {lan} proto { tcp, udp } to { {dns} } port 53\n"
+                .to_string(),
+            lan_subnet: Some("192.168.2.0/24".parse().unwrap()),
+            dns: vec!["1.1.1.1".to_string(), "9.9.9.9".to_string()],
+        };
+        assert_eq!(
+            template.render("utun4", "en0").unwrap(),
+            "ext_if = \"utun4\"\n\
+             nat on $ext_if from 192.168.2.0/24 to any -> ($ext_if)\n\
+             pass in on en0 proto { tcp, udp } to { 1.1.1.1, 9.9.9.9 } port 53\n"
+        );
+
+        // Without a known subnet, pf works it out from the interface
+        let template = PfTemplate {
+            text: "from {lan_subnet}".to_string(),
+            lan_subnet: None,
+            dns: Vec::new(),
+        };
+        assert_eq!(template.render("utun4", "en0").unwrap(), "from en0:network");
+
+        // Unknown placeholders are listed once each, in order
+        let values = [("vpn", "utun4".to_string())];
+        assert_eq!(
+            render_template("{vpn} {lan_if} {wan} {lan_if}", &values),
+            Err(vec!["lan_if".to_string(), "wan".to_string()])
+        );
+        let template = PfTemplate {
+            text: "nat on {vpn_if}".to_string(),
+            lan_subnet: None,
+            dns: Vec::new(),
+        };
+        let error = template.render("utun4", "en0").unwrap_err().to_string();
+        assert!(error.contains("{vpn_if}"), "{}", error);
+
+        // Unterminated braces are left alone
+        assert_eq!(render_template("{vpn", &values).unwrap(), "{vpn");
+    }
+
+    fn single(name: &str) -> Vec<Uplink> {
+        vec![Uplink {
+            interface: name.to_string(),
+            gateway: None,
+        }]
+    }
+
+    #[test]
+    fn test_generate_rules_round_robin_uplinks() {
+        let uplinks = vec![
+            Uplink {
+                interface: "utun4".to_string(),
+                gateway: Some(Ipv4Addr::new(10, 8, 0, 5)),
+            },
+            Uplink {
+                interface: "utun5".to_string(),
+                gateway: None,
+            },
+        ];
+        let no_nat: Vec<Ipv4Net> = vec!["10.0.50.0/24".parse().unwrap()];
+        let rules =
+            Firewall::generate_rules(&uplinks, "en0", 1400, &no_nat, "tunshare-natpmp", false);
+
+        assert!(rules.contains("table <tunshare_direct> const { $int_if:network, 10.0.50.0/24 }"));
+        assert!(rules.contains(
+            "pass in quick on $int_if route-to { (utun4 10.8.0.5), utun5 } round-robin \
+             inet from $int_if:network to ! <tunshare_direct> keep state"
+        ));
+        // Each uplink translates and passes its own traffic
+        assert!(rules.contains("nat on $ext_if inet from $int_if:network to any -> ($ext_if)"));
+        assert!(rules.contains("nat on utun5 inet from $int_if:network to any -> (utun5)"));
+        assert!(rules.contains("no nat on utun5 inet from $int_if:network to 10.0.50.0/24"));
+        assert!(rules.contains("scrub out on utun5 inet proto tcp"));
+        assert!(rules.contains("pass out quick on utun5 inet from (utun5) to any keep state"));
+        // Balancing must win over the catch-all LAN rule
+        let balance = rules.find("route-to").unwrap();
+        let catch_all = rules.find("pass quick on $int_if all").unwrap();
+        assert!(balance < catch_all);
+
+        // A single uplink doesn't balance
+        let rules = Firewall::generate_rules(&single("utun4"), "en0", 1400, &no_nat, "x", false);
+        assert!(!rules.contains("route-to"));
+        assert!(!rules.contains("tunshare_direct"));
+    }
+
+    #[test]
+    fn test_generate_rules_references_clients_anchor() {
+        let rules =
+            Firewall::generate_rules(&single("utun4"), "en0", 1400, &[], "tunshare-natpmp", false);
+        let anchor = rules.find("anchor \"tunshare_clients\"").unwrap();
+        let catch_all = rules.find("pass quick on $int_if all").unwrap();
+        assert!(anchor < catch_all);
+    }
+
+    #[test]
+    fn test_generate_rules_references_natpmp_anchor() {
+        let rules =
+            Firewall::generate_rules(&single("utun4"), "en0", 1400, &[], "custom-anchor", false);
+        assert!(rules.contains("rdr-anchor \"custom-anchor\""));
+        assert!(rules.contains("\nanchor \"custom-anchor\""));
+        assert!(!rules.contains("\"natpmp\""));
+        assert!(!rules.contains("/*"));
+
+        // Servers preserving the anchor's rules load theirs into sub-anchors
+        let rules = Firewall::generate_rules(&single("utun4"), "en0", 1400, &[], "shared", true);
+        assert!(rules.contains("rdr-anchor \"shared\"\nrdr-anchor \"shared/*\"\n"));
+        assert!(rules.contains("\nanchor \"shared\"\nanchor \"shared/*\"\n"));
+    }
+
+    #[test]
+    fn test_reload_keeps_anchor_references() {
+        let mut firewall = Firewall::new().with_natpmp_anchor("custom-natpmp");
+        let before = firewall.ruleset(&single("utun4"), "en0", &[]).unwrap();
+
+        // A reload with new firewall-only settings
+        firewall.mss_override = Some(1240);
+        let no_nat = ["10.0.50.0/24".parse().unwrap()];
+        let after = firewall.ruleset(&single("utun4"), "en0", &no_nat).unwrap();
+
+        let anchors = |rules: &str| -> Vec<String> {
+            rules
+                .lines()
+                .filter(|line| line.contains("anchor \""))
+                .map(str::to_string)
+                .collect()
+        };
+        assert_eq!(anchors(&before), anchors(&after));
+        assert_eq!(
+            anchors(&after),
+            [
+                "rdr-anchor \"custom-natpmp\"",
+                "anchor \"tunshare_clients\"",
+                "anchor \"custom-natpmp\"",
+            ]
+        );
+        assert!(after.contains("max-mss 1240"));
+        assert!(after.contains("no nat on $ext_if inet from $int_if:network to 10.0.50.0/24"));
+    }
+
+    #[test]
+    fn test_mss_override_takes_precedence() {
+        assert_eq!(Firewall::effective_mss(None), DEFAULT_MSS);
+        assert_eq!(Firewall::effective_mss(Some(1240)), 1240);
+
+        let firewall = Firewall::new().with_mss_override(Some(1240));
+        let mss = Firewall::effective_mss(firewall.mss_override);
+        let rules =
+            Firewall::generate_rules(&single("utun4"), "en0", mss, &[], "tunshare-natpmp", false);
+        assert!(rules.contains("max-mss 1240\n"));
+        assert!(!rules.contains("max-mss 1400"));
+    }
+
+    #[test]
+    fn test_generate_rules_no_nat_precedes_catch_all() {
+        let no_nat: Vec<Ipv4Net> = vec!["10.0.50.0/24".parse().unwrap()];
+        let rules = Firewall::generate_rules(
+            &single("utun4"),
+            "en0",
+            1400,
+            &no_nat,
+            "tunshare-natpmp",
+            false,
+        );
+        let no_nat_rule = rules
+            .find("no nat on $ext_if inet from $int_if:network to 10.0.50.0/24")
+            .unwrap();
+        let catch_all = rules.find("\nnat on $ext_if").unwrap();
+        assert!(no_nat_rule < catch_all);
+    }
+
+    #[test]
+    fn test_parse_label_counters() {
+        let output = "client-192.168.2.50 120 900 5000 400 1000 500 4000\n\
+                      client-192.168.2.51 30 200 90000 100 30000 100 60000 4\n\
+                      other-label 1 1 1 1 1 1 1\n";
+        let counters = parse_label_counters(output);
+        assert_eq!(
+            counters,
+            vec![
+                ("192.168.2.51".to_string(), 30000, 60000),
+                ("192.168.2.50".to_string(), 1000, 4000),
+            ]
+        );
+
+        assert!(parse_label_counters("").is_empty());
+    }
+
+    #[test]
+    fn test_parse_nat_counters() {
+        let output = "no nat on utun4 inet from 192.168.2.0/24 to 10.0.50.0/24\n  \
+                      [ Evaluations: 40        Packets: 12        Bytes: 800         States: 0     ]\n  \
+                      [ Inserted: uid 0 pid 4242 State Creations: 0     ]\n\
+                      nat on utun4 inet from 192.168.2.0/24 to any -> (utun4) static-port\n  \
+                      [ Evaluations: 1200      Packets: 90000     Bytes: 4200000000  States: 31    ]\n  \
+                      [ Inserted: uid 0 pid 4242 State Creations: 310   ]\n\
+                      nat on utun5 inet from 192.168.2.0/24 to any -> (utun5) static-port\n  \
+                      [ Evaluations: 10        Packets: 100       Bytes: 5000        States: 1]\n\
+                      rdr-anchor \"tunshare-natpmp\" all\n  \
+                      [ Evaluations: 5         Packets: 7         Bytes: 9           States: 0     ]\n";
+        assert_eq!(parse_nat_counters(output), (90100, 4200005000));
+
+        // Without -v there are no counter lines
+        assert_eq!(
+            parse_nat_counters("nat on utun4 inet from 192.168.2.0/24 to any -> (utun4)\n"),
+            (0, 0)
+        );
+        assert_eq!(parse_nat_counters(""), (0, 0));
+    }
+
+    #[test]
+    fn test_nat_totals_across_reloads() {
+        let mut totals = NatTotals::default();
+        totals.record((100, 5000));
+        totals.record((150, 9000));
+        assert_eq!(totals.total(), (150, 9000));
+
+        // The ruleset was reloaded: pf counts from zero again
+        totals.record((10, 400));
+        assert_eq!(totals.total(), (160, 9400));
+        totals.record((20, 1000));
+        assert_eq!(totals.total(), (170, 10000));
+    }
+
+    #[test]
+    fn test_parse_state_clients() {
+        let states = "\
+all tcp 10.8.0.6:62000 (192.168.2.50:62000) -> 1.1.1.1:443       ESTABLISHED:ESTABLISHED
+all udp 192.168.2.51:5353 -> 224.0.0.251:5353       SINGLE:NO_TRAFFIC
+all tcp 192.168.2.1:22 <- 192.168.2.50:51000       ESTABLISHED:ESTABLISHED
+all tcp 10.0.0.5:443 <- 10.0.0.9:1234       ESTABLISHED:ESTABLISHED
+all tcp 10.8.0.6:62001 (192.168.3.20:62001) -> 1.1.1.1:443       ESTABLISHED:ESTABLISHED
+";
+        let gateway = Ipv4Addr::new(192, 168, 2, 1);
+        let clients =
+            Firewall::parse_state_clients(states, gateway, "192.168.2.0/24".parse().unwrap());
+        assert_eq!(
+            clients,
+            vec![
+                Ipv4Addr::new(192, 168, 2, 50),
+                Ipv4Addr::new(192, 168, 2, 51)
+            ]
+        );
+
+        // A wider LAN takes in clients beyond the gateway's /24
+        let clients =
+            Firewall::parse_state_clients(states, gateway, "192.168.2.0/23".parse().unwrap());
+        assert_eq!(clients.len(), 3);
+        assert!(clients.contains(&Ipv4Addr::new(192, 168, 3, 20)));
+    }
+
+    #[test]
+    fn test_parse_states() {
+        let states = "\
+No ALTQ support in kernel
+all tcp 10.8.0.6:62000 (192.168.2.50:62000) -> 1.1.1.1:443       ESTABLISHED:ESTABLISHED
+all udp 192.168.2.51:5353 -> 224.0.0.251:5353       SINGLE:NO_TRAFFIC
+all tcp 192.168.2.1:22 <- 192.168.2.50:51000       ESTABLISHED:ESTABLISHED
+ALL icmp 192.168.2.50:3 -> 10.8.0.6:61000 -> 8.8.8.8:61000       0:0
+   age 00:01:02, expires in 00:00:10, 12:12 pkts, 1008:1008 bytes
+";
+        let parsed = Firewall::parse_states(states);
+        assert_eq!(parsed.len(), 4);
+
+        assert_eq!(
+            parsed[0],
+            PfState {
+                interface: "all".to_string(),
+                protocol: "tcp".to_string(),
+                source: "192.168.2.50:62000".to_string(),
+                destination: "1.1.1.1:443".to_string(),
+                nat: Some("10.8.0.6:62000".to_string()),
+                state: "ESTABLISHED:ESTABLISHED".to_string(),
+            }
+        );
+
+        assert_eq!(parsed[1].protocol, "udp");
+        assert_eq!(parsed[1].source, "192.168.2.51:5353");
+        assert_eq!(parsed[1].nat, None);
+        assert_eq!(parsed[1].state, "SINGLE:NO_TRAFFIC");
+
+        // Inbound: initiator is on the right
+        assert_eq!(parsed[2].source, "192.168.2.50:51000");
+        assert_eq!(parsed[2].destination, "192.168.2.1:22");
+
+        // Legacy three-address form
+        assert_eq!(parsed[3].source, "192.168.2.50:3");
+        assert_eq!(parsed[3].nat.as_deref(), Some("10.8.0.6:61000"));
+        assert_eq!(parsed[3].destination, "8.8.8.8:61000");
+    }
+
+    #[test]
+    fn test_cleanup_report_success() {
+        let report = CleanupSteps {
+            restore_rules: Some(Ok(())),
+            disable_pf: None,
+            flush_anchor: Ok(()),
+            remove_config: Ok(()),
+        }
+        .report();
+        assert_eq!(
+            report,
+            CleanupReport {
+                nat_removed: true,
+                anchor_flushed: true,
+                pf_disabled: false,
+                errors: Vec::new(),
+            }
+        );
+        assert!(report.is_clean());
+
+        // No default ruleset: pf is turned off instead
+        let report = CleanupSteps {
+            restore_rules: None,
+            disable_pf: Some(Ok(())),
+            flush_anchor: Ok(()),
+            remove_config: Ok(()),
+        }
+        .report();
+        assert!(report.nat_removed && report.pf_disabled);
+    }
+
+    #[test]
+    fn test_cleanup_report_partial_failure() {
+        let report = CleanupSteps {
+            restore_rules: Some(Err("syntax error".to_string())),
+            disable_pf: None,
+            flush_anchor: Ok(()),
+            remove_config: Err("permission denied".to_string()),
+        }
+        .report();
+        assert!(!report.nat_removed);
+        assert!(report.anchor_flushed);
+        assert!(!report.pf_disabled);
+        assert_eq!(
+            report.errors,
+            vec![
+                "Failed to restore default rules: syntax error",
+                "Failed to remove config file: permission denied",
+            ]
+        );
+
+        let report = CleanupSteps {
+            restore_rules: None,
+            disable_pf: Some(Err("busy".to_string())),
+            flush_anchor: Err("no such anchor".to_string()),
+            remove_config: Ok(()),
+        }
+        .report();
+        assert!(!report.nat_removed && !report.pf_disabled && !report.anchor_flushed);
+        assert_eq!(report.errors.len(), 2);
+    }
+
+    #[test]
+    fn test_cleanup_plan_pf_initially_disabled() {
+        // We enabled pf, so it goes back off after the default rules are restored
+        assert_eq!(
+            CleanupPlan::new(false, true),
+            CleanupPlan {
+                restore_rules: true,
+                disable_pf: true,
+            }
+        );
+        assert_eq!(
+            CleanupPlan::new(false, false),
+            CleanupPlan {
+                restore_rules: false,
+                disable_pf: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_cleanup_plan_pf_initially_enabled() {
+        // Someone else's firewall: restore the default rules, never disable
+        assert_eq!(
+            CleanupPlan::new(true, true),
+            CleanupPlan {
+                restore_rules: true,
+                disable_pf: false,
+            }
+        );
+        assert!(!CleanupPlan::new(true, false).disable_pf);
+        assert!(Firewall::new().was_pf_enabled);
+    }
+
+    #[test]
+    fn test_classify_pfctl_error() {
+        let syntax = "/etc/pf.anchors/tunshare:12: syntax error\npfctl: Syntax error in config file: pf rules not loaded\n";
+        assert!(matches!(
+            classify_pfctl_error(syntax),
+            TunshareError::PfSyntaxError { line: Some(12), ref stderr } if stderr == syntax.trim()
+        ));
+        // A template's own rules are the user's to fix
+        let hint = classify_pfctl_error(syntax)
+            .hint(Some(Path::new("/Users/me/pf.conf")))
+            .unwrap();
+        assert!(hint.contains("/Users/me/pf.conf"));
+        assert!(classify_pfctl_error(syntax)
+            .hint(None)
+            .unwrap()
+            .contains("please report"));
+        assert!(matches!(
+            classify_pfctl_error("stdin:3: syntax error"),
+            TunshareError::PfSyntaxError { line: Some(3), .. }
+        ));
+
+        let missing = "no IP address found for utun4\n/etc/pf.anchors/tunshare:4: could not parse host specification\n";
+        assert!(matches!(
+            classify_pfctl_error(missing),
+            TunshareError::PfInterfaceMissing { iface, .. } if iface == "utun4"
+        ));
+
+        assert!(matches!(
+            classify_pfctl_error("pfctl: /dev/pf: Operation not permitted\n"),
+            TunshareError::PfPermission { .. }
+        ));
+        let denied = classify_pfctl_error("pfctl: /dev/pf: Permission denied");
+        assert_eq!(
+            denied.pfctl_output(),
+            Some("pfctl: /dev/pf: Permission denied")
+        );
+
+        // Anything else keeps pfctl's own words, with context from the caller
+        assert!(matches!(
+            classify_pfctl_error("pfctl: DIOCADDRULE: Invalid argument\n"),
+            TunshareError::FirewallError(output) if output == "pfctl: DIOCADDRULE: Invalid argument"
+        ));
+        assert_eq!(
+            pfctl_failure(
+                "Failed to load rules",
+                "pfctl: DIOCADDRULE: Invalid argument"
+            )
+            .to_string(),
+            "Firewall error: Failed to load rules: pfctl: DIOCADDRULE: Invalid argument"
+        );
     }
 }
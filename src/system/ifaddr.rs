@@ -0,0 +1,84 @@
+//! Gateway address assignment for LAN interfaces that have no IPv4 yet.
+//!
+//! The address is added as an alias so that any address configured later by
+//! the system is left alone, and removed again when sharing stops.
+
+use crate::error::{Result, TunshareError};
+use ipnet::Ipv4Net;
+use std::net::Ipv4Addr;
+use std::process::Command as SyncCommand;
+use tokio::process::Command;
+
+/// Gateway address offered when `lan_gateway_ip` isn't configured.
+pub const DEFAULT_GATEWAY: Ipv4Net = Ipv4Net::new_assert(Ipv4Addr::new(192, 168, 42, 1), 24);
+
+/// `ifconfig` arguments that add `address` to `ifname` as an alias.
+fn assign_args(ifname: &str, address: Ipv4Net) -> Vec<String> {
+    vec![
+        ifname.to_string(),
+        "inet".into(),
+        address.addr().to_string(),
+        "netmask".into(),
+        address.netmask().to_string(),
+        "alias".into(),
+    ]
+}
+
+/// `ifconfig` arguments that remove the alias again.
+fn remove_args(ifname: &str, address: Ipv4Net) -> Vec<String> {
+    vec![
+        ifname.to_string(),
+        "inet".into(),
+        address.addr().to_string(),
+        "-alias".into(),
+    ]
+}
+
+/// Add `address` to `ifname` (`ifconfig <if> inet <ip> netmask <mask> alias`).
+pub async fn assign(ifname: &str, address: Ipv4Net) -> Result<()> {
+    let args = assign_args(ifname, address);
+    let command = format!("ifconfig {}", args.join(" "));
+    let output = Command::new("ifconfig")
+        .args(&args)
+        .output()
+        .await
+        .map_err(|e| TunshareError::CommandFailed {
+            command: command.clone(),
+            message: e.to_string(),
+        })?;
+
+    if !output.status.success() {
+        return Err(TunshareError::CommandFailed {
+            command,
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Remove an address added by [`assign`]. Synchronous for use in Drop;
+/// errors are ignored since the interface may already be gone.
+pub fn remove_sync(ifname: &str, address: Ipv4Net) {
+    let _ = SyncCommand::new("ifconfig")
+        .args(remove_args(ifname, address))
+        .output();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ifconfig_args() {
+        let address: Ipv4Net = "192.168.42.1/24".parse().unwrap();
+        assert_eq!(
+            assign_args("en5", address).join(" "),
+            "en5 inet 192.168.42.1 netmask 255.255.255.0 alias"
+        );
+        assert_eq!(
+            remove_args("en5", address).join(" "),
+            "en5 inet 192.168.42.1 -alias"
+        );
+        assert_eq!(DEFAULT_GATEWAY, address);
+    }
+}
@@ -1,15 +1,25 @@
-//! System interaction modules for network, firewall, DNS, DHCP, and sysctl operations.
+//! System interaction modules for network, firewall, DNS, DHCP, probing, sysctl,
+//! power source, process lookups, post-stop verification and retried command
+//! execution.
 
+pub mod arp;
+pub mod clipboard;
 pub mod dhcp;
 pub mod dns;
+pub mod exec;
 pub mod firewall;
+pub mod ifaddr;
 pub mod natpmp;
 pub mod network;
+pub mod power;
+pub mod probe;
+pub mod process;
 pub mod sysctl;
+pub mod verify;
 
 pub use dhcp::DhcpServer;
 pub use dns::discover_vpn_dns;
-pub use firewall::Firewall;
+pub use firewall::{Firewall, NatTotals, PfState, PfTemplate, Uplink};
 pub use natpmp::NatPmpServer;
-pub use network::{detect_lan_interfaces, detect_vpn_interfaces, InterfaceInfo};
+pub use network::{detect_lan_interfaces, detect_vpn_interfaces, InterfaceInfo, LanInterfaces};
 pub use sysctl::IpForwarding;
@@ -6,23 +6,34 @@
 use crate::error::{Result, TunshareError};
 use std::collections::HashMap;
 use std::fmt;
-use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::future::Future;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::ops::RangeInclusive;
 use std::process::Command as SyncCommand;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use tokio::io::AsyncWriteExt;
 use tokio::net::UdpSocket;
 use tokio::process::Command;
-use tokio::sync::watch;
+use tokio::sync::{mpsc, watch};
 
 const NATPMP_PORT: u16 = 5351;
+/// RFC 6886 §3.2.1: address-change announcements go to all-hosts on the client port.
+const ANNOUNCE_ADDR: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(224, 0, 0, 1), 5350);
+/// How often the external address is re-read without an explicit trigger.
+const IP_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
 /// RFC 6886: response opcode = request opcode + 128.
 const RESPONSE_FLAG: u8 = 128;
-const PF_ANCHOR_NAME: &str = "natpmp";
+/// Default pf anchor for mapping rules. Namespaced so it can't collide with
+/// another NAT-PMP/UPnP daemon's `natpmp` anchor.
+pub const DEFAULT_ANCHOR_NAME: &str = "tunshare-natpmp";
 const MAX_LIFETIME: u32 = 7200;
 const MIN_ALLOWED_PORT: u16 = 1024;
+/// Default external ports clients may map: everything above the privileged range.
+pub const DEFAULT_PORT_RANGE: (u16, u16) = (MIN_ALLOWED_PORT, u16::MAX);
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-enum Protocol {
+/// Transport protocol of a mapping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Protocol {
     Udp,
     Tcp,
 }
@@ -36,10 +47,18 @@ impl fmt::Display for Protocol {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-struct MappingKey {
-    protocol: Protocol,
-    external_port: u16,
+/// Identifies a mapping: one per protocol and external port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MappingKey {
+    pub protocol: Protocol,
+    pub external_port: u16,
+}
+
+/// Requests to the running server task, besides shutdown.
+#[derive(Debug)]
+enum ServerCommand {
+    /// Drop a mapping and reload the anchor without it.
+    RemoveMapping(MappingKey),
 }
 
 #[derive(Debug, Clone)]
@@ -58,11 +77,58 @@ impl Mapping {
     }
 }
 
+/// Read-only copy of an active mapping, published for display and external tools.
+#[derive(Debug, Clone)]
+pub struct MappingSnapshot {
+    pub protocol: Protocol,
+    pub external_port: u16,
+    pub internal_ip: Ipv4Addr,
+    pub internal_port: u16,
+    created_at: Instant,
+    lifetime_secs: u32,
+}
+
+impl MappingSnapshot {
+    /// Seconds until the mapping expires (0 once expired).
+    pub fn remaining_secs(&self) -> u64 {
+        (self.lifetime_secs as u64).saturating_sub(self.created_at.elapsed().as_secs())
+    }
+
+    /// Key to remove this mapping with (`NatPmpServer::remove_mapping`).
+    pub fn key(&self) -> MappingKey {
+        MappingKey {
+            protocol: self.protocol,
+            external_port: self.external_port,
+        }
+    }
+}
+
 /// NAT-PMP server that runs as a tokio task.
 pub struct NatPmpServer {
     ext_ifname: String,
     lan_network: String,
     shutdown_tx: watch::Sender<bool>,
+    /// pf anchor the mapping rules are loaded into.
+    anchor: String,
+    /// Latest mapping table, republished by the server task on every change.
+    mappings_tx: watch::Sender<Vec<MappingSnapshot>>,
+    /// External ports clients may map.
+    port_range: RangeInclusive<u16>,
+    /// Address to advertise when the external interface has several (None = first).
+    external_ip: Option<Ipv4Addr>,
+    /// Poked to re-read the external address before the next periodic refresh.
+    refresh_tx: watch::Sender<()>,
+    /// Why the last anchor reload failed (None once one succeeds).
+    anchor_error_tx: watch::Sender<Option<String>>,
+    /// Reader for `anchor_error_tx`, marks which failure was already reported.
+    anchor_error_rx: watch::Receiver<Option<String>>,
+    /// Keep our rules in a sub-anchor of `anchor`, leaving the rules already
+    /// in it alone (see [`instance_anchor`]).
+    preserve_existing: bool,
+    /// Requests for the server task.
+    command_tx: mpsc::UnboundedSender<ServerCommand>,
+    /// Receiving end of `command_tx`, handed to the task by `start`.
+    command_rx: Option<mpsc::UnboundedReceiver<ServerCommand>>,
 }
 
 impl NatPmpServer {
@@ -72,41 +138,152 @@ impl NatPmpServer {
     /// the LAN interface only) but is not currently used.
     pub fn new(ext_ifname: &str, _lan_ifname: &str, lan_network: &str) -> Self {
         let (shutdown_tx, _) = watch::channel(false);
+        let (mappings_tx, _) = watch::channel(Vec::new());
+        let (refresh_tx, _) = watch::channel(());
+        let (anchor_error_tx, anchor_error_rx) = watch::channel(None);
+        let (command_tx, command_rx) = mpsc::unbounded_channel();
         Self {
             ext_ifname: ext_ifname.to_string(),
             lan_network: lan_network.to_string(),
             shutdown_tx,
+            anchor: DEFAULT_ANCHOR_NAME.to_string(),
+            mappings_tx,
+            port_range: DEFAULT_PORT_RANGE.0..=DEFAULT_PORT_RANGE.1,
+            external_ip: None,
+            refresh_tx,
+            anchor_error_tx,
+            anchor_error_rx,
+            preserve_existing: false,
+            command_tx,
+            command_rx: Some(command_rx),
+        }
+    }
+
+    /// Use a different pf anchor for mapping rules.
+    /// The main ruleset must reference the same anchor (see `Firewall::with_natpmp_anchor`).
+    pub fn with_anchor(mut self, anchor: &str) -> Self {
+        self.anchor = anchor.to_string();
+        self
+    }
+
+    /// Only hand out external ports in `low..=high`.
+    /// Requests for ports outside the range are refused with result code 2.
+    /// The range never reaches below 1024; one left empty by that (or
+    /// inverted) is refused.
+    pub fn with_port_range(mut self, low: u16, high: u16) -> Result<Self> {
+        let allowed = low.max(MIN_ALLOWED_PORT);
+        if high < allowed {
+            return Err(TunshareError::InvalidPortRange { low, high });
         }
+        self.port_range = allowed..=high;
+        Ok(self)
+    }
+
+    /// Advertise `ip` as the external address and redirect only traffic sent to it.
+    /// Falls back to the interface's first address while `ip` isn't assigned to it.
+    pub fn with_external_ip(mut self, ip: Ipv4Addr) -> Self {
+        self.external_ip = Some(ip);
+        self
+    }
+
+    /// Never replace or flush the anchor itself, for an anchor another
+    /// tunshare instance (or a manual setup) still has live mappings in. Our
+    /// rules go into a sub-anchor of it instead (see [`instance_anchor`]).
+    pub fn with_preserve_existing(mut self, preserve: bool) -> Self {
+        self.preserve_existing = preserve;
+        self
+    }
+
+    /// The pf anchor this server loads, reloads and flushes its rules in.
+    pub fn rules_anchor(&self) -> String {
+        instance_anchor(&self.anchor, self.preserve_existing)
+    }
+
+    /// Flush the rules anchor through `run` (see `load_anchor`), which is
+    /// only ever our own sub-anchor when preserving existing rules.
+    async fn flush_stale_rules<F, Fut>(&self, run: F)
+    where
+        F: Fn(Vec<String>, Option<String>) -> Fut,
+        Fut: Future<Output = std::result::Result<(), String>>,
+    {
+        flush_anchor(run, &self.rules_anchor()).await;
+    }
+
+    /// Whether `name` is usable as a pf anchor name.
+    pub fn is_valid_anchor_name(name: &str) -> bool {
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
     }
 
     /// Start the NAT-PMP server. Spawns a long-lived tokio task.
-    pub async fn start(&self) -> Result<()> {
+    pub async fn start(&mut self) -> Result<()> {
         // Flush any stale anchor rules from a previous run
-        Self::stop().await.ok();
-
-        let addr = SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, NATPMP_PORT);
-        let socket = UdpSocket::bind(addr)
-            .await
-            .map_err(|e| TunshareError::CommandFailed {
-                command: "bind NAT-PMP UDP socket".into(),
-                message: format!("Failed to bind port {}: {}", NATPMP_PORT, e),
-            })?;
+        self.flush_stale_rules(run_pfctl).await;
+        let anchor = self.rules_anchor();
+
+        // Dual-stack (the macOS default for `::`), so clients sending from
+        // IPv4-mapped addresses reach us too; IPv4-only if IPv6 is off
+        let dual_stack = SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, NATPMP_PORT, 0, 0);
+        let bound = match UdpSocket::bind(dual_stack).await {
+            Err(e) if e.kind() != std::io::ErrorKind::AddrInUse => {
+                UdpSocket::bind(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, NATPMP_PORT)).await
+            }
+            result => result,
+        };
+        let socket = match bound {
+            Ok(socket) => socket,
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
+                // Usually a leftover miniupnpd or another NAT-PMP daemon
+                return Err(TunshareError::PortInUse {
+                    port: NATPMP_PORT,
+                    holder: Self::port_holder().await,
+                });
+            }
+            Err(e) => {
+                return Err(TunshareError::CommandFailed {
+                    command: "bind NAT-PMP UDP socket".into(),
+                    message: format!("Failed to bind port {}: {}", NATPMP_PORT, e),
+                })
+            }
+        };
 
         let ext_ifname = self.ext_ifname.clone();
         let lan_network = self.lan_network.clone();
+        let port_range = self.port_range.clone();
+        let preferred_ip = self.external_ip;
         let mut shutdown_rx = self.shutdown_tx.subscribe();
+        let mappings_tx = self.mappings_tx.clone();
+        let anchor_error_tx = self.anchor_error_tx.clone();
+        let Some(mut command_rx) = self.command_rx.take() else {
+            return Err(TunshareError::CommandFailed {
+                command: "start NAT-PMP server".into(),
+                message: "the server was already started".into(),
+            });
+        };
+        let lookup_ifname = ext_ifname.clone();
+        let mut ip_rx = spawn_external_ip_monitor(
+            move || {
+                let ifname = lookup_ifname.clone();
+                async move { get_interface_ips(&ifname).await }
+            },
+            preferred_ip,
+            self.refresh_tx.subscribe(),
+            self.shutdown_tx.subscribe(),
+        )
+        .await;
+
+        let announce_addr = announce_addr(socket.local_addr().is_ok_and(|a| a.is_ipv6()));
 
         tokio::spawn(async move {
             let mut mappings: HashMap<MappingKey, Mapping> = HashMap::new();
             let mut buf = [0u8; 64];
-            let mut external_ip = get_interface_ip(&ext_ifname)
-                .await
-                .unwrap_or(Ipv4Addr::UNSPECIFIED);
-            let mut expiry_interval = tokio::time::interval(std::time::Duration::from_secs(30));
-            let mut ip_refresh_interval = tokio::time::interval(std::time::Duration::from_secs(60));
-            // Consume the first immediate ticks
+            let mut external_ip = *ip_rx.borrow_and_update();
+            let mut monitor_alive = true;
+            let mut expiry_interval = tokio::time::interval(Duration::from_secs(30));
+            // Consume the first immediate tick
             expiry_interval.tick().await;
-            ip_refresh_interval.tick().await;
 
             let server_start = Instant::now();
 
@@ -119,15 +296,20 @@ impl NatPmpServer {
                                     continue;
                                 }
                                 if let Some(response) = handle_request(
+                                    run_pfctl,
                                     &buf[..len],
                                     src,
                                     external_ip,
                                     server_start,
                                     &ext_ifname,
+                                    &anchor,
+                                    &port_range,
                                     &mut mappings,
+                                    &anchor_error_tx,
                                 ).await {
                                     let _ = socket.send_to(&response, src).await;
                                 }
+                                mappings_tx.send_replace(snapshot_mappings(&mappings));
                             }
                             Err(_) => continue,
                         }
@@ -136,17 +318,33 @@ impl NatPmpServer {
                         let before = mappings.len();
                         mappings.retain(|_, m| !m.is_expired());
                         if mappings.len() != before {
-                            reload_anchor_rules(&ext_ifname, &mappings).await;
+                            reload_anchor_rules(&ext_ifname, external_ip, &anchor, &mappings, &anchor_error_tx).await;
+                            mappings_tx.send_replace(snapshot_mappings(&mappings));
                         }
                     }
-                    _ = ip_refresh_interval.tick() => {
-                        if let Some(ip) = get_interface_ip(&ext_ifname).await {
-                            external_ip = ip;
+                    changed = ip_rx.changed(), if monitor_alive => {
+                        if changed.is_err() {
+                            monitor_alive = false;
+                            continue;
                         }
+                        external_ip = *ip_rx.borrow_and_update();
+                        // rdr rules match on the external address
+                        reload_anchor_rules(&ext_ifname, external_ip, &anchor, &mappings, &anchor_error_tx).await;
+                        let sssoe = server_start.elapsed().as_secs() as u32;
+                        let announcement = build_external_address_response(sssoe, external_ip);
+                        let _ = socket.send_to(&announcement, announce_addr).await;
                     }
+                    Some(command) = command_rx.recv() => match command {
+                        ServerCommand::RemoveMapping(key) => {
+                            if evict_mapping(run_pfctl, key, &ext_ifname, external_ip, &anchor, &mut mappings, &anchor_error_tx).await {
+                                mappings_tx.send_replace(snapshot_mappings(&mappings));
+                            }
+                        }
+                    },
                     _ = shutdown_rx.changed() => {
                         if *shutdown_rx.borrow() {
-                            flush_anchor_rules().await;
+                            flush_anchor(run_pfctl, &anchor).await;
+                            mappings_tx.send_replace(Vec::new());
                             break;
                         }
                     }
@@ -157,6 +355,34 @@ impl NatPmpServer {
         Ok(())
     }
 
+    /// Re-read the external interface's address now instead of waiting for the
+    /// periodic refresh, e.g. after the VPN reconnects. A changed address is
+    /// loaded into the rdr rules and announced to LAN clients.
+    pub fn refresh_external_ip(&self) {
+        self.refresh_tx.send_replace(());
+    }
+
+    /// Drop a mapping (e.g. one a departed client never released) and reload
+    /// the anchor without it. Unknown keys are ignored.
+    pub fn remove_mapping(&self, key: MappingKey) {
+        let _ = self.command_tx.send(ServerCommand::RemoveMapping(key));
+    }
+
+    /// Current mappings, sorted by external port.
+    pub fn mappings(&self) -> Vec<MappingSnapshot> {
+        self.mappings_tx.borrow().clone()
+    }
+
+    /// Why loading the mapping rules into the anchor failed, if that happened
+    /// since the last call. Mappings are answered but not forwarded until a
+    /// later reload succeeds.
+    pub fn new_anchor_error(&mut self) -> Option<String> {
+        if !self.anchor_error_rx.has_changed().unwrap_or(false) {
+            return None;
+        }
+        self.anchor_error_rx.borrow_and_update().clone()
+    }
+
     /// Signal the server task to shut down.
     pub fn shutdown(&self) {
         let _ = self.shutdown_tx.send(true);
@@ -164,8 +390,9 @@ impl NatPmpServer {
 
     /// Flush all NAT-PMP pf anchor rules (async wrapper).
     /// Delegates to `stop_sync` via `spawn_blocking`.
-    pub async fn stop() -> Result<()> {
-        tokio::task::spawn_blocking(Self::stop_sync)
+    pub async fn stop(anchor: &str) -> Result<()> {
+        let anchor = anchor.to_string();
+        tokio::task::spawn_blocking(move || Self::stop_sync(&anchor))
             .await
             .map_err(|e| TunshareError::CommandFailed {
                 command: "natpmp stop (spawn_blocking)".into(),
@@ -175,24 +402,100 @@ impl NatPmpServer {
     }
 
     /// Synchronous flush. Single source of truth for NAT-PMP cleanup.
-    pub fn stop_sync() {
-        let _ = SyncCommand::new("pfctl")
-            .args(["-a", PF_ANCHOR_NAME, "-F", "all"])
-            .output();
+    pub fn stop_sync(anchor: &str) {
+        let _ = SyncCommand::new("pfctl").args(flush_args(anchor)).output();
     }
 
-    /// Derive a /24 network CIDR from a gateway IP (e.g., 192.168.2.1 -> "192.168.2.0/24").
-    pub fn network_from_ip(ip: Ipv4Addr) -> String {
-        let o = ip.octets();
-        format!("{}.{}.{}.0/24", o[0], o[1], o[2])
+    /// Identify the process currently bound to the NAT-PMP port, as `(pid, name)`.
+    pub async fn port_holder() -> Option<(u32, String)> {
+        let output = Command::new("lsof")
+            .args(["-nP", &format!("-iUDP:{}", NATPMP_PORT)])
+            .output()
+            .await
+            .ok()?;
+
+        parse_lsof_holder(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    /// Ask the process holding the NAT-PMP port to exit (SIGTERM), then give
+    /// it a moment to release the socket.
+    pub async fn stop_port_holder(pid: u32) -> Result<()> {
+        let output = Command::new("kill")
+            .arg(pid.to_string())
+            .output()
+            .await
+            .map_err(|e| TunshareError::CommandFailed {
+                command: format!("kill {}", pid),
+                message: e.to_string(),
+            })?;
+
+        if !output.status.success() {
+            return Err(TunshareError::CommandFailed {
+                command: format!("kill {}", pid),
+                message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            });
+        }
+
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        Ok(())
+    }
+}
+
+/// Copy the live mapping table into snapshots, sorted by external port.
+fn snapshot_mappings(mappings: &HashMap<MappingKey, Mapping>) -> Vec<MappingSnapshot> {
+    let mut snapshots: Vec<MappingSnapshot> = mappings
+        .values()
+        .map(|m| MappingSnapshot {
+            protocol: m.protocol,
+            external_port: m.external_port,
+            internal_ip: m.internal_ip,
+            internal_port: m.internal_port,
+            created_at: m.created_at,
+            lifetime_secs: m.lifetime_secs,
+        })
+        .collect();
+    snapshots.sort_by_key(|m| (m.external_port, m.protocol));
+    snapshots
+}
+
+/// Parse `lsof -nP -iUDP:<port>` output into the first holder's `(pid, command)`.
+fn parse_lsof_holder(output: &str) -> Option<(u32, String)> {
+    // Skip the "COMMAND PID USER ..." header
+    let line = output.lines().nth(1)?;
+    let mut fields = line.split_whitespace();
+    let command = fields.next()?.to_string();
+    let pid = fields.next()?.parse().ok()?;
+    Some((pid, command))
+}
+
+/// Where address-change announcements go; a dual-stack socket can only
+/// send to the multicast group through its IPv4-mapped form.
+fn announce_addr(ipv6_socket: bool) -> SocketAddr {
+    if ipv6_socket {
+        SocketAddr::V6(SocketAddrV6::new(
+            ANNOUNCE_ADDR.ip().to_ipv6_mapped(),
+            ANNOUNCE_ADDR.port(),
+            0,
+            0,
+        ))
+    } else {
+        SocketAddr::V4(ANNOUNCE_ADDR)
+    }
+}
+
+/// The IPv4 address of a client, unwrapping IPv4-mapped IPv6 sources
+/// (`::ffff:a.b.c.d`), as a dual-stack socket reports every IPv4 client.
+fn client_ipv4(src: &SocketAddr) -> Option<Ipv4Addr> {
+    match src {
+        SocketAddr::V4(v4) => Some(*v4.ip()),
+        SocketAddr::V6(v6) => v6.ip().to_ipv4_mapped(),
     }
 }
 
 /// Check if a client address is on the LAN network (CIDR /24 check).
 fn is_lan_client(src: &SocketAddr, lan_network: &str) -> bool {
-    let client_ip = match src {
-        SocketAddr::V4(v4) => *v4.ip(),
-        SocketAddr::V6(_) => return false,
+    let Some(client_ip) = client_ipv4(src) else {
+        return false;
     };
 
     let Some((network_str, prefix_str)) = lan_network.split_once('/') else {
@@ -223,17 +526,28 @@ fn is_lan_client(src: &SocketAddr, lan_network: &str) -> bool {
 }
 
 /// Handle a single NAT-PMP request, returning the response bytes.
-async fn handle_request(
+/// Anchor reloads go through `run` (see `load_anchor`).
+#[allow(clippy::too_many_arguments)]
+async fn handle_request<F, Fut>(
+    run: F,
     data: &[u8],
     src: SocketAddr,
     external_ip: Ipv4Addr,
     server_start: Instant,
     ext_ifname: &str,
+    anchor: &str,
+    port_range: &RangeInclusive<u16>,
     mappings: &mut HashMap<MappingKey, Mapping>,
-) -> Option<Vec<u8>> {
+    anchor_errors: &watch::Sender<Option<String>>,
+) -> Option<Vec<u8>>
+where
+    F: Fn(Vec<String>, Option<String>) -> Fut,
+    Fut: Future<Output = std::result::Result<(), String>>,
+{
     if data.len() < 2 {
         return None;
     }
+    let client_ip = client_ipv4(&src);
 
     let version = data[0];
     if version != 0 {
@@ -263,9 +577,8 @@ async fn handle_request(
             let suggested_external = u16::from_be_bytes([data[6], data[7]]);
             let lifetime = u32::from_be_bytes([data[8], data[9], data[10], data[11]]);
 
-            let client_ip = match src {
-                SocketAddr::V4(v4) => *v4.ip(),
-                _ => return Some(build_error_response(resp_opcode, 2)),
+            let Some(client_ip) = client_ip else {
+                return Some(build_error_response(resp_opcode, 2));
             };
 
             // Delete all mappings for this client
@@ -273,13 +586,21 @@ async fn handle_request(
                 let before = mappings.len();
                 mappings.retain(|_, m| m.internal_ip != client_ip);
                 if mappings.len() != before {
-                    reload_anchor_rules(ext_ifname, mappings).await;
+                    reload_anchor_with(
+                        &run,
+                        ext_ifname,
+                        external_ip,
+                        anchor,
+                        mappings,
+                        anchor_errors,
+                    )
+                    .await;
                 }
                 return Some(build_mapping_response(resp_opcode, sssoe, 0, 0, 0));
             }
 
             // Determine external port
-            let external_port = if suggested_external >= MIN_ALLOWED_PORT {
+            let external_port = if port_range.contains(&suggested_external) {
                 let key = MappingKey {
                     protocol,
                     external_port: suggested_external,
@@ -289,7 +610,7 @@ async fn handle_request(
                 {
                     suggested_external
                 } else {
-                    match find_available_port(mappings, protocol) {
+                    match find_available_port(mappings, protocol, port_range) {
                         Some(p) => p,
                         None => return Some(build_error_response(resp_opcode, 4)), // Out of resources
                     }
@@ -303,13 +624,21 @@ async fn handle_request(
                             && m.internal_port == internal_port
                             && m.protocol == protocol
                     })
-                    .map(|(k, _)| k.clone())
+                    .map(|(k, _)| *k)
                     .collect();
                 for key in &to_remove {
                     mappings.remove(key);
                 }
                 if !to_remove.is_empty() {
-                    reload_anchor_rules(ext_ifname, mappings).await;
+                    reload_anchor_with(
+                        &run,
+                        ext_ifname,
+                        external_ip,
+                        anchor,
+                        mappings,
+                        anchor_errors,
+                    )
+                    .await;
                 }
                 return Some(build_mapping_response(
                     resp_opcode,
@@ -318,17 +647,16 @@ async fn handle_request(
                     0,
                     0,
                 ));
-            } else {
-                // Suggested port < 1024, find a free one
-                match find_available_port(mappings, protocol) {
+            } else if suggested_external == 0 {
+                // No preference, find a free one
+                match find_available_port(mappings, protocol, port_range) {
                     Some(p) => p,
                     None => return Some(build_error_response(resp_opcode, 4)),
                 }
-            };
-
-            if external_port < MIN_ALLOWED_PORT {
+            } else {
+                // Outside the allowed range
                 return Some(build_error_response(resp_opcode, 2));
-            }
+            };
 
             let capped_lifetime = lifetime.min(MAX_LIFETIME);
 
@@ -339,7 +667,15 @@ async fn handle_request(
                     external_port,
                 };
                 mappings.remove(&key);
-                reload_anchor_rules(ext_ifname, mappings).await;
+                reload_anchor_with(
+                    &run,
+                    ext_ifname,
+                    external_ip,
+                    anchor,
+                    mappings,
+                    anchor_errors,
+                )
+                .await;
                 return Some(build_mapping_response(
                     resp_opcode,
                     sssoe,
@@ -363,7 +699,15 @@ async fn handle_request(
                 created_at: Instant::now(),
             };
             mappings.insert(key, mapping);
-            reload_anchor_rules(ext_ifname, mappings).await;
+            reload_anchor_with(
+                &run,
+                ext_ifname,
+                external_ip,
+                anchor,
+                mappings,
+                anchor_errors,
+            )
+            .await;
 
             Some(build_mapping_response(
                 resp_opcode,
@@ -417,51 +761,215 @@ fn build_error_response(opcode: u8, result_code: u16) -> Vec<u8> {
     resp
 }
 
-/// Find an available external port for a mapping.
-fn find_available_port(mappings: &HashMap<MappingKey, Mapping>, protocol: Protocol) -> Option<u16> {
-    for port in MIN_ALLOWED_PORT..=65535 {
-        let key = MappingKey {
+/// Find an available external port for a mapping within `port_range`.
+fn find_available_port(
+    mappings: &HashMap<MappingKey, Mapping>,
+    protocol: Protocol,
+    port_range: &RangeInclusive<u16>,
+) -> Option<u16> {
+    port_range.clone().find(|&port| {
+        !mappings.contains_key(&MappingKey {
             protocol,
             external_port: port,
-        };
-        if !mappings.contains_key(&key) {
-            return Some(port);
-        }
-    }
-    None
+        })
+    })
+}
+
+/// Pick the address to advertise: `preferred` if the interface still has it,
+/// otherwise the first one.
+pub fn select_external_ip(addresses: &[Ipv4Addr], preferred: Option<Ipv4Addr>) -> Option<Ipv4Addr> {
+    preferred
+        .filter(|ip| addresses.contains(ip))
+        .or_else(|| addresses.first().copied())
 }
 
-/// Get the IPv4 address of a network interface.
-async fn get_interface_ip(ifname: &str) -> Option<Ipv4Addr> {
-    let output = Command::new("ifconfig").arg(ifname).output().await.ok()?;
+/// Spawn a task that keeps the advertised external address current.
+///
+/// `lookup` is re-run every `IP_REFRESH_INTERVAL` and whenever `refresh_rx`
+/// fires; a different selection is published on the returned channel.
+/// The first lookup happens before returning, so the channel starts populated
+/// (`UNSPECIFIED` if the interface has no address yet).
+async fn spawn_external_ip_monitor<F, Fut>(
+    lookup: F,
+    preferred: Option<Ipv4Addr>,
+    mut refresh_rx: watch::Receiver<()>,
+    mut shutdown_rx: watch::Receiver<bool>,
+) -> watch::Receiver<Ipv4Addr>
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = Vec<Ipv4Addr>> + Send,
+{
+    let initial = select_external_ip(&lookup().await, preferred).unwrap_or(Ipv4Addr::UNSPECIFIED);
+    let (ip_tx, ip_rx) = watch::channel(initial);
+    refresh_rx.mark_unchanged();
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(IP_REFRESH_INTERVAL);
+        // Consume the first immediate tick
+        interval.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                changed = refresh_rx.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                }
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() {
+                        break;
+                    }
+                    continue;
+                }
+                _ = ip_tx.closed() => break,
+            }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    for line in stdout.lines() {
-        let trimmed = line.trim();
-        if trimmed.starts_with("inet ") {
-            let parts: Vec<&str> = trimmed.split_whitespace().collect();
-            if parts.len() >= 2 {
-                return parts[1].parse().ok();
+            if let Some(ip) = select_external_ip(&lookup().await, preferred) {
+                ip_tx.send_if_modified(|current| {
+                    let changed = *current != ip;
+                    *current = ip;
+                    changed
+                });
             }
         }
+    });
+
+    ip_rx
+}
+
+/// Get all IPv4 addresses of a network interface, in `ifconfig` order.
+async fn get_interface_ips(ifname: &str) -> Vec<Ipv4Addr> {
+    let Ok(output) = Command::new("ifconfig").arg(ifname).output().await else {
+        return Vec::new();
+    };
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            if parts.next() != Some("inet") {
+                return None;
+            }
+            parts.next()?.parse().ok()
+        })
+        .collect()
+}
+
+/// The anchor an instance keeps its mapping rules in: `anchor` itself, or
+/// with `preserve_existing` its own `<anchor>/<pid>` sub-anchor, so rules
+/// someone else loaded into `anchor` are never replaced or flushed. The main
+/// ruleset then references the sub-anchors too
+/// (see `Firewall::with_natpmp_sub_anchors`).
+pub fn instance_anchor(anchor: &str, preserve_existing: bool) -> String {
+    if preserve_existing {
+        format!("{}/{}", anchor, std::process::id())
+    } else {
+        anchor.to_string()
+    }
+}
+
+/// Reload the pf anchor with current mappings, publishing the outcome on
+/// `anchor_errors` so the app can log a failure.
+async fn reload_anchor_rules(
+    ext_ifname: &str,
+    external_ip: Ipv4Addr,
+    anchor: &str,
+    mappings: &HashMap<MappingKey, Mapping>,
+    anchor_errors: &watch::Sender<Option<String>>,
+) {
+    reload_anchor_with(
+        run_pfctl,
+        ext_ifname,
+        external_ip,
+        anchor,
+        mappings,
+        anchor_errors,
+    )
+    .await;
+}
+
+/// Remove `key` from `mappings` and reload the anchor without it through
+/// `run` (see `load_anchor`). Returns whether there was such a mapping.
+async fn evict_mapping<F, Fut>(
+    run: F,
+    key: MappingKey,
+    ext_ifname: &str,
+    external_ip: Ipv4Addr,
+    anchor: &str,
+    mappings: &mut HashMap<MappingKey, Mapping>,
+    anchor_errors: &watch::Sender<Option<String>>,
+) -> bool
+where
+    F: Fn(Vec<String>, Option<String>) -> Fut,
+    Fut: Future<Output = std::result::Result<(), String>>,
+{
+    if mappings.remove(&key).is_none() {
+        return false;
     }
-    None
+    reload_anchor_with(
+        run,
+        ext_ifname,
+        external_ip,
+        anchor,
+        mappings,
+        anchor_errors,
+    )
+    .await;
+    true
 }
 
-/// Reload the pf anchor with current mappings.
-async fn reload_anchor_rules(ext_ifname: &str, mappings: &HashMap<MappingKey, Mapping>) {
+/// `reload_anchor_rules` through `run` (see `load_anchor`).
+async fn reload_anchor_with<F, Fut>(
+    run: F,
+    ext_ifname: &str,
+    external_ip: Ipv4Addr,
+    anchor: &str,
+    mappings: &HashMap<MappingKey, Mapping>,
+    anchor_errors: &watch::Sender<Option<String>>,
+) where
+    F: Fn(Vec<String>, Option<String>) -> Fut,
+    Fut: Future<Output = std::result::Result<(), String>>,
+{
     if mappings.is_empty() {
-        flush_anchor_rules().await;
+        flush_anchor(run, anchor).await;
         return;
     }
 
+    let rules = build_anchor_rules(ext_ifname, external_ip, &snapshot_mappings(mappings));
+    let error = load_anchor(run, anchor, &rules).await.err();
+    anchor_errors.send_if_modified(|current| {
+        // Report each failure, but a run of successes only once
+        let changed = error.is_some() || current.is_some();
+        *current = error;
+        changed
+    });
+}
+
+/// The anchor rules for `mappings` on `ext_ifname`, in external-port order.
+///
+/// An unspecified `external_ip` (not known yet) redirects traffic to any
+/// address on the interface.
+pub fn build_anchor_rules(
+    ext_ifname: &str,
+    external_ip: Ipv4Addr,
+    mappings: &[MappingSnapshot],
+) -> String {
+    // Only redirect traffic for the advertised address once we know it
+    let destination = if external_ip.is_unspecified() {
+        "any".to_string()
+    } else {
+        external_ip.to_string()
+    };
+
     let mut rules = String::new();
-    for mapping in mappings.values() {
+    for mapping in mappings {
         // rdr rule: redirect incoming traffic to internal host
         rules.push_str(&format!(
-            "rdr pass on {} proto {} from any to any port {} -> {} port {}\n",
+            "rdr pass on {} proto {} from any to {} port {} -> {} port {}\n",
             ext_ifname,
             mapping.protocol,
+            destination,
             mapping.external_port,
             mapping.internal_ip,
             mapping.internal_port,
@@ -472,32 +980,114 @@ async fn reload_anchor_rules(ext_ifname: &str, mappings: &HashMap<MappingKey, Ma
             ext_ifname, mapping.protocol, mapping.internal_ip, mapping.internal_port,
         ));
     }
+    rules
+}
 
-    let mut child = match Command::new("pfctl")
-        .args(["-a", PF_ANCHOR_NAME, "-f", "-"])
-        .stdin(std::process::Stdio::piped())
+/// Load `rules` into `anchor` through `run`, which runs pfctl with the given
+/// arguments and optional stdin and returns its stderr on failure.
+///
+/// Some pfctl builds can't read an anchor from stdin (`-f -`); if that fails
+/// the rules are written to a temp file and loaded from there instead.
+async fn load_anchor<F, Fut>(run: F, anchor: &str, rules: &str) -> std::result::Result<(), String>
+where
+    F: Fn(Vec<String>, Option<String>) -> Fut,
+    Fut: Future<Output = std::result::Result<(), String>>,
+{
+    let stdin_error = match run(load_args(anchor, "-"), Some(rules.to_string())).await {
+        Ok(()) => return Ok(()),
+        Err(e) => e,
+    };
+
+    let path = anchor_rules_path(anchor);
+    let path = path.to_string_lossy();
+    if let Err(e) = write_private(&path, rules).await {
+        return Err(format!(
+            "{} (and writing {} failed: {})",
+            stdin_error, path, e
+        ));
+    }
+    let result = run(load_args(anchor, &path), None).await;
+    let _ = tokio::fs::remove_file(path.as_ref()).await;
+    result.map_err(|e| format!("{} (retry from {}: {})", stdin_error, path, e))
+}
+
+/// Temp file the anchor rules are loaded from when stdin doesn't work.
+fn anchor_rules_path(anchor: &str) -> std::path::PathBuf {
+    let name = anchor.replace('/', "_");
+    std::env::temp_dir().join(format!("tunshare-{}-{}.conf", name, std::process::id()))
+}
+
+/// Write `contents` to a fresh file only we can read, replacing a stale one
+/// but never following a link planted in its place.
+async fn write_private(path: &str, contents: &str) -> std::io::Result<()> {
+    let _ = tokio::fs::remove_file(path).await;
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+        .await?;
+    file.write_all(contents.as_bytes()).await?;
+    file.flush().await
+}
+
+/// Run pfctl with `args`, feeding it `stdin` if given. Err carries its stderr.
+async fn run_pfctl(args: Vec<String>, stdin: Option<String>) -> std::result::Result<(), String> {
+    let mut child = Command::new("pfctl")
+        .args(&args)
+        .stdin(if stdin.is_some() {
+            std::process::Stdio::piped()
+        } else {
+            std::process::Stdio::null()
+        })
         .stdout(std::process::Stdio::null())
-        .stderr(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped())
         .spawn()
-    {
-        Ok(c) => c,
-        Err(_) => return,
-    };
+        .map_err(|e| format!("pfctl: {}", e))?;
+
+    if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        // Dropping the pipe closes it so pfctl sees the end of the rules
+        pipe.write_all(input.as_bytes())
+            .await
+            .map_err(|e| format!("writing to pfctl: {}", e))?;
+    }
 
-    if let Some(ref mut stdin) = child.stdin {
-        let _ = stdin.write_all(rules.as_bytes()).await;
-        let _ = stdin.shutdown().await;
+    let output = child
+        .wait_with_output()
+        .await
+        .map_err(|e| format!("pfctl: {}", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        Err(if stderr.is_empty() {
+            format!("pfctl {} exited with {}", args.join(" "), output.status)
+        } else {
+            stderr
+        })
     }
+}
 
-    let _ = child.wait().await;
+/// Flush all rules from `anchor` through `run` (see `load_anchor`).
+async fn flush_anchor<F, Fut>(run: F, anchor: &str)
+where
+    F: Fn(Vec<String>, Option<String>) -> Fut,
+    Fut: Future<Output = std::result::Result<(), String>>,
+{
+    let _ = run(flush_args(anchor).map(str::to_string).to_vec(), None).await;
 }
 
-/// Flush all rules from the natpmp anchor.
-async fn flush_anchor_rules() {
-    let _ = Command::new("pfctl")
-        .args(["-a", PF_ANCHOR_NAME, "-F", "all"])
-        .output()
-        .await;
+/// pfctl arguments to load rules into `anchor` from `source` (`-` = stdin).
+fn load_args(anchor: &str, source: &str) -> Vec<String> {
+    ["-a", anchor, "-f", source]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect()
+}
+
+/// pfctl arguments to flush all rules from `anchor`.
+fn flush_args(anchor: &str) -> [&str; 4] {
+    ["-a", anchor, "-F", "all"]
 }
 
 #[cfg(test)]
@@ -505,14 +1095,322 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_network_from_ip() {
+    fn test_anchor_name_in_pfctl_args() {
+        let server = NatPmpServer::new("utun4", "en0", "192.168.2.0/24");
+        assert_eq!(server.rules_anchor(), "tunshare-natpmp");
+        assert_eq!(
+            load_args(&server.rules_anchor(), "-"),
+            ["-a", "tunshare-natpmp", "-f", "-"]
+        );
+
+        let server = server.with_anchor("custom_anchor");
+        assert_eq!(
+            flush_args(&server.rules_anchor()),
+            ["-a", "custom_anchor", "-F", "all"]
+        );
+
+        assert!(NatPmpServer::is_valid_anchor_name("tunshare-natpmp"));
+        assert!(!NatPmpServer::is_valid_anchor_name(""));
+        assert!(!NatPmpServer::is_valid_anchor_name("bad anchor\""));
+    }
+
+    #[test]
+    fn test_rules_anchor() {
+        let server = NatPmpServer::new("utun4", "en0", "192.168.2.0/24").with_anchor("shared");
+        assert_eq!(server.rules_anchor(), "shared");
+
+        let server = server.with_preserve_existing(true);
+        assert_eq!(
+            server.rules_anchor(),
+            format!("shared/{}", std::process::id())
+        );
+        assert!(!anchor_rules_path(&server.rules_anchor())
+            .to_string_lossy()
+            .contains("shared/"));
+    }
+
+    #[tokio::test]
+    async fn test_preserve_existing_never_touches_shared_anchor() {
+        use std::sync::{Arc, Mutex};
+
+        let server = NatPmpServer::new("utun4", "en0", "192.168.2.0/24")
+            .with_anchor("shared")
+            .with_preserve_existing(true);
+        let own = server.rules_anchor();
+
+        let key = MappingKey {
+            protocol: Protocol::Udp,
+            external_port: 51413,
+        };
+        let mut mappings = HashMap::from([(
+            key,
+            Mapping {
+                internal_ip: Ipv4Addr::new(192, 168, 2, 50),
+                internal_port: 51413,
+                external_port: 51413,
+                protocol: Protocol::Udp,
+                lifetime_secs: 3600,
+                created_at: Instant::now(),
+            },
+        )]);
+
+        let calls: Arc<Mutex<Vec<Vec<String>>>> = Arc::default();
+        let seen = calls.clone();
+        let run = move |args: Vec<String>, _stdin: Option<String>| {
+            seen.lock().unwrap().push(args);
+            async { Ok(()) }
+        };
+        let (errors, _) = watch::channel(None);
+        let external = Ipv4Addr::new(10, 8, 0, 6);
+
+        // A mapping change, then the last mapping going away
+        reload_anchor_with(&run, "utun4", external, &own, &mappings, &errors).await;
+        assert!(evict_mapping(&run, key, "utun4", external, &own, &mut mappings, &errors).await);
+
+        let calls = calls.lock().unwrap();
+        assert_eq!(
+            *calls,
+            [
+                load_args(&own, "-"),
+                flush_args(&own).map(str::to_string).to_vec()
+            ]
+        );
+        assert!(calls.iter().all(|args| args[1] != "shared"));
+    }
+
+    #[tokio::test]
+    async fn test_startup_flush_targets_rules_anchor() {
+        use std::sync::{Arc, Mutex};
+
+        let calls: Arc<Mutex<Vec<Vec<String>>>> = Arc::default();
+        let seen = calls.clone();
+        let run = move |args: Vec<String>, _stdin: Option<String>| {
+            seen.lock().unwrap().push(args);
+            async { Ok(()) }
+        };
+
+        let server = NatPmpServer::new("utun4", "en0", "192.168.2.0/24").with_anchor("shared");
+        server.flush_stale_rules(&run).await;
+        let server = server.with_preserve_existing(true);
+        server.flush_stale_rules(&run).await;
+
+        let own = format!("shared/{}", std::process::id());
         assert_eq!(
-            NatPmpServer::network_from_ip(Ipv4Addr::new(192, 168, 2, 1)),
-            "192.168.2.0/24"
+            *calls.lock().unwrap(),
+            [
+                flush_args("shared").map(str::to_string).to_vec(),
+                flush_args(&own).map(str::to_string).to_vec()
+            ]
         );
+    }
+
+    #[test]
+    fn test_select_external_ip() {
+        let tunnel = Ipv4Addr::new(10, 8, 0, 6);
+        let public = Ipv4Addr::new(203, 0, 113, 7);
+        let addresses = [tunnel, public];
+
+        assert_eq!(select_external_ip(&addresses, None), Some(tunnel));
+        assert_eq!(select_external_ip(&addresses, Some(public)), Some(public));
+        // Preferred address no longer on the interface: fall back to the first
         assert_eq!(
-            NatPmpServer::network_from_ip(Ipv4Addr::new(10, 0, 0, 1)),
-            "10.0.0.0/24"
+            select_external_ip(&addresses, Some(Ipv4Addr::new(198, 51, 100, 1))),
+            Some(tunnel)
+        );
+        assert_eq!(select_external_ip(&[], Some(public)), None);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_external_ip_triggers_lookup() {
+        use std::sync::{Arc, Mutex};
+
+        let first = Ipv4Addr::new(10, 8, 0, 2);
+        let reconnected = Ipv4Addr::new(10, 8, 0, 9);
+        let addresses = Arc::new(Mutex::new(vec![first]));
+        let server = NatPmpServer::new("utun4", "en0", "192.168.2.0/24");
+
+        let source = addresses.clone();
+        let mut ip_rx = spawn_external_ip_monitor(
+            move || {
+                let current = source.lock().unwrap().clone();
+                async move { current }
+            },
+            None,
+            server.refresh_tx.subscribe(),
+            server.shutdown_tx.subscribe(),
+        )
+        .await;
+        assert_eq!(*ip_rx.borrow_and_update(), first);
+
+        // Well inside the periodic interval, so only the trigger can explain the update
+        *addresses.lock().unwrap() = vec![reconnected];
+        server.refresh_external_ip();
+        tokio::time::timeout(Duration::from_secs(5), ip_rx.changed())
+            .await
+            .expect("refresh should not wait for the timer")
+            .unwrap();
+        assert_eq!(*ip_rx.borrow(), reconnected);
+
+        server.shutdown();
+    }
+
+    #[test]
+    fn test_build_anchor_rules() {
+        let mapping = |protocol, external_port, internal_port| MappingSnapshot {
+            protocol,
+            external_port,
+            internal_ip: Ipv4Addr::new(192, 168, 2, 100),
+            internal_port,
+            created_at: Instant::now(),
+            lifetime_secs: 3600,
+        };
+        let mappings = [
+            mapping(Protocol::Udp, 51413, 51413),
+            mapping(Protocol::Tcp, 8080, 80),
+        ];
+
+        assert_eq!(
+            build_anchor_rules("utun4", Ipv4Addr::new(10, 8, 0, 2), &mappings),
+            "rdr pass on utun4 proto udp from any to 10.8.0.2 port 51413 -> 192.168.2.100 port 51413\n\
+             pass in quick on utun4 proto udp from any to 192.168.2.100 port 51413\n\
+             rdr pass on utun4 proto tcp from any to 10.8.0.2 port 8080 -> 192.168.2.100 port 80\n\
+             pass in quick on utun4 proto tcp from any to 192.168.2.100 port 80\n"
+        );
+
+        // Before the external address is known, any address matches
+        let rules = build_anchor_rules("utun4", Ipv4Addr::UNSPECIFIED, &mappings[1..]);
+        assert!(rules.starts_with("rdr pass on utun4 proto tcp from any to any port 8080 "));
+        assert!(build_anchor_rules("utun4", Ipv4Addr::UNSPECIFIED, &[]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_load_anchor_falls_back_to_temp_file() {
+        use std::sync::{Arc, Mutex};
+
+        let rules = "pass in quick on utun4 proto tcp from any to 192.168.2.100 port 8080\n";
+        let calls: Arc<Mutex<Vec<Vec<String>>>> = Arc::default();
+        let seen = calls.clone();
+        let run = move |args: Vec<String>, stdin: Option<String>| {
+            seen.lock().unwrap().push(args.clone());
+            async move {
+                match stdin {
+                    Some(_) => Err("pfctl: -f - not supported".to_string()),
+                    // The temp file holds the rules while pfctl reads it
+                    None => match std::fs::read_to_string(&args[3]) {
+                        Ok(contents) if contents == rules => Ok(()),
+                        other => Err(format!("unexpected file: {:?}", other)),
+                    },
+                }
+            }
+        };
+
+        assert_eq!(load_anchor(run, "tunshare-test", rules).await, Ok(()));
+        let calls = calls.lock().unwrap().clone();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0], load_args("tunshare-test", "-"));
+        let path = anchor_rules_path("tunshare-test");
+        assert_eq!(
+            calls[1],
+            load_args("tunshare-test", &path.to_string_lossy())
+        );
+        assert!(!path.exists(), "temp file should be removed");
+
+        // Both forms failing reports both reasons
+        let failing = |_: Vec<String>, _: Option<String>| async { Err("syntax error".to_string()) };
+        let error = load_anchor(failing, "tunshare-test2", rules)
+            .await
+            .unwrap_err();
+        assert!(error.starts_with("syntax error (retry from "));
+        assert!(!anchor_rules_path("tunshare-test2").exists());
+    }
+
+    #[tokio::test]
+    async fn test_evict_mapping_reloads_without_it() {
+        use std::sync::{Arc, Mutex};
+
+        let mapping = |port: u16, host: u8| Mapping {
+            internal_ip: Ipv4Addr::new(192, 168, 2, host),
+            internal_port: port,
+            external_port: port,
+            protocol: Protocol::Tcp,
+            lifetime_secs: 3600,
+            created_at: Instant::now(),
+        };
+        let key = |port: u16| MappingKey {
+            protocol: Protocol::Tcp,
+            external_port: port,
+        };
+        let mut mappings = HashMap::from([
+            (key(6881), mapping(6881, 50)),
+            (key(8080), mapping(8080, 51)),
+        ]);
+
+        // pfctl arguments and stdin of each call
+        type Calls = Vec<(Vec<String>, Option<String>)>;
+        let loads: Arc<Mutex<Calls>> = Arc::default();
+        let seen = loads.clone();
+        let run = move |args: Vec<String>, stdin: Option<String>| {
+            seen.lock().unwrap().push((args, stdin));
+            async { Ok(()) }
+        };
+        let (errors, _) = watch::channel(None);
+        let external = Ipv4Addr::new(10, 8, 0, 6);
+
+        assert!(
+            evict_mapping(
+                &run,
+                key(6881),
+                "utun4",
+                external,
+                "tunshare-test",
+                &mut mappings,
+                &errors
+            )
+            .await
+        );
+        {
+            let loads = loads.lock().unwrap();
+            assert_eq!(loads.len(), 1);
+            assert_eq!(loads[0].0, load_args("tunshare-test", "-"));
+            let rules = loads[0].1.as_deref().unwrap();
+            assert!(rules.contains("port 8080 -> 192.168.2.51"));
+            assert!(!rules.contains("6881"), "{}", rules);
+        }
+
+        // Unknown keys don't touch the anchor
+        assert!(
+            !evict_mapping(
+                &run,
+                key(6881),
+                "utun4",
+                external,
+                "tunshare-test",
+                &mut mappings,
+                &errors
+            )
+            .await
+        );
+        assert_eq!(loads.lock().unwrap().len(), 1);
+
+        // Evicting the last mapping empties the anchor
+        assert!(
+            evict_mapping(
+                &run,
+                key(8080),
+                "utun4",
+                external,
+                "tunshare-test",
+                &mut mappings,
+                &errors
+            )
+            .await
+        );
+        assert_eq!(
+            loads.lock().unwrap()[1],
+            (
+                flush_args("tunshare-test").map(str::to_string).to_vec(),
+                None
+            )
         );
     }
 
@@ -560,9 +1458,10 @@ mod tests {
 
     #[test]
     fn test_find_available_port() {
+        let range = DEFAULT_PORT_RANGE.0..=DEFAULT_PORT_RANGE.1;
         let mappings = HashMap::new();
         assert_eq!(
-            find_available_port(&mappings, Protocol::Tcp),
+            find_available_port(&mappings, Protocol::Tcp, &range),
             Some(MIN_ALLOWED_PORT)
         );
 
@@ -581,9 +1480,120 @@ mod tests {
                 created_at: Instant::now(),
             },
         );
-        assert_eq!(find_available_port(&mappings, Protocol::Tcp), Some(1025));
+        assert_eq!(
+            find_available_port(&mappings, Protocol::Tcp, &range),
+            Some(1025)
+        );
         // UDP should still find 1024 since it's a different protocol
-        assert_eq!(find_available_port(&mappings, Protocol::Udp), Some(1024));
+        assert_eq!(
+            find_available_port(&mappings, Protocol::Udp, &range),
+            Some(1024)
+        );
+        // Exhausted range
+        assert_eq!(
+            find_available_port(&mappings, Protocol::Tcp, &(1024..=1024)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_with_port_range() {
+        let server = NatPmpServer::new("utun4", "en0", "192.168.2.0/24");
+        assert_eq!(server.port_range, (1024..=65535));
+
+        let server = server.with_port_range(1024, 49151).unwrap();
+        assert_eq!(server.port_range, (1024..=49151));
+
+        // Never below 1024
+        let server = server.with_port_range(80, 2000).unwrap();
+        assert_eq!(server.port_range, (1024..=2000));
+
+        // Inverted, or nothing left above 1023
+        assert!(matches!(
+            NatPmpServer::new("utun4", "en0", "192.168.2.0/24").with_port_range(5000, 4000),
+            Err(TunshareError::InvalidPortRange {
+                low: 5000,
+                high: 4000
+            })
+        ));
+        assert!(NatPmpServer::new("utun4", "en0", "192.168.2.0/24")
+            .with_port_range(80, 443)
+            .is_err());
+    }
+
+    /// Send a TCP map request for `external_port` and return the response's
+    /// result code and assigned external port, plus the anchor rules it
+    /// loaded (if any).
+    async fn map_tcp(
+        external_port: u16,
+        range: &RangeInclusive<u16>,
+    ) -> (u16, u16, Option<String>) {
+        map_tcp_from("192.168.2.100:5350".parse().unwrap(), external_port, range).await
+    }
+
+    /// [`map_tcp`] from a given client address.
+    async fn map_tcp_from(
+        src: SocketAddr,
+        external_port: u16,
+        range: &RangeInclusive<u16>,
+    ) -> (u16, u16, Option<String>) {
+        use std::sync::{Arc, Mutex};
+        let mut request = vec![0u8, 2, 0, 0];
+        request.extend_from_slice(&8080u16.to_be_bytes());
+        request.extend_from_slice(&external_port.to_be_bytes());
+        request.extend_from_slice(&3600u32.to_be_bytes());
+
+        let loaded: Arc<Mutex<Option<String>>> = Arc::default();
+        let seen = loaded.clone();
+        let run = move |_args: Vec<String>, stdin: Option<String>| {
+            *seen.lock().unwrap() = stdin;
+            async { Ok(()) }
+        };
+
+        let mut mappings = HashMap::new();
+        let resp = handle_request(
+            run,
+            &request,
+            src,
+            Ipv4Addr::new(10, 8, 0, 2),
+            Instant::now(),
+            "utun-test",
+            "tunshare-natpmp-test",
+            range,
+            &mut mappings,
+            &watch::channel(None).0,
+        )
+        .await
+        .unwrap();
+
+        let result_code = u16::from_be_bytes([resp[2], resp[3]]);
+        let assigned = if resp.len() >= 12 {
+            u16::from_be_bytes([resp[10], resp[11]])
+        } else {
+            0
+        };
+        let rules = loaded.lock().unwrap().take();
+        (result_code, assigned, rules)
+    }
+
+    #[tokio::test]
+    async fn test_port_range_boundaries() {
+        let range = 1024..=49151;
+        let rdr = |port: u16| {
+            format!(
+                "rdr pass on utun-test proto tcp from any to 10.8.0.2 port {} -> 192.168.2.100 port 8080\n\
+                 pass in quick on utun-test proto tcp from any to 192.168.2.100 port 8080\n",
+                port
+            )
+        };
+
+        assert_eq!(map_tcp(1024, &range).await, (0, 1024, Some(rdr(1024))));
+        assert_eq!(map_tcp(49151, &range).await, (0, 49151, Some(rdr(49151))));
+        // Refused without touching the anchor
+        assert_eq!(map_tcp(1023, &range).await, (2, 0, None));
+        assert_eq!(map_tcp(49152, &range).await, (2, 0, None));
+        // No preference: first free port in the range
+        assert_eq!(map_tcp(0, &range).await, (0, 1024, Some(rdr(1024))));
     }
 
     #[test]
@@ -600,6 +1610,60 @@ mod tests {
         assert!(!is_lan_client(&boundary, lan));
     }
 
+    #[tokio::test]
+    async fn test_mapping_from_ipv4_mapped_client() {
+        let range = 1024..=49151;
+        let mapped: SocketAddr = "[::ffff:192.168.2.100]:5350".parse().unwrap();
+        let rules = "rdr pass on utun-test proto tcp from any to 10.8.0.2 port 40000 -> 192.168.2.100 port 8080\n\
+                     pass in quick on utun-test proto tcp from any to 192.168.2.100 port 8080\n";
+        assert_eq!(
+            map_tcp_from(mapped, 40000, &range).await,
+            (0, 40000, Some(rules.to_string()))
+        );
+
+        // Native IPv6 clients have no IPv4 address to map to
+        let native: SocketAddr = "[fe80::1]:5350".parse().unwrap();
+        assert_eq!(map_tcp_from(native, 40000, &range).await, (2, 0, None));
+    }
+
+    #[test]
+    fn test_announce_addr() {
+        assert_eq!(announce_addr(false), "224.0.0.1:5350".parse().unwrap());
+        assert_eq!(
+            announce_addr(true),
+            "[::ffff:224.0.0.1]:5350".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_is_lan_client_ipv4_mapped() {
+        let lan = "192.168.2.0/24";
+
+        let mapped: SocketAddr = "[::ffff:192.168.2.100]:12345".parse().unwrap();
+        assert!(is_lan_client(&mapped, lan));
+
+        let mapped_off_lan: SocketAddr = "[::ffff:10.0.0.1]:12345".parse().unwrap();
+        assert!(!is_lan_client(&mapped_off_lan, lan));
+
+        let link_local: SocketAddr = "[fe80::1]:12345".parse().unwrap();
+        assert!(!is_lan_client(&link_local, lan));
+    }
+
+    #[test]
+    fn test_parse_lsof_holder() {
+        let output = "\
+COMMAND     PID USER   FD   TYPE             DEVICE SIZE/OFF NODE NAME
+miniupnpd  4321 root    7u  IPv4 0x1234567890abcdef      0t0  UDP *:5351
+";
+        assert_eq!(
+            parse_lsof_holder(output),
+            Some((4321, "miniupnpd".to_string()))
+        );
+
+        // No holder: lsof prints nothing
+        assert_eq!(parse_lsof_holder(""), None);
+    }
+
     #[test]
     fn test_mapping_expiry() {
         let mapping = Mapping {
@@ -1,23 +1,100 @@
 //! Network interface detection for VPN and LAN interfaces.
 
 use crate::error::{Result, TunshareError};
+use crate::system::exec::{self, RetryPolicy};
+use ipnet::Ipv4Net;
+use std::collections::HashMap;
+use std::future::Future;
 use std::net::Ipv4Addr;
+use std::process::{Output, Stdio};
+use std::time::Duration;
 use tokio::process::Command;
 
+/// How long `networksetup` may take before hardware-port descriptions are skipped.
+/// Under sudo it can hang waiting on a privacy prompt nobody will answer, and
+/// it shares the interface detection budget with everything else.
+const NETWORKSETUP_TIMEOUT: Duration = Duration::from_secs(2);
+
 /// Information about a network interface.
 #[derive(Debug, Clone)]
 pub struct InterfaceInfo {
     pub name: String,
+    /// First IPv4 address (`ipv4_addresses[0]`).
     pub ipv4_address: Option<Ipv4Addr>,
+    /// Every IPv4 address, in `ifconfig` order.
+    pub ipv4_addresses: Vec<Ipv4Addr>,
+    /// Far end of a point-to-point link (`inet A --> B`), typical for VPN tunnels.
+    pub peer_ipv4: Option<Ipv4Addr>,
+    /// Broadcast address of a broadcast-capable link (`... broadcast B`).
+    pub broadcast_ipv4: Option<Ipv4Addr>,
+    /// Netmask of the primary address (`netmask 0xffffff00`).
+    pub netmask: Option<Ipv4Addr>,
     pub description: Option<String>,
     pub is_up: bool,
+    /// `RUNNING` flag: the link is live (a tunnel connected, a cable in).
+    pub is_running: bool,
+    /// Member ports of a bridge (`member: en1 ...` lines), in `ifconfig` order.
+    pub members: Vec<String>,
+}
+
+impl InterfaceInfo {
+    /// How the interface is shown everywhere in the UI: `en5 (USB 10/100/1000 LAN)`,
+    /// or with a kind tag when there's no description, e.g. `ppp0 [PPP]`.
+    pub fn display_label(&self) -> String {
+        match (&self.description, self.kind()) {
+            (Some(desc), _) => format!("{} ({})", self.name, desc),
+            (None, Some(kind)) => format!("{} [{}]", self.name, kind),
+            (None, None) => self.name.clone(),
+        }
+    }
+
+    /// Interface kind guessed from the name, for interfaces `networksetup`
+    /// doesn't describe. None for `en*`/`utun*`, whose lists already say what they are.
+    fn kind(&self) -> Option<&'static str> {
+        [("bridge", "bridge"), ("ppp", "PPP"), ("ipsec", "IPsec")]
+            .into_iter()
+            .find(|(prefix, _)| self.name.starts_with(prefix))
+            .map(|(_, kind)| kind)
+    }
+
+    /// Whether the name or description contains `needle`, ignoring case.
+    pub fn matches_filter(&self, needle: &str) -> bool {
+        let needle = needle.to_lowercase();
+        self.name.to_lowercase().contains(&needle)
+            || self
+                .description
+                .as_ref()
+                .is_some_and(|d| d.to_lowercase().contains(&needle))
+    }
+
+    /// Network of the primary address, e.g. `192.168.2.0/24`.
+    pub fn subnet(&self) -> Option<Ipv4Net> {
+        let net = Ipv4Net::with_netmask(self.ipv4_address?, self.netmask?).ok()?;
+        Some(net.trunc())
+    }
+
+    /// Whether the IPv4 address is private (RFC 1918), carrier-grade NAT
+    /// space (100.64.0.0/10) or link-local (169.254.0.0/16, self-assigned).
+    /// False when the interface has no IPv4 address.
+    pub fn is_private_ipv4(&self) -> bool {
+        self.ipv4_address.is_some_and(|ip| {
+            let o = ip.octets();
+            ip.is_private() || ip.is_link_local() || (o[0] == 100 && (o[1] & 0xc0) == 64)
+        })
+    }
+}
+
+/// LAN interfaces found by [`detect_lan_interfaces`].
+#[derive(Debug, Clone, Default)]
+pub struct LanInterfaces {
+    pub interfaces: Vec<InterfaceInfo>,
+    /// Why hardware-port descriptions are missing, if `networksetup` failed.
+    pub descriptions_unavailable: Option<String>,
 }
 
 /// Detect VPN interfaces (utun* with IPv4 and point-to-point flag).
-pub async fn detect_vpn_interfaces() -> Result<Vec<InterfaceInfo>> {
-    let output = Command::new("ifconfig")
-        .arg("-a")
-        .output()
+pub async fn detect_vpn_interfaces(retry: RetryPolicy) -> Result<Vec<InterfaceInfo>> {
+    let output = exec::output_with_retry(retry, "ifconfig", &["-a"])
         .await
         .map_err(|e| TunshareError::CommandFailed {
             command: "ifconfig -a".into(),
@@ -38,25 +115,36 @@ pub async fn detect_vpn_interfaces() -> Result<Vec<InterfaceInfo>> {
     Ok(vpn_interfaces)
 }
 
-/// Detect LAN interfaces using networksetup to get hardware ports.
-pub async fn detect_lan_interfaces() -> Result<Vec<InterfaceInfo>> {
-    // Get hardware ports mapping
-    let ports_output = Command::new("networksetup")
-        .args(["-listallhardwareports"])
-        .output()
-        .await
-        .map_err(|e| TunshareError::CommandFailed {
-            command: "networksetup -listallhardwareports".into(),
-            message: e.to_string(),
-        })?;
+/// First and last usable host address of `net`.
+/// /31 and /32 have no network or broadcast address to leave out.
+pub fn host_range(net: Ipv4Net) -> (Ipv4Addr, Ipv4Addr) {
+    if net.prefix_len() >= 31 {
+        return (net.network(), net.broadcast());
+    }
+    let first = Ipv4Addr::from(u32::from(net.network()) + 1);
+    let last = Ipv4Addr::from(u32::from(net.broadcast()) - 1);
+    (first, last)
+}
+
+/// Move recently used interfaces to the front, most recent first (`mru` is
+/// ordered that way). The rest keep their detection order after them.
+pub fn sort_by_mru(interfaces: &mut [InterfaceInfo], mru: &[String]) {
+    interfaces.sort_by_key(|iface| {
+        mru.iter()
+            .position(|name| *name == iface.name)
+            .unwrap_or(usize::MAX)
+    });
+}
 
-    let ports_stdout = String::from_utf8_lossy(&ports_output.stdout);
-    let port_map = parse_hardware_ports(&ports_stdout);
+/// Detect LAN interfaces, described by their hardware port from `networksetup`.
+///
+/// If `networksetup` fails or hangs (e.g. blocked by macOS privacy controls),
+/// interfaces are classified from `ifconfig` alone, without descriptions.
+pub async fn detect_lan_interfaces(retry: RetryPolicy) -> Result<LanInterfaces> {
+    let port_map = hardware_ports().await;
 
     // Get interface details from ifconfig
-    let ifconfig_output = Command::new("ifconfig")
-        .arg("-a")
-        .output()
+    let ifconfig_output = exec::output_with_retry(retry, "ifconfig", &["-a"])
         .await
         .map_err(|e| TunshareError::CommandFailed {
             command: "ifconfig -a".into(),
@@ -64,22 +152,102 @@ pub async fn detect_lan_interfaces() -> Result<Vec<InterfaceInfo>> {
         })?;
 
     let ifconfig_stdout = String::from_utf8_lossy(&ifconfig_output.stdout);
-    let mut interfaces = parse_interfaces(&ifconfig_stdout);
-
-    // Filter to LAN interfaces (en*) that are up with IPv4
-    let lan_interfaces: Vec<InterfaceInfo> = interfaces
-        .iter_mut()
-        .filter(|iface| iface.name.starts_with("en") && iface.is_up && iface.ipv4_address.is_some())
-        .map(|iface| {
-            // Add description from hardware ports
-            if let Some(desc) = port_map.get(&iface.name) {
-                iface.description = Some(desc.clone());
-            }
-            iface.clone()
-        })
+    let interfaces = parse_interfaces(&ifconfig_stdout);
+
+    Ok(LanInterfaces {
+        interfaces: classify_lan_interfaces(interfaces, port_map.as_ref().ok()),
+        descriptions_unavailable: port_map.err(),
+    })
+}
+
+/// Map of device name -> hardware port name from `networksetup`,
+/// or why it couldn't be read.
+async fn hardware_ports() -> std::result::Result<HashMap<String, String>, String> {
+    let command = Command::new("networksetup")
+        .args(["-listallhardwareports"])
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    read_hardware_ports(command, NETWORKSETUP_TIMEOUT).await
+}
+
+/// Wait up to `timeout` for `command` (a `networksetup` run) and parse its
+/// output. Dropping the command on timeout kills it.
+async fn read_hardware_ports<F>(
+    command: F,
+    timeout: Duration,
+) -> std::result::Result<HashMap<String, String>, String>
+where
+    F: Future<Output = std::io::Result<Output>>,
+{
+    let output = match tokio::time::timeout(timeout, command).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => return Err(e.to_string()),
+        Err(_) => return Err("networksetup timed out".to_string()),
+    };
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("networksetup failed: {}", stderr.trim()));
+    }
+
+    Ok(parse_hardware_ports(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+/// Keep LAN interfaces (en* and bridge*) that are up, described by hardware
+/// port when `port_map` is available. Ones without IPv4 are kept too:
+/// tunshare can assign them a gateway address (see `system::ifaddr`).
+///
+/// A bridge shares to every wired client on its member ports at once, so
+/// its members aren't offered on their own.
+fn classify_lan_interfaces(
+    interfaces: Vec<InterfaceInfo>,
+    port_map: Option<&HashMap<String, String>>,
+) -> Vec<InterfaceInfo> {
+    let members: Vec<String> = interfaces
+        .iter()
+        .flat_map(|iface| iface.members.iter().cloned())
         .collect();
+    interfaces
+        .into_iter()
+        .filter(|iface| {
+            (iface.name.starts_with("en") || iface.name.starts_with("bridge"))
+                && iface.is_up
+                && !members.contains(&iface.name)
+        })
+        .map(|mut iface| {
+            iface.description = port_map.and_then(|map| map.get(&iface.name).cloned());
+            iface
+        })
+        .collect()
+}
 
-    Ok(lan_interfaces)
+/// Look up the interface the routing table uses for `destination`
+/// (`route -n get <destination>`). Returns `None` if the lookup fails.
+pub async fn route_interface(destination: &str) -> Option<String> {
+    let output = Command::new("route")
+        .args(["-n", "get", destination])
+        .output()
+        .await
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    parse_route_interface(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Extract the `interface:` field from `route -n get` output.
+fn parse_route_interface(output: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        line.trim()
+            .strip_prefix("interface:")
+            .map(|iface| iface.trim().to_string())
+            .filter(|iface| !iface.is_empty())
+    })
 }
 
 /// Parse ifconfig output to extract interface information.
@@ -98,25 +266,56 @@ fn parse_interfaces(output: &str) -> Vec<InterfaceInfo> {
             // Parse interface name (everything before first colon)
             if let Some(name_end) = line.find(':') {
                 let name = line[..name_end].to_string();
-                let is_up = line.contains("<UP");
+                let is_up = has_flag(&line[name_end + 1..], "UP");
+                let is_running = has_flag(&line[name_end + 1..], "RUNNING");
 
                 current_iface = Some(InterfaceInfo {
                     name,
                     ipv4_address: None,
+                    ipv4_addresses: Vec::new(),
+                    peer_ipv4: None,
+                    broadcast_ipv4: None,
+                    netmask: None,
                     description: None,
                     is_up,
+                    is_running,
+                    members: Vec::new(),
                 });
             }
         } else if let Some(ref mut iface) = current_iface {
             // Parse inet line for IPv4 address
-            let trimmed = line.trim();
-            if trimmed.starts_with("inet ") {
-                // Format: inet 10.8.0.6 --> 10.8.0.5 netmask 0xffffffff
-                // or:     inet 192.168.2.1 netmask 0xffffff00 broadcast 192.168.2.255
-                let parts: Vec<&str> = trimmed.split_whitespace().collect();
-                if parts.len() >= 2 {
-                    iface.ipv4_address = parts[1].parse::<Ipv4Addr>().ok();
+            // Format: inet 10.8.0.6 --> 10.8.0.5 netmask 0xffffffff
+            // or:     inet 192.168.2.1 netmask 0xffffff00 broadcast 192.168.2.255
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            // Bridge member port: member: en1 flags=3<LEARNING,DISCOVER>
+            if parts.len() >= 2 && parts[0] == "member:" {
+                iface.members.push(parts[1].to_string());
+                continue;
+            }
+            if parts.len() >= 2 && parts[0] == "inet" {
+                let Ok(ip) = parts[1].parse::<Ipv4Addr>() else {
+                    continue;
+                };
+                iface.ipv4_addresses.push(ip);
+                // Peer and broadcast describe the primary (first) address
+                if iface.ipv4_address.is_some() {
+                    continue;
                 }
+                let after = |keyword: &str| {
+                    parts
+                        .iter()
+                        .position(|p| *p == keyword)
+                        .and_then(|i| parts.get(i + 1))
+                        .and_then(|p| p.parse::<Ipv4Addr>().ok())
+                };
+                iface.ipv4_address = Some(ip);
+                iface.peer_ipv4 = after("-->");
+                iface.broadcast_ipv4 = after("broadcast");
+                iface.netmask = parts
+                    .iter()
+                    .position(|p| *p == "netmask")
+                    .and_then(|i| parts.get(i + 1))
+                    .and_then(|mask| parse_hex_netmask(mask));
             }
         }
     }
@@ -129,10 +328,38 @@ fn parse_interfaces(output: &str) -> Vec<InterfaceInfo> {
     interfaces
 }
 
+/// Parse an ifconfig netmask like `0xffffff00`.
+fn parse_hex_netmask(mask: &str) -> Option<Ipv4Addr> {
+    let hex = mask.strip_prefix("0x")?;
+    u32::from_str_radix(hex, 16).ok().map(Ipv4Addr::from)
+}
+
+/// Check whether the `flags=NNNN<A,B,C>` field of an ifconfig header line
+/// contains `flag` as an exact comma-delimited token.
+fn has_flag(header: &str, flag: &str) -> bool {
+    let Some(flags) = header
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix("flags="))
+    else {
+        return false;
+    };
+
+    let Some(start) = flags.find('<') else {
+        return false;
+    };
+    let Some(end) = flags[start..].find('>') else {
+        return false;
+    };
+
+    flags[start + 1..start + end]
+        .split(',')
+        .any(|token| token.trim() == flag)
+}
+
 /// Parse networksetup -listallhardwareports output.
 /// Returns a map of device name -> hardware port name.
-fn parse_hardware_ports(output: &str) -> std::collections::HashMap<String, String> {
-    let mut map = std::collections::HashMap::new();
+fn parse_hardware_ports(output: &str) -> HashMap<String, String> {
+    let mut map = HashMap::new();
     let mut current_port: Option<String> = None;
 
     for line in output.lines() {
@@ -155,10 +382,55 @@ fn parse_hardware_ports(output: &str) -> std::collections::HashMap<String, Strin
     map
 }
 
+/// An up, running interface named `name` with no addresses, for tests to
+/// build variants of with `..iface(name)`.
+#[cfg(test)]
+pub(crate) fn iface(name: &str) -> InterfaceInfo {
+    InterfaceInfo {
+        name: name.to_string(),
+        ipv4_address: None,
+        ipv4_addresses: Vec::new(),
+        peer_ipv4: None,
+        broadcast_ipv4: None,
+        netmask: None,
+        description: None,
+        is_up: true,
+        is_running: true,
+        members: Vec::new(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[tokio::test]
+    async fn test_slow_networksetup_is_skipped() {
+        use std::os::unix::process::ExitStatusExt;
+        use std::time::Instant;
+
+        let output = |stdout: &str| Output {
+            status: std::process::ExitStatus::from_raw(0),
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: Vec::new(),
+        };
+
+        let started = Instant::now();
+        let slow = async {
+            tokio::time::sleep(Duration::from_secs(30)).await;
+            Ok(output(""))
+        };
+        let result = read_hardware_ports(slow, Duration::from_millis(50)).await;
+        assert_eq!(result, Err("networksetup timed out".to_string()));
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        let fast = async { Ok(output("Hardware Port: Thunderbolt Ethernet\nDevice: en5\n")) };
+        let ports = read_hardware_ports(fast, Duration::from_millis(50))
+            .await
+            .unwrap();
+        assert_eq!(ports["en5"], "Thunderbolt Ethernet");
+    }
+
     #[test]
     fn test_parse_interfaces() {
         let output = r#"lo0: flags=8049<UP,LOOPBACK,RUNNING,MULTICAST> mtu 16384
@@ -180,6 +452,271 @@ utun3: flags=8051<UP,POINTOPOINT,RUNNING,MULTICAST> mtu 1500
 
         let utun3 = interfaces.iter().find(|i| i.name == "utun3").unwrap();
         assert!(utun3.is_up);
+        assert!(utun3.is_running);
         assert_eq!(utun3.ipv4_address, Some(Ipv4Addr::new(10, 8, 0, 6)));
     }
+
+    #[test]
+    fn test_parse_interfaces_peer_and_broadcast() {
+        let output = "en0: flags=8863<UP,BROADCAST,RUNNING> mtu 1500
+	inet 192.168.2.1 netmask 0xffffff00 broadcast 192.168.2.255
+utun3: flags=8051<UP,POINTOPOINT,RUNNING> mtu 1500
+	inet 10.8.0.6 --> 10.8.0.5 netmask 0xffffffff
+lo0: flags=8049<UP,LOOPBACK,RUNNING> mtu 16384
+	inet 127.0.0.1 netmask 0xff000000
+";
+
+        let interfaces = parse_interfaces(output);
+        let find = |name: &str| interfaces.iter().find(|i| i.name == name).unwrap();
+
+        let en0 = find("en0");
+        assert_eq!(en0.peer_ipv4, None);
+        assert_eq!(en0.broadcast_ipv4, Some(Ipv4Addr::new(192, 168, 2, 255)));
+
+        let utun3 = find("utun3");
+        assert_eq!(utun3.ipv4_address, Some(Ipv4Addr::new(10, 8, 0, 6)));
+        assert_eq!(utun3.peer_ipv4, Some(Ipv4Addr::new(10, 8, 0, 5)));
+        assert_eq!(utun3.broadcast_ipv4, None);
+
+        let lo0 = find("lo0");
+        assert_eq!(lo0.peer_ipv4, None);
+        assert_eq!(lo0.broadcast_ipv4, None);
+    }
+
+    #[test]
+    fn test_subnet_and_host_range() {
+        let output = "en0: flags=8863<UP,BROADCAST,RUNNING> mtu 1500
+	inet 192.168.2.1 netmask 0xffffff00 broadcast 192.168.2.255
+en5: flags=8863<UP,BROADCAST,RUNNING> mtu 1500
+	inet 10.0.3.9 netmask 0xfffffffe
+utun3: flags=8051<UP,POINTOPOINT,RUNNING> mtu 1500
+	inet 10.8.0.6 --> 10.8.0.5
+";
+
+        let interfaces = parse_interfaces(output);
+        let en0 = &interfaces[0];
+        assert_eq!(en0.netmask, Some(Ipv4Addr::new(255, 255, 255, 0)));
+        assert_eq!(en0.subnet(), Some("192.168.2.0/24".parse().unwrap()));
+        assert_eq!(
+            en0.subnet().map(host_range),
+            Some((
+                Ipv4Addr::new(192, 168, 2, 1),
+                Ipv4Addr::new(192, 168, 2, 254)
+            ))
+        );
+
+        // Point-to-point /31: both addresses are usable
+        let en5 = &interfaces[1];
+        assert_eq!(
+            en5.subnet().map(host_range),
+            Some((Ipv4Addr::new(10, 0, 3, 8), Ipv4Addr::new(10, 0, 3, 9)))
+        );
+
+        assert_eq!(interfaces[2].subnet(), None);
+    }
+
+    #[test]
+    fn test_parse_interfaces_multiple_addresses() {
+        let output = "utun4: flags=8051<UP,POINTOPOINT,RUNNING> mtu 1400
+	inet 10.8.0.6 --> 10.8.0.5 netmask 0xffffffff
+	inet 203.0.113.7 --> 203.0.113.1 netmask 0xffffffff
+	inet6 fe80::1%utun4 prefixlen 64 scopeid 0x12
+";
+
+        let interfaces = parse_interfaces(output);
+        let utun4 = &interfaces[0];
+        assert_eq!(utun4.ipv4_address, Some(Ipv4Addr::new(10, 8, 0, 6)));
+        assert_eq!(
+            utun4.ipv4_addresses,
+            vec![Ipv4Addr::new(10, 8, 0, 6), Ipv4Addr::new(203, 0, 113, 7)]
+        );
+        // Peer of the first address, not the last one seen
+        assert_eq!(utun4.peer_ipv4, Some(Ipv4Addr::new(10, 8, 0, 5)));
+    }
+
+    #[test]
+    fn test_parse_interfaces_up_flag_is_exact_token() {
+        let output = "lo0: flags=8049<LOOPBACK,UP,RUNNING> mtu 16384
+	inet 127.0.0.1 netmask 0xff000000
+en5: flags=8822<GROUP_UP_NONSENSE,BROADCAST> mtu 1500
+	inet 192.168.3.1 netmask 0xffffff00
+en6: flags=8822<UPPER,BROADCAST> mtu 1500
+bridge0: flags=8863<UP> mtu 1500
+gif0: flags=8010<POINTOPOINT,MULTICAST> mtu 1280
+";
+
+        let interfaces = parse_interfaces(output);
+        let is_up = |name: &str| interfaces.iter().find(|i| i.name == name).unwrap().is_up;
+
+        assert!(is_up("lo0"));
+        assert!(!is_up("en5"));
+        assert!(!is_up("en6"));
+        assert!(is_up("bridge0"));
+        assert!(!is_up("gif0"));
+    }
+
+    #[test]
+    fn test_is_private_ipv4() {
+        let with_ip = |ip: Option<Ipv4Addr>| InterfaceInfo {
+            ipv4_address: ip,
+            ipv4_addresses: ip.into_iter().collect(),
+            ..iface("en0")
+        };
+        let private = |a, b, c, d| with_ip(Some(Ipv4Addr::new(a, b, c, d))).is_private_ipv4();
+
+        assert!(private(10, 1, 2, 3));
+        assert!(private(172, 16, 0, 1));
+        assert!(private(172, 31, 255, 254));
+        assert!(!private(172, 32, 0, 1));
+        assert!(private(192, 168, 2, 1));
+        assert!(private(100, 64, 0, 1));
+        assert!(private(100, 127, 255, 254));
+        assert!(!private(100, 128, 0, 1));
+        assert!(private(169, 254, 12, 34));
+        assert!(!private(169, 255, 0, 1));
+        assert!(!private(8, 8, 8, 8));
+        assert!(!with_ip(None).is_private_ipv4());
+    }
+
+    #[test]
+    fn test_matches_filter() {
+        let usb = InterfaceInfo {
+            description: Some("USB 10/100/1000 LAN".to_string()),
+            ..iface("en5")
+        };
+        assert!(usb.matches_filter(""));
+        assert!(usb.matches_filter("EN5"));
+        assert!(usb.matches_filter("usb"));
+        assert!(!usb.matches_filter("utun"));
+    }
+
+    #[test]
+    fn test_sort_by_mru() {
+        let mut interfaces: Vec<InterfaceInfo> =
+            ["utun0", "utun3", "utun4", "utun7"].map(iface).into();
+        let mru = [
+            "utun7".to_string(),
+            "utun9".to_string(),
+            "utun3".to_string(),
+        ];
+
+        sort_by_mru(&mut interfaces, &mru);
+        let names: Vec<&str> = interfaces.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(names, ["utun7", "utun3", "utun0", "utun4"]);
+    }
+
+    #[test]
+    fn test_display_label() {
+        let mut iface = InterfaceInfo {
+            description: Some("USB 10/100/1000 LAN".to_string()),
+            ..iface("en5")
+        };
+        assert_eq!(iface.display_label(), "en5 (USB 10/100/1000 LAN)");
+
+        iface.description = None;
+        assert_eq!(iface.display_label(), "en5");
+
+        iface.name = "ppp0".to_string();
+        assert_eq!(iface.display_label(), "ppp0 [PPP]");
+        iface.description = Some("PPPoE".to_string());
+        assert_eq!(iface.display_label(), "ppp0 (PPPoE)");
+    }
+
+    #[test]
+    fn test_has_flag() {
+        assert!(has_flag(" flags=8049<LOOPBACK,UP,RUNNING> mtu 16384", "UP"));
+        assert!(has_flag(
+            " flags=8049<LOOPBACK,UP,RUNNING> mtu 16384",
+            "RUNNING"
+        ));
+        assert!(!has_flag(" flags=8822<GROUP_UP_NONSENSE> mtu 1500", "UP"));
+        assert!(!has_flag(" mtu 1500", "UP"));
+        assert!(!has_flag(" flags=8049<LOOPBACK,UP", "UP"));
+    }
+
+    #[test]
+    fn test_parse_route_interface() {
+        let output = "   route to: default
+destination: default
+       mask: default
+    gateway: 192.168.1.1
+  interface: en0
+      flags: <UP,GATEWAY,DONE,STATIC,PRCLONING>
+";
+        assert_eq!(parse_route_interface(output), Some("en0".to_string()));
+        assert_eq!(
+            parse_route_interface("route: writing to routing socket: not in table\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_classify_lan_interfaces_without_networksetup() {
+        let output = "lo0: flags=8049<UP,LOOPBACK,RUNNING> mtu 16384
+	inet 127.0.0.1 netmask 0xff000000
+en0: flags=8863<UP,BROADCAST,RUNNING> mtu 1500
+	inet 192.168.2.1 netmask 0xffffff00 broadcast 192.168.2.255
+en1: flags=8822<BROADCAST,SMART> mtu 1500
+	inet 192.168.9.1 netmask 0xffffff00
+en5: flags=8863<UP,BROADCAST,RUNNING> mtu 1500
+	ether aa:bb:cc:dd:ee:ff
+utun3: flags=8051<UP,POINTOPOINT,RUNNING> mtu 1500
+	inet 10.8.0.6 --> 10.8.0.5 netmask 0xffffffff
+";
+        let interfaces = parse_interfaces(output);
+
+        let fallback = classify_lan_interfaces(interfaces.clone(), None);
+        let names: Vec<&str> = fallback.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(names, ["en0", "en5"]);
+        assert_eq!(fallback[0].description, None);
+        // Up without an address: kept, a gateway address can be assigned
+        assert_eq!(fallback[1].ipv4_address, None);
+
+        let ports = HashMap::from([("en0".to_string(), "Wi-Fi".to_string())]);
+        let described = classify_lan_interfaces(interfaces, Some(&ports));
+        assert_eq!(described.len(), 2);
+        assert_eq!(described[0].description.as_deref(), Some("Wi-Fi"));
+    }
+
+    #[test]
+    fn test_classify_bridge_with_members() {
+        let output = "en0: flags=8863<UP,BROADCAST,RUNNING> mtu 1500
+	inet 192.168.1.20 netmask 0xffffff00 broadcast 192.168.1.255
+bridge0: flags=8863<UP,BROADCAST,SMART,RUNNING,SIMPLEX,MULTICAST> mtu 1500
+	options=63<RXCSUM,TXCSUM,TSO4,TSO6>
+	ether 36:1d:5a:08:c4:00
+	inet 192.168.2.1 netmask 0xffffff00 broadcast 192.168.2.255
+	Configuration:
+		id 0:0:0:0:0:0 priority 0 hellotime 0 fwddelay 0
+		maxage 0 holdcnt 0 proto stp maxaddr 100 timeout 1200
+		root id 0:0:0:0:0:0 priority 0 ifcost 0 port 0
+		ipfilter disabled flags 0x0
+	member: en1 flags=3<LEARNING,DISCOVER>
+	        ifmaxaddr 0 port 9 priority 0 path cost 0
+	member: en2 flags=3<LEARNING,DISCOVER>
+	        ifmaxaddr 0 port 10 priority 0 path cost 0
+	status: active
+bridge1: flags=8822<BROADCAST,SMART,SIMPLEX,MULTICAST> mtu 1500
+en1: flags=8963<UP,BROADCAST,SMART,RUNNING,PROMISC,SIMPLEX,MULTICAST> mtu 1500
+	options=460<TSO4,TSO6,CHANNEL_IO>
+	ether 36:1d:5a:08:c4:00
+	media: autoselect <full-duplex>
+	status: inactive
+en2: flags=8963<UP,BROADCAST,SMART,RUNNING,PROMISC,SIMPLEX,MULTICAST> mtu 1500
+	options=460<TSO4,TSO6,CHANNEL_IO>
+	ether 36:1d:5a:08:c4:04
+	media: autoselect <full-duplex>
+	status: inactive
+";
+        // The member ports are only offered through the bridge
+        let lan = classify_lan_interfaces(parse_interfaces(output), None);
+        let names: Vec<&str> = lan.iter().map(|i| i.name.as_str()).collect();
+        assert_eq!(names, ["en0", "bridge0"]);
+
+        let bridge = &lan[1];
+        assert_eq!(bridge.members, ["en1", "en2"]);
+        assert_eq!(bridge.ipv4_address, Some(Ipv4Addr::new(192, 168, 2, 1)));
+        assert_eq!(bridge.display_label(), "bridge0 [bridge]");
+        assert!(lan[0].members.is_empty());
+    }
 }
@@ -0,0 +1,44 @@
+//! Power source detection, so background sampling can back off on battery.
+
+use tokio::process::Command;
+
+/// Whether `pmset -g batt` output says the Mac runs on battery.
+///
+/// The first line names the source: `Now drawing from 'Battery Power'`,
+/// `'AC Power'` or `'UPS Power'`. A UPS is running on its battery too.
+pub fn parse_on_battery(output: &str) -> bool {
+    output
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Now drawing from "))
+        .is_some_and(|source| source.contains("Battery Power") || source.contains("UPS Power"))
+}
+
+/// Whether the Mac currently runs on battery. False if `pmset` can't be run.
+pub async fn on_battery() -> bool {
+    Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+        .await
+        .is_ok_and(|output| parse_on_battery(&String::from_utf8_lossy(&output.stdout)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_on_battery() {
+        let battery = "Now drawing from 'Battery Power'\n \
+                       -InternalBattery-0 (id=4653155)\t85%; discharging; 4:12 remaining present: true\n";
+        assert!(parse_on_battery(battery));
+
+        let charging = "Now drawing from 'AC Power'\n \
+                        -InternalBattery-0 (id=4653155)\t85%; charging; 0:40 remaining present: true\n";
+        assert!(!parse_on_battery(charging));
+
+        // A Mac mini has no battery line at all
+        assert!(!parse_on_battery("Now drawing from 'AC Power'\n"));
+        assert!(parse_on_battery("Now drawing from 'UPS Power'\n"));
+        assert!(!parse_on_battery(""));
+    }
+}
@@ -0,0 +1,288 @@
+//! Reachability probes for port forwards and client-facing DNS.
+
+use std::fmt;
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4};
+use std::time::{Duration, Instant};
+
+use tokio::net::{TcpStream, UdpSocket};
+
+use crate::system::natpmp::Protocol;
+
+/// How long to wait for a TCP handshake or a UDP reply.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Name looked up when testing a DNS server.
+pub const DNS_PROBE_NAME: &str = "apple.com";
+
+/// Outcome of probing a forwarded port.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ForwardProbeResult {
+    /// Something accepted the connection (TCP) or replied (UDP).
+    Open,
+    /// The port actively refused (TCP RST / ICMP port unreachable).
+    Closed,
+    /// No answer at all -- dropped somewhere along the way.
+    Filtered,
+    /// UDP without a reply: either nothing listens or the service stays silent.
+    OpenOrFiltered,
+    /// The probe itself couldn't be sent.
+    Failed(String),
+}
+
+impl fmt::Display for ForwardProbeResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ForwardProbeResult::Open => f.write_str("open"),
+            ForwardProbeResult::Closed => f.write_str("closed"),
+            ForwardProbeResult::Filtered => f.write_str("filtered"),
+            ForwardProbeResult::OpenOrFiltered => f.write_str("open|filtered"),
+            ForwardProbeResult::Failed(e) => write!(f, "probe failed: {}", e),
+        }
+    }
+}
+
+/// Try to reach `external_ip:port` from this host.
+///
+/// Probing our own external address exercises the local routing path only;
+/// a result from outside the VPN is still the definitive test.
+pub async fn probe_forward(
+    external_ip: Ipv4Addr,
+    port: u16,
+    proto: Protocol,
+) -> ForwardProbeResult {
+    let target = SocketAddrV4::new(external_ip, port);
+    match proto {
+        Protocol::Tcp => probe_tcp(target).await,
+        Protocol::Udp => probe_udp(target).await,
+    }
+}
+
+async fn probe_tcp(target: SocketAddrV4) -> ForwardProbeResult {
+    match tokio::time::timeout(PROBE_TIMEOUT, TcpStream::connect(target)).await {
+        Ok(Ok(_)) => ForwardProbeResult::Open,
+        Ok(Err(e)) if e.kind() == ErrorKind::ConnectionRefused => ForwardProbeResult::Closed,
+        Ok(Err(e)) => ForwardProbeResult::Failed(e.to_string()),
+        Err(_) => ForwardProbeResult::Filtered,
+    }
+}
+
+async fn probe_udp(target: SocketAddrV4) -> ForwardProbeResult {
+    let socket = match UdpSocket::bind(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, 0)).await {
+        Ok(socket) => socket,
+        Err(e) => return ForwardProbeResult::Failed(e.to_string()),
+    };
+
+    // Connecting lets ICMP port-unreachable surface as ConnectionRefused
+    if let Err(e) = socket.connect(target).await {
+        return ForwardProbeResult::Failed(e.to_string());
+    }
+    if let Err(e) = socket.send(&[0u8]).await {
+        return ForwardProbeResult::Failed(e.to_string());
+    }
+
+    let mut buf = [0u8; 512];
+    match tokio::time::timeout(PROBE_TIMEOUT, socket.recv(&mut buf)).await {
+        Ok(Ok(_)) => ForwardProbeResult::Open,
+        Ok(Err(e)) if e.kind() == ErrorKind::ConnectionRefused => ForwardProbeResult::Closed,
+        Ok(Err(e)) => ForwardProbeResult::Failed(e.to_string()),
+        // Silence is ambiguous for UDP
+        Err(_) => ForwardProbeResult::OpenOrFiltered,
+    }
+}
+
+/// Outcome of querying a DNS server advertised to clients.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DnsProbeResult {
+    /// The server answered with `answers` records.
+    Answered { latency: Duration, answers: u16 },
+    /// The server replied with an error code (SERVFAIL, REFUSED, ...).
+    Rejected(&'static str),
+    /// No reply within the timeout.
+    TimedOut,
+    /// The query couldn't be sent.
+    Failed(String),
+}
+
+impl fmt::Display for DnsProbeResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DnsProbeResult::Answered { latency, answers } => write!(
+                f,
+                "ok in {} ms ({} answer{})",
+                latency.as_millis(),
+                answers,
+                if *answers == 1 { "" } else { "s" }
+            ),
+            DnsProbeResult::Rejected(rcode) => write!(f, "error: {}", rcode),
+            DnsProbeResult::TimedOut => f.write_str("no reply"),
+            DnsProbeResult::Failed(e) => write!(f, "probe failed: {}", e),
+        }
+    }
+}
+
+/// A minimal recursive A query for `name`.
+fn build_dns_query(id: u16, name: &str) -> Vec<u8> {
+    let mut query = Vec::with_capacity(18 + name.len());
+    query.extend_from_slice(&id.to_be_bytes());
+    // Flags: standard query, recursion desired
+    query.extend_from_slice(&0x0100u16.to_be_bytes());
+    // One question, no answer/authority/additional records
+    query.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.split('.').filter(|l| !l.is_empty()) {
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    // QTYPE A, QCLASS IN
+    query.extend_from_slice(&[0, 1, 0, 1]);
+    query
+}
+
+/// Answer count of a reply to query `id`, or the error code it carries.
+/// `None` if the packet isn't a reply to our query.
+fn parse_dns_reply(id: u16, reply: &[u8]) -> Option<Result<u16, &'static str>> {
+    if reply.len() < 12 || u16::from_be_bytes([reply[0], reply[1]]) != id {
+        return None;
+    }
+    let flags = u16::from_be_bytes([reply[2], reply[3]]);
+    if flags & 0x8000 == 0 {
+        return None;
+    }
+    let rcode = match flags & 0x000f {
+        0 => return Some(Ok(u16::from_be_bytes([reply[6], reply[7]]))),
+        1 => "FORMERR",
+        2 => "SERVFAIL",
+        3 => "NXDOMAIN",
+        4 => "NOTIMP",
+        5 => "REFUSED",
+        _ => "unknown rcode",
+    };
+    Some(Err(rcode))
+}
+
+/// Query `server` (an address as advertised to clients) on port 53, sending
+/// from `source` for IPv4 servers so the query takes the same path out as a
+/// client's would.
+pub async fn probe_dns(server: &str, source: Option<Ipv4Addr>) -> DnsProbeResult {
+    // Scoped link-local addresses can't be parsed with their zone
+    let address = server.split('%').next().unwrap_or_default();
+    match address.parse::<IpAddr>() {
+        Ok(ip) => probe_dns_at(SocketAddr::new(ip, 53), source).await,
+        Err(_) => DnsProbeResult::Failed(format!("{} is not an IP address", server)),
+    }
+}
+
+async fn probe_dns_at(target: SocketAddr, source: Option<Ipv4Addr>) -> DnsProbeResult {
+    let bind: SocketAddr = match target {
+        SocketAddr::V4(_) => (source.unwrap_or(Ipv4Addr::UNSPECIFIED), 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = match UdpSocket::bind(bind).await {
+        Ok(socket) => socket,
+        Err(e) => return DnsProbeResult::Failed(e.to_string()),
+    };
+    if let Err(e) = socket.connect(target).await {
+        return DnsProbeResult::Failed(e.to_string());
+    }
+
+    // Any id will do; the clock's nanoseconds are unpredictable enough
+    let id = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| (d.subsec_nanos() >> 8) as u16)
+        .unwrap_or_default();
+    let started = Instant::now();
+    if let Err(e) = socket.send(&build_dns_query(id, DNS_PROBE_NAME)).await {
+        return DnsProbeResult::Failed(e.to_string());
+    }
+
+    let wait = async {
+        let mut buf = [0u8; 512];
+        loop {
+            let n = socket.recv(&mut buf).await?;
+            // Ignore stray packets that don't answer our query
+            if let Some(reply) = parse_dns_reply(id, &buf[..n]) {
+                return Ok::<_, std::io::Error>(reply);
+            }
+        }
+    };
+    match tokio::time::timeout(PROBE_TIMEOUT, wait).await {
+        Ok(Ok(Ok(answers))) => DnsProbeResult::Answered {
+            latency: started.elapsed(),
+            answers,
+        },
+        Ok(Ok(Err(rcode))) => DnsProbeResult::Rejected(rcode),
+        Ok(Err(e)) if e.kind() == ErrorKind::ConnectionRefused => {
+            DnsProbeResult::Failed("port 53 closed".to_string())
+        }
+        Ok(Err(e)) => DnsProbeResult::Failed(e.to_string()),
+        Err(_) => DnsProbeResult::TimedOut,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_probe_tcp_open_and_closed() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let open = probe_forward(Ipv4Addr::LOCALHOST, port, Protocol::Tcp).await;
+        assert_eq!(open, ForwardProbeResult::Open);
+
+        drop(listener);
+        let closed = probe_forward(Ipv4Addr::LOCALHOST, port, Protocol::Tcp).await;
+        assert_eq!(closed, ForwardProbeResult::Closed);
+    }
+
+    #[test]
+    fn test_dns_query_and_reply() {
+        let query = build_dns_query(0xbeef, "apple.com");
+        assert_eq!(&query[..4], &[0xbe, 0xef, 0x01, 0x00]);
+        assert_eq!(&query[12..23], b"\x05apple\x03com\x00");
+        assert_eq!(&query[23..], &[0, 1, 0, 1]);
+
+        let mut reply = query.clone();
+        reply[2] = 0x81;
+        reply[3] = 0x80;
+        reply[7] = 2;
+        assert_eq!(parse_dns_reply(0xbeef, &reply), Some(Ok(2)));
+        reply[3] = 0x85;
+        assert_eq!(parse_dns_reply(0xbeef, &reply), Some(Err("REFUSED")));
+
+        // Not ours: wrong id, our own query echoed back, or truncated
+        assert_eq!(parse_dns_reply(0x1234, &reply), None);
+        assert_eq!(parse_dns_reply(0xbeef, &query), None);
+        assert_eq!(parse_dns_reply(0xbeef, &reply[..8]), None);
+    }
+
+    #[tokio::test]
+    async fn test_probe_dns_answered() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let target = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 512];
+            let (n, peer) = server.recv_from(&mut buf).await.unwrap();
+            // Flip the query into a one-answer reply
+            buf[2] |= 0x80;
+            buf[7] = 1;
+            server.send_to(&buf[..n], peer).await.unwrap();
+        });
+
+        let result = probe_dns_at(target, Some(Ipv4Addr::LOCALHOST)).await;
+        assert!(matches!(
+            result,
+            DnsProbeResult::Answered { answers: 1, .. }
+        ));
+        assert!(result.to_string().ends_with("(1 answer)"));
+    }
+
+    #[tokio::test]
+    async fn test_probe_dns_rejects_names() {
+        let result = probe_dns("dns.example", None).await;
+        assert!(matches!(result, DnsProbeResult::Failed(_)));
+    }
+}
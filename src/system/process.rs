@@ -0,0 +1,45 @@
+//! Process lookups by PID, for the DHCP daemon and other tunshare instances.
+
+use std::process::Command;
+
+/// Whether process `pid` still exists.
+pub fn is_alive(pid: u32) -> bool {
+    let Ok(pid) = i32::try_from(pid) else {
+        return false;
+    };
+    // Signal 0 only checks that the process exists
+    let alive = unsafe { libc::kill(pid, 0) } == 0;
+    alive || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Name of process `pid` (e.g. `dnsmasq`), or None if there's no such process.
+pub fn name(pid: u32) -> Option<String> {
+    let output = Command::new("ps")
+        .args(["-p", &pid.to_string(), "-o", "comm="])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())?;
+    // macOS prints the full path of the executable
+    let comm = String::from_utf8_lossy(&output.stdout);
+    let name = comm.trim().rsplit('/').next().unwrap_or_default();
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// Whether `pid` is another running tunshare: alive, not us, and running
+/// the same program.
+pub fn is_other_instance(pid: u32) -> bool {
+    let own = std::process::id();
+    pid != own && is_alive(pid) && name(pid) == name(own)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_alive() {
+        assert!(is_alive(std::process::id()));
+        assert!(!is_alive(u32::MAX));
+        assert!(!is_other_instance(std::process::id()));
+    }
+}
@@ -1,67 +1,229 @@
-//! IP forwarding control via sysctl.
+//! Kernel tunables via sysctl: IP forwarding, plus any extra ones configured.
 
 use crate::error::{Result, TunshareError};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::path::Path;
 use std::process::Command as SyncCommand;
 use tokio::process::Command;
 
-/// Manages IP forwarding state.
-pub struct IpForwarding {
-    /// The original state before we modified it.
-    original_state: Option<bool>,
+/// Records the original values of the sysctls we changed, so the change can
+/// still be undone after a crash (see `IpForwarding::restore_orphaned`).
+/// Under /var/run, which only root can write to: a marker anyone could plant
+/// or point elsewhere would be written and replayed as root.
+const MARKER_PATH: &str = "/var/run/tunshare-forwarding.orig";
+
+/// The IPv4 forwarding switch sharing depends on.
+pub const FORWARDING: &str = "net.inet.ip.forwarding";
+
+/// Whether `key` is a `net.*` sysctl name. Nothing else is ever written,
+/// whether it comes from the config or from a marker file.
+pub fn is_net_key(key: &str) -> bool {
+    key.starts_with("net.")
+        && key
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "._".contains(c))
 }
 
-impl IpForwarding {
+/// Whether `key` may be set as an extra tunable: a `net.*` sysctl other than
+/// forwarding, which is always managed.
+pub fn is_allowed_tunable(key: &str) -> bool {
+    key != FORWARDING && is_net_key(key)
+}
+
+/// Sets sysctls, remembering each one's value from before the first change
+/// so they can all be put back.
+#[derive(Debug, Default)]
+pub struct SysctlManager {
+    /// `(key, original value)` in the order they were first changed.
+    originals: Vec<(String, String)>,
+}
+
+impl SysctlManager {
     pub fn new() -> Self {
-        Self {
-            original_state: None,
-        }
+        Self::default()
     }
 
-    /// Get the current IP forwarding state.
-    pub async fn get_state(&self) -> Result<bool> {
+    /// Current value of `key`.
+    pub async fn get(key: &str) -> Result<String> {
         let output = Command::new("sysctl")
             .arg("-n")
-            .arg("net.inet.ip.forwarding")
+            .arg(key)
             .output()
             .await
             .map_err(|e| TunshareError::CommandFailed {
-                command: "sysctl -n net.inet.ip.forwarding".into(),
+                command: format!("sysctl -n {}", key),
                 message: e.to_string(),
             })?;
+        if !output.status.success() {
+            return Err(TunshareError::CommandFailed {
+                command: format!("sysctl -n {}", key),
+                message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            });
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// Set `key` to `value`, saving its original value the first time.
+    pub async fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        if self.original(key).is_none() {
+            let original = Self::get(key).await?;
+            self.record(key, original);
+        }
+        let (key, value) = (key.to_string(), value.to_string());
+        tokio::task::spawn_blocking(move || set_sync(&key, &value))
+            .await
+            .map_err(|e| TunshareError::CommandFailed {
+                command: "sysctl set (spawn_blocking)".into(),
+                message: e.to_string(),
+            })?
+    }
+
+    fn record(&mut self, key: &str, original: String) {
+        self.originals.push((key.to_string(), original));
+    }
+
+    /// Value of `key` from before we changed it (None = we haven't).
+    pub fn original(&self, key: &str) -> Option<&str> {
+        self.originals
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Whether any sysctl has been changed and not restored.
+    pub fn is_modified(&self) -> bool {
+        !self.originals.is_empty()
+    }
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let value = stdout.trim();
+    /// Stop owning the change to `key`: it won't be restored.
+    pub fn forget(&mut self, key: &str) {
+        self.originals.retain(|(k, _)| k != key);
+    }
+
+    /// The originals to write back, last change first. Taking them means we
+    /// no longer own the changes.
+    fn take_originals(&mut self) -> Vec<(String, String)> {
+        let mut originals = std::mem::take(&mut self.originals);
+        originals.reverse();
+        originals
+    }
 
-        match value {
-            "1" => Ok(true),
-            "0" => Ok(false),
-            _ => Err(TunshareError::ParseError(format!(
-                "Unexpected sysctl value: {}",
-                value
-            ))),
+    /// Restore every changed sysctl (async wrapper around `restore_all_sync`).
+    pub async fn restore_all(&mut self) -> Result<()> {
+        let originals = self.take_originals();
+        tokio::task::spawn_blocking(move || restore_sync(&originals))
+            .await
+            .map_err(|e| TunshareError::CommandFailed {
+                command: "restore (spawn_blocking)".into(),
+                message: e.to_string(),
+            })?
+    }
+
+    /// Restore every changed sysctl, trying them all even if one fails.
+    /// Returns the first failure.
+    pub fn restore_all_sync(&mut self) -> Result<()> {
+        restore_sync(&self.take_originals())
+    }
+
+    /// The originals as marker file contents, one `key=value` per line.
+    fn marker_contents(&self) -> String {
+        self.originals
+            .iter()
+            .map(|(key, value)| format!("{}={}\n", key, value))
+            .collect()
+    }
+}
+
+/// Manages IP forwarding state, and the extra sysctls changed alongside it.
+pub struct IpForwarding {
+    sysctls: SysctlManager,
+    /// Extra `(key, value)` sysctls set while forwarding is enabled.
+    tunables: Vec<(String, String)>,
+    /// Keep forwarding on when restoring, for other tools that rely on it.
+    leave_enabled: bool,
+}
+
+impl IpForwarding {
+    pub fn new() -> Self {
+        Self {
+            sysctls: SysctlManager::new(),
+            tunables: Vec::new(),
+            leave_enabled: false,
         }
     }
 
-    /// Enable IP forwarding, saving the original state.
+    /// Don't switch forwarding back off on restore, even if it was off before.
+    pub fn with_leave_enabled(mut self, leave_enabled: bool) -> Self {
+        self.leave_enabled = leave_enabled;
+        self
+    }
+
+    /// Also set these sysctls on enable, and restore them with forwarding.
+    /// Keys that aren't allowed tunables (see [`is_allowed_tunable`]) are dropped.
+    pub fn with_tunables(mut self, tunables: Vec<(String, String)>) -> Self {
+        self.tunables = tunables
+            .into_iter()
+            .filter(|(key, _)| is_allowed_tunable(key))
+            .collect();
+        self
+    }
+
+    /// Whether restoring will skip turning forwarding off (it was off before
+    /// we enabled it, and `leave_enabled` is set).
+    pub fn will_leave_enabled(&self) -> bool {
+        self.leave_enabled && self.original_state() == Some(false)
+    }
+
+    /// Drop forwarding from the restore if it's to be left on.
+    fn prepare_restore(&mut self) {
+        if self.will_leave_enabled() {
+            self.sysctls.forget(FORWARDING);
+        }
+    }
+
+    /// Get the current IP forwarding state.
+    pub async fn get_state(&self) -> Result<bool> {
+        let value = SysctlManager::get(FORWARDING).await?;
+        parse_flag(&value)
+            .ok_or_else(|| TunshareError::ParseError(format!("Unexpected sysctl value: {}", value)))
+    }
+
+    /// Enable IP forwarding and set the tunables, saving the original values.
+    /// If a tunable can't be set, everything changed so far is restored.
     pub async fn enable(&mut self) -> Result<()> {
-        // Save original state if not already saved
-        if self.original_state.is_none() {
-            self.original_state = Some(self.get_state().await?);
+        self.sysctls.set(FORWARDING, "1").await?;
+        self.write_marker();
+
+        for (key, value) in self.tunables.clone() {
+            if let Err(e) = self.sysctls.set(&key, &value).await {
+                let _ = self.restore().await;
+                return Err(e);
+            }
+            self.write_marker();
         }
+        Ok(())
+    }
 
-        self.set_state(true).await
+    fn write_marker(&self) {
+        let contents = format!(
+            "pid={}\n{}",
+            std::process::id(),
+            self.sysctls.marker_contents()
+        );
+        let _ = write_marker_file(Path::new(MARKER_PATH), &contents);
     }
 
-    /// Restore the original IP forwarding state (async wrapper).
-    /// Delegates to `restore_sync` via `spawn_blocking`.
+    /// Restore the original values (async wrapper).
+    /// Delegates to `SysctlManager::restore_all` via `spawn_blocking`.
+    /// The marker is kept if restoring fails, so the next run can retry.
     pub async fn restore(&mut self) -> Result<()> {
-        if let Some(original) = self.original_state.take() {
-            tokio::task::spawn_blocking(move || set_state_sync(original))
-                .await
-                .map_err(|e| TunshareError::CommandFailed {
-                    command: "restore (spawn_blocking)".into(),
-                    message: e.to_string(),
-                })??;
+        let modified = self.is_modified();
+        self.prepare_restore();
+        self.sysctls.restore_all().await?;
+        if modified {
+            let _ = fs::remove_file(MARKER_PATH);
         }
         Ok(())
     }
@@ -69,47 +231,164 @@ impl IpForwarding {
     /// Disable IP forwarding.
     #[allow(dead_code)]
     pub async fn disable(&self) -> Result<()> {
-        self.set_state(false).await
-    }
-
-    async fn set_state(&self, enabled: bool) -> Result<()> {
-        tokio::task::spawn_blocking(move || set_state_sync(enabled))
+        tokio::task::spawn_blocking(|| set_sync(FORWARDING, "0"))
             .await
             .map_err(|e| TunshareError::CommandFailed {
-                command: "set_state (spawn_blocking)".into(),
+                command: "disable (spawn_blocking)".into(),
                 message: e.to_string(),
             })?
     }
 
+    /// Forwarding's state from before we enabled it (None = we haven't).
+    pub fn original_state(&self) -> Option<bool> {
+        self.sysctls.original(FORWARDING).and_then(parse_flag)
+    }
+
     /// Returns whether we have saved the original state (meaning we've modified it).
     pub fn is_modified(&self) -> bool {
-        self.original_state.is_some()
+        self.sysctls.is_modified()
     }
 
-    /// Synchronous restore for use in Drop.
+    /// Synchronous restore for use in Drop. Like `restore`, keeps the
+    /// marker if restoring fails.
     pub fn restore_sync(&mut self) {
-        if let Some(original) = self.original_state.take() {
-            let _ = set_state_sync(original);
+        let modified = self.is_modified();
+        self.prepare_restore();
+        if self.sysctls.restore_all_sync().is_ok() && modified {
+            let _ = fs::remove_file(MARKER_PATH);
         }
     }
 }
 
+impl IpForwarding {
+    /// PID of the run that left the marker, if there is one and it recorded
+    /// its PID (older releases didn't).
+    pub fn marker_pid() -> Option<u32> {
+        read_marker_file(Path::new(MARKER_PATH)).and_then(|contents| parse_marker_pid(&contents))
+    }
+
+    /// Undo sysctls left changed by a run that never restored them (it
+    /// crashed or was killed), going by the marker it left. Leaves forwarding
+    /// on if it was on before that run, or if `leave_enabled`.
+    ///
+    /// Returns whether forwarding was turned off.
+    pub async fn restore_orphaned(leave_enabled: bool) -> Result<bool> {
+        let Some(contents) = read_marker_file(Path::new(MARKER_PATH)) else {
+            return Ok(false);
+        };
+        let _ = fs::remove_file(MARKER_PATH);
+
+        let originals: Vec<(String, String)> = parse_marker(&contents)
+            .into_iter()
+            .rev()
+            .filter(|(key, value)| key != FORWARDING || !(value == "1" || leave_enabled))
+            .collect();
+        let forwarding_off = originals.iter().any(|(key, _)| key == FORWARDING);
+
+        tokio::task::spawn_blocking(move || restore_sync(&originals))
+            .await
+            .map_err(|e| TunshareError::CommandFailed {
+                command: "restore_orphaned (spawn_blocking)".into(),
+                message: e.to_string(),
+            })??;
+        Ok(forwarding_off)
+    }
+}
+
+/// Replace the marker at `path` with `contents`. The old file is removed and a
+/// new one created (mode 0600), so a symlink planted there is never followed.
+fn write_marker_file(path: &Path, contents: &str) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(path)?;
+    file.write_all(contents.as_bytes())
+}
+
+/// Contents of the marker at `path`, if it's a regular file written by this
+/// user and nobody else can write to. Anything else is ignored.
+fn read_marker_file(path: &Path) -> Option<String> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(path)
+        .ok()?;
+    let meta = file.metadata().ok()?;
+    let owner = unsafe { libc::geteuid() };
+    if !meta.is_file() || meta.uid() != owner || meta.mode() & 0o022 != 0 {
+        return None;
+    }
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).ok()?;
+    Some(contents)
+}
+
+/// The `pid=` line of a marker.
+fn parse_marker_pid(contents: &str) -> Option<u32> {
+    contents
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("pid=")?.parse().ok())
+}
+
+/// Original values recorded in the marker file, as `(key, value)`.
+/// Markers from older versions hold just forwarding's `0` or `1`. Lines for
+/// anything but a `net.*` sysctl with an integer value are skipped.
+fn parse_marker(contents: &str) -> Vec<(String, String)> {
+    if let Some(state) = parse_flag(contents.trim()) {
+        return vec![(FORWARDING.to_string(), u8::from(state).to_string())];
+    }
+    contents
+        .lines()
+        .filter_map(|line| line.trim().split_once('='))
+        .filter(|(key, value)| is_net_key(key) && value.parse::<i64>().is_ok())
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+/// A boolean sysctl value.
+fn parse_flag(value: &str) -> Option<bool> {
+    match value {
+        "1" => Some(true),
+        "0" => Some(false),
+        _ => None,
+    }
+}
+
 impl Default for IpForwarding {
     fn default() -> Self {
         Self::new()
     }
 }
 
-/// Standalone sync implementation for setting IP forwarding state.
+/// Write each `(key, value)` back, trying them all. Returns the first failure.
+fn restore_sync(originals: &[(String, String)]) -> Result<()> {
+    let mut result = Ok(());
+    for (key, value) in originals {
+        if let Err(e) = set_sync(key, value) {
+            if result.is_ok() {
+                result = Err(e);
+            }
+        }
+    }
+    result
+}
+
+/// Standalone sync implementation for setting a sysctl.
 /// Single source of truth for both sync and async paths.
-fn set_state_sync(enabled: bool) -> Result<()> {
-    let value = if enabled { "1" } else { "0" };
+fn set_sync(key: &str, value: &str) -> Result<()> {
+    let command = format!("sysctl -w {}={}", key, value);
     let output = SyncCommand::new("sysctl")
         .arg("-w")
-        .arg(format!("net.inet.ip.forwarding={}", value))
+        .arg(format!("{}={}", key, value))
         .output()
         .map_err(|e| TunshareError::CommandFailed {
-            command: format!("sysctl -w net.inet.ip.forwarding={}", value),
+            command: command.clone(),
             message: e.to_string(),
         })?;
 
@@ -119,10 +398,150 @@ fn set_state_sync(enabled: bool) -> Result<()> {
             return Err(TunshareError::PermissionDenied);
         }
         return Err(TunshareError::CommandFailed {
-            command: format!("sysctl -w net.inet.ip.forwarding={}", value),
+            command,
             message: stderr.to_string(),
         });
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modified_from(original: bool, leave_enabled: bool) -> IpForwarding {
+        let mut fwd = IpForwarding::new().with_leave_enabled(leave_enabled);
+        fwd.sysctls
+            .record(FORWARDING, u8::from(original).to_string());
+        fwd
+    }
+
+    fn restore_targets(fwd: &mut IpForwarding) -> Vec<(String, String)> {
+        fwd.prepare_restore();
+        fwd.sysctls.take_originals()
+    }
+
+    fn pair(key: &str, value: &str) -> (String, String) {
+        (key.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn test_parse_marker() {
+        assert_eq!(parse_marker("0\n"), [pair(FORWARDING, "0")]);
+        assert_eq!(parse_marker("1"), [pair(FORWARDING, "1")]);
+        assert_eq!(
+            parse_marker("net.inet.ip.forwarding=0\nnet.inet.ip.redirect=1\n"),
+            [pair(FORWARDING, "0"), pair("net.inet.ip.redirect", "1")]
+        );
+        assert_eq!(
+            parse_marker("kern.maxproc=1\nnet.inet.ip.forwarding=0\nnet.inet.ip.ttl=64 ; x=1\n"),
+            [pair(FORWARDING, "0")]
+        );
+        assert!(parse_marker("").is_empty());
+        assert!(parse_marker("yes").is_empty());
+
+        // The writer's PID is kept apart from the sysctls
+        let contents = "pid=4242\nnet.inet.ip.forwarding=0\n";
+        assert_eq!(parse_marker(contents), [pair(FORWARDING, "0")]);
+        assert_eq!(parse_marker_pid(contents), Some(4242));
+        assert_eq!(parse_marker_pid("net.inet.ip.forwarding=0\n"), None);
+        assert_eq!(parse_marker_pid("0\n"), None);
+    }
+
+    #[test]
+    fn test_marker_file() {
+        let dir = std::env::temp_dir().join(format!("tunshare-marker-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let marker = dir.join("marker");
+        let target = dir.join("target");
+
+        write_marker_file(&marker, "net.inet.ip.forwarding=0\n").unwrap();
+        assert_eq!(fs::metadata(&marker).unwrap().mode() & 0o777, 0o600);
+        assert_eq!(
+            read_marker_file(&marker).as_deref(),
+            Some("net.inet.ip.forwarding=0\n")
+        );
+
+        // A planted symlink is replaced, not written through
+        fs::write(&target, "keep").unwrap();
+        fs::remove_file(&marker).unwrap();
+        std::os::unix::fs::symlink(&target, &marker).unwrap();
+        assert_eq!(read_marker_file(&marker), None);
+        write_marker_file(&marker, "net.inet.ip.forwarding=1\n").unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "keep");
+        assert!(fs::symlink_metadata(&marker).unwrap().is_file());
+
+        // Writable by others: not trusted
+        fs::set_permissions(&marker, std::os::unix::fs::PermissionsExt::from_mode(0o666)).unwrap();
+        assert_eq!(read_marker_file(&marker), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_multi_key_save_restore() {
+        let mut sysctls = SysctlManager::new();
+        sysctls.record(FORWARDING, "0".to_string());
+        sysctls.record("net.inet.ip.redirect", "1".to_string());
+        assert_eq!(sysctls.original("net.inet.ip.redirect"), Some("1"));
+        assert_eq!(sysctls.original("net.inet.icmp.drop_redirect"), None);
+
+        // The marker round-trips every key
+        assert_eq!(
+            parse_marker(&sysctls.marker_contents()),
+            [pair(FORWARDING, "0"), pair("net.inet.ip.redirect", "1")]
+        );
+
+        // Restored last change first, and only once
+        assert_eq!(
+            sysctls.take_originals(),
+            [pair("net.inet.ip.redirect", "1"), pair(FORWARDING, "0")]
+        );
+        assert!(!sysctls.is_modified());
+        assert!(sysctls.take_originals().is_empty());
+    }
+
+    #[test]
+    fn test_tunables_only_net() {
+        let fwd = IpForwarding::new().with_tunables(vec![
+            pair("net.inet.ip.redirect", "0"),
+            pair("kern.maxfiles", "1"),
+            pair(FORWARDING, "0"),
+            pair("net.inet.ip.ttl=1 kern.x", "1"),
+        ]);
+        assert_eq!(fwd.tunables, [pair("net.inet.ip.redirect", "0")]);
+    }
+
+    #[test]
+    fn test_restore_target() {
+        // Default: back to whatever it was
+        assert_eq!(
+            restore_targets(&mut modified_from(false, false)),
+            [pair(FORWARDING, "0")]
+        );
+        assert_eq!(
+            restore_targets(&mut modified_from(true, false)),
+            [pair(FORWARDING, "1")]
+        );
+
+        // Leave enabled: the disable step is skipped, an already-on state is kept
+        let mut fwd = modified_from(false, true);
+        fwd.sysctls.record("net.inet.ip.redirect", "1".to_string());
+        assert!(fwd.will_leave_enabled());
+        assert_eq!(
+            restore_targets(&mut fwd),
+            [pair("net.inet.ip.redirect", "1")]
+        );
+        assert!(!fwd.is_modified());
+
+        let fwd = modified_from(true, true);
+        assert!(!fwd.will_leave_enabled());
+
+        // Nothing to restore if we never enabled it
+        assert!(restore_targets(&mut IpForwarding::new()).is_empty());
+        assert!(!IpForwarding::new()
+            .with_leave_enabled(true)
+            .will_leave_enabled());
+    }
+}
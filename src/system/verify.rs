@@ -0,0 +1,287 @@
+//! Post-stop check that the system is back to how sharing found it.
+//!
+//! Cleanup reports what it tried; this looks at what's actually there
+//! afterwards, and names the command that removes anything left behind.
+
+use crate::system::exec::RetryPolicy;
+use crate::system::firewall::{self, Firewall};
+use crate::system::{DhcpServer, IpForwarding, NatPmpServer};
+use tokio::process::Command;
+
+/// What the system looked like before sharing started.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Baseline {
+    /// IP forwarding before we enabled it (None = we never changed it).
+    pub forwarding: Option<bool>,
+    /// `leave_forwarding_enabled` is set, so forwarding may stay on.
+    pub leave_forwarding_enabled: bool,
+    /// NAT-PMP anchor in use.
+    pub natpmp_anchor: String,
+    /// The DHCP daemon was left running on purpose (`persistent_dhcp`).
+    pub dhcp_kept: bool,
+}
+
+/// What the system looks like after stopping.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ObservedState {
+    /// Main NAT and filter rules (`pfctl -sn` and `-sr`).
+    pub main_rules: String,
+    /// Rules still loaded in each of our anchors, as `(anchor, rules)`.
+    pub anchor_rules: Vec<(String, String)>,
+    /// IP forwarding now (None = couldn't read it).
+    pub forwarding: Option<bool>,
+    /// DHCP daemons we started that are still running.
+    pub dhcp_pids: Vec<u32>,
+    /// Process holding the NAT-PMP port, as `(pid, name)`.
+    pub natpmp_port_holder: Option<(u32, String)>,
+}
+
+/// Something sharing left behind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Residue {
+    /// The main ruleset is still tunshare's NAT ruleset.
+    NatRules,
+    /// An anchor still has rules loaded.
+    Anchor(String),
+    /// IP forwarding isn't back to its pre-start value.
+    Forwarding { expected: bool },
+    /// A DHCP daemon we started is still running.
+    DhcpDaemon(u32),
+    /// A tunshare process other than this one still holds the NAT-PMP port.
+    NatPmpPort(u32),
+}
+
+impl Residue {
+    /// Command that removes this leftover.
+    pub fn fix_command(&self) -> String {
+        match self {
+            Residue::NatRules => "sudo pfctl -f /etc/pf.conf".to_string(),
+            Residue::Anchor(anchor) => format!("sudo pfctl -a {} -F all", anchor),
+            Residue::Forwarding { expected } => format!(
+                "sudo sysctl -w net.inet.ip.forwarding={}",
+                u8::from(*expected)
+            ),
+            Residue::DhcpDaemon(pid) | Residue::NatPmpPort(pid) => format!("sudo kill {}", pid),
+        }
+    }
+}
+
+impl std::fmt::Display for Residue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Residue::NatRules => write!(f, "tunshare's NAT rules are still loaded"),
+            Residue::Anchor(anchor) => write!(f, "pf anchor {} still has rules", anchor),
+            Residue::Forwarding { expected } => write!(
+                f,
+                "IP forwarding is {} (was {} before sharing)",
+                if *expected { "off" } else { "on" },
+                if *expected { "on" } else { "off" }
+            ),
+            Residue::DhcpDaemon(pid) => write!(f, "DHCP daemon still running (pid {})", pid),
+            Residue::NatPmpPort(pid) => write!(
+                f,
+                "NAT-PMP port still held by another tunshare process (pid {})",
+                pid
+            ),
+        }
+    }
+}
+
+/// Compare `state` against `baseline`.
+///
+/// `own_pid` is this process: its NAT-PMP socket closes as the server task
+/// winds down, and killing it would take the app down. Other programs'
+/// NAT-PMP servers aren't blamed on us either.
+pub fn detect_residue(baseline: &Baseline, state: &ObservedState, own_pid: u32) -> Vec<Residue> {
+    let mut residue = Vec::new();
+
+    if firewall::is_tunshare_ruleset(&state.main_rules) {
+        residue.push(Residue::NatRules);
+    }
+    residue.extend(
+        state
+            .anchor_rules
+            .iter()
+            .filter(|(_, rules)| !rules.trim().is_empty())
+            .map(|(anchor, _)| Residue::Anchor(anchor.clone())),
+    );
+
+    if let (Some(original), Some(now)) = (baseline.forwarding, state.forwarding) {
+        let left_on = now && baseline.leave_forwarding_enabled;
+        if now != original && !left_on {
+            residue.push(Residue::Forwarding { expected: original });
+        }
+    }
+
+    if !baseline.dhcp_kept {
+        residue.extend(state.dhcp_pids.iter().map(|&pid| Residue::DhcpDaemon(pid)));
+    }
+    if let Some((pid, name)) = &state.natpmp_port_holder {
+        if *pid != own_pid && name.starts_with("tunshare") {
+            residue.push(Residue::NatPmpPort(*pid));
+        }
+    }
+
+    residue
+}
+
+/// Check for anything sharing left behind after it was stopped.
+pub async fn verify_clean_state(baseline: &Baseline, retry: RetryPolicy) -> Vec<Residue> {
+    let mut anchor_rules = Vec::new();
+    for anchor in [firewall::CLIENTS_ANCHOR_NAME, &baseline.natpmp_anchor] {
+        let rules =
+            pfctl_show(&["-a", anchor, "-sn"]).await + &pfctl_show(&["-a", anchor, "-sr"]).await;
+        anchor_rules.push((anchor.to_string(), rules));
+    }
+
+    let state = ObservedState {
+        main_rules: Firewall::get_current_rules(retry).await.unwrap_or_default(),
+        anchor_rules,
+        forwarding: IpForwarding::new().get_state().await.ok(),
+        dhcp_pids: DhcpServer::running_pids(),
+        natpmp_port_holder: NatPmpServer::port_holder().await,
+    };
+    detect_residue(baseline, &state, std::process::id())
+}
+
+/// Output of a `pfctl` listing, or nothing if it couldn't run.
+async fn pfctl_show(args: &[&str]) -> String {
+    Command::new("pfctl")
+        .args(args)
+        .output()
+        .await
+        .map(|output| String::from_utf8_lossy(&output.stdout).to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn baseline() -> Baseline {
+        Baseline {
+            forwarding: Some(false),
+            leave_forwarding_enabled: false,
+            natpmp_anchor: "tunshare-natpmp".to_string(),
+            dhcp_kept: false,
+        }
+    }
+
+    fn clean_state() -> ObservedState {
+        ObservedState {
+            main_rules: "scrub-anchor \"com.apple/*\" all fragment reassemble\n".to_string(),
+            anchor_rules: vec![
+                ("tunshare_clients".to_string(), String::new()),
+                ("tunshare-natpmp".to_string(), "\n".to_string()),
+            ],
+            forwarding: Some(false),
+            dhcp_pids: Vec::new(),
+            natpmp_port_holder: None,
+        }
+    }
+
+    #[test]
+    fn test_clean_state_has_no_residue() {
+        assert!(detect_residue(&baseline(), &clean_state(), 100).is_empty());
+
+        // Another program's NAT-PMP server isn't ours to clean up
+        let state = ObservedState {
+            natpmp_port_holder: Some((200, "miniupnpd".to_string())),
+            ..clean_state()
+        };
+        assert!(detect_residue(&baseline(), &state, 100).is_empty());
+
+        // Our own server lets go of it as it shuts down
+        let state = ObservedState {
+            natpmp_port_holder: Some((100, "tunshare".to_string())),
+            ..clean_state()
+        };
+        assert!(detect_residue(&baseline(), &state, 100).is_empty());
+
+        // Nor is a DHCP daemon left running with persistent_dhcp
+        let state = ObservedState {
+            dhcp_pids: vec![4242],
+            ..clean_state()
+        };
+        let kept = Baseline {
+            dhcp_kept: true,
+            ..baseline()
+        };
+        assert!(detect_residue(&kept, &state, 100).is_empty());
+    }
+
+    #[test]
+    fn test_detect_residue() {
+        let state = ObservedState {
+            main_rules: "nat on utun4 from 192.168.2.0/24 to any -> (utun4)\n\
+                         anchor \"tunshare_clients\" all\n"
+                .to_string(),
+            anchor_rules: vec![
+                ("tunshare_clients".to_string(), String::new()),
+                (
+                    "tunshare-natpmp".to_string(),
+                    "rdr pass on utun4 inet proto tcp from any to 10.8.0.2 port = 6881 -> 192.168.2.100 port 6881\n".to_string(),
+                ),
+            ],
+            forwarding: Some(true),
+            dhcp_pids: vec![4242],
+            natpmp_port_holder: Some((300, "tunshare".to_string())),
+        };
+        let residue = detect_residue(&baseline(), &state, 100);
+        assert_eq!(
+            residue,
+            [
+                Residue::NatRules,
+                Residue::Anchor("tunshare-natpmp".to_string()),
+                Residue::Forwarding { expected: false },
+                Residue::DhcpDaemon(4242),
+                Residue::NatPmpPort(300),
+            ]
+        );
+        assert_eq!(residue[4].fix_command(), "sudo kill 300");
+        assert_eq!(
+            residue[1].fix_command(),
+            "sudo pfctl -a tunshare-natpmp -F all"
+        );
+        assert_eq!(
+            residue[2].fix_command(),
+            "sudo sysctl -w net.inet.ip.forwarding=0"
+        );
+    }
+
+    #[test]
+    fn test_forwarding_left_on_on_purpose() {
+        let state = ObservedState {
+            forwarding: Some(true),
+            ..clean_state()
+        };
+        let leave_on = Baseline {
+            leave_forwarding_enabled: true,
+            ..baseline()
+        };
+        assert!(detect_residue(&leave_on, &state, 100).is_empty());
+
+        // Never touched, or unreadable: nothing to compare
+        let untouched = Baseline {
+            forwarding: None,
+            ..baseline()
+        };
+        assert!(detect_residue(&untouched, &state, 100).is_empty());
+        let unreadable = ObservedState {
+            forwarding: None,
+            ..clean_state()
+        };
+        assert!(detect_residue(&baseline(), &unreadable, 100).is_empty());
+
+        // Was on before; turned off counts even with leave_forwarding_enabled
+        let was_on = Baseline {
+            forwarding: Some(true),
+            leave_forwarding_enabled: true,
+            ..baseline()
+        };
+        assert_eq!(
+            detect_residue(&was_on, &clean_state(), 100),
+            [Residue::Forwarding { expected: true }]
+        );
+    }
+}
@@ -9,49 +9,156 @@ use ratatui::{
 };
 
 use crate::app::DebugInfo;
+use crate::system::PfState;
 use crate::ui::theme::{colors, styles, symbols};
 use crate::ui::widgets::Card;
 
+/// Maximum number of clients listed in the top talkers card.
+const MAX_TOP_TALKERS: usize = 5;
+
+/// Kind of line in a pf rules diff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DiffKind {
+    Same,
+    Added,
+    Removed,
+}
+
 /// Render the debug panel filling the content area.
-pub fn render_debug_panel(frame: &mut Frame, area: Rect, debug_info: &DebugInfo) {
+///
+/// With `prev` set, pf rules are shown as a diff against that earlier fetch.
+/// With `state_scroll` set, the pf state table is listed starting at that index.
+/// With `anchor_preview` set, the NAT-PMP anchor rules replace the pf rules,
+/// with the given mapping highlighted.
+pub fn render_debug_panel(
+    frame: &mut Frame,
+    area: Rect,
+    debug_info: &DebugInfo,
+    prev: Option<&DebugInfo>,
+    state_scroll: Option<usize>,
+    anchor_preview: Option<(&str, usize)>,
+) {
+    // Top clients card: one row per client (at least one for the empty message)
+    let talker_rows = debug_info.top_talkers.len().clamp(1, MAX_TOP_TALKERS) as u16;
+
     // Split into sections
+    let mut constraints = vec![
+        Constraint::Length(
+            10 + dhcp_command_rows(debug_info, area.width)
+                + u16::from(debug_info.dhcp_boot.is_some()),
+        ), // System Status
+        Constraint::Length(talker_rows + 2), // Top clients
+    ];
+    if state_scroll.is_some() {
+        constraints.push(Constraint::Fill(1)); // PF states (shares room with the rules)
+    }
+    constraints.push(Constraint::Fill(1)); // PF rules
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Length(8), // System Status (expanded to include sample states)
-            Constraint::Min(8),    // PF rules (gets more room)
-        ])
+        .constraints(constraints)
         .split(area);
 
-    // Render status summary (includes sample connections)
+    // Render status summary
     render_status_summary(frame, chunks[0], debug_info);
 
-    // Render PF rules
-    render_pf_rules(frame, chunks[1], debug_info);
+    // Render per-client traffic
+    render_top_talkers(frame, chunks[1], debug_info);
+
+    // Render PF states and rules
+    if let Some(scroll) = state_scroll {
+        render_pf_states(frame, chunks[2], debug_info, scroll);
+    }
+    let rules_area = chunks[chunks.len() - 1];
+    match anchor_preview {
+        Some((rules, selected)) => render_anchor_preview(frame, rules_area, rules, selected),
+        None => render_pf_rules(frame, rules_area, debug_info, prev),
+    }
+}
+
+/// The NAT-PMP anchor rules for the current mappings, as they would be loaded.
+///
+/// Each mapping is an rdr rule and a pass rule; the `selected` one's pair is
+/// highlighted and kept in view.
+fn render_anchor_preview(frame: &mut Frame, area: Rect, rules: &str, selected: usize) {
+    let card = Card::new(Span::styled(
+        " NAT-PMP Anchor (preview, not applied) ",
+        styles::card_title(),
+    ));
+    frame.render_widget(card, area);
+
+    let inner = Rect::new(
+        area.x + 1,
+        area.y + 1,
+        area.width.saturating_sub(2),
+        area.height.saturating_sub(2),
+    );
+
+    let lines: Vec<Line> = if rules.is_empty() {
+        vec![Line::from(Span::styled(
+            "  No mappings (or NAT-PMP is off): the anchor is empty",
+            Style::default().fg(colors::TEXT_SECONDARY),
+        ))]
+    } else {
+        let skip = (selected * 2 + 2).saturating_sub(inner.height as usize);
+        rules
+            .lines()
+            .enumerate()
+            .skip(skip)
+            .take(inner.height as usize)
+            .map(|(i, line)| {
+                if i / 2 == selected {
+                    return Line::from(Span::styled(
+                        format!("{} {}", symbols::SELECTED, line),
+                        styles::selected(),
+                    ));
+                }
+                let style = if line.starts_with("rdr ") {
+                    Style::default().fg(colors::ACCENT)
+                } else {
+                    Style::default().fg(colors::success())
+                };
+                Line::from(Span::styled(format!("  {}", line), style))
+            })
+            .collect()
+    };
+
+    let paragraph = Paragraph::new(lines).wrap(Wrap { trim: false });
+    frame.render_widget(paragraph, inner);
+}
+
+/// Label in front of the DHCP command line.
+const DHCP_COMMAND_LABEL: &str = "  Command:       ";
+
+/// Rows the wrapped DHCP command line takes in a card `width` wide.
+fn dhcp_command_rows(info: &DebugInfo, width: u16) -> u16 {
+    let inner = width.saturating_sub(2).max(1) as usize;
+    info.dhcp_command.as_ref().map_or(0, |command| {
+        (DHCP_COMMAND_LABEL.len() + command.chars().count()).div_ceil(inner) as u16
+    })
 }
 
 fn render_status_summary(frame: &mut Frame, area: Rect, info: &DebugInfo) {
     let pf_status = if info.pf_enabled {
         Span::styled(
             format!("{} Enabled", symbols::STATUS_ACTIVE),
-            Style::default().fg(colors::SUCCESS),
+            Style::default().fg(colors::success()),
         )
     } else {
         Span::styled(
-            format!("{} Disabled", symbols::STATUS_INACTIVE),
-            Style::default().fg(colors::ERROR),
+            format!("{} Disabled", symbols::ERROR),
+            Style::default().fg(colors::error()),
         )
     };
 
     let ip_fwd_status = if info.ip_forwarding_enabled {
         Span::styled(
             format!("{} Enabled", symbols::STATUS_ACTIVE),
-            Style::default().fg(colors::SUCCESS),
+            Style::default().fg(colors::success()),
         )
     } else {
         Span::styled(
             format!("{} Disabled", symbols::STATUS_INACTIVE),
-            Style::default().fg(colors::WARNING),
+            Style::default().fg(colors::warning()),
         )
     };
 
@@ -65,12 +172,12 @@ fn render_status_summary(frame: &mut Frame, area: Rect, info: &DebugInfo) {
         if let Some((start, end)) = &info.dhcp_range {
             Span::styled(
                 format!("{} Active ({}-{})", symbols::STATUS_ACTIVE, start, end),
-                Style::default().fg(colors::SUCCESS),
+                Style::default().fg(colors::success()),
             )
         } else {
             Span::styled(
                 format!("{} Active", symbols::STATUS_ACTIVE),
-                Style::default().fg(colors::SUCCESS),
+                Style::default().fg(colors::success()),
             )
         }
     } else {
@@ -81,9 +188,13 @@ fn render_status_summary(frame: &mut Frame, area: Rect, info: &DebugInfo) {
     };
 
     let natpmp_status = if info.natpmp_running {
+        let external = info
+            .natpmp_external_ip
+            .map(|ip| format!(" (external {})", ip))
+            .unwrap_or_default();
         Span::styled(
-            format!("{} Active", symbols::STATUS_ACTIVE),
-            Style::default().fg(colors::SUCCESS),
+            format!("{} Active{}", symbols::STATUS_ACTIVE, external),
+            Style::default().fg(colors::success()),
         )
     } else {
         Span::styled(
@@ -115,6 +226,39 @@ fn render_status_summary(frame: &mut Frame, area: Rect, info: &DebugInfo) {
             ),
             dhcp_status,
         ]),
+    ];
+    // The exact invocation, for reproducing DHCP problems outside the app
+    if let Some(command) = &info.dhcp_command {
+        lines.push(Line::from(vec![
+            Span::styled(
+                DHCP_COMMAND_LABEL,
+                Style::default().fg(colors::TEXT_SECONDARY),
+            ),
+            Span::styled(command.clone(), Style::default().fg(colors::TEXT_PRIMARY)),
+        ]));
+    }
+    if let Some(boot) = &info.dhcp_boot {
+        lines.push(Line::from(vec![
+            Span::styled(
+                "  Netboot:       ",
+                Style::default().fg(colors::TEXT_SECONDARY),
+            ),
+            Span::styled(boot.to_string(), Style::default().fg(colors::TEXT_PRIMARY)),
+        ]));
+    }
+    lines.extend([
+        Line::from(vec![
+            Span::styled(
+                "  dnsmasq:       ",
+                Style::default().fg(colors::TEXT_SECONDARY),
+            ),
+            Span::styled(
+                info.dnsmasq_version
+                    .map(|v| v.to_string())
+                    .unwrap_or_else(|| "not found".into()),
+                Style::default().fg(colors::TEXT_PRIMARY),
+            ),
+        ]),
         Line::from(vec![
             Span::styled(
                 "  NAT-PMP:       ",
@@ -122,6 +266,36 @@ fn render_status_summary(frame: &mut Frame, area: Rect, info: &DebugInfo) {
             ),
             natpmp_status,
         ]),
+        Line::from(vec![
+            Span::styled(
+                "  LAN Broadcast: ",
+                Style::default().fg(colors::TEXT_SECONDARY),
+            ),
+            Span::styled(
+                info.lan_broadcast
+                    .map(|ip| ip.to_string())
+                    .unwrap_or_else(|| "none".into()),
+                Style::default().fg(colors::TEXT_PRIMARY),
+            ),
+        ]),
+        Line::from(vec![
+            Span::styled(
+                "  MSS Clamp:     ",
+                Style::default().fg(colors::TEXT_SECONDARY),
+            ),
+            Span::styled(
+                info.mss_clamp.0.to_string(),
+                Style::default().fg(colors::TEXT_PRIMARY),
+            ),
+            Span::styled(
+                if info.mss_clamp.1 {
+                    " (override)"
+                } else {
+                    " (default)"
+                },
+                Style::default().fg(colors::TEXT_SECONDARY),
+            ),
+        ]),
         Line::from(vec![
             Span::styled(
                 "  Active States: ",
@@ -134,26 +308,7 @@ fn render_status_summary(frame: &mut Frame, area: Rect, info: &DebugInfo) {
                     .add_modifier(Modifier::BOLD),
             ),
         ]),
-    ];
-
-    // Add sample connections from PF states
-    let state_lines: Vec<&str> = info.pf_states.lines().collect();
-    let total_states = state_lines.len().saturating_sub(1);
-    if total_states > 0 {
-        lines.push(Line::from(""));
-        let inner_width = area.width.saturating_sub(8) as usize;
-        for line in state_lines.iter().skip(1).take(2) {
-            let display = if line.len() > inner_width {
-                format!("{}...", &line[..inner_width.saturating_sub(3)])
-            } else {
-                line.to_string()
-            };
-            lines.push(Line::from(Span::styled(
-                format!("    {}", display),
-                Style::default().fg(colors::TEXT_SECONDARY),
-            )));
-        }
-    }
+    ]);
 
     let card = Card::new(Span::styled(" System Status ", styles::card_title()));
     frame.render_widget(card, area);
@@ -165,12 +320,156 @@ fn render_status_summary(frame: &mut Frame, area: Rect, info: &DebugInfo) {
         area.height.saturating_sub(2),
     );
 
-    let paragraph = Paragraph::new(lines);
+    let paragraph = Paragraph::new(lines).wrap(Wrap { trim: false });
     frame.render_widget(paragraph, inner);
 }
 
-fn render_pf_rules(frame: &mut Frame, area: Rect, info: &DebugInfo) {
-    let card = Card::new(Span::styled(" PF Rules ", styles::card_title()));
+fn render_top_talkers(frame: &mut Frame, area: Rect, info: &DebugInfo) {
+    let card = Card::new(Span::styled(" Top Clients ", styles::card_title()));
+    frame.render_widget(card, area);
+
+    let inner = Rect::new(
+        area.x + 1,
+        area.y + 1,
+        area.width.saturating_sub(2),
+        area.height.saturating_sub(2),
+    );
+
+    let lines: Vec<Line> = if info.top_talkers.is_empty() {
+        vec![Line::from(Span::styled(
+            "  No client traffic recorded yet",
+            Style::default().fg(colors::TEXT_SECONDARY),
+        ))]
+    } else {
+        info.top_talkers
+            .iter()
+            .take(MAX_TOP_TALKERS)
+            .map(|(client, bytes_in, bytes_out)| {
+                Line::from(vec![
+                    Span::styled(
+                        format!("  {:<18}", client),
+                        Style::default().fg(colors::TEXT_PRIMARY),
+                    ),
+                    Span::styled(
+                        format!("\u{2191} {:>10}   ", format_bytes(*bytes_in)),
+                        Style::default().fg(colors::TEXT_SECONDARY),
+                    ),
+                    Span::styled(
+                        format!("\u{2193} {:>10}", format_bytes(*bytes_out)),
+                        Style::default().fg(colors::ACCENT),
+                    ),
+                ])
+            })
+            .collect()
+    };
+
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+fn render_pf_states(frame: &mut Frame, area: Rect, info: &DebugInfo, scroll: usize) {
+    let title = if info.pf_states.is_empty() {
+        " PF States ".to_string()
+    } else {
+        format!(
+            " PF States ({}-{} of {}) ",
+            scroll + 1,
+            (scroll + area.height.saturating_sub(3) as usize).min(info.pf_states.len()),
+            info.pf_states.len()
+        )
+    };
+    let card = Card::new(Span::styled(title, styles::card_title()));
+    frame.render_widget(card, area);
+
+    let inner = Rect::new(
+        area.x + 1,
+        area.y + 1,
+        area.width.saturating_sub(2),
+        area.height.saturating_sub(2),
+    );
+
+    if info.pf_states.is_empty() {
+        frame.render_widget(
+            Paragraph::new(Span::styled(
+                "  No active states",
+                Style::default().fg(colors::TEXT_SECONDARY),
+            )),
+            inner,
+        );
+        return;
+    }
+
+    let mut lines = vec![Line::from(Span::styled(
+        format!(
+            "  {:<5} {:<22} {:<22} {:<22} {}",
+            "PROTO", "SOURCE", "DESTINATION", "NAT", "STATE"
+        ),
+        Style::default()
+            .fg(colors::TEXT_SECONDARY)
+            .add_modifier(Modifier::BOLD),
+    ))];
+    lines.extend(
+        info.pf_states
+            .iter()
+            .skip(scroll)
+            .take(inner.height.saturating_sub(1) as usize)
+            .map(format_pf_state),
+    );
+
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+/// One aligned row of the state table, colored by protocol.
+fn format_pf_state(state: &PfState) -> Line<'static> {
+    let proto_color = match state.protocol.as_str() {
+        "tcp" => colors::ACCENT,
+        "udp" => colors::LAN,
+        "icmp" | "icmp6" => colors::warning(),
+        _ => colors::TEXT_PRIMARY,
+    };
+
+    Line::from(vec![
+        Span::styled(
+            format!("  {:<5} ", state.protocol),
+            Style::default().fg(proto_color),
+        ),
+        Span::styled(
+            format!("{:<22} {:<22} ", state.source, state.destination),
+            Style::default().fg(colors::TEXT_PRIMARY),
+        ),
+        Span::styled(
+            format!("{:<22} ", state.nat.as_deref().unwrap_or("-")),
+            Style::default().fg(colors::TEXT_SECONDARY),
+        ),
+        Span::styled(
+            state.state.clone(),
+            Style::default().fg(colors::TEXT_SECONDARY),
+        ),
+    ])
+}
+
+/// Format a byte count with a binary unit suffix (e.g. "4.2 GB").
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+fn render_pf_rules(frame: &mut Frame, area: Rect, info: &DebugInfo, prev: Option<&DebugInfo>) {
+    let title = if prev.is_some() {
+        " PF Rules (changes since last fetch) "
+    } else {
+        " PF Rules "
+    };
+    let card = Card::new(Span::styled(title, styles::card_title()));
     frame.render_widget(card, area);
 
     let inner = Rect::new(
@@ -180,6 +479,31 @@ fn render_pf_rules(frame: &mut Frame, area: Rect, info: &DebugInfo) {
         area.height.saturating_sub(2),
     );
 
+    if let Some(prev) = prev {
+        let rules: Vec<Line> = diff_lines(&prev.pf_rules, &info.pf_rules)
+            .into_iter()
+            .take(inner.height as usize)
+            .map(|(kind, line)| match kind {
+                DiffKind::Added => Line::from(Span::styled(
+                    format!("+ {}", line),
+                    Style::default().fg(colors::success()),
+                )),
+                DiffKind::Removed => Line::from(Span::styled(
+                    format!("- {}", line),
+                    Style::default().fg(colors::error()),
+                )),
+                DiffKind::Same => Line::from(Span::styled(
+                    format!("  {}", line),
+                    Style::default().fg(colors::TEXT_SECONDARY),
+                )),
+            })
+            .collect();
+
+        let paragraph = Paragraph::new(rules).wrap(Wrap { trim: false });
+        frame.render_widget(paragraph, inner);
+        return;
+    }
+
     let rules: Vec<Line> = info
         .pf_rules
         .lines()
@@ -190,9 +514,9 @@ fn render_pf_rules(frame: &mut Frame, area: Rect, info: &DebugInfo) {
             } else if line.starts_with("nat ") || line.starts_with("scrub ") {
                 Style::default().fg(colors::ACCENT)
             } else if line.starts_with("pass ") {
-                Style::default().fg(colors::SUCCESS)
+                Style::default().fg(colors::success())
             } else if line.starts_with("block ") {
-                Style::default().fg(colors::ERROR)
+                Style::default().fg(colors::error())
             } else {
                 Style::default().fg(colors::TEXT_PRIMARY)
             };
@@ -203,3 +527,178 @@ fn render_pf_rules(frame: &mut Frame, area: Rect, info: &DebugInfo) {
     let paragraph = Paragraph::new(rules).wrap(Wrap { trim: false });
     frame.render_widget(paragraph, inner);
 }
+
+/// Line-based diff of `old` against `new` (longest common subsequence).
+fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<(DiffKind, &'a str)> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // lcs[i][j] = LCS length of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = Vec::with_capacity(old.len().max(new.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            diff.push((DiffKind::Same, new[j]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            diff.push((DiffKind::Removed, old[i]));
+            i += 1;
+        } else {
+            diff.push((DiffKind::Added, new[j]));
+            j += 1;
+        }
+    }
+    diff.extend(old[i..].iter().map(|l| (DiffKind::Removed, *l)));
+    diff.extend(new[j..].iter().map(|l| (DiffKind::Added, *l)));
+    diff
+}
+
+impl DebugInfo {
+    /// The panel's status summary and pf rules as plain text, for pasting
+    /// into a bug report.
+    pub fn to_report_string(&self) -> String {
+        let on_off = |on: bool| if on { "enabled" } else { "disabled" };
+        let running = |on: bool| if on { "running" } else { "stopped" };
+
+        let mut lines = vec![
+            "System status".to_string(),
+            format!("  PF Firewall:   {}", on_off(self.pf_enabled)),
+            format!(
+                "  IP Forwarding: {}{}",
+                on_off(self.ip_forwarding_enabled),
+                if self.ip_forwarding_modified {
+                    " (modified)"
+                } else {
+                    ""
+                }
+            ),
+            format!(
+                "  DHCP Server:   {}{}",
+                running(self.dhcp_running),
+                self.dhcp_range
+                    .as_ref()
+                    .map(|(start, end)| format!(" ({}-{})", start, end))
+                    .unwrap_or_default()
+            ),
+        ];
+        if let Some(command) = &self.dhcp_command {
+            lines.push(format!("{}{}", DHCP_COMMAND_LABEL, command));
+        }
+        if let Some(boot) = &self.dhcp_boot {
+            lines.push(format!("  Netboot:       {}", boot));
+        }
+        lines.extend([
+            format!(
+                "  dnsmasq:       {}",
+                self.dnsmasq_version
+                    .map(|v| v.to_string())
+                    .unwrap_or_else(|| "not found".into())
+            ),
+            format!(
+                "  NAT-PMP:       {}{}",
+                running(self.natpmp_running),
+                self.natpmp_external_ip
+                    .map(|ip| format!(" (external {})", ip))
+                    .unwrap_or_default()
+            ),
+            format!(
+                "  LAN Broadcast: {}",
+                self.lan_broadcast
+                    .map(|ip| ip.to_string())
+                    .unwrap_or_else(|| "none".into())
+            ),
+            format!(
+                "  MSS Clamp:     {} ({})",
+                self.mss_clamp.0,
+                if self.mss_clamp.1 {
+                    "override"
+                } else {
+                    "default"
+                }
+            ),
+            format!("  Active States: {}", self.pf_state_count),
+            String::new(),
+            "pf rules".to_string(),
+        ]);
+        if self.pf_rules.trim().is_empty() {
+            lines.push("  (none loaded)".to_string());
+        } else {
+            lines.extend(self.pf_rules.lines().map(|line| format!("  {}", line)));
+        }
+
+        let mut report = lines.join("\n");
+        report.push('\n');
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_string() {
+        let info = DebugInfo {
+            pf_rules: "nat on utun4 from 192.168.2.0/24 to any -> (utun4)\n".to_string(),
+            pf_states: Vec::new(),
+            pf_state_count: 12,
+            pf_enabled: true,
+            ip_forwarding_modified: true,
+            ip_forwarding_enabled: true,
+            dhcp_running: true,
+            dhcp_range: Some(("192.168.2.100".to_string(), "192.168.2.200".to_string())),
+            dhcp_command: None,
+            dhcp_boot: None,
+            natpmp_running: false,
+            natpmp_external_ip: None,
+            lan_broadcast: None,
+            mss_clamp: (1240, true),
+            dnsmasq_version: None,
+            top_talkers: Vec::new(),
+        };
+        let report = info.to_report_string();
+        assert!(report.contains("  IP Forwarding: enabled (modified)\n"));
+        assert!(report.contains("  DHCP Server:   running (192.168.2.100-192.168.2.200)\n"));
+        assert!(report.contains("  NAT-PMP:       stopped\n"));
+        assert!(report.contains("  MSS Clamp:     1240 (override)\n"));
+        assert!(
+            report.ends_with("pf rules\n  nat on utun4 from 192.168.2.0/24 to any -> (utun4)\n")
+        );
+
+        let empty = DebugInfo {
+            pf_rules: String::new(),
+            ..info
+        };
+        assert!(empty
+            .to_report_string()
+            .ends_with("pf rules\n  (none loaded)\n"));
+    }
+
+    #[test]
+    fn test_diff_lines() {
+        let old = "scrub in all\nnat on utun4\npass quick on en0 all";
+        let new = "scrub in all\nnat on utun4\nrdr pass on utun4 port 8080\npass out quick";
+        assert_eq!(
+            diff_lines(old, new),
+            vec![
+                (DiffKind::Same, "scrub in all"),
+                (DiffKind::Same, "nat on utun4"),
+                (DiffKind::Removed, "pass quick on en0 all"),
+                (DiffKind::Added, "rdr pass on utun4 port 8080"),
+                (DiffKind::Added, "pass out quick"),
+            ]
+        );
+    }
+}
@@ -2,6 +2,8 @@
 //!
 //! Step-based interface selection with tree-style details.
 
+use std::net::Ipv4Addr;
+
 use ratatui::{
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Modifier, Style},
@@ -10,7 +12,7 @@ use ratatui::{
     Frame,
 };
 
-use crate::app::App;
+use crate::app::{App, MissingInterfaces};
 use crate::system::InterfaceInfo;
 use crate::ui::theme::{colors, styles, symbols};
 use crate::ui::widgets::Card;
@@ -42,8 +44,9 @@ pub fn render_vpn_selection(frame: &mut Frame, area: Rect, app: &App) {
             content_area,
             "VPN Interfaces",
             &app.vpn_interfaces,
+            &app.filtered_indices(&app.vpn_interfaces),
             app.selected_vpn,
-            true,
+            app,
         );
     }
 }
@@ -75,7 +78,14 @@ pub fn render_lan_selection(frame: &mut Frame, area: Rect, app: &App) {
         if let Some(vpn) = app.vpn_interfaces.get(vpn_idx) {
             let effective_dns = app.dns.effective();
             let dns_source = app.dns.source();
-            render_selected_vpn_summary(frame, chunks[0], vpn, &effective_dns, dns_source);
+            render_selected_vpn_summary(
+                frame,
+                chunks[0],
+                vpn,
+                &effective_dns,
+                dns_source,
+                app.natpmp_external_ip,
+            );
         }
     }
 
@@ -93,8 +103,9 @@ pub fn render_lan_selection(frame: &mut Frame, area: Rect, app: &App) {
             chunks[1],
             "LAN Interfaces",
             &app.lan_interfaces,
+            &app.filtered_indices(&app.lan_interfaces),
             app.selected_lan,
-            true,
+            app,
         );
     }
 }
@@ -137,6 +148,7 @@ fn render_selected_vpn_summary(
     vpn: &InterfaceInfo,
     dns_servers: &[String],
     dns_source: &str,
+    natpmp_external_ip: Option<Ipv4Addr>,
 ) {
     let card = Card::new(Span::styled(" Selected VPN ", styles::card_title()));
     frame.render_widget(card, area);
@@ -162,9 +174,9 @@ fn render_selected_vpn_summary(
         )
     };
 
-    let summary_line = Line::from(vec![
+    let mut summary_spans = vec![
         Span::styled(
-            &vpn.name,
+            vpn.display_label(),
             styles::vpn_interface().add_modifier(Modifier::BOLD),
         ),
         Span::raw("  "),
@@ -174,27 +186,50 @@ fn render_selected_vpn_summary(
         Span::raw("    "),
         Span::styled("DNS: ", Style::default().fg(colors::TEXT_SECONDARY)),
         Span::styled(dns_display, Style::default().fg(colors::TEXT_PRIMARY)),
-    ]);
+    ];
+
+    // Only worth showing when there's a choice to make
+    if let Some(external) = natpmp_external_ip.filter(|_| vpn.ipv4_addresses.len() > 1) {
+        summary_spans.extend([
+            Span::raw("    "),
+            Span::styled("NAT-PMP: ", Style::default().fg(colors::TEXT_SECONDARY)),
+            Span::styled(
+                external.to_string(),
+                Style::default().fg(colors::TEXT_PRIMARY),
+            ),
+            Span::styled(" (e: change)", Style::default().fg(colors::TEXT_SECONDARY)),
+        ]);
+    }
 
-    let summary_para = Paragraph::new(summary_line);
+    let summary_para = Paragraph::new(Line::from(summary_spans));
     frame.render_widget(summary_para, inner);
 }
 
 /// Render interface list with tree-style details.
+///
+/// Only the entries at `visible` (indices into `interfaces`, from the list
+/// filter) are shown; the filter text goes in the card title.
 fn render_interface_list(
     frame: &mut Frame,
     area: Rect,
     title: &str,
     interfaces: &[InterfaceInfo],
+    visible: &[usize],
     selected: Option<usize>,
-    is_focused: bool,
+    app: &App,
 ) {
     // Determine if this is VPN or LAN based on title
     let is_vpn = title.contains("VPN");
 
-    let card = Card::new(Span::styled(format!(" {} ", title), styles::card_title()))
-        .focused(is_focused)
-        .item_count(interfaces.len());
+    let card_title = if app.filtering || !app.filter_buffer.is_empty() {
+        let cursor = if app.filtering { "_" } else { "" };
+        format!(" {}  /{}{} ", title, app.filter_buffer, cursor)
+    } else {
+        format!(" {} ", title)
+    };
+    let card = Card::new(Span::styled(card_title, styles::card_title()))
+        .focused(true)
+        .item_count(visible.len());
 
     frame.render_widget(card, area);
 
@@ -207,7 +242,15 @@ fn render_interface_list(
 
     // Render each interface with tree-style details
     let mut y_offset = 0u16;
-    for (i, iface) in interfaces.iter().enumerate() {
+    if visible.is_empty() {
+        let empty = Paragraph::new(Line::from(Span::styled(
+            "No interfaces match the filter",
+            styles::hint(),
+        )));
+        frame.render_widget(empty, Rect::new(inner.x, inner.y, inner.width, 1));
+    }
+
+    for (i, iface) in visible.iter().map(|&i| (i, &interfaces[i])) {
         if y_offset >= inner.height {
             break;
         }
@@ -215,7 +258,7 @@ fn render_interface_list(
         let is_selected = selected == Some(i);
 
         // Main interface line
-        let prefix = if is_selected && is_focused {
+        let prefix = if is_selected {
             format!("{} ", symbols::SELECTED)
         } else {
             "  ".to_string()
@@ -231,17 +274,24 @@ fn render_interface_list(
             styles::unselected()
         };
 
-        // Interface name with optional description
-        let display_name = if let Some(ref desc) = iface.description {
-            format!("{} ({})", iface.name, desc)
-        } else {
-            iface.name.clone()
-        };
+        let display_name = iface.display_label();
 
-        let main_line = Line::from(vec![
+        let mut main_line = Line::from(vec![
             Span::styled(prefix, name_style),
             Span::styled(display_name, name_style),
         ]);
+        if is_vpn && app.extra_uplinks.contains(&iface.name) {
+            main_line.push_span(Span::styled(
+                "  + uplink",
+                Style::default().fg(colors::ACCENT),
+            ));
+        }
+        if is_vpn && app.failover_vpn.as_ref() == Some(&iface.name) {
+            main_line.push_span(Span::styled(
+                "  failover",
+                Style::default().fg(colors::ACCENT),
+            ));
+        }
 
         let main_area = Rect::new(inner.x, inner.y + y_offset, inner.width, 1);
         frame.render_widget(Paragraph::new(main_line), main_area);
@@ -251,23 +301,62 @@ fn render_interface_list(
         if is_selected && y_offset + 2 <= inner.height {
             // IP line
             if let Some(ip) = iface.ipv4_address {
-                let ip_line = Line::from(vec![
+                let mut spans = vec![
                     Span::styled(
                         format!("  {} ", symbols::TREE_BRANCH),
                         styles::tree_branch(),
                     ),
                     Span::styled("IP: ", Style::default().fg(colors::TEXT_SECONDARY)),
                     Span::styled(ip.to_string(), Style::default().fg(colors::TEXT_PRIMARY)),
+                ];
+                if let Some(peer) = iface.peer_ipv4 {
+                    spans.push(Span::styled(
+                        format!("  peer {}", peer),
+                        Style::default().fg(colors::TEXT_SECONDARY),
+                    ));
+                }
+                let ip_line = Line::from(spans);
+                let ip_area = Rect::new(inner.x, inner.y + y_offset, inner.width, 1);
+                frame.render_widget(Paragraph::new(ip_line), ip_area);
+                y_offset += 1;
+            } else {
+                // LAN interfaces without IPv4 get a gateway address assigned on start
+                let ip_line = Line::from(vec![
+                    Span::styled(
+                        format!("  {} ", symbols::TREE_BRANCH),
+                        styles::tree_branch(),
+                    ),
+                    Span::styled("IP: ", Style::default().fg(colors::TEXT_SECONDARY)),
+                    Span::styled(
+                        "none (a gateway address will be assigned)",
+                        Style::default().fg(colors::TEXT_SECONDARY),
+                    ),
                 ]);
                 let ip_area = Rect::new(inner.x, inner.y + y_offset, inner.width, 1);
                 frame.render_widget(Paragraph::new(ip_line), ip_area);
                 y_offset += 1;
             }
 
-            // Status line
-            let status_icon = symbols::STATUS_ACTIVE;
-            let status_text = "Connected";
-            let status_style = styles::status_active();
+            // Bridge member ports
+            if !iface.members.is_empty() && y_offset + 2 <= inner.height {
+                let members_line = Line::from(vec![
+                    Span::styled(
+                        format!("  {} ", symbols::TREE_BRANCH),
+                        styles::tree_branch(),
+                    ),
+                    Span::styled("Members: ", Style::default().fg(colors::TEXT_SECONDARY)),
+                    Span::styled(
+                        iface.members.join(", "),
+                        Style::default().fg(colors::TEXT_PRIMARY),
+                    ),
+                ]);
+                let members_area = Rect::new(inner.x, inner.y + y_offset, inner.width, 1);
+                frame.render_widget(Paragraph::new(members_line), members_area);
+                y_offset += 1;
+            }
+
+            // Status line, from the flags seen at the last scan
+            let (status_icon, status_text, status_style) = link_status(iface);
 
             let status_line = Line::from(vec![
                 Span::styled(format!("  {} ", symbols::TREE_END), styles::tree_branch()),
@@ -284,6 +373,114 @@ fn render_interface_list(
     }
 }
 
+/// Icon, text and style for an interface's link state: down when it isn't
+/// `UP`, no link when it's up but not `RUNNING` (a tunnel that isn't
+/// connected, an unplugged cable).
+fn link_status(iface: &InterfaceInfo) -> (&'static str, &'static str, Style) {
+    if !iface.is_up {
+        (symbols::ERROR, "Down", styles::status_down())
+    } else if !iface.is_running {
+        (symbols::WARNING, "No link", styles::status_degraded())
+    } else {
+        (symbols::STATUS_ACTIVE, "Connected", styles::status_active())
+    }
+}
+
+/// Where the troubleshooting section of the docs lives.
+const DOCS_URL: &str = "https://github.com/Mehdi-Hp/tunshare#requirements";
+
+/// Render the full-screen explanation shown when detection found nothing to share.
+pub fn render_missing_interfaces(frame: &mut Frame, area: Rect, app: &App) {
+    let Some(missing) = app.missing_interfaces() else {
+        return;
+    };
+
+    let (title, causes): (&str, &[&str]) = match missing {
+        MissingInterfaces::Vpn => (
+            "No VPN interface found",
+            &[
+                "Your VPN client isn't connected yet",
+                "The VPN doesn't create a utun tunnel with an IPv4 address",
+            ],
+        ),
+        MissingInterfaces::Lan => (
+            "No LAN interface found",
+            &[
+                "No Ethernet, USB or Wi-Fi adapter (en*) or bridge (bridge*) is up",
+                "The adapter is there but turned off (down)",
+                "Sharing needs a second NIC when Wi-Fi is your uplink",
+            ],
+        ),
+        MissingInterfaces::Both => (
+            "No VPN or LAN interface found",
+            &[
+                "Your VPN client isn't connected yet",
+                "No Ethernet, USB or Wi-Fi adapter (en*) or bridge (bridge*) is up",
+            ],
+        ),
+    };
+
+    let card = Card::new(Span::styled(" Nothing to share ", styles::card_title())).focused(true);
+    frame.render_widget(card, area);
+
+    let inner = Rect::new(
+        area.x + 2,
+        area.y + 1,
+        area.width.saturating_sub(4),
+        area.height.saturating_sub(2),
+    );
+
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled(symbols::WARNING, Style::default().fg(colors::warning())),
+            Span::raw(" "),
+            Span::styled(
+                title,
+                Style::default()
+                    .fg(colors::error())
+                    .add_modifier(Modifier::BOLD),
+            ),
+        ]),
+        Line::raw(""),
+        Line::from(Span::styled(
+            "Likely causes:",
+            Style::default().fg(colors::TEXT_PRIMARY),
+        )),
+    ];
+    let last = causes.len().saturating_sub(1);
+    lines.extend(causes.iter().enumerate().map(|(i, cause)| {
+        let branch = if i == last {
+            symbols::TREE_END
+        } else {
+            symbols::TREE_BRANCH
+        };
+        Line::from(vec![
+            Span::styled(format!("{} ", branch), styles::tree_branch()),
+            Span::styled(*cause, Style::default().fg(colors::TEXT_SECONDARY)),
+        ])
+    }));
+    lines.extend([
+        Line::raw(""),
+        Line::from(vec![
+            Span::styled("Fix it, then press ", styles::hint()),
+            Span::styled("r", styles::help_key()),
+            Span::styled(" to rescan.", styles::hint()),
+        ]),
+        Line::from(vec![
+            Span::styled("Docs: ", styles::hint()),
+            Span::styled(DOCS_URL, Style::default().fg(colors::ACCENT)),
+        ]),
+    ]);
+
+    // Center the block vertically
+    let height = (lines.len() as u16).min(inner.height);
+    let y = inner.y + (inner.height - height) / 2;
+    frame.render_widget(
+        Paragraph::new(lines),
+        Rect::new(inner.x, y, inner.width, height),
+    );
+}
+
 /// Render a message when no interfaces are found.
 pub fn render_no_interfaces(frame: &mut Frame, area: Rect, title: &str, message: &str) {
     let card = Card::new(Span::styled(format!(" {} ", title), styles::card_title()));
@@ -297,9 +494,9 @@ pub fn render_no_interfaces(frame: &mut Frame, area: Rect, title: &str, message:
     );
 
     let msg_line = Line::from(vec![
-        Span::styled(symbols::WARNING, Style::default().fg(colors::WARNING)),
+        Span::styled(symbols::WARNING, Style::default().fg(colors::warning())),
         Span::raw(" "),
-        Span::styled(message, Style::default().fg(colors::ERROR)),
+        Span::styled(message, Style::default().fg(colors::error())),
     ]);
 
     let msg_para = Paragraph::new(msg_line).alignment(Alignment::Center);
@@ -309,3 +506,26 @@ pub fn render_no_interfaces(frame: &mut Frame, area: Rect, title: &str, message:
     let msg_area = Rect::new(inner.x, msg_y, inner.width, 1);
     frame.render_widget(msg_para, msg_area);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::network::iface;
+
+    #[test]
+    fn test_link_status() {
+        let utun = |is_up, is_running| InterfaceInfo {
+            ipv4_address: Some(Ipv4Addr::new(10, 8, 0, 2)),
+            ipv4_addresses: vec![Ipv4Addr::new(10, 8, 0, 2)],
+            is_up,
+            is_running,
+            ..iface("utun4")
+        };
+        let text = |is_up, is_running| link_status(&utun(is_up, is_running)).1;
+
+        assert_eq!(text(true, true), "Connected");
+        assert_eq!(text(true, false), "No link");
+        assert_eq!(text(false, false), "Down");
+        assert_eq!(link_status(&utun(false, true)).2, styles::status_down());
+    }
+}
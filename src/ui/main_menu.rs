@@ -4,42 +4,148 @@ use ratatui::{
     layout::{Alignment, Rect},
     style::{Modifier, Style},
     text::{Line, Span},
-    widgets::{Clear, Paragraph},
+    widgets::{Clear, Paragraph, Wrap},
     Frame,
 };
 
-use crate::app::{App, AppState, DnsEditMode, MenuItem, DNS_PRESETS};
+use ipnet::Ipv4Net;
+
+use crate::app::{App, AppState, DnsEditMode, MenuItem};
 use crate::health::HealthStatus;
+use crate::system::network;
+use crate::ui::status::truncate_with_ellipsis;
 use crate::ui::theme::{borders, colors, styles, symbols};
 use crate::ui::widgets::Card;
 
+/// Width of each interface box in the connection diagram.
+const DIAGRAM_BOX_WIDTH: u16 = 16;
+/// Width of the gap holding the arrow between the boxes.
+const DIAGRAM_ARROW_WIDTH: u16 = 10;
+
+/// How the connection diagram is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DiagramLayout {
+    /// Labeled boxes joined by an arrow (4 rows).
+    Boxes,
+    /// `utun4 (10.8.0.6) → en0 (192.168.2.1)` on one row, for narrow terminals.
+    Inline,
+}
+
+impl DiagramLayout {
+    /// The box diagram if it fits in `width` columns, else the inline form.
+    fn for_width(width: u16) -> Self {
+        if width >= DIAGRAM_BOX_WIDTH * 2 + DIAGRAM_ARROW_WIDTH {
+            Self::Boxes
+        } else {
+            Self::Inline
+        }
+    }
+
+    fn rows(self) -> u16 {
+        match self {
+            Self::Boxes => 4,
+            Self::Inline => 1,
+        }
+    }
+}
+
+/// The header title, with the instance `label` after it shortened to fit
+/// in `width` columns. The label is left out when not even one character fits.
+fn header_title(label: Option<&str>, width: usize) -> String {
+    let title = format!("{} VPN Share", symbols::APP_ICON);
+    let Some(label) = label else {
+        return title;
+    };
+    let prefix = format!("{} \u{2014} ", title);
+    match width
+        .checked_sub(prefix.chars().count())
+        .filter(|&room| room > 0)
+    {
+        Some(room) => format!("{}{}", prefix, truncate_with_ellipsis(label, room)),
+        None => title,
+    }
+}
+
 /// Render the single-line header with app title and status badge.
 pub fn render_header(frame: &mut Frame, area: Rect, app: &App) {
-    let (status_text, status_style, status_icon) = if app.is_sharing() {
+    let mut reason = None;
+    let (status_text, status_style, status_icon) = if app.is_sharing() && app.health_paused() {
+        ("Health paused", styles::status_inactive(), symbols::PAUSED)
+    } else if app.is_sharing() {
         match app.health_status() {
             HealthStatus::Healthy => ("Active", styles::status_active(), symbols::STATUS_ACTIVE),
-            HealthStatus::Degraded(_) => ("Degraded", styles::status_degraded(), symbols::WARNING),
-            HealthStatus::Down(_) => ("VPN Down", styles::status_down(), symbols::ERROR),
+            HealthStatus::Degraded(r) => {
+                reason = Some(r.as_str());
+                ("Degraded", styles::status_degraded(), symbols::WARNING)
+            }
+            HealthStatus::Down(r) => {
+                reason = Some(r.as_str());
+                ("VPN Down", styles::status_down(), symbols::ERROR)
+            }
         }
     } else {
         let text = match app.state {
-            AppState::SelectingVpn | AppState::SelectingLan | AppState::EditingDns => "Configuring",
+            AppState::SelectingVpn
+            | AppState::SelectingLan
+            | AppState::EditingDns
+            | AppState::SelectingProfile
+            | AppState::NoInterfaces => "Configuring",
             _ => "Inactive",
         };
         (text, styles::status_inactive(), symbols::STATUS_INACTIVE)
     };
 
     // Build the header line
-    let title = Span::styled(format!("{} VPN Share", symbols::APP_ICON), styles::title());
+    let mut status = Span::styled(format!("{} {}", status_icon, status_text), status_style);
 
-    let status = Span::styled(format!("{} {}", status_icon, status_text), status_style);
+    // Sampling is throttled on battery
+    let battery = Span::styled(
+        if app.on_battery {
+            format!("{} ", symbols::BATTERY)
+        } else {
+            String::new()
+        },
+        Style::default().fg(colors::TEXT_SECONDARY),
+    );
 
-    // Calculate spacing
-    let title_width = title.content.chars().count();
-    let status_width = status.content.chars().count();
-    let spacing = area.width as usize - title_width - status_width;
+    // Daily sharing window, when one is set
+    let hours = Span::styled(
+        app.active_hours()
+            .map(|hours| format!("{} ", hours))
+            .unwrap_or_default(),
+        Style::default().fg(colors::TEXT_SECONDARY),
+    );
 
-    let header_line = Line::from(vec![title, Span::raw(" ".repeat(spacing.max(1))), status]);
+    // Name the instance when a label is set, shortened to what's left beside the status
+    let extras_width = hours.width() + battery.width();
+    let title = Span::styled(
+        header_title(
+            app.session_label.as_deref(),
+            (area.width as usize).saturating_sub(extras_width + status.width() + 1),
+        ),
+        styles::title(),
+    );
+    let title_width = title.width();
+
+    // Append the reason when it fits, so problems are visible without opening logs
+    if let Some(reason) = reason {
+        let detailed = format!("{} {}: {}", status_icon, status_text, reason);
+        if title_width + extras_width + detailed.chars().count() < area.width as usize {
+            status = Span::styled(detailed, status_style);
+        }
+    }
+
+    // Calculate spacing
+    let status_width = extras_width + status.width();
+    let spacing = (area.width as usize).saturating_sub(title_width + status_width);
+
+    let header_line = Line::from(vec![
+        title,
+        Span::raw(" ".repeat(spacing.max(1))),
+        hours,
+        battery,
+        status,
+    ]);
 
     let header = Paragraph::new(header_line);
     frame.render_widget(header, area);
@@ -74,7 +180,11 @@ pub fn render_main_menu(frame: &mut Frame, area: Rect, app: &App) {
 
     for (i, item) in items.iter().enumerate() {
         match item {
-            MenuItem::StartSharing | MenuItem::StopSharing => group_action.push((i, item)),
+            MenuItem::StartSharing
+            | MenuItem::Profiles
+            | MenuItem::CleanUpLeftovers
+            | MenuItem::StopSharing
+            | MenuItem::RestartSharing => group_action.push((i, item)),
             MenuItem::ToggleDhcp | MenuItem::ToggleNatPmp | MenuItem::SetDns => {
                 group_settings.push((i, item))
             }
@@ -255,10 +365,13 @@ fn render_menu_item(
 }
 
 /// Get the static label string for a menu item (for width calculation).
-fn menu_item_label_str(item: &MenuItem) -> &'static str {
+pub fn menu_item_label_str(item: &MenuItem) -> &'static str {
     match item {
         MenuItem::StartSharing => "Start VPN Sharing",
+        MenuItem::Profiles => "Start From Profile",
+        MenuItem::CleanUpLeftovers => "Clean Up Leftover Rules",
         MenuItem::StopSharing => "Stop VPN Sharing",
+        MenuItem::RestartSharing => "Restart VPN Sharing",
         MenuItem::ToggleDhcp => "DHCP Server",
         MenuItem::ToggleNatPmp => "NAT-PMP Server",
         MenuItem::SetDns => "DNS Server",
@@ -269,10 +382,27 @@ fn menu_item_label_str(item: &MenuItem) -> &'static str {
 /// Get label and optional status badge for a menu item.
 fn menu_item_label_status(item: &MenuItem, app: &App) -> (String, Option<StatusBadge>) {
     match item {
+        MenuItem::StartSharing | MenuItem::Profiles | MenuItem::CleanUpLeftovers
+            if app.read_only =>
+        {
+            (
+                menu_item_label_str(item).to_string(),
+                Some(StatusBadge::Disabled("requires sudo".to_string())),
+            )
+        }
         MenuItem::StartSharing => ("Start VPN Sharing".to_string(), None),
+        MenuItem::CleanUpLeftovers => ("Clean Up Leftover Rules".to_string(), None),
+        MenuItem::Profiles => {
+            let badge = match app.profiles.len() {
+                0 => StatusBadge::Disabled("none saved".to_string()),
+                n => StatusBadge::Value(n.to_string()),
+            };
+            ("Start From Profile".to_string(), Some(badge))
+        }
         MenuItem::StopSharing => ("Stop VPN Sharing".to_string(), None),
+        MenuItem::RestartSharing => ("Restart VPN Sharing".to_string(), None),
         MenuItem::ToggleDhcp => {
-            if !app.dnsmasq_installed {
+            if app.dhcp_backend.is_none() {
                 (
                     "DHCP Server".to_string(),
                     Some(StatusBadge::Disabled("not installed".to_string())),
@@ -291,8 +421,9 @@ fn menu_item_label_status(item: &MenuItem, app: &App) -> (String, Option<StatusB
             }
         }
         MenuItem::SetDns => {
-            let value = if let Some(ref dns) = app.dns.custom {
-                dns.clone()
+            let custom = app.dns.custom_servers();
+            let value = if !custom.is_empty() {
+                custom.join(", ")
             } else {
                 let effective = app.dns.effective();
                 if effective.is_empty() {
@@ -308,8 +439,12 @@ fn menu_item_label_status(item: &MenuItem, app: &App) -> (String, Option<StatusB
 }
 
 /// Check if a menu item should be disabled (grayed out).
-fn is_menu_item_disabled(item: &MenuItem, app: &App) -> bool {
-    matches!(item, MenuItem::ToggleDhcp if !app.dnsmasq_installed)
+pub fn is_menu_item_disabled(item: &MenuItem, app: &App) -> bool {
+    match item {
+        MenuItem::StartSharing | MenuItem::Profiles | MenuItem::CleanUpLeftovers => app.read_only,
+        MenuItem::ToggleDhcp => app.dhcp_backend.is_none(),
+        _ => false,
+    }
 }
 
 /// Render the DNS editing overlay (dispatches by mode).
@@ -317,15 +452,24 @@ pub fn render_dns_edit(frame: &mut Frame, area: Rect, app: &App) {
     match app.dns.edit_mode {
         DnsEditMode::SelectingPreset => render_dns_preset_list(frame, area, app),
         DnsEditMode::CustomInput => render_dns_custom_input(frame, area, app),
+        DnsEditMode::Ordering => render_dns_ordering(frame, area, app),
     }
 }
 
+/// Shown while custom DNS servers replace the ones the VPN pushed.
+const DNS_OVERRIDE_WARNING: &str =
+    "Custom DNS overrides VPN DNS \u{2014} internal domains may not resolve.";
+
 /// Render the DNS preset selection list.
 fn render_dns_preset_list(frame: &mut Frame, area: Rect, app: &App) {
     // Items: Auto-detect, presets..., Custom...
-    let item_count = 1 + DNS_PRESETS.len() + 1; // auto + presets + custom
+    let presets = &app.dns.presets;
+    let item_count = 1 + presets.len() + 1; // auto + presets + custom
+
+    // Wrapped over two rows, plus a gap before the items
+    let warning_rows = if app.dns.overrides_vpn() { 2 } else { 0 };
     let card_width = 44u16.min(area.width.saturating_sub(4));
-    let card_height = (item_count as u16 + 4).min(area.height.saturating_sub(2)); // items + current line + padding
+    let card_height = (item_count as u16 + 4 + warning_rows).min(area.height.saturating_sub(2)); // items + current line + padding
     let card_x = area.x + (area.width.saturating_sub(card_width)) / 2;
     let card_y = area.y + (area.height.saturating_sub(card_height)) / 2;
     let card_area = Rect::new(card_x, card_y, card_width, card_height);
@@ -342,8 +486,9 @@ fn render_dns_preset_list(frame: &mut Frame, area: Rect, app: &App) {
     );
 
     // Current value line
-    let current_text = if let Some(ref dns) = app.dns.custom {
-        format!("Current: {} (custom)", dns)
+    let custom = app.dns.custom_servers();
+    let current_text = if !custom.is_empty() {
+        format!("Current: {} ({})", custom.join(", "), app.dns.source())
     } else {
         let effective = app.dns.effective();
         if effective.is_empty() {
@@ -363,12 +508,29 @@ fn render_dns_preset_list(frame: &mut Frame, area: Rect, app: &App) {
     let current_area = Rect::new(inner.x, inner.y, inner.width, 1);
     frame.render_widget(Paragraph::new(current_line), current_area);
 
+    if warning_rows > 0 {
+        let warning = Paragraph::new(Line::from(vec![
+            Span::styled(
+                format!("{} ", symbols::WARNING),
+                Style::default().fg(colors::warning()),
+            ),
+            Span::styled(DNS_OVERRIDE_WARNING, Style::default().fg(colors::warning())),
+        ]))
+        .wrap(Wrap { trim: true });
+        let warning_area = Rect::new(inner.x, inner.y + 1, inner.width, 2);
+        frame.render_widget(warning, warning_area);
+    }
+
     // Render each item
-    let items_y = inner.y + 2; // gap after current line
+    let items_y = inner.y + 2 + warning_rows; // gap after current line
     let name_col_width = 18u16;
 
-    for i in 0..item_count {
-        let y = items_y + i as u16;
+    // A long user preset list scrolls to keep the selection in view
+    let visible = (inner.y + inner.height).saturating_sub(items_y) as usize;
+    let start = (app.dns.preset_selected + 1).saturating_sub(visible);
+
+    for (row, i) in (start..item_count).enumerate() {
+        let y = items_y + row as u16;
         if y >= inner.y + inner.height {
             break;
         }
@@ -392,14 +554,14 @@ fn render_dns_preset_list(frame: &mut Frame, area: Rect, app: &App) {
                 Span::styled(prefix, style),
                 Span::styled("Auto-detect", style),
             ])
-        } else if i <= DNS_PRESETS.len() {
-            let preset = &DNS_PRESETS[i - 1];
+        } else if i <= presets.len() {
+            let preset = &presets[i - 1];
             let name = format!("{:<width$}", preset.name, width = name_col_width as usize);
             Line::from(vec![
                 Span::styled(prefix, style),
                 Span::styled(name, style),
                 Span::styled(
-                    preset.ip,
+                    preset.primary.as_str(),
                     if is_selected {
                         style
                     } else {
@@ -462,31 +624,222 @@ fn render_dns_custom_input(frame: &mut Frame, area: Rect, app: &App) {
     frame.render_widget(Paragraph::new(input_line), input_area);
 }
 
-/// Render connection info when sharing is active — single merged card with diagram + config.
-pub fn render_connection_info(frame: &mut Frame, area: Rect, app: &App) {
-    if !app.is_sharing() {
+/// Render the DNS server order list, with each server's on/off state.
+fn render_dns_ordering(frame: &mut Frame, area: Rect, app: &App) {
+    let listed = app.dns.listed();
+    let rows = listed.len().max(1) as u16;
+    let card_width = 44u16.min(area.width.saturating_sub(4));
+    let card_height = (rows + 4).min(area.height.saturating_sub(2)); // rows + source line + padding
+    let card_x = area.x + (area.width.saturating_sub(card_width)) / 2;
+    let card_y = area.y + (area.height.saturating_sub(card_height)) / 2;
+    let card_area = Rect::new(card_x, card_y, card_width, card_height);
+
+    frame.render_widget(Clear, area);
+    let card = Card::new(Span::styled(" DNS Server Order ", styles::card_title())).focused(true);
+    frame.render_widget(card, card_area);
+
+    let inner = Rect::new(
+        card_area.x + 2,
+        card_area.y + 1,
+        card_area.width.saturating_sub(4),
+        card_area.height.saturating_sub(2),
+    );
+
+    let source_line = Line::from(Span::styled(
+        format!("Source: {}", app.dns.source()),
+        Style::default().fg(colors::TEXT_SECONDARY),
+    ));
+    frame.render_widget(
+        Paragraph::new(source_line),
+        Rect::new(inner.x, inner.y, inner.width, 1),
+    );
+
+    let items_y = inner.y + 2;
+    if listed.is_empty() {
+        let empty = Line::from(Span::styled(
+            "No DNS servers detected yet",
+            Style::default().fg(colors::TEXT_SECONDARY),
+        ));
+        frame.render_widget(
+            Paragraph::new(empty),
+            Rect::new(inner.x, items_y, inner.width, 1),
+        );
         return;
     }
 
-    let (Some(vpn_idx), Some(lan_idx)) = (app.selected_vpn, app.selected_lan) else {
+    for (i, server) in listed.iter().enumerate() {
+        let y = items_y + i as u16;
+        if y >= inner.y + inner.height {
+            break;
+        }
+
+        let is_selected = i == app.dns.order_selected;
+        let enabled = !app.dns.disabled.contains(server);
+        let prefix = if is_selected {
+            format!("  {}  ", symbols::SELECTED)
+        } else {
+            "     ".to_string()
+        };
+        let style = if is_selected {
+            styles::selected()
+        } else if enabled {
+            styles::unselected()
+        } else {
+            Style::default().fg(colors::TEXT_SECONDARY)
+        };
+
+        let line = Line::from(vec![
+            Span::styled(prefix, style),
+            Span::styled(if enabled { "[x] " } else { "[ ] " }, style),
+            Span::styled(server.clone(), style),
+        ]);
+        frame.render_widget(Paragraph::new(line), Rect::new(inner.x, y, inner.width, 1));
+    }
+}
+
+/// Render the saved profile picker.
+pub fn render_profile_picker(frame: &mut Frame, area: Rect, app: &App) {
+    let rows = app.profiles.len().max(1) as u16;
+    let card_width = 56u16.min(area.width.saturating_sub(4));
+    let card_height = (rows + 2).min(area.height.saturating_sub(2));
+    let card_x = area.x + (area.width.saturating_sub(card_width)) / 2;
+    let card_y = area.y + (area.height.saturating_sub(card_height)) / 2;
+    let card_area = Rect::new(card_x, card_y, card_width, card_height);
+
+    frame.render_widget(Clear, area);
+    let card = Card::new(Span::styled(" Profiles ", styles::card_title())).focused(true);
+    frame.render_widget(card, card_area);
+
+    let inner = Rect::new(
+        card_area.x + 2,
+        card_area.y + 1,
+        card_area.width.saturating_sub(4),
+        card_area.height.saturating_sub(2),
+    );
+
+    if app.profiles.is_empty() {
+        let hint = Line::from(Span::styled(
+            "No profiles. While sharing, press a to save one.",
+            Style::default().fg(colors::TEXT_SECONDARY),
+        ));
+        frame.render_widget(Paragraph::new(hint), inner);
         return;
-    };
+    }
 
-    let (Some(vpn), Some(lan)) = (
-        app.vpn_interfaces.get(vpn_idx),
-        app.lan_interfaces.get(lan_idx),
-    ) else {
+    // Keep the selection visible when there are more profiles than rows
+    let visible = inner.height as usize;
+    let first = app
+        .selected_profile
+        .saturating_sub(visible.saturating_sub(1));
+
+    let lines: Vec<Line> = app
+        .profiles
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(visible)
+        .map(|(i, profile)| {
+            let is_selected = i == app.selected_profile;
+            let (prefix, style) = if is_selected {
+                (format!("{}  ", symbols::SELECTED), styles::selected())
+            } else {
+                ("   ".to_string(), styles::unselected())
+            };
+            Line::from(vec![
+                Span::styled(prefix, style),
+                Span::styled(format!("{:<16}", profile.name), style),
+                Span::styled(
+                    format!("{} {} {}", profile.vpn, symbols::ARROW_RIGHT, profile.lan),
+                    Style::default().fg(colors::TEXT_SECONDARY),
+                ),
+            ])
+        })
+        .collect();
+
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+/// Render the profile name input.
+pub fn render_profile_name_input(frame: &mut Frame, area: Rect, app: &App) {
+    render_text_input(
+        frame,
+        area,
+        " Save Profile ",
+        "Saves interfaces, DNS, DHCP and NAT-PMP",
+        &app.profile_name_input,
+    );
+}
+
+/// Render the instance label prompt.
+pub fn render_label_input(frame: &mut Frame, area: Rect, app: &App) {
+    render_text_input(
+        frame,
+        area,
+        " Label This Instance ",
+        "Shown in the header and status output",
+        &app.label_input,
+    );
+}
+
+/// A small centered card with a hint line and a one-line text input.
+fn render_text_input(frame: &mut Frame, area: Rect, title: &str, hint: &str, input: &str) {
+    let card_width = 44u16.min(area.width.saturating_sub(4));
+    let card_height = 5u16;
+    let card_x = area.x + (area.width.saturating_sub(card_width)) / 2;
+    let card_y = area.y + (area.height.saturating_sub(card_height)) / 2;
+    let card_area = Rect::new(card_x, card_y, card_width, card_height);
+
+    frame.render_widget(Clear, card_area);
+    let card = Card::new(Span::styled(title.to_string(), styles::card_title())).focused(true);
+    frame.render_widget(card, card_area);
+
+    let inner = Rect::new(
+        card_area.x + 2,
+        card_area.y + 1,
+        card_area.width.saturating_sub(4),
+        card_area.height.saturating_sub(2),
+    );
+
+    // Hint line
+    let hint = Line::from(Span::styled(
+        hint.to_string(),
+        Style::default().fg(colors::TEXT_SECONDARY),
+    ));
+    let hint_area = Rect::new(inner.x, inner.y, inner.width, 1);
+    frame.render_widget(Paragraph::new(hint), hint_area);
+
+    // Input line with cursor
+    let input_display = format!("{}█", input);
+    let input_line = Line::from(vec![
+        Span::styled("Name: ", Style::default().fg(colors::TEXT_SECONDARY)),
+        Span::styled(
+            input_display,
+            Style::default()
+                .fg(colors::TEXT_PRIMARY)
+                .add_modifier(Modifier::BOLD),
+        ),
+    ]);
+    let input_area = Rect::new(inner.x, inner.y + 2, inner.width, 1);
+    frame.render_widget(Paragraph::new(input_line), input_area);
+}
+
+/// Render connection info when sharing is active — single merged card with diagram + config.
+pub fn render_connection_info(frame: &mut Frame, area: Rect, app: &App) {
+    // Rendered from the session, not the selection indices: the interface
+    // lists can be rescanned while sharing
+    let Some(session) = app.session.as_ref() else {
         return;
     };
 
-    let vpn_ip = vpn
-        .ipv4_address
-        .map(|a| a.to_string())
-        .unwrap_or_else(|| "?.?.?.?".into());
-    let lan_ip = lan
-        .ipv4_address
+    let vpn_ip = session
+        .vpn_ip
         .map(|a| a.to_string())
         .unwrap_or_else(|| "?.?.?.?".into());
+    let lan_ip = if session.lan_ip.is_unspecified() {
+        "?.?.?.?".to_string()
+    } else {
+        session.lan_ip.to_string()
+    };
 
     // Draw a single card over the full area
     let card = Card::new(Span::styled(" Connection ", styles::card_title())).focused(true);
@@ -506,30 +859,49 @@ pub fn render_connection_info(frame: &mut Frame, area: Rect, app: &App) {
     //  row 5: blank
     //  row 6: separator
     //  row 7: blank
-    //  row 8-11: config rows (4 rows)
+    //  row 8-14: config rows (7 rows)
+    //
+    // Too narrow for the boxes, rows 1-4 become a single inline row.
 
     let diagram_start_y = inner.y + 1;
+    let layout = DiagramLayout::for_width(inner.width);
+
+    match layout {
+        DiagramLayout::Boxes => render_diagram_inner(
+            frame,
+            inner,
+            diagram_start_y,
+            &session.vpn_name,
+            &vpn_ip,
+            &session.lan_name,
+            &lan_ip,
+        ),
+        DiagramLayout::Inline => render_diagram_line(
+            frame,
+            Rect::new(inner.x, diagram_start_y, inner.width, 1),
+            &session.vpn_name,
+            &vpn_ip,
+            &session.lan_name,
+            &lan_ip,
+        ),
+    }
 
-    // Render diagram inline (labels + boxes + arrow)
-    render_diagram_inner(
-        frame,
-        inner,
-        diagram_start_y,
-        &vpn.name,
-        &vpn_ip,
-        &lan.name,
-        &lan_ip,
-    );
-
-    // Separator after diagram (labels row + 3 box rows + 1 blank = 5 rows from diagram_start_y)
-    let sep_y = diagram_start_y + 5;
+    // Separator after the diagram and a blank row
+    let sep_y = diagram_start_y + layout.rows() + 1;
     if sep_y < inner.y + inner.height {
         render_separator_line(frame, inner, sep_y);
     }
 
     // Config rows start after separator + blank
     let config_start_y = sep_y + 2;
-    render_config_rows(frame, inner, config_start_y, &lan_ip, app);
+    render_config_rows(
+        frame,
+        inner,
+        config_start_y,
+        &lan_ip,
+        session.lan_subnet,
+        app,
+    );
 }
 
 /// Render the diagram (labels, boxes, arrow) into the given inner area at the specified y offset.
@@ -542,8 +914,8 @@ fn render_diagram_inner(
     lan_name: &str,
     lan_ip: &str,
 ) {
-    let box_width = 16u16;
-    let arrow_width = 10u16;
+    let box_width = DIAGRAM_BOX_WIDTH;
+    let arrow_width = DIAGRAM_ARROW_WIDTH;
     let total_width = box_width * 2 + arrow_width;
 
     let start_x = inner.x + (inner.width.saturating_sub(total_width)) / 2;
@@ -582,8 +954,45 @@ fn render_diagram_inner(
     frame.render_widget(Paragraph::new(Line::from(arrow)), arrow_area);
 }
 
+/// Render the diagram as one line, `utun4 (10.8.0.6) → en0 (192.168.2.1)`,
+/// cut short if even that doesn't fit.
+fn render_diagram_line(
+    frame: &mut Frame,
+    area: Rect,
+    vpn_name: &str,
+    vpn_ip: &str,
+    lan_name: &str,
+    lan_ip: &str,
+) {
+    let vpn = format!("{} ({})", vpn_name, vpn_ip);
+    let lan = format!("{} ({})", lan_name, lan_ip);
+    let line = if vpn.chars().count() + lan.chars().count() + 3 <= area.width as usize {
+        Line::from(vec![
+            Span::styled(vpn, styles::vpn_interface()),
+            Span::styled(" \u{2192} ", Style::default().fg(colors::ACCENT)),
+            Span::styled(lan, styles::lan_interface()),
+        ])
+    } else {
+        Line::from(Span::styled(
+            truncate_with_ellipsis(
+                &format!("{} \u{2192} {}", vpn_name, lan_name),
+                area.width as usize,
+            ),
+            styles::unselected(),
+        ))
+    };
+    frame.render_widget(Paragraph::new(line), area);
+}
+
 /// Render config items as a vertical 2-column table (label left, value right).
-fn render_config_rows(frame: &mut Frame, inner: Rect, start_y: u16, gateway: &str, app: &App) {
+fn render_config_rows(
+    frame: &mut Frame,
+    inner: Rect,
+    start_y: u16,
+    gateway: &str,
+    lan_subnet: Option<Ipv4Net>,
+    app: &App,
+) {
     let dns_servers = app.dns.effective();
     let dns_source = app.dns.source();
     let dhcp_active = app.dhcp_active();
@@ -616,12 +1025,48 @@ fn render_config_rows(frame: &mut Frame, inner: Rect, start_y: u16, gateway: &st
 
     let natpmp_status = if natpmp_active { "Active" } else { "Off" };
 
-    let config_items: &[(&str, String, bool)] = &[
+    // What a client configured by hand needs besides the gateway
+    let subnet = lan_subnet
+        .map(|net| net.to_string())
+        .unwrap_or_else(|| "unknown".into());
+    let hosts = lan_subnet
+        .map(network::host_range)
+        .map(|(first, last)| format!("{} - {}", first, last))
+        .unwrap_or_else(|| "unknown".into());
+
+    let mut config_items: Vec<(&str, String, bool)> = vec![
         ("Gateway", gateway.to_string(), false),
+        ("Subnet", subnet, false),
+        ("Usable", hosts, false),
         ("DNS", dns_str, false),
         ("WAN", dhcp_status, dhcp_active),
         ("NAT-PMP", natpmp_status.to_string(), natpmp_active),
+        (
+            "Clients",
+            app.session
+                .as_ref()
+                .map(|s| s.connected_clients.to_string())
+                .unwrap_or_default(),
+            false,
+        ),
     ];
+    if let Some(session) = app.session.as_ref().filter(|s| !s.extra_uplinks.is_empty()) {
+        let uplinks = std::iter::once(session.vpn_name.as_str())
+            .chain(session.extra_uplinks.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" + ");
+        config_items.push(("Balancing", uplinks, true));
+    }
+    if let Some(session) = app.session.as_ref() {
+        if let Some(ref standby) = session.failover_vpn {
+            let value = if session.on_standby {
+                format!("{} (in use, {} down)", standby, session.vpn_name)
+            } else {
+                format!("{} (standby)", standby)
+            };
+            config_items.push(("Failover", value, session.on_standby));
+        }
+    }
 
     let padding = 3u16;
 
@@ -637,7 +1082,7 @@ fn render_config_rows(frame: &mut Frame, inner: Rect, start_y: u16, gateway: &st
         );
 
         let value_style = if *is_active {
-            Style::default().fg(colors::SUCCESS)
+            Style::default().fg(colors::success())
         } else {
             Style::default().fg(colors::TEXT_PRIMARY)
         };
@@ -658,6 +1103,19 @@ fn render_config_rows(frame: &mut Frame, inner: Rect, start_y: u16, gateway: &st
         let row_area = Rect::new(inner.x + padding, y, usable_width, 1);
         frame.render_widget(Paragraph::new(line), row_area);
     }
+
+    // After a blank row, if there's room
+    let warning_y = start_y + config_items.len() as u16 + 1;
+    if app.dns.overrides_vpn() && warning_y < inner.y + inner.height {
+        let usable_width = inner.width.saturating_sub(padding * 2);
+        let text = format!("{} {}", symbols::WARNING, DNS_OVERRIDE_WARNING);
+        let line = Line::from(Span::styled(
+            truncate_with_ellipsis(&text, usable_width as usize),
+            Style::default().fg(colors::warning()),
+        ));
+        let row_area = Rect::new(inner.x + padding, warning_y, usable_width, 1);
+        frame.render_widget(Paragraph::new(line), row_area);
+    }
 }
 
 /// Render an interface box.
@@ -696,3 +1154,31 @@ fn render_interface_box(frame: &mut Frame, area: Rect, name: &str, ip: &str, is_
         frame.render_widget(ip_para, ip_area);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagram_layout_for_width() {
+        assert_eq!(DiagramLayout::for_width(80), DiagramLayout::Boxes);
+        assert_eq!(DiagramLayout::for_width(42), DiagramLayout::Boxes);
+        assert_eq!(DiagramLayout::for_width(41), DiagramLayout::Inline);
+        assert_eq!(DiagramLayout::for_width(0), DiagramLayout::Inline);
+    }
+
+    #[test]
+    fn test_header_title_shortens_label() {
+        let title = header_title(None, 80);
+        let width = title.chars().count() + 3;
+        assert_eq!(
+            header_title(Some("office-vpn"), 80),
+            format!("{} \u{2014} office-vpn", title)
+        );
+        assert_eq!(
+            header_title(Some("office-vpn"), width + 4),
+            format!("{} \u{2014} off\u{2026}", title)
+        );
+        assert_eq!(header_title(Some("office-vpn"), width), title);
+    }
+}
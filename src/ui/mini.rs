@@ -0,0 +1,186 @@
+//! Compact single-column layout for terminals too small for the full UI.
+//!
+//! No cards, diagram or overlays: a status line, a short list for the
+//! current screen, the latest log entry and the key help.
+
+use ratatui::{
+    layout::Rect,
+    style::Style,
+    text::{Line, Span},
+    widgets::Paragraph,
+    Frame,
+};
+
+use crate::app::{App, AppState, MissingInterfaces};
+use crate::system::InterfaceInfo;
+use crate::ui::main_menu::{is_menu_item_disabled, menu_item_label_str, render_header};
+use crate::ui::status::{level_icon_style, render_help, truncate_with_ellipsis};
+use crate::ui::theme::{colors, styles, symbols};
+
+/// Render the whole UI in the compact layout.
+pub fn render_mini(frame: &mut Frame, area: Rect, app: &App) {
+    let row = |offset: u16| Rect::new(area.x, area.y + offset, area.width, 1);
+    let width = area.width as usize;
+
+    // Status line; a pending operation takes its place
+    match app.pending_op {
+        Some(op) => {
+            let elapsed = app.pending_elapsed().unwrap_or_default().as_secs();
+            let text = format!("{} {} ({}s)", symbols::LOADING_STILL, op.display(), elapsed);
+            frame.render_widget(
+                Paragraph::new(Span::styled(
+                    truncate_with_ellipsis(&text, width),
+                    Style::default().fg(colors::ACCENT),
+                )),
+                row(0),
+            );
+        }
+        None => render_header(frame, row(0), app),
+    }
+
+    // Body between the status line and the last two rows
+    let body_height = area.height.saturating_sub(3) as usize;
+    let (lines, selected) = body_lines(app);
+    let start = selected
+        .map(|i| (i + 1).saturating_sub(body_height))
+        .unwrap_or(0);
+    for (i, (text, style)) in lines.iter().skip(start).take(body_height).enumerate() {
+        frame.render_widget(
+            Paragraph::new(Span::styled(truncate_with_ellipsis(text, width), *style)),
+            row(1 + i as u16),
+        );
+    }
+
+    // Latest log entry
+    if let Some(entry) = app.logs.back() {
+        let (icon, style) = level_icon_style(entry.level);
+        let text = format!("{} {}", icon, entry.message.replace('\n', " "));
+        frame.render_widget(
+            Paragraph::new(Line::from(Span::styled(
+                truncate_with_ellipsis(&text, width),
+                style,
+            ))),
+            row(area.height - 2),
+        );
+    }
+
+    render_help(frame, row(area.height - 1), app.help_text());
+}
+
+/// Lines for the current screen, and which one is selected.
+fn body_lines(app: &App) -> (Vec<(String, Style)>, Option<usize>) {
+    match app.state {
+        AppState::SelectingVpn => interface_lines(app, &app.vpn_interfaces, app.selected_vpn),
+        AppState::SelectingLan => interface_lines(app, &app.lan_interfaces, app.selected_lan),
+        AppState::Active => (session_lines(app), None),
+        AppState::Menu => menu_lines(app),
+        AppState::NoInterfaces => {
+            let missing = match app.missing_interfaces() {
+                Some(MissingInterfaces::Vpn) => "No VPN interface found",
+                Some(MissingInterfaces::Lan) => "No LAN interface found",
+                _ => "No interfaces found",
+            };
+            (vec![(missing.to_string(), styles::unselected())], None)
+        }
+        AppState::EditingDns
+        | AppState::SelectingProfile
+        | AppState::NamingProfile
+        | AppState::NamingLabel => (
+            vec![(
+                "Enlarge the terminal for this screen".to_string(),
+                styles::hint(),
+            )],
+            None,
+        ),
+    }
+}
+
+fn menu_lines(app: &App) -> (Vec<(String, Style)>, Option<usize>) {
+    let lines = app
+        .menu_items()
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let selected = i == app.selected_menu_item;
+            let style = if is_menu_item_disabled(item, app) {
+                Style::default().fg(colors::TEXT_SECONDARY)
+            } else if selected {
+                styles::selected()
+            } else {
+                styles::unselected()
+            };
+            (
+                format!("{} {}", marker(selected), menu_item_label_str(item)),
+                style,
+            )
+        })
+        .collect();
+    (lines, Some(app.selected_menu_item))
+}
+
+fn interface_lines(
+    app: &App,
+    interfaces: &[InterfaceInfo],
+    selected: Option<usize>,
+) -> (Vec<(String, Style)>, Option<usize>) {
+    let visible = app.filtered_indices(interfaces);
+    let lines = visible
+        .iter()
+        .map(|&i| {
+            let interface = &interfaces[i];
+            let is_selected = selected == Some(i);
+            let address = interface
+                .ipv4_address
+                .map(|ip| format!("  {}", ip))
+                .unwrap_or_default();
+            let style = if is_selected {
+                styles::selected()
+            } else {
+                styles::unselected()
+            };
+            (
+                format!("{} {}{}", marker(is_selected), interface.name, address),
+                style,
+            )
+        })
+        .collect();
+    let position = selected.and_then(|s| visible.iter().position(|&i| i == s));
+    (lines, position)
+}
+
+fn session_lines(app: &App) -> Vec<(String, Style)> {
+    let Some(session) = app.session.as_ref() else {
+        return Vec::new();
+    };
+    let dns = app.dns.effective();
+    vec![
+        (
+            format!("{} -> {}", session.vpn_name, session.lan_name),
+            styles::unselected(),
+        ),
+        (
+            format!("Clients: {}", session.connected_clients),
+            styles::unselected(),
+        ),
+        (
+            format!(
+                "DNS: {}",
+                if dns.is_empty() {
+                    "none".to_string()
+                } else {
+                    dns.join(", ")
+                }
+            ),
+            styles::unselected(),
+        ),
+    ]
+}
+
+/// Selection marker, or the same width of blank.
+fn marker(selected: bool) -> &'static str {
+    if selected {
+        symbols::SELECTED
+    } else {
+        " "
+    }
+}
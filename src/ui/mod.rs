@@ -3,6 +3,7 @@
 pub mod debug;
 pub mod interface_select;
 pub mod main_menu;
+pub mod mini;
 pub mod status;
 pub mod theme;
 pub mod widgets;
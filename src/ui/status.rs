@@ -8,21 +8,98 @@ use ratatui::{
     Frame,
 };
 
+use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 
-use crate::app::LogEntry;
+use crate::app::{format_duration, DnsProbeReport, ForwardProbeReport, LogEntry, StopSummary};
+use crate::diagnostics::Diagnostic;
+use crate::system::probe::{DnsProbeResult, ForwardProbeResult, DNS_PROBE_NAME};
+use crate::ui::debug::format_bytes;
 use crate::ui::theme::{colors, styles, symbols};
 use crate::ui::widgets::Card;
 
-/// Log level for styling.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Log level for styling, ordered from least to most severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum LogLevel {
+    #[default]
     Info,
     Success,
     Warning,
     Error,
 }
 
+impl LogLevel {
+    /// Strictest recording threshold allowed: warnings and errors are always
+    /// kept so failures can't be hidden by accident.
+    pub const MAX_THRESHOLD: LogLevel = LogLevel::Warning;
+
+    /// Parse a level name as written on the command line.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "info" => Some(Self::Info),
+            "success" => Some(Self::Success),
+            "warning" | "warn" => Some(Self::Warning),
+            "error" => Some(Self::Error),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Success => "success",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        }
+    }
+
+    /// This level used as a recording threshold, capped at [`Self::MAX_THRESHOLD`].
+    pub fn as_threshold(self) -> Self {
+        self.min(Self::MAX_THRESHOLD)
+    }
+
+    /// Next threshold for the cycle key: info → success → warning → info.
+    pub fn next_threshold(self) -> Self {
+        match self.as_threshold() {
+            Self::Info => Self::Success,
+            Self::Success => Self::Warning,
+            _ => Self::Info,
+        }
+    }
+
+    /// Whether an entry at this level is kept when recording at `threshold`.
+    pub fn is_recorded(self, threshold: LogLevel) -> bool {
+        self >= threshold.as_threshold()
+    }
+}
+
+/// Subsystem a log entry relates to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogCategory {
+    Firewall,
+    Dhcp,
+    NatPmp,
+    Dns,
+    Network,
+    #[default]
+    General,
+}
+
+impl LogCategory {
+    /// Name used in the JSON log.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Firewall => "firewall",
+            Self::Dhcp => "dhcp",
+            Self::NatPmp => "natpmp",
+            Self::Dns => "dns",
+            Self::Network => "network",
+            Self::General => "general",
+        }
+    }
+}
+
 impl LogEntry {
     pub fn info(message: impl Into<String>) -> Self {
         Self::new(message, LogLevel::Info)
@@ -40,12 +117,20 @@ impl LogEntry {
         Self::new(message, LogLevel::Error)
     }
 
+    /// Tag the entry with a category (entries default to `General`).
+    pub fn with_category(mut self, category: LogCategory) -> Self {
+        self.category = category;
+        self
+    }
+
     fn new(message: impl Into<String>, level: LogLevel) -> Self {
         let now = chrono::Local::now();
         Self {
             timestamp: now.format("%H:%M").to_string(), // Shorter format
+            time: now,
             message: message.into(),
             level,
+            category: LogCategory::General,
         }
     }
 }
@@ -54,12 +139,17 @@ impl LogEntry {
 pub use LogLevel as LogEntryLevel;
 
 /// Render the compact status/log panel.
+///
+/// `selected` is the index into `logs` of the highlighted entry (expanded mode);
+/// the view scrolls back as needed to keep it visible.
 pub fn render_status_panel(
     frame: &mut Frame,
     area: Rect,
     logs: &VecDeque<LogEntry>,
     max_lines: usize,
     expanded: bool,
+    selected: Option<usize>,
+    min_level: LogLevel,
 ) {
     let visible_count = if expanded {
         max_lines
@@ -67,18 +157,53 @@ pub fn render_status_panel(
         max_lines.min(10) // Collapsed shows 10 lines max
     };
 
+    // Follow the tail unless the selection is above it
+    let mut start = logs.len().saturating_sub(visible_count);
+    if let Some(sel) = selected {
+        start = start.min(sel);
+    }
+
+    let width = area.width as usize;
     let visible_logs: Vec<Line> = logs
         .iter()
-        .rev()
+        .enumerate()
+        .skip(start)
         .take(visible_count)
-        .rev()
-        .map(|entry| format_log_entry(entry))
+        .map(|(i, entry)| format_log_entry(entry, width, selected == Some(i)))
         .collect();
 
+    let label = if expanded && selected.is_none() && !logs.is_empty() {
+        " Activity (Tab: select) "
+    } else {
+        " Activity "
+    };
+    let (errors, warnings) = level_counts(logs);
+    let mut title_spans = vec![Span::styled(label, styles::card_title())];
+    if errors > 0 {
+        title_spans.push(Span::styled(
+            format!("{} {} ", symbols::ERROR, plural(errors, "error")),
+            Style::default().fg(colors::error()),
+        ));
+    }
+    if warnings > 0 {
+        title_spans.push(Span::styled(
+            format!("{} {} ", symbols::WARNING, plural(warnings, "warning")),
+            Style::default().fg(colors::warning()),
+        ));
+    }
+    if min_level.as_threshold() > LogLevel::Info {
+        title_spans.push(Span::styled(
+            format!("recording {}+ ", min_level.as_threshold().label()),
+            Style::default().fg(colors::TEXT_SECONDARY),
+        ));
+    }
+    let title = Line::from(title_spans);
+    let title_width = title.width() as u16;
+
     let log_panel = Paragraph::new(visible_logs)
         .block(
             Block::default()
-                .title(Span::styled(" Activity ", styles::card_title()))
+                .title(title)
                 .title_alignment(ratatui::layout::Alignment::Left)
                 .borders(Borders::TOP)
                 .border_style(styles::border_unfocused()),
@@ -91,7 +216,7 @@ pub fn render_status_panel(
     let count_text = format!(" {} items ", logs.len());
     let count_width = count_text.len() as u16;
     let count_x = area.x + area.width.saturating_sub(count_width + 1);
-    if count_x > area.x + 12 {
+    if count_x > area.x + title_width + 2 {
         let count_para = Paragraph::new(Line::from(Span::styled(
             count_text,
             Style::default().fg(colors::TEXT_SECONDARY),
@@ -101,23 +226,356 @@ pub fn render_status_panel(
     }
 }
 
-/// Format a single log entry with icon.
-fn format_log_entry(entry: &LogEntry) -> Line<'static> {
-    let (icon, msg_style) = match entry.level {
-        LogLevel::Success => (symbols::STATUS_ACTIVE, Style::default().fg(colors::SUCCESS)),
+/// Number of `(error, warning)` entries currently in the log.
+pub fn level_counts(logs: &VecDeque<LogEntry>) -> (usize, usize) {
+    logs.iter()
+        .fold((0, 0), |(errors, warnings), entry| match entry.level {
+            LogLevel::Error => (errors + 1, warnings),
+            LogLevel::Warning => (errors, warnings + 1),
+            LogLevel::Info | LogLevel::Success => (errors, warnings),
+        })
+}
+
+/// "1 error", "2 errors".
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("1 {}", noun)
+    } else {
+        format!("{} {}s", count, noun)
+    }
+}
+
+/// Icon and message style for a log level.
+pub fn level_icon_style(level: LogLevel) -> (&'static str, Style) {
+    match level {
+        LogLevel::Success => (
+            symbols::STATUS_ACTIVE,
+            Style::default().fg(colors::success()),
+        ),
         LogLevel::Info => ("i", Style::default().fg(colors::TEXT_PRIMARY)),
-        LogLevel::Warning => (symbols::WARNING, Style::default().fg(colors::WARNING)),
-        LogLevel::Error => (symbols::ERROR, Style::default().fg(colors::ERROR)),
+        LogLevel::Warning => (symbols::WARNING, Style::default().fg(colors::warning())),
+        LogLevel::Error => (symbols::ERROR, Style::default().fg(colors::error())),
+    }
+}
+
+/// Format a single log entry with icon, truncated to fit on one line of `width`.
+fn format_log_entry(entry: &LogEntry, width: usize, selected: bool) -> Line<'static> {
+    let (icon, msg_style) = level_icon_style(entry.level);
+    let msg_style = if selected {
+        msg_style.add_modifier(Modifier::REVERSED)
+    } else {
+        msg_style
     };
 
+    let timestamp = format!("  {}  ", entry.timestamp);
+    let icon = format!("{}  ", icon);
+    let available = width.saturating_sub(timestamp.chars().count() + icon.chars().count());
+    let message = truncate_with_ellipsis(&entry.message.replace('\n', " "), available);
+
     Line::from(vec![
+        Span::styled(timestamp, Style::default().fg(colors::TEXT_SECONDARY)),
+        Span::styled(icon, msg_style),
+        Span::styled(message, msg_style),
+    ])
+}
+
+/// Shorten `text` to at most `max` characters, ending with an ellipsis if cut.
+pub fn truncate_with_ellipsis(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    if max == 0 {
+        return String::new();
+    }
+    let mut truncated: String = text.chars().take(max - 1).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Render the full, untruncated text of a log entry in a centered overlay.
+pub fn render_log_detail(frame: &mut Frame, area: Rect, entry: &LogEntry) {
+    let (icon, msg_style) = level_icon_style(entry.level);
+
+    let card_width = 72u16.min(area.width.saturating_sub(4));
+    let text_width = card_width.saturating_sub(4).max(1) as usize;
+    let text_lines: usize = entry
+        .message
+        .lines()
+        .map(|l| l.chars().count().div_ceil(text_width).max(1))
+        .sum();
+    let card_height = (text_lines as u16 + 4).min(area.height.saturating_sub(2));
+    let card_x = area.x + (area.width.saturating_sub(card_width)) / 2;
+    let card_y = area.y + (area.height.saturating_sub(card_height)) / 2;
+    let card_area = Rect::new(card_x, card_y, card_width, card_height);
+
+    frame.render_widget(Clear, card_area);
+    let title = format!(" {} {} ", icon, entry.timestamp);
+    let card = Card::new(Span::styled(title, styles::card_title())).focused(true);
+    frame.render_widget(card, card_area);
+
+    let inner = Rect::new(
+        card_area.x + 2,
+        card_area.y + 1,
+        card_area.width.saturating_sub(4),
+        card_area.height.saturating_sub(2),
+    );
+
+    let text: Vec<Line> = entry
+        .message
+        .lines()
+        .map(|l| Line::from(Span::styled(l.to_string(), msg_style)))
+        .collect();
+    let detail = Paragraph::new(text).wrap(Wrap { trim: false });
+    frame.render_widget(
+        detail,
+        Rect::new(
+            inner.x,
+            inner.y + 1,
+            inner.width,
+            inner.height.saturating_sub(1),
+        ),
+    );
+}
+
+/// Render port forward probe results in a centered overlay.
+pub fn render_probe_report(frame: &mut Frame, area: Rect, report: &ForwardProbeReport) {
+    let card_width = 64u16.min(area.width.saturating_sub(4));
+    let card_height = (report.results.len() as u16 + 5).min(area.height.saturating_sub(2));
+    let card_x = area.x + (area.width.saturating_sub(card_width)) / 2;
+    let card_y = area.y + (area.height.saturating_sub(card_height)) / 2;
+    let card_area = Rect::new(card_x, card_y, card_width, card_height);
+
+    frame.render_widget(Clear, card_area);
+    let card = Card::new(Span::styled(" Port Forward Test ", styles::card_title())).focused(true);
+    frame.render_widget(card, card_area);
+
+    let inner = Rect::new(
+        card_area.x + 2,
+        card_area.y + 1,
+        card_area.width.saturating_sub(4),
+        card_area.height.saturating_sub(2),
+    );
+
+    let mut lines = vec![
+        Line::from(Span::styled(
+            format!("Probed on {} from this Mac", report.external_ip),
+            Style::default().fg(colors::TEXT_SECONDARY),
+        )),
+        Line::from(""),
+    ];
+    for (forward, result) in &report.results {
+        let (icon, style) = match result {
+            ForwardProbeResult::Open => (
+                symbols::STATUS_ACTIVE,
+                Style::default().fg(colors::success()),
+            ),
+            ForwardProbeResult::OpenOrFiltered => ("?", Style::default().fg(colors::TEXT_PRIMARY)),
+            ForwardProbeResult::Failed(_) => (symbols::ERROR, Style::default().fg(colors::error())),
+            _ => (symbols::WARNING, Style::default().fg(colors::warning())),
+        };
+        lines.push(Line::from(vec![
+            Span::styled(format!("{}  ", icon), style),
+            Span::styled(format!("{:<32}", forward), styles::unselected()),
+            Span::styled(result.to_string(), style),
+        ]));
+    }
+
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+/// Render client DNS test results in a centered overlay.
+pub fn render_dns_probe_report(frame: &mut Frame, area: Rect, report: &DnsProbeReport) {
+    let card_width = 64u16.min(area.width.saturating_sub(4));
+    let card_height = (report.results.len() as u16 + 5).min(area.height.saturating_sub(2));
+    let card_x = area.x + (area.width.saturating_sub(card_width)) / 2;
+    let card_y = area.y + (area.height.saturating_sub(card_height)) / 2;
+    let card_area = Rect::new(card_x, card_y, card_width, card_height);
+
+    frame.render_widget(Clear, card_area);
+    let card = Card::new(Span::styled(" Client DNS Test ", styles::card_title())).focused(true);
+    frame.render_widget(card, card_area);
+
+    let inner = Rect::new(
+        card_area.x + 2,
+        card_area.y + 1,
+        card_area.width.saturating_sub(4),
+        card_area.height.saturating_sub(2),
+    );
+
+    let mut lines = vec![
+        Line::from(Span::styled(
+            format!("Looked up {} from {}", DNS_PROBE_NAME, report.source),
+            Style::default().fg(colors::TEXT_SECONDARY),
+        )),
+        Line::from(""),
+    ];
+    for (server, result) in &report.results {
+        let (icon, style) = match result {
+            DnsProbeResult::Answered { .. } => (
+                symbols::STATUS_ACTIVE,
+                Style::default().fg(colors::success()),
+            ),
+            DnsProbeResult::Failed(_) => (symbols::ERROR, Style::default().fg(colors::error())),
+            _ => (symbols::WARNING, Style::default().fg(colors::warning())),
+        };
+        lines.push(Line::from(vec![
+            Span::styled(format!("{}  ", icon), style),
+            Span::styled(format!("{:<28}", server), styles::unselected()),
+            Span::styled(result.to_string(), style),
+        ]));
+    }
+
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+/// Render the expected-vs-actual diagnostics checklist in a centered overlay.
+pub fn render_diagnostics(frame: &mut Frame, area: Rect, checks: &[Diagnostic]) {
+    let failed = checks.iter().filter(|c| !c.passed).count() as u16;
+    let card_width = 72u16.min(area.width.saturating_sub(4));
+    let card_height = (checks.len() as u16 + failed + 4).min(area.height.saturating_sub(2));
+    let card_x = area.x + (area.width.saturating_sub(card_width)) / 2;
+    let card_y = area.y + (area.height.saturating_sub(card_height)) / 2;
+    let card_area = Rect::new(card_x, card_y, card_width, card_height);
+
+    frame.render_widget(Clear, card_area);
+    let card = Card::new(Span::styled(" Diagnostics ", styles::card_title())).focused(true);
+    frame.render_widget(card, card_area);
+
+    let inner = Rect::new(
+        card_area.x + 2,
+        card_area.y + 1,
+        card_area.width.saturating_sub(4),
+        card_area.height.saturating_sub(2),
+    );
+
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled(
+                format!("   {:<16}", "Check"),
+                Style::default().fg(colors::TEXT_SECONDARY),
+            ),
+            Span::styled(
+                format!("{:<28}", "Expected"),
+                Style::default().fg(colors::TEXT_SECONDARY),
+            ),
+            Span::styled("Actual", Style::default().fg(colors::TEXT_SECONDARY)),
+        ]),
+        Line::from(""),
+    ];
+    for check in checks {
+        let (icon, style) = if check.passed {
+            (
+                symbols::STATUS_ACTIVE,
+                Style::default().fg(colors::success()),
+            )
+        } else {
+            (symbols::ERROR, Style::default().fg(colors::error()))
+        };
+        lines.push(Line::from(vec![
+            Span::styled(format!("{}  ", icon), style),
+            Span::styled(format!("{:<16}", check.check), styles::unselected()),
+            Span::styled(
+                format!("{:<28}", check.expected),
+                Style::default().fg(colors::TEXT_SECONDARY),
+            ),
+            Span::styled(check.actual.clone(), style),
+        ]));
+        if let Some(hint) = check.hint {
+            lines.push(Line::from(vec![
+                Span::styled(format!("   {} ", symbols::TREE_END), styles::tree_branch()),
+                Span::styled(hint, styles::hint()),
+            ]));
+        }
+    }
+
+    frame.render_widget(Paragraph::new(lines), inner);
+}
+
+/// Render the end-of-session summary overlay.
+pub fn render_stop_summary(frame: &mut Frame, area: Rect, summary: &StopSummary) {
+    let card_width = 56u16.min(area.width.saturating_sub(4));
+    let card_height = (summary.warnings.len().max(1) as u16 + 8).min(area.height.saturating_sub(2));
+    let card_x = area.x + (area.width.saturating_sub(card_width)) / 2;
+    let card_y = area.y + (area.height.saturating_sub(card_height)) / 2;
+    let card_area = Rect::new(card_x, card_y, card_width, card_height);
+
+    frame.render_widget(Clear, card_area);
+    let card = Card::new(Span::styled(" Sharing Stopped ", styles::card_title())).focused(true);
+    frame.render_widget(card, card_area);
+
+    let inner = Rect::new(
+        card_area.x + 2,
+        card_area.y + 1,
+        card_area.width.saturating_sub(4),
+        card_area.height.saturating_sub(2),
+    );
+
+    let label = |text: &str| {
         Span::styled(
-            format!("  {}  ", entry.timestamp),
+            format!("{:<15}", text),
             Style::default().fg(colors::TEXT_SECONDARY),
+        )
+    };
+    let traffic = match summary.traffic {
+        Some((up, down)) => format!(
+            "\u{2191} {}   \u{2193} {}",
+            format_bytes(up),
+            format_bytes(down)
         ),
-        Span::styled(format!("{}  ", icon), msg_style),
-        Span::styled(entry.message.clone(), msg_style),
-    ])
+        None => "not recorded".to_string(),
+    };
+    let forwarded = match summary.forwarded {
+        Some((packets, bytes)) => format!("{} in {} packets", format_bytes(bytes), packets),
+        None => "not recorded".to_string(),
+    };
+
+    let mut lines = vec![
+        Line::from(vec![
+            label("Interfaces:"),
+            Span::styled(
+                format!(
+                    "{} {} {}",
+                    summary.vpn_name,
+                    symbols::ARROW_RIGHT,
+                    summary.lan_name
+                ),
+                styles::unselected(),
+            ),
+        ]),
+        Line::from(vec![
+            label("Duration:"),
+            Span::styled(format_duration(summary.duration), styles::unselected()),
+        ]),
+        Line::from(vec![
+            label("Traffic:"),
+            Span::styled(traffic, styles::unselected()),
+        ]),
+        Line::from(vec![
+            label("Forwarded:"),
+            Span::styled(forwarded, styles::unselected()),
+        ]),
+        Line::from(vec![
+            label("Peak clients:"),
+            Span::styled(summary.peak_clients.to_string(), styles::unselected()),
+        ]),
+        Line::from(""),
+    ];
+
+    if summary.warnings.is_empty() {
+        lines.push(Line::from(Span::styled(
+            format!("{} Cleanup complete", symbols::STATUS_ACTIVE),
+            Style::default().fg(colors::success()),
+        )));
+    } else {
+        for warning in &summary.warnings {
+            lines.push(Line::from(Span::styled(
+                format!("{} {}", symbols::WARNING, warning),
+                Style::default().fg(colors::warning()),
+            )));
+        }
+    }
+
+    frame.render_widget(Paragraph::new(lines).wrap(Wrap { trim: true }), inner);
 }
 
 /// Render help text at the bottom with styled keys.
@@ -130,6 +588,69 @@ pub fn render_help(frame: &mut Frame, area: Rect, context_help: &str) {
     frame.render_widget(help_text, area);
 }
 
+/// Smallest terminal anything is drawn in (the compact layout).
+pub const MIN_WIDTH: u16 = 20;
+pub const MIN_HEIGHT: u16 = 4;
+/// Smallest terminal the full layout is drawn in.
+pub const FULL_WIDTH: u16 = 40;
+pub const FULL_HEIGHT: u16 = 12;
+
+/// Which layout fits the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayoutMode {
+    /// Only a notice asking for a bigger terminal.
+    TooSmall,
+    /// A status line, a compact list and the latest log line, in one column.
+    Mini,
+    /// Cards, diagram and log panel.
+    Full,
+}
+
+impl LayoutMode {
+    /// The layout for a terminal of `area`'s size.
+    pub fn for_area(area: Rect) -> Self {
+        if area.width >= FULL_WIDTH && area.height >= FULL_HEIGHT {
+            LayoutMode::Full
+        } else if area.width >= MIN_WIDTH && area.height >= MIN_HEIGHT {
+            LayoutMode::Mini
+        } else {
+            LayoutMode::TooSmall
+        }
+    }
+}
+
+/// Render a single notice instead of the UI when the terminal is too small.
+pub fn render_too_small(frame: &mut Frame, area: Rect) {
+    let message = Paragraph::new(Line::from(Span::styled(
+        format!(
+            "Terminal too small (need {}\u{d7}{})",
+            MIN_WIDTH, MIN_HEIGHT
+        ),
+        Style::default().fg(colors::warning()),
+    )))
+    .alignment(Alignment::Center)
+    .wrap(Wrap { trim: true });
+
+    // Vertically centered; the text wraps onto more lines when narrow
+    let y = area.y + area.height.saturating_sub(1) / 2;
+    let height = area.height - (y - area.y);
+    frame.render_widget(message, Rect::new(area.x, y, area.width, height));
+}
+
+/// Render a transient notice in place of the help line.
+pub fn render_toast(frame: &mut Frame, area: Rect, text: &str) {
+    let toast = Line::from(vec![
+        Span::styled(format!("  {} ", symbols::WARNING), styles::help_key()),
+        Span::styled(
+            text.to_string(),
+            Style::default()
+                .fg(colors::warning())
+                .add_modifier(Modifier::BOLD),
+        ),
+    ]);
+    frame.render_widget(Paragraph::new(toast), area);
+}
+
 /// Parse help text and style keys differently.
 fn parse_help_text(text: &str) -> Vec<Span<'static>> {
     let mut spans = Vec::new();
@@ -176,12 +697,14 @@ fn parse_help_text(text: &str) -> Vec<Span<'static>> {
 /// Render a loading indicator overlay with moon spinner.
 ///
 /// If `elapsed` is provided, appends the elapsed seconds to the message
-/// (e.g. "Starting VPN sharing... (3s)").
+/// (e.g. "Starting VPN sharing... (3s)"). With `reduced_motion`, a still glyph
+/// replaces the spinner and the seconds are the only thing that changes.
 pub fn render_loading_indicator(
     frame: &mut Frame,
     area: Rect,
     message: &str,
     elapsed: Option<std::time::Duration>,
+    reduced_motion: bool,
 ) {
     let display_msg = match elapsed {
         Some(dur) => format!("{} ({}s)", message, dur.as_secs()),
@@ -201,12 +724,15 @@ pub fn render_loading_indicator(
     frame.render_widget(Clear, popup_area);
 
     // Get spinner frame based on time (moon phases)
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .map(|d| d.as_millis())
-        .unwrap_or(0);
-    let spinner_idx = ((now / 150) % symbols::MOON_SPINNER.len() as u128) as usize;
-    let spinner = symbols::MOON_SPINNER[spinner_idx];
+    let spinner = if reduced_motion {
+        symbols::LOADING_STILL
+    } else {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        symbols::MOON_SPINNER[((now / 150) % symbols::MOON_SPINNER.len() as u128) as usize]
+    };
 
     let card = Card::empty().border_style(Style::default().fg(colors::ACCENT));
     frame.render_widget(card, popup_area);
@@ -231,3 +757,75 @@ pub fn render_loading_indicator(
 
     frame.render_widget(loading_text, inner);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_too_small_at_any_size() {
+        use ratatui::{backend::TestBackend, Terminal};
+
+        for (width, height) in [(1, 1), (5, 2), (19, 11), (80, 3)] {
+            let area = Rect::new(0, 0, width, height);
+            assert_eq!(LayoutMode::for_area(area), LayoutMode::TooSmall);
+            let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+            terminal
+                .draw(|frame| render_too_small(frame, frame.area()))
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_layout_mode() {
+        let mode = |width, height| LayoutMode::for_area(Rect::new(0, 0, width, height));
+        assert_eq!(mode(MIN_WIDTH, MIN_HEIGHT), LayoutMode::Mini);
+        assert_eq!(mode(39, 40), LayoutMode::Mini);
+        assert_eq!(mode(120, 11), LayoutMode::Mini);
+        assert_eq!(mode(30, 8), LayoutMode::Mini);
+        assert_eq!(mode(FULL_WIDTH, FULL_HEIGHT), LayoutMode::Full);
+        assert_eq!(mode(120, 40), LayoutMode::Full);
+        assert_eq!(mode(19, 40), LayoutMode::TooSmall);
+        assert_eq!(mode(120, 3), LayoutMode::TooSmall);
+    }
+
+    #[test]
+    fn test_level_counts() {
+        let mut logs = VecDeque::new();
+        assert_eq!(level_counts(&logs), (0, 0));
+
+        logs.push_back(LogEntry::error("a"));
+        logs.push_back(LogEntry::warning("b"));
+        logs.push_back(LogEntry::info("c"));
+        logs.push_back(LogEntry::warning("d"));
+        assert_eq!(level_counts(&logs), (1, 2));
+
+        // Evicted entries no longer count
+        logs.pop_front();
+        assert_eq!(level_counts(&logs), (0, 2));
+        assert_eq!(plural(1, "error"), "1 error");
+        assert_eq!(plural(5, "warning"), "5 warnings");
+    }
+
+    #[test]
+    fn test_log_level_threshold() {
+        assert_eq!(LogLevel::parse("WARN"), Some(LogLevel::Warning));
+        assert_eq!(LogLevel::parse("debug"), None);
+
+        // Info is the default and records everything
+        let threshold = LogLevel::default();
+        assert!(LogLevel::Info.is_recorded(threshold));
+
+        assert!(!LogLevel::Info.is_recorded(LogLevel::Success));
+        assert!(LogLevel::Success.is_recorded(LogLevel::Success));
+
+        // Asking for errors only still records warnings
+        assert_eq!(LogLevel::Error.as_threshold(), LogLevel::Warning);
+        assert!(LogLevel::Warning.is_recorded(LogLevel::Error));
+        assert!(!LogLevel::Success.is_recorded(LogLevel::Error));
+
+        assert_eq!(LogLevel::Info.next_threshold(), LogLevel::Success);
+        assert_eq!(LogLevel::Warning.next_threshold(), LogLevel::Info);
+        assert_eq!(LogLevel::Error.next_threshold(), LogLevel::Info);
+    }
+}
@@ -1,6 +1,9 @@
 //! Centralized theme definitions for the TUI.
 //!
 //! Inspired by lazygit, k9s, and bottom terminal applications.
+//!
+//! Statuses never rely on color alone: each one pairs its color with a
+//! distinct glyph (● ok, ⚠ warning, ✗ error, ○ inactive).
 
 /// Border character set for rounded boxes.
 pub mod borders {
@@ -20,6 +23,8 @@ pub mod symbols {
     pub const SELECTED: &str = "\u{25b6}"; // ▶
     pub const WARNING: &str = "\u{26a0}"; // ⚠
     pub const ERROR: &str = "\u{2717}"; // ✗
+    pub const BATTERY: &str = "\u{1f50b}"; // 🔋
+    pub const PAUSED: &str = "\u{23f8}"; // ⏸
     pub const TREE_BRANCH: &str = "\u{251c}\u{2500}"; // ├─
     pub const TREE_END: &str = "\u{2514}\u{2500}"; // └─
     pub const ARROW_RIGHT: &str = "\u{2500}\u{2500}\u{2500}\u{2500}\u{25b6}"; // ────▶
@@ -28,12 +33,29 @@ pub mod symbols {
     /// Moon phase spinner characters for loading animations.
     pub const MOON_SPINNER: &[char] = &['\u{25d0}', '\u{25d3}', '\u{25d1}', '\u{25d2}'];
     // ◐◓◑◒
+    /// Loading glyph in reduced-motion mode.
+    pub const LOADING_STILL: char = '\u{25d4}'; // ◔
 }
 
 /// Color palette for the application.
 pub mod colors {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
     use ratatui::style::Color;
 
+    /// Whether the color-blind friendly palette is active.
+    static COLORBLIND: AtomicBool = AtomicBool::new(false);
+
+    /// Switch status colors to hues that stay apart for red-green color blindness
+    /// (blue/orange instead of green/red).
+    pub fn set_colorblind(enabled: bool) {
+        COLORBLIND.store(enabled, Ordering::Relaxed);
+    }
+
+    fn colorblind() -> bool {
+        COLORBLIND.load(Ordering::Relaxed)
+    }
+
     /// Default border color (inactive).
     pub const BORDER_DEFAULT: Color = Color::Gray;
     /// Focused/active border color.
@@ -45,11 +67,31 @@ pub mod colors {
     pub const TEXT_SECONDARY: Color = Color::DarkGray;
 
     /// Success/active status color.
-    pub const SUCCESS: Color = Color::Green;
+    pub fn success() -> Color {
+        if colorblind() {
+            Color::Rgb(86, 180, 233) // sky blue
+        } else {
+            Color::Green
+        }
+    }
+
     /// Warning color.
-    pub const WARNING: Color = Color::Yellow;
+    pub fn warning() -> Color {
+        if colorblind() {
+            Color::Rgb(240, 228, 66) // yellow
+        } else {
+            Color::Yellow
+        }
+    }
+
     /// Error color.
-    pub const ERROR: Color = Color::Red;
+    pub fn error() -> Color {
+        if colorblind() {
+            Color::Rgb(230, 159, 0) // orange
+        } else {
+            Color::Red
+        }
+    }
     /// Accent color (title, spinners, info).
     pub const ACCENT: Color = Color::Cyan;
     /// LAN interface indicator color.
@@ -71,7 +113,7 @@ pub mod styles {
     /// Style for active status badge.
     pub fn status_active() -> Style {
         Style::default()
-            .fg(colors::SUCCESS)
+            .fg(colors::success())
             .add_modifier(Modifier::BOLD)
     }
 
@@ -83,7 +125,7 @@ pub mod styles {
     /// Style for selected/highlighted items.
     pub fn selected() -> Style {
         Style::default()
-            .fg(colors::WARNING)
+            .fg(colors::warning())
             .add_modifier(Modifier::BOLD)
     }
 
@@ -123,7 +165,7 @@ pub mod styles {
 
     /// Style for VPN interface text.
     pub fn vpn_interface() -> Style {
-        Style::default().fg(colors::SUCCESS)
+        Style::default().fg(colors::success())
     }
 
     /// Style for LAN interface text.
@@ -150,7 +192,7 @@ pub mod styles {
 
     /// Style for ON status badge.
     pub fn status_on() -> Style {
-        Style::default().fg(colors::SUCCESS)
+        Style::default().fg(colors::success())
     }
 
     /// Style for OFF status badge.
@@ -161,14 +203,14 @@ pub mod styles {
     /// Style for degraded status badge (connection warning).
     pub fn status_degraded() -> Style {
         Style::default()
-            .fg(colors::WARNING)
+            .fg(colors::warning())
             .add_modifier(Modifier::BOLD)
     }
 
     /// Style for down status badge (connection lost).
     pub fn status_down() -> Style {
         Style::default()
-            .fg(colors::ERROR)
+            .fg(colors::error())
             .add_modifier(Modifier::BOLD)
     }
 